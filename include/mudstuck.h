@@ -0,0 +1,48 @@
+/* Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+   top-level directory of this distribution for license information. */
+
+/* The C interface to mudstuck, in the shared library built with the
+   `ffi` feature.  See src/ffi.rs.  Should the library fail
+   unexpectedly, functions return NULL or -1, and the game is best
+   ended. */
+
+#ifndef MUDSTUCK_H
+#define MUDSTUCK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MudstuckGame MudstuckGame;
+
+/* Start a game of the bundle in `len` bytes at `bundle`.  Returns
+   NULL on errors, and the error in `*error` unless `error` is NULL. */
+MudstuckGame *mudstuck_new(const uint8_t *bundle, size_t len, uint64_t seed, char **error);
+
+/* Handle a line typed by the player, and return the text shown. */
+char *mudstuck_step(MudstuckGame *game, const char *input);
+
+/* Return 1 once the game is over, and 0 before. */
+int mudstuck_over(const MudstuckGame *game);
+
+/* Return the game saved to a string, or NULL if it cannot be saved. */
+char *mudstuck_save(MudstuckGame *game);
+
+/* Restore a game saved with mudstuck_save.  Returns 0 on success, and
+   -1 on errors, with the error in `*error` unless `error` is NULL. */
+int mudstuck_restore(MudstuckGame *game, const char *saved, char **error);
+
+/* End a game. */
+void mudstuck_free(MudstuckGame *game);
+
+/* Free a string returned by the library. */
+void mudstuck_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,163 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Player accounts, for servers whose players come back.  Players
+//! with an account log in with their name and password, and play the
+//! character linked to their account.  When they leave, the
+//! character is taken out of the world with everything it carries,
+//! and put back into the room it left when they log in again.
+//!
+//! Passwords are not kept, only hashes of them, salted with random
+//! bytes of their own and stretched with PBKDF2.  Accounts are kept in
+//! storage (see `storage`), usually a file, which is written whenever
+//! an account changes, so they outlast the server.  The characters of
+//! accounts outlast it only if the world is restored too; otherwise
+//! players get new characters.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use pbkdf2;
+use sha2::Sha256;
+
+use super::types::*;
+use super::load::new_id;
+use super::storage::{Files, Storage};
+use super::engine::Engine;
+use super::session::Session;
+use super::chat::DEFAULT_CHANNEL;
+
+/// How often passwords are hashed.
+const ROUNDS: u32 = 10000;
+
+/// A player's account.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// The salt of the password hash, in hex.
+    salt: String,
+    /// The password hash, in hex.
+    hash: String,
+    /// The player's character, once the player has played.
+    pub character: Option<InternalName>,
+    /// The room the character left, while the player is away.
+    pub room: Option<InternalName>,
+}
+
+/// The accounts of a server, by the lowercase names of their players.
+#[derive(Default)]
+pub struct Accounts {
+    accounts: BTreeMap<String, Account>,
+    /// Where the accounts are kept, if anywhere.
+    storage: Option<Arc<Storage>>,
+}
+
+/// Return bytes in hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Return the hash of a password with the given salt, in hex.
+fn hash(password: &str, salt: &str) -> String {
+    let mut res = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), ROUNDS, &mut res);
+    hex(&res)
+}
+
+/// Return true if two strings are the same, taking the same time
+/// wherever they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+impl Accounts {
+    /// Read the accounts kept in a file.  There are none yet if the
+    /// file does not exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Accounts, String> {
+        let dir = path.as_ref().parent().unwrap_or(Path::new("."));
+        Accounts::open(Arc::new(Files::new(dir).accounts_file(path.as_ref())))
+    }
+
+    /// Read the accounts kept in storage.
+    pub fn open(storage: Arc<Storage>) -> Result<Accounts, String> {
+        let accounts = try!(storage.load_accounts());
+        Ok(Accounts { accounts: accounts, storage: Some(storage) })
+    }
+
+    /// Write the accounts to their storage, if they have one.
+    pub fn save(&self) -> Result<(), String> {
+        match self.storage {
+            Some(ref s) => s.save_accounts(&self.accounts),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the account of the player with the given name.
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(&name.to_lowercase())
+    }
+
+    /// Make an account for a player, and save it.
+    pub fn create(&mut self, name: &str, password: &str) -> Result<(), String> {
+        if self.get(name).is_some() {
+            return Err(format!("There is an account for {} already.", name));
+        }
+        if password.is_empty() {
+            return Err("Passwords cannot be empty.".to_string());
+        }
+        let salt = hex(new_id().as_bytes());
+        let account = Account {
+            name: name.to_string(),
+            hash: hash(password, &salt),
+            salt: salt,
+            character: None,
+            room: None,
+        };
+        self.accounts.insert(name.to_lowercase(), account);
+        self.save()
+    }
+
+    /// Return true if the password is the one of the player's account.
+    pub fn authenticate(&self, name: &str, password: &str) -> bool {
+        self.get(name).map_or(false, |a| same(&hash(password, &a.salt), &a.hash))
+    }
+
+    /// Remember where the character of a player's account is, and
+    /// save the accounts.
+    pub fn link(&mut self, name: &str, character: Option<InternalName>, room: Option<InternalName>) -> Result<(), String> {
+        if let Some(a) = self.accounts.get_mut(&name.to_lowercase()) {
+            a.character = character;
+            a.room = room;
+        }
+        self.save()
+    }
+}
+
+impl Engine {
+    /// Bring a character back into the world, into the given room or
+    /// else the start location.  Returns nothing if the character is
+    /// not in the world any more.
+    pub fn rejoin(&mut self, character: InternalName, room: Option<InternalName>) -> Option<Session> {
+        if self.world.entity(&character).and_then(|e| e.character()).is_none() {
+            return None;
+        }
+        let room = room.filter(|r| self.world.entity(r).and_then(|e| e.room()).is_some())
+            .unwrap_or(self.world.start_location);
+        self.world.place(character, room);
+        self.chat.join(character, DEFAULT_CHANNEL);
+        Some(Session::new(character))
+    }
+
+    /// Take the character of a session out of the world, with
+    /// everything it carries, until it rejoins.  Returns the room it
+    /// was in.
+    pub fn park(&mut self, session: Session) -> Option<InternalName> {
+        self.chat.leave_all(&session.player);
+        let room = self.world.location_of(&session.player);
+        if let Some(r) = room.and_then(|r| self.world.entity_mut(&r)).and_then(|e| e.room_mut()) {
+            r.entities.retain(|e| *e != session.player);
+        }
+        room
+    }
+}
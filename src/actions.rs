@@ -0,0 +1,836 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Execution of player commands.  A parsed command is carried out on
+//! behalf of a player, changing the world and reporting the outcome
+//! to the player.
+
+use super::types::*;
+use super::command::{Command, Connector, Verb};
+use super::error::Error;
+use super::{print_wrap, apply_change};
+use super::senses::Sense;
+
+impl<'a> PlayerState<'a> {
+    /// Carry out a parsed command, in stages (see `rules`), and end
+    /// the turn.  The outcome, including refusals like a missing key,
+    /// is reported to the player.  An error is only returned for verbs
+    /// which are not supported yet.
+    pub fn execute(&mut self, cmd: &Command) -> Result<(), Error> {
+        try!(self.perform(cmd));
+        self.end_turn();
+        Ok(())
+    }
+
+    /// Carry out the usual action of a command: the transition of the
+    /// state machine of its direct object, if there is one, or else
+    /// what the verb does.
+    pub fn carry_out(&mut self, cmd: &Command) -> Result<(), Error> {
+        if self.transition(cmd) {
+            return Ok(());
+        }
+        match cmd.verb {
+            Verb::Get => self.take(cmd),
+            Verb::Put => self.put(cmd),
+            Verb::Lock => self.lock(cmd, true),
+            Verb::Unlock => self.lock(cmd, false),
+            Verb::Wear => self.wear(cmd),
+            Verb::Remove => self.remove(cmd),
+            Verb::Eat => self.consume(cmd, false),
+            Verb::Drink => self.consume(cmd, true),
+            Verb::Read => self.read(cmd),
+            Verb::Ask => self.ask(cmd),
+            Verb::Attack => self.attack(cmd),
+            Verb::Buy => self.buy(cmd),
+            Verb::Sell => self.sell(cmd),
+            Verb::List => self.list(cmd),
+            Verb::Look => self.look_at(cmd),
+            Verb::Search => self.search(cmd),
+            Verb::Move => self.go(cmd),
+            Verb::Open => self.open(cmd, true),
+            Verb::Close => self.open(cmd, false),
+            Verb::Enter if cmd.indirect_object.is_some() => self.enter_code(cmd),
+            Verb::Enter => self.enter(cmd),
+            Verb::Leave => self.leave(cmd),
+            Verb::Tie => self.tie(cmd),
+            Verb::Untie => self.untie(cmd),
+            Verb::SwitchOn => self.switch(cmd, true),
+            Verb::SwitchOff => self.switch(cmd, false),
+            Verb::Light => self.light(cmd),
+            Verb::Listen => self.sense(cmd, Sense::Hearing),
+            Verb::Smell => self.sense(cmd, Sense::Smell),
+            Verb::Give => self.give(cmd),
+            Verb::Show => self.show(cmd),
+            Verb::Turn => self.turn(cmd),
+            _ => Err(Error::Unimplemented("verb not supported yet")),
+        }
+    }
+
+    /// Let the world advance by one turn and show the player what
+    /// happened in the current location meanwhile.  Shared worlds
+    /// advance on the engine's clock instead (see `step`), and only
+    /// the player's own surroundings react to the command.
+    pub fn end_turn(&mut self) {
+        self.world.dispatch();
+        if !self.world.shared {
+            let notices = self.world.tick();
+            self.notice(&notices);
+            self.burn_fuses();
+        }
+        self.ambient();
+        // Poison has already taken its toll on the player's
+        // character, like on every other character.
+        if self.me().conditions.contains(&Condition::Poisoned) {
+            say!("{}", msg!(self.world, "nauseous"));
+        }
+        self.defend();
+        self.world.dispatch();
+        self.update_quests();
+        self.autosave();
+    }
+
+    /// Show the player the messages of a turn which can be noticed in
+    /// the current location or by the player's character.
+    pub fn notice(&self, notices: &[(InternalName, String)]) {
+        for &(ref room, ref text) in notices {
+            if *room == self.location() || *room == self.id {
+                print_wrap(text, 72);
+            }
+        }
+    }
+
+    /// Print the player's health, strength and conditions.
+    pub fn show_status(&self) {
+        say!("{}", msg!(self.world, "health", health = self.me().stats.health, max = self.me().stats.max_health));
+        say!("{}", msg!(self.world, "strength", strength = self.me().stats.strength));
+        say!("{}", msg!(self.world, "purse", coins = self.me().purse));
+        for c in self.me().conditions.iter() {
+            match *c {
+                Condition::Poisoned => say!("{}", msg!(self.world, "poisoned")),
+                Condition::Exhausted => say!("{}", msg!(self.world, "exhausted")),
+            }
+        }
+    }
+
+    /// Print the list of things the player is carrying, followed by
+    /// the things the player is wearing.
+    pub fn show_inventory(&self) {
+        if self.me().inventory.len() == 0 && self.me().worn.len() == 0 {
+            say!("{}", msg!(self.world, "carrying_nothing"));
+            return;
+        }
+        if self.me().inventory.len() > 0 {
+            say!("{}", msg!(self.world, "carrying"));
+            for n in self.me().inventory.iter() {
+                self.print_item(n, "");
+            }
+        }
+        if self.me().worn.len() > 0 {
+            say!("{}", msg!(self.world, "wearing"));
+            for n in self.me().worn.iter() {
+                let slot = match self.world.entity(n).and_then(|e| e.wearable()) {
+                    Some(w) => msg!(self.world, slot_name(w.slot)),
+                    None => String::new(),
+                };
+                self.print_item(n, &slot);
+            }
+        }
+    }
+
+    /// Total warmth of everything the player is wearing.
+    pub fn warmth(&self) -> i32 {
+        self.me().worn.iter().filter_map(|n| self.world.entity(n).and_then(|e| e.wearable())).map(|w| w.warmth).sum()
+    }
+
+    /// Total armor of everything the player is wearing.
+    pub fn armor(&self) -> i32 {
+        self.me().worn.iter().filter_map(|n| self.world.entity(n).and_then(|e| e.wearable())).map(|w| w.armor).sum()
+    }
+
+    /// Print the short description of an item as part of a list,
+    /// followed by an optional remark.
+    fn print_item(&self, n: &InternalName, remark: &str) {
+        let ent = self.world.entity(n).unwrap();
+        match self.eval_str(ent.short_text(self.world.locale())).map(|s| self.world.with_quantity(s, self.world.quantity(n))) {
+            Ok(ref s) if remark.len() > 0 =>
+                say!("  {} ({})", s, remark),
+            Ok(s) =>
+                say!("  {}", s),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+    }
+
+    /// Return the names of all entities in the player's location
+    /// besides the player, including the doors leading out of it.
+    pub fn room_contents(&self) -> Vec<InternalName> {
+        let mut res = match self.world.entity(&self.location()).and_then(|e| e.room()) {
+            Some(r) => r.entities.iter().filter(|e| **e != self.id).cloned().collect(),
+            None => vec![],
+        };
+        for d in self.world.doors_at(&self.location()) {
+            if !res.contains(&d) {
+                res.push(d);
+            }
+        }
+        res
+    }
+
+    /// Return the names of all entities the player can see and refer
+    /// to: the contents of the location and the visible contents of
+    /// containers, unless it is dark, and everything the player
+    /// carries or wears.  Not everything in scope can be reached.
+    pub fn scope(&self) -> Vec<InternalName> {
+        let mut res = if self.is_dark() {
+            vec![]
+        } else {
+            let mut r = self.room_contents();
+            r.extend(self.container_contents());
+            r
+        };
+        res.extend(self.me().inventory.iter().cloned());
+        res.extend(self.me().worn.iter().cloned());
+        res
+    }
+
+    /// Remove an entity from wherever the player could reach it: the
+    /// inventory, the worn items, the current location, or an open
+    /// container there.
+    fn detach(&mut self, name: &InternalName) {
+        self.me_mut().inventory.retain(|e| e != name);
+        self.me_mut().worn.retain(|e| e != name);
+        if let Some(room) = self.world.entity_mut(&self.location()).and_then(|e| e.room_mut()) {
+            room.entities.retain(|e| e != name);
+        }
+        if let Some(holder) = self.world.holder_of(name) {
+            if let Some(c) = self.world.entity_mut(&holder).and_then(|e| e.container_mut()) {
+                c.contents.retain(|e| e != name);
+            }
+        }
+    }
+
+    fn take(&mut self, cmd: &Command) -> Result<(), Error> {
+        let (count, words) = match cmd.direct_object {
+            Some(ref w) => split_quantity(w),
+            None => {
+                say!("{}", msg!(self.world, "take_what"));
+                return Ok(());
+            }
+        };
+        let candidates = match cmd.indirect_object {
+            Some((Connector::From, ref w)) =>
+                match self.world.find_entity(w, &self.scope()) {
+                    Some(c) => self.world.visible_contents(&c),
+                    None => {
+                        say!("{}", msg!(self.world, "not_here"));
+                        return Ok(());
+                    }
+                },
+            _ => {
+                let mut c = self.room_contents();
+                c.extend(self.container_contents());
+                c
+            }
+        };
+        let name = match self.world.find_entity(&words, &candidates) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        if let Some(coins) = self.world.entity(&name).unwrap().money() {
+            self.world.destroy(&name);
+            self.me_mut().purse += coins;
+            say!("{}", msg!(self.world, "coins_taken", coins = coins));
+            return Ok(());
+        }
+        if !self.world.entity(&name).unwrap().is_portable() {
+            say!("{}", msg!(self.world, "not_takeable"));
+            return Ok(());
+        }
+        if self.world.attached_to(&name).len() > 0 {
+            say!("{}", msg!(self.world, "tied_up"));
+            return Ok(());
+        }
+        let name = match self.take_part(&name, count) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        self.detach(&name);
+        say!("{}", msg!(self.world, "taken"));
+        self.notice_theft(&name);
+        let inventory = self.me().inventory.clone();
+        let item = match self.world.merge_stack(&name, &inventory) {
+            Some(stack) => stack,
+            None => {
+                self.me_mut().inventory.push(name);
+                name
+            },
+        };
+        self.world.publish(Event::ItemTaken { who: self.id, item: item });
+        Ok(())
+    }
+
+    fn put(&mut self, cmd: &Command) -> Result<(), Error> {
+        if cmd.indirect_object.is_some() {
+            return self.put_into(cmd);
+        }
+        let (count, words) = match cmd.direct_object {
+            Some(ref w) => split_quantity(w),
+            None => {
+                say!("{}", msg!(self.world, "drop_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(&words, &self.me().inventory) {
+            Some(n) => n,
+            None => {
+                if self.world.find_entity(&words, &self.me().worn).is_some() {
+                    say!("{}", msg!(self.world, "remove_first"));
+                } else {
+                    say!("{}", msg!(self.world, "not_carried"));
+                }
+                return Ok(());
+            }
+        };
+        let name = match self.take_part(&name, count) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        self.me_mut().inventory.retain(|e| *e != name);
+        let contents = self.room_contents();
+        let item = match self.world.merge_stack(&name, &contents) {
+            Some(stack) => stack,
+            None => {
+                if let Some(room) = self.world.entity_mut(&self.location()).and_then(|e| e.room_mut()) {
+                    room.entities.push(name);
+                }
+                name
+            },
+        };
+        say!("{}", msg!(self.world, "dropped"));
+        self.world.publish(Event::ItemDropped { who: self.id, item: item });
+        Ok(())
+    }
+
+    /// Return the part of a stack a command is about.  Without a
+    /// count, or with the full count, that is the stack itself,
+    /// otherwise the requested number of items is split off.  Tells
+    /// the player and returns None if there are not enough items.
+    pub fn take_part(&mut self, name: &InternalName, count: Option<u32>) -> Option<InternalName> {
+        let available = self.world.quantity(name);
+        match count {
+            None =>
+                Some(*name),
+            Some(c) if c == available =>
+                Some(*name),
+            Some(c) if c == 0 || c > available => {
+                say!("{}", msg!(self.world, "not_that_many"));
+                None
+            },
+            Some(c) =>
+                self.world.split_stack(name, c),
+        }
+    }
+
+    /// Lock (if `lock` is true) or unlock the direct object.  This
+    /// requires a fitting key in the player's inventory, either named
+    /// explicitly ("unlock door with key") or found automatically.
+    fn lock(&mut self, cmd: &Command, lock: bool) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                if lock {
+                    say!("{}", msg!(self.world, "lock_what"));
+                } else {
+                    say!("{}", msg!(self.world, "unlock_what"));
+                }
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        let key = {
+            let l = match self.world.entity(&name).unwrap().lock() {
+                Some(l) => l,
+                None => {
+                    say!("{}", msg!(self.world, "no_lock"));
+                    return Ok(());
+                }
+            };
+            if l.locked == lock {
+                if lock {
+                    say!("{}", msg!(self.world, "already_locked"));
+                } else {
+                    say!("{}", msg!(self.world, "not_locked"));
+                }
+                return Ok(());
+            }
+            if l.code.is_some() && !lock {
+                say!("{}", msg!(self.world, "needs_code"));
+                return Ok(());
+            }
+            if l.code.is_some() {
+                if let Some(l) = self.world.entity_mut(&name).and_then(|e| e.lock_mut()) {
+                    l.locked = true;
+                }
+                say!("{}", msg!(self.world, "locked_done"));
+                return Ok(());
+            }
+            match cmd.indirect_object {
+                Some((_, ref key_words)) => {
+                    match self.world.find_entity(key_words, &self.me().inventory) {
+                        None => {
+                            say!("{}", msg!(self.world, "not_carried"));
+                            return Ok(());
+                        },
+                        Some(k) if self.world.key_fits(l, &k) =>
+                            k,
+                        Some(_) => {
+                            say!("{}", msg!(self.world, "wrong_key"));
+                            return Ok(());
+                        }
+                    }
+                },
+                None => {
+                    match self.me().inventory.iter().find(|k| self.world.key_fits(l, k)) {
+                        Some(k) => *k,
+                        None => {
+                            say!("{}", msg!(self.world, "no_fitting_key"));
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        };
+        if let Some(l) = self.world.entity_mut(&name).and_then(|e| e.lock_mut()) {
+            l.locked = lock;
+        }
+        let key_desc = self.world.entity(&key).map(|e| e.short_text(self.world.locale()).to_string()).unwrap_or_default();
+        match self.eval_str(&key_desc) {
+            Ok(s) =>
+                if lock {
+                    print_wrap(&msg!(self.world, "locked_with", key = s), 72)
+                } else {
+                    print_wrap(&msg!(self.world, "unlocked_with", key = s), 72)
+                },
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        Ok(())
+    }
+
+    fn wear(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "wear_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.me().inventory) {
+            Some(n) => n,
+            None => {
+                if self.world.find_entity(words, &self.me().worn).is_some() {
+                    say!("{}", msg!(self.world, "already_worn"));
+                } else {
+                    say!("{}", msg!(self.world, "not_carried"));
+                }
+                return Ok(());
+            }
+        };
+        let slot = match self.world.entity(&name).unwrap().wearable() {
+            Some(w) => w.slot,
+            None => {
+                say!("{}", msg!(self.world, "not_wearable"));
+                return Ok(());
+            }
+        };
+        let occupied = self.me().worn.iter().any(|n| match self.world.entity(n).and_then(|e| e.wearable()) {
+            Some(w) => w.slot == slot,
+            None => false,
+        });
+        if occupied {
+            say!("{}", msg!(self.world, "slot_taken", slot = msg!(self.world, slot_name(slot))));
+            return Ok(());
+        }
+        self.me_mut().inventory.retain(|e| *e != name);
+        self.me_mut().worn.push(name);
+        say!("{}", msg!(self.world, "worn"));
+        Ok(())
+    }
+
+    fn remove(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "remove_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.me().worn) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_worn"));
+                return Ok(());
+            }
+        };
+        self.me_mut().worn.retain(|e| *e != name);
+        self.me_mut().inventory.push(name);
+        say!("{}", msg!(self.world, "removed"));
+        Ok(())
+    }
+
+    /// Eat (if `drink` is false) or drink one portion of the direct
+    /// object, showing its effect.  The last portion destroys it.
+    fn consume(&mut self, cmd: &Command, drink: bool) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                if drink {
+                    say!("{}", msg!(self.world, "drink_what"));
+                } else {
+                    say!("{}", msg!(self.world, "eat_what"));
+                }
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        let (effect, change, left) = match self.world.entity_mut(&name).and_then(|e| e.consumable_mut(drink)) {
+            Some(c) => {
+                c.portions = c.portions.saturating_sub(1);
+                (c.effect.clone(), c.change.clone(), c.portions)
+            },
+            None => {
+                if drink {
+                    say!("{}", msg!(self.world, "not_drinkable"));
+                } else {
+                    say!("{}", msg!(self.world, "not_edible"));
+                }
+                return Ok(());
+            }
+        };
+        match self.eval_str(&effect) {
+            Ok(ref s) if s.len() > 0 =>
+                print_wrap(s, 72),
+            Ok(_) =>
+                say!("{}", msg!(self.world, "eaten")),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        {
+            let me = self.me_mut();
+            apply_change(&mut me.stats, &mut me.conditions, &change);
+        }
+        if left == 0 {
+            self.world.destroy(&name);
+            say!("{}", msg!(self.world, "nothing_left"));
+        }
+        Ok(())
+    }
+
+    /// Show the text written on the direct object.
+    fn read(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "read_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        let text = match self.world.entity(&name).unwrap().text() {
+            Some(t) => t.clone(),
+            None => {
+                say!("{}", msg!(self.world, "nothing_written"));
+                return Ok(());
+            }
+        };
+        match self.eval_str(&text) {
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        Ok(())
+    }
+
+    /// Ask the direct object, which must be a character, about the
+    /// topic given as the indirect object ("ask guard about door").
+    /// A topic matches if its keyword is one of the words given.
+    fn ask(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "ask_whom"));
+                return Ok(());
+            }
+        };
+        let topic = match cmd.indirect_object {
+            Some((Connector::About, ref t)) => t,
+            _ => {
+                say!("{}", msg!(self.world, "ask_about_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.room_contents()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "nobody_here"));
+                return Ok(());
+            }
+        };
+        let response = match self.world.entity(&name).unwrap().character() {
+            Some(c) if c.stats.health > 0 =>
+                match c.topics.iter().find(|&(k, _)| topic.contains(k)) {
+                    Some((_, r)) => r.clone(),
+                    None => c.fallback.clone(),
+                },
+            _ => {
+                say!("{}", msg!(self.world, "no_answer"));
+                return Ok(());
+            }
+        };
+        match self.eval_str(&response) {
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        Ok(())
+    }
+
+    /// Look at something ("look at door"), or at a place relative to
+    /// something ("look under rubble"), which may reveal what is
+    /// hidden there.  Without an object, look around.
+    fn look_at(&mut self, cmd: &Command) -> Result<(), Error> {
+        if let Some((conn, ref words)) = cmd.indirect_object {
+            let position = match conn {
+                Connector::Under => Hiding::Under,
+                Connector::Behind => Hiding::Behind,
+                Connector::Into => Hiding::Inside,
+                _ => {
+                    say!("{}", msg!(self.world, "look_where"));
+                    return Ok(());
+                }
+            };
+            match self.world.find_entity(words, &self.scope()) {
+                Some(n) =>
+                    if !self.reveal(&n, Some(position)) {
+                        say!("{}", msg!(self.world, "nothing_there"));
+                    },
+                None =>
+                    say!("{}", msg!(self.world, "not_here")),
+            }
+            return Ok(());
+        }
+        match cmd.direct_object {
+            Some(ref words) =>
+                match self.world.find_entity(words, &self.scope()) {
+                    Some(n) => self.examine(&n),
+                    None => say!("{}", msg!(self.world, "not_here")),
+                },
+            None =>
+                self.look(),
+        }
+        Ok(())
+    }
+
+    /// Print the short and long description of an entity.
+    fn examine(&self, name: &InternalName) {
+        let ent = self.world.entity(name).unwrap();
+        let shrt = self.eval_str(ent.short_text(self.world.locale())).map(|s| self.world.with_quantity(s, self.world.quantity(name)));
+        for d in [shrt, self.long_description(name)].iter() {
+            match *d {
+                Ok(ref s) =>
+                    print_wrap(s, 72),
+                Err(ref e) =>
+                    say!("{}", msg!(self.world, "error", error = e))
+            }
+        }
+        self.show_contents(name);
+    }
+
+    /// Search the direct object, revealing everything it hides.
+    /// Without an object, search everything in the location.
+    fn search(&mut self, cmd: &Command) -> Result<(), Error> {
+        let targets = match cmd.direct_object {
+            Some(ref words) =>
+                match self.world.find_entity(words, &self.scope()) {
+                    Some(n) => vec![n],
+                    None => {
+                        say!("{}", msg!(self.world, "not_here"));
+                        return Ok(());
+                    }
+                },
+            None =>
+                self.room_contents(),
+        };
+        let mut found = false;
+        for t in targets.iter() {
+            found = self.reveal(t, None) || found;
+        }
+        if !found {
+            say!("{}", msg!(self.world, "find_nothing"));
+        }
+        Ok(())
+    }
+
+    /// Reveal the entities hidden by `name` at the given position, or
+    /// at any position if None.  Revealed entities become part of the
+    /// player's location.  Returns true if anything was revealed.
+    fn reveal(&mut self, name: &InternalName, position: Option<Hiding>) -> bool {
+        let (found, text) = match self.world.entity_mut(name).and_then(|e| e.concealment_mut()) {
+            Some(ref mut c) if c.hidden.len() > 0 && position.map(|p| p == c.position).unwrap_or(true) =>
+                (c.hidden.drain(..).collect::<Vec<_>>(), c.reveal.clone()),
+            _ =>
+                return false,
+        };
+        if let Some(room) = self.world.entity_mut(&self.location()).and_then(|e| e.room_mut()) {
+            room.entities.extend(found);
+        }
+        match self.eval_str(&text) {
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        true
+    }
+
+    /// Open (if `open` is true) or close the direct object.  Locked
+    /// things cannot be opened.
+    fn open(&mut self, cmd: &Command, open: bool) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                if open {
+                    say!("{}", msg!(self.world, "open_what"));
+                } else {
+                    say!("{}", msg!(self.world, "close_what"));
+                }
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        let (closed, locked) = {
+            let e = self.world.entity(&name).unwrap();
+            (e.closed(), e.lock().map(|l| l.locked).unwrap_or(false))
+        };
+        match closed {
+            None =>
+                say!("{}", msg!(self.world, "not_closable")),
+            Some(c) if c != open =>
+                if open {
+                    say!("{}", msg!(self.world, "already_open"));
+                } else {
+                    say!("{}", msg!(self.world, "already_closed"));
+                },
+            Some(_) if open && locked =>
+                say!("{}", msg!(self.world, "locked")),
+            Some(_) => {
+                if let Some(c) = self.world.entity_mut(&name).and_then(|e| e.closed_mut()) {
+                    *c = !open;
+                }
+                if open {
+                    say!("{}", msg!(self.world, "opened"));
+                    self.world.publish(Event::EntityOpened { who: self.id, what: name });
+                } else {
+                    say!("{}", msg!(self.world, "closed_done"));
+                    self.world.publish(Event::EntityClosed { who: self.id, what: name });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Report why the direct object could not be switched on (if
+    /// `on` is true) or off.  Switching itself is a transition of the
+    /// object's state machine.
+    fn switch(&mut self, cmd: &Command, on: bool) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                if on {
+                    say!("{}", msg!(self.world, "switch_on_what"));
+                } else {
+                    say!("{}", msg!(self.world, "switch_off_what"));
+                }
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        match self.world.entity(&name).and_then(|e| e.machine()).map(|m| m.state.as_str()) {
+            Some("on") if on =>
+                say!("{}", msg!(self.world, "already_on")),
+            Some("off") if !on =>
+                say!("{}", msg!(self.world, "already_off")),
+            _ =>
+                say!("{}", msg!(self.world, "not_switchable")),
+        }
+        Ok(())
+    }
+}
+
+/// Return a description of where on the body a slot is.
+fn slot_name(slot: Slot) -> &'static str {
+    match slot {
+        Slot::Head => "slot_head",
+        Slot::Hands => "slot_hands",
+        Slot::Torso => "slot_torso",
+    }
+}
+
+/// Split a leading number off the words naming an object, as in
+/// "take 3 nails".
+pub fn split_quantity(words: &Name) -> (Option<u32>, Name) {
+    match words.first().and_then(|w| w.parse::<u32>().ok()) {
+        Some(n) => (Some(n), words[1..].to_vec()),
+        None => (None, words.clone()),
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Tying and attaching things to each other, like a rope to a
+//! railing.  Attached things cannot be taken until they are untied.
+
+use super::types::*;
+use super::command::Command;
+use super::error::Error;
+use super::relation::ATTACHED;
+
+impl<'a> PlayerState<'a> {
+    /// Tie the direct object to the indirect object ("tie rope to
+    /// cart").  Only attachable things can be tied to something.
+    /// Things tied while carried are put down.
+    pub fn tie(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "tie_what"));
+                return Ok(());
+            }
+        };
+        let anchor_words = match cmd.indirect_object {
+            Some((_, ref w)) => w,
+            None => {
+                say!("{}", msg!(self.world, "tie_to_what"));
+                return Ok(());
+            }
+        };
+        let scope = self.scope();
+        let (name, anchor) = match (self.world.find_entity(words, &scope), self.world.find_entity(anchor_words, &scope)) {
+            (Some(n), Some(a)) => (n, a),
+            _ => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) || !self.reach(&anchor) {
+            return Ok(());
+        }
+        if !self.world.entity(&name).unwrap().attributes.iter().any(|a| match a { &Attribute::Attachable => true, _ => false }) {
+            say!("{}", msg!(self.world, "not_tieable"));
+            return Ok(());
+        }
+        if name == anchor {
+            say!("{}", msg!(self.world, "impossible"));
+            return Ok(());
+        }
+        if self.world.relations.related(ATTACHED, &name, &anchor) {
+            say!("{}", msg!(self.world, "already_tied"));
+            return Ok(());
+        }
+        if self.me().inventory.contains(&name) {
+            self.me_mut().inventory.retain(|e| *e != name);
+            if let Some(room) = self.world.entity_mut(&self.location()).and_then(|e| e.room_mut()) {
+                room.entities.push(name);
+            }
+        }
+        self.world.relations.relate(ATTACHED, name, anchor);
+        say!("{}", msg!(self.world, "tied"));
+        Ok(())
+    }
+
+    /// Untie the direct object from the indirect object, or from
+    /// everything it is attached to if no indirect object is given.
+    pub fn untie(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "untie_what"));
+                return Ok(());
+            }
+        };
+        let scope = self.scope();
+        let name = match self.world.find_entity(words, &scope) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        let anchor = match cmd.indirect_object {
+            Some((_, ref w)) =>
+                match self.world.find_entity(w, &scope) {
+                    Some(a) => Some(a),
+                    None => {
+                        say!("{}", msg!(self.world, "not_here"));
+                        return Ok(());
+                    }
+                },
+            None => None,
+        };
+        if self.world.relations.unrelate(ATTACHED, &name, anchor.as_ref()) == 0 {
+            say!("{}", msg!(self.world, "not_tied"));
+        } else {
+            say!("{}", msg!(self.world, "untied"));
+        }
+        Ok(())
+    }
+}
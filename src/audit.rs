@@ -0,0 +1,110 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The audit log: every command players type on a server, with what
+//! came of it, for moderators looking into what happened.  Servers
+//! keep it when told to with `Server::audit`, or `--audit FILE`, and
+//! append one JSON object per command to the file:
+//!
+//! ```text
+//! {"session":0,"player":"Anna","timestamp":1475049600,"command":"take lantern",
+//!  "outcome":{"result":"done","text":"Genommen.\n","events":[{"type":"item_taken","who":"…","item":"…"}]}}
+//! ```
+//!
+//! The outcome is `done`, with the text the player was shown and the
+//! events the command caused (see `events`), `over` if the game ended
+//! for the player, or `refused` with the reason, for commands the
+//! server does not carry out.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use super::types::*;
+use super::step::{Output, OutputEvent, SessionId};
+
+/// What came of a command.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum Outcome {
+    /// The command was carried out.
+    Done { text: String, events: Vec<Event> },
+    /// The command was carried out, and the game is over for the
+    /// player.
+    Over { text: String, events: Vec<Event> },
+    /// The command was not carried out.
+    Refused { reason: String },
+}
+
+/// A command, as written to the audit log.
+#[derive(Serialize)]
+struct Entry<'a> {
+    session: SessionId,
+    player: &'a str,
+    /// Seconds since the epoch.
+    timestamp: u64,
+    command: &'a str,
+    outcome: &'a Outcome,
+}
+
+/// The audit log of a server.
+pub struct AuditLog {
+    file: File,
+    /// The events caused since the last command was written.
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl AuditLog {
+    /// Append the audit log of the world to the file at `path`.  The
+    /// events of the world are collected until the log is dropped.
+    pub fn open<P: AsRef<Path>>(path: P, world: &mut World) -> io::Result<AuditLog> {
+        let file = try!(OpenOptions::new().create(true).append(true).open(path));
+        let events = Arc::new(Mutex::new(vec![]));
+        let collected = Arc::downgrade(&events);
+        world.subscribe(move |_, e| if let Some(events) = collected.upgrade() {
+            events.lock().map(|mut v| v.push(e.clone())).unwrap_or(());
+        });
+        Ok(AuditLog { file: file, events: events })
+    }
+
+    /// Forget the events caused so far, before a command is carried
+    /// out.
+    pub fn begin(&mut self) {
+        self.events.lock().map(|mut v| v.clear()).unwrap_or(());
+    }
+
+    /// Return what came of a command carried out in a session, from
+    /// what the engine returned for it.
+    pub fn outcome(&mut self, session: SessionId, outputs: &[OutputEvent]) -> Outcome {
+        let events = self.events.lock().map(|mut v| v.drain(..).collect()).unwrap_or_default();
+        let mine = || outputs.iter().filter(|o| o.session == session);
+        let text = mine().filter_map(|o| match o.output {
+            Output::Text { ref text } => Some(text.as_str()),
+            _ => None,
+        }).collect();
+        if mine().any(|o| o.output == Output::Over) {
+            Outcome::Over { text: text, events: events }
+        } else {
+            Outcome::Done { text: text, events: events }
+        }
+    }
+
+    /// Write a command typed by a player in a session, and what came
+    /// of it.
+    pub fn write(&mut self, session: SessionId, player: &str, command: &str, outcome: &Outcome)
+                 -> Result<(), String> {
+        let entry = Entry {
+            session: session,
+            player: player,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            command: command,
+            outcome: outcome,
+        };
+        let line = try!(serde_json::to_string(&entry).map_err(|e| e.to_string()));
+        writeln!(self.file, "{}", line).map_err(|e| e.to_string())
+    }
+}
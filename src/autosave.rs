@@ -0,0 +1,57 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Saving games automatically, and checkpoints.  When the world has
+//! an `Autosave`, the game is saved every few turns.  Checkpoints are
+//! saved games with a name, like `before_maze`, which are kept in
+//! files called like the checkpoint, with `.sav` appended.
+
+use std::path::PathBuf;
+
+use super::types::*;
+
+/// Return the file a checkpoint is kept in.  Checkpoint names may
+/// only contain letters, digits, `_` and `-`.
+pub fn checkpoint_file(name: &str) -> Result<PathBuf, String> {
+    if name.len() == 0 || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("invalid checkpoint name: {}", name));
+    }
+    Ok(PathBuf::from(format!("{}.sav", name)))
+}
+
+impl World {
+    /// Save the game every `every` turns to the file at `path`.
+    pub fn set_autosave<P: Into<PathBuf>>(&mut self, every: u64, path: P) {
+        self.autosave = Some(Autosave { every: every, path: path.into() });
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Save the game if it is time to do so.  Called at the end of
+    /// every turn.
+    pub fn autosave(&self) {
+        let autosave = match self.world.autosave {
+            Some(ref a) if a.every > 0 && self.world.turn % a.every == 0 => a.clone(),
+            _ => return,
+        };
+        if let Err(e) = self.save(&autosave.path) {
+            say!("{}", msg!(self.world, "autosave_failed", error = e));
+        }
+    }
+
+    /// Save the game as the checkpoint with the given name, replacing
+    /// an older checkpoint of that name.
+    pub fn checkpoint(&self, name: &str) -> Result<(), String> {
+        let path = try!(checkpoint_file(name));
+        self.save(path)
+    }
+
+    /// Restore the game from the checkpoint with the given name.
+    pub fn restore_checkpoint(&mut self, name: &str) -> Result<(), String> {
+        let path = try!(checkpoint_file(name));
+        if !path.exists() {
+            return Err(format!("no checkpoint named {}", name));
+        }
+        self.restore(path)
+    }
+}
@@ -6,51 +6,388 @@
 extern crate mudstuck;
 extern crate rustyline;
 
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::process;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use mudstuck::*;
-use mudstuck::types::*;
+use mudstuck::types::{World, Severity};
+
+/// A world file which is reloaded when it changes.
+struct Watch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl Watch {
+    fn new(path: PathBuf) -> Watch {
+        let modified = Watch::modified(&path);
+        Watch { path: path, modified: modified }
+    }
+
+    fn modified(path: &PathBuf) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Reload the world file into the world if it has changed since
+    /// it was last loaded.
+    fn check(&mut self, world: &mut World) {
+        let modified = Watch::modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match world.reload_from_path(&self.path) {
+            Ok(()) => say!("(World file changed, reloaded.)"),
+            Err(e) => say!("(Cannot reload the world file: {}.)", e),
+        }
+    }
+}
+
+const USAGE: &'static str = "\
+Usage: mudstuck [play] [WORLD] [OPTIONS]
+       mudstuck serve [WORLD] [--telnet PORT] [--websocket PORT] [OPTIONS]
+       mudstuck test FILES...
+       mudstuck lint FILES...
+       mudstuck translations WORLD LOCALE
+       mudstuck --schema
+
+Play the world in the file WORLD, or the example world if none is given,
+or serve it to players connecting with telnet or from browsers.
+
+Options:
+  --example         play the example world
+  --lang LOCALE     show descriptions and messages in another language
+  --restore FILE    restore a saved game or a checkpoint
+  --translations FILE  merge the translations of a PO file
+  --seed N          seed the random number generator with N
+  --telnet PORT     serve the world to telnet clients on PORT
+  --websocket PORT  serve the world to browsers on PORT
+  --wizard NAME     let the account NAME use all wizard commands (needs accounts)
+  --compress        offer telnet clients compressed output (MCCP2)
+  --accounts FILE   let players log in with accounts kept in FILE
+  --storage DIR     keep the world's state and the accounts in DIR
+  --database FILE   keep them in the SQLite database FILE instead
+  --audit FILE      log the commands of players to FILE
+  --journal FILE    keep a journal in FILE, and recover from it after crashes
+  --help            show this help";
+
+/// The options for playing a game.
+#[derive(Default)]
+struct Options {
+    world: Option<String>,
+    example: bool,
+    lang: Option<String>,
+    restore: Option<String>,
+    translations: Vec<String>,
+    seed: Option<u64>,
+    telnet: Option<u16>,
+    websocket: Option<u16>,
+    wizards: Vec<String>,
+    compress: bool,
+    accounts: Option<String>,
+    journal: Option<String>,
+    audit: Option<String>,
+    storage: Option<String>,
+    database: Option<String>,
+    help: bool,
+}
 
-fn show_help() {
-    println!("Commands:");
-    println!("  help or h   show this help");
-    println!("  quit or q   quit the game");
-    println!("  look or l   describe your surroundings");
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter().skip_while(|a| *a == "play" || *a == "serve");
+        while let Some(a) = args.next() {
+            let mut value = || args.next().cloned().ok_or(format!("{} needs a value", a));
+            match a.as_str() {
+                "--example" => options.example = true,
+                "--lang" => options.lang = Some(try!(value())),
+                "--restore" => options.restore = Some(try!(value())),
+                "--translations" => options.translations.push(try!(value())),
+                "--seed" => options.seed = Some(try!(value().and_then(|v| v.parse().map_err(|_| format!("invalid seed: {}", v))))),
+                "--telnet" => options.telnet = Some(try!(value().and_then(|v| v.parse().map_err(|_| format!("invalid port: {}", v))))),
+                "--websocket" => options.websocket = Some(try!(value().and_then(|v| v.parse().map_err(|_| format!("invalid port: {}", v))))),
+                "--wizard" => options.wizards.push(try!(value())),
+                "--compress" => options.compress = true,
+                "--accounts" => options.accounts = Some(try!(value())),
+                "--storage" => options.storage = Some(try!(value())),
+                "--database" => options.database = Some(try!(value())),
+                "--audit" => options.audit = Some(try!(value())),
+                "--journal" => options.journal = Some(try!(value())),
+                "--help" | "-h" => options.help = true,
+                _ if a.starts_with("--") => return Err(format!("unknown option: {}", a)),
+                _ if options.world.is_none() => options.world = Some(a.clone()),
+                _ => return Err(format!("more than one world given: {}", a)),
+            }
+        }
+        Ok(options)
+    }
 }
 
-fn repl(ps: &PlayerState) {
-    let mut rl = rustyline::Editor::new();
+fn repl(engine: &mut Engine, mut watch: Option<Watch>) {
+    let me = engine.attach(Session::new(engine.player));
+    let mut rl = rustyline::Editor::<()>::new();
     loop {
         let readline = rl.readline(">> ");
+        if let Some(ref mut w) = watch {
+            w.check(&mut engine.world);
+        }
         match readline {
-            Ok(ref s) if s == "quit" || s == "q" => break,
-            Ok(ref s) if s == "look" || s == "l" => ps.look(),
-            Ok(ref s) if s == "help" || s == "h" => show_help(),
-            Ok(ref s) if s == "desc" || s == "d" => ps.describe("rusty.metal.door"),
-            Ok(ref s) =>
-                match command::parse(s) {
-                    Err(e) => {
-                        println!("I don't know how to do that.");
-                        println!("({})", e);
-                    },
-                    Ok(cmd) => {
-                        println!("trying to {:?}", cmd);
-                        println!("I don't know how to do that.");
-                    },
-                },
-            Err(_)   => println!("No input"),
+            Ok(ref s) if s == "quit" || s == "q" => {
+                engine.with_player(|ps| ps.show_score(true));
+                break;
+            },
+            Ok(ref s) => {
+                let mut over = false;
+                for e in engine.step(me, s) {
+                    match e.output {
+                        // Shown as it was captured, and in the transcript already.
+                        Output::Text { text } => output::release(&text),
+                        Output::Over => over = true,
+                        _ => {},
+                    }
+                }
+                if over {
+                    break;
+                }
+            },
+            Err(_)   => say!("No input"),
         }
     }
 }
 
+/// Play the walkthroughs in the given files, or those in the given
+/// bundles, reporting failures.  Returns true if all of them pass.
+fn test(files: &[String]) -> bool {
+    let mut ok = true;
+    for f in files {
+        let res = if f.contains(".bundle") {
+            Bundle::open(f).and_then(|b| b.run_walkthroughs())
+        } else {
+            Walkthrough::run_file(f)
+        };
+        match res {
+            Ok(()) => say!("{}: ok", f),
+            Err(e) => {
+                say!("{}: FAILED\n{}", f, e);
+                ok = false;
+            },
+        }
+    }
+    ok
+}
+
+/// Print the descriptions of a world and the game's messages as a PO
+/// file for translating them into the locale.
+fn translations(args: &[String]) -> bool {
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        return false;
+    }
+    match World::load_from_path(&args[0]) {
+        Ok(w) => {
+            print!("{}", w.export_translations(&args[1]));
+            true
+        },
+        Err(e) => {
+            eprintln!("Cannot load the world: {}.", e);
+            false
+        },
+    }
+}
+
+/// Lint the world files, reporting what is found.  Returns true if
+/// no errors are found.
+fn lint(files: &[String]) -> bool {
+    let mut ok = true;
+    for f in files {
+        match World::load_unchecked(f) {
+            Ok(w) => {
+                let findings = w.lint();
+                for finding in findings.iter() {
+                    say!("{}: {}", f, finding);
+                }
+                let errors = findings.iter().filter(|x| x.severity == Severity::Error).count();
+                say!("{}: {} errors, {} warnings", f, errors, findings.len() - errors);
+                ok = ok && errors == 0;
+            },
+            Err(e) => {
+                say!("{}: error: {}", f, e);
+                ok = false;
+            },
+        }
+    }
+    ok
+}
+
+/// Open the SQLite database at `path` as storage.
+#[cfg(feature = "sqlite")]
+fn open_database(path: &str) -> Result<Arc<Storage>, String> {
+    Sqlite::open(path).map(|db| Arc::new(db) as Arc<Storage>)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_database(_path: &str) -> Result<Arc<Storage>, String> {
+    Err("SQLite is not supported in this build".to_string())
+}
+
 fn main() {
-    println!("If you don't know what to do, type \"help\" (without the quotes).");
-    println!("To leave the game, type \"quit\".");
-    println!("");
-
-    let w = make_example_world();
-    let ps = PlayerState {
-        world: &w,
-        location: w.start_location,
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map_or(false, |a| a == "test") {
+        if !test(&args[2..]) {
+            process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map_or(false, |a| a == "lint") {
+        if !lint(&args[2..]) {
+            process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map_or(false, |a| a == "translations") {
+        if !translations(&args[2..]) {
+            process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map_or(false, |a| a == "--schema") {
+        match World::schema() {
+            Ok(s) => println!("{}", s),
+            Err(e) => {
+                eprintln!("Cannot trace the world format: {}.", e);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+    let options = match Options::parse(&args[1..]) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        },
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
+    let serving = options.telnet.is_some() || options.websocket.is_some();
+    if args.get(1).map_or(false, |a| a == "serve") && !serving {
+        eprintln!("serve needs --telnet PORT or --websocket PORT\n{}", USAGE);
+        process::exit(2);
+    }
+
+    let (mut w, watch) = match options.world {
+        Some(ref path) if !options.example =>
+            match World::load_from_path(path) {
+                Ok(w) => (w, Some(Watch::new(PathBuf::from(path)))),
+                Err(e) => {
+                    say!("Cannot load the world: {}.", e);
+                    process::exit(1);
+                }
+            },
+        _ => (make_example_world(), None),
     };
+    let seed = options.seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() ^ d.subsec_nanos() as u64).unwrap_or(0)
+    });
+    for file in options.translations.iter() {
+        match fs::read_to_string(file).map_err(|e| e.to_string()).and_then(|t| w.import_translations(&t)) {
+            Ok((locale, _)) => if options.lang.is_none() {
+                w.locale = Some(locale);
+            },
+            Err(e) => {
+                say!("Cannot read the translations in {}: {}.", file, e);
+                process::exit(1);
+            },
+        }
+    }
+    if serving {
+        if let Some(lang) = options.lang {
+            w.locale = Some(lang);
+        }
+        let name = w.name.clone();
+        let config = ServerConfig { compression: options.compress, ..ServerConfig::default() };
+        let mut server = Server::with_config(w, seed, config);
+        if let Some(ref dir) = options.storage {
+            if let Err(e) = server.use_storage(Arc::new(Files::new(dir.as_str()))) {
+                eprintln!("Cannot use the storage in {}: {}", dir, e);
+                process::exit(1);
+            }
+        }
+        if let Some(ref path) = options.database {
+            if let Err(e) = open_database(path).and_then(|db| server.use_storage(db)) {
+                eprintln!("Cannot use the database {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        if let Some(ref path) = options.accounts {
+            match Accounts::load(path) {
+                Ok(a) => server.use_accounts(a),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                },
+            }
+        }
+        for name in options.wizards.iter() {
+            if let Err(e) = server.grant(name, Role::Wizard) {
+                eprintln!("Cannot make {} a wizard: {}; use --accounts, --storage or --database.", name, e);
+                process::exit(1);
+            }
+        }
+        if let Some(ref path) = options.audit {
+            if let Err(e) = server.audit(path) {
+                eprintln!("Cannot open the audit log {}: {}.", path, e);
+                process::exit(1);
+            }
+        }
+        if let Some(ref path) = options.journal {
+            match server.keep_journal(path, SNAPSHOT_INTERVAL) {
+                Ok(0) => {},
+                Ok(n) => say!("Recovered from the journal, {} lines stepped again.", n),
+                Err(e) => {
+                    eprintln!("Cannot keep the journal: {}", e);
+                    process::exit(1);
+                },
+            }
+        }
+        for &(port, protocol) in [(options.telnet, Protocol::Telnet), (options.websocket, Protocol::WebSocket)].iter() {
+            let port = match port {
+                Some(p) => p,
+                None => continue,
+            };
+            if let Err(e) = TcpListener::bind(("0.0.0.0", port)).and_then(|l| server.listen(l, protocol)) {
+                eprintln!("Cannot listen on port {}: {}.", port, e);
+                process::exit(1);
+            }
+            say!("Serving {} on port {}.", name, port);
+        }
+        server.run();
+        return;
+    }
+
+    say!("If you don't know what to do, type \"help\" (without the quotes).");
+    say!("To leave the game, type \"quit\".");
+    say!("");
+
+    let mut engine = Engine::new(w, seed);
+    if let Some(lang) = options.lang {
+        engine.world.locale = Some(lang);
+    }
+    if let Some(ref file) = options.restore {
+        let res = engine.with_player(|ps| if Path::new(file).exists() { ps.restore(file) } else { ps.restore_checkpoint(file) });
+        if let Err(e) = res {
+            say!("Cannot restore the game: {}.", e);
+            process::exit(1);
+        }
+        engine.handle("look");
+    }
 
-    repl(&ps);
+    repl(&mut engine, watch);
 }
@@ -0,0 +1,298 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Building worlds in code.  A `WorldBuilder` declares entities by
+//! symbolic names and configures the most recently declared one:
+//!
+//! ```
+//! use mudstuck::WorldBuilder;
+//!
+//! let world = WorldBuilder::new("Cells")
+//!     .room("cell").desc("Eine Zelle")
+//!     .room("tunnel").desc("Ein Tunnel")
+//!     .door("metal_door").desc("Eine Metalltür").between("cell", "tunnel")
+//!     .item("spoon").desc("Ein Löffel").in_room("cell")
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(world.entities.len(), 4);
+//! ```
+//!
+//! Names are turned into UUIDs when they are first used, so entities
+//! can be referred to before they are declared.  The first room is
+//! the start location unless another one is given.
+
+use std::collections::BTreeMap;
+
+
+use super::types::*;
+use super::load::{alias_id, WorldFile, WORLD_VERSION};
+use super::prefab::Prefab;
+
+/// A builder for worlds.
+pub struct WorldBuilder {
+    name: String,
+    start: Option<String>,
+    ids: BTreeMap<String, InternalName>,
+    entities: Vec<Entity>,
+    kinds: BTreeMap<String, Kind>,
+    regions: BTreeMap<String, Region>,
+    /// Doors, the rooms they connect, and whether they only lead
+    /// from the first room to the second.
+    doors: Vec<(String, String, String, bool)>,
+    /// Entities and the rooms they are in.
+    placements: Vec<(String, String)>,
+    errors: Vec<String>,
+}
+
+impl WorldBuilder {
+    /// Start building a world with the given name.
+    pub fn new(name: &str) -> WorldBuilder {
+        WorldBuilder {
+            name: name.to_string(),
+            start: None,
+            ids: BTreeMap::new(),
+            entities: vec![],
+            kinds: BTreeMap::new(),
+            regions: BTreeMap::new(),
+            doors: vec![],
+            placements: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Return the UUID of the entity with the given name, allocating
+    /// one if the name has not been used before.  Names stand for the
+    /// same UUIDs as aliases in world files, so worlds are built the
+    /// same way every time.
+    pub fn id(&mut self, name: &str) -> InternalName {
+        *self.ids.entry(name.to_string()).or_insert_with(|| alias_id(name))
+    }
+
+    /// Declare an entity, which is configured by the following calls.
+    pub fn entity(mut self, name: &str) -> WorldBuilder {
+        let id = self.id(name);
+        if self.entities.iter().any(|e| e.id == id) {
+            self.errors.push(format!("{} declared twice", name));
+        }
+        self.entities.push(Entity {
+            id: id,
+            name: name.split('_').map(|w| w.to_lowercase()).collect(),
+            alias: Some(name.to_string()),
+            kind: None,
+            tags: vec![],
+            properties: BTreeMap::new(),
+            short_description: String::new(),
+            long_description: String::new(),
+            sound_description: None,
+            smell_description: None,
+            translations: BTreeMap::new(),
+            attributes: vec![],
+        });
+        self
+    }
+
+    /// Declare a room.
+    pub fn room(self, name: &str) -> WorldBuilder {
+        self.entity(name).attribute(Attribute::Roomlike(Room { entities: vec![], region: None, position: None }))
+    }
+
+    /// Declare a door, which must be placed `between` two rooms.
+    pub fn door(self, name: &str) -> WorldBuilder {
+        self.entity(name)
+    }
+
+    /// Declare an item.
+    pub fn item(self, name: &str) -> WorldBuilder {
+        self.entity(name)
+    }
+
+    /// Declare a character with the given stats.
+    pub fn character(self, name: &str, stats: Stats) -> WorldBuilder {
+        self.entity(name).attribute(Attribute::Characterlike(Character::new(stats)))
+    }
+
+    /// Change the current entity, if there is one.
+    fn with<F: FnOnce(&mut Entity)>(mut self, what: &str, f: F) -> WorldBuilder {
+        match self.entities.last_mut() {
+            Some(e) => f(e),
+            None => self.errors.push(format!("{} without an entity", what)),
+        }
+        self
+    }
+
+    /// Return the symbolic name of the current entity.
+    fn current(&self) -> Option<String> {
+        self.entities.last().and_then(|e| e.alias.clone())
+    }
+
+    /// Set the words the current entity is called by.
+    pub fn named(self, words: &str) -> WorldBuilder {
+        let name = words.split_whitespace().map(|w| w.to_lowercase()).collect();
+        self.with("named", |e| e.name = name)
+    }
+
+    /// Set the short description of the current entity.
+    pub fn desc(self, short: &str) -> WorldBuilder {
+        self.with("desc", |e| e.short_description = short.to_string())
+    }
+
+    /// Set the long description of the current entity.
+    pub fn long(self, long: &str) -> WorldBuilder {
+        self.with("long", |e| e.long_description = long.to_string())
+    }
+
+    /// Set what can be heard of the current entity.
+    pub fn sound(self, sound: &str) -> WorldBuilder {
+        self.with("sound", |e| e.sound_description = Some(sound.to_string()))
+    }
+
+    /// Set what can be smelled of the current entity.
+    pub fn smell(self, smell: &str) -> WorldBuilder {
+        self.with("smell", |e| e.smell_description = Some(smell.to_string()))
+    }
+
+    /// Set the short and long descriptions of the current entity in
+    /// another language.
+    pub fn translated(self, locale: &str, short: &str, long: &str) -> WorldBuilder {
+        let t = Translation { short: short.to_string(), long: long.to_string(), ..Translation::default() };
+        self.with("translated", |e| { e.translations.insert(locale.to_string(), t); })
+    }
+
+    /// Tag the current entity.
+    pub fn tag(self, tag: &str) -> WorldBuilder {
+        self.with("tag", |e| e.tags.push(tag.to_string()))
+    }
+
+    /// Let the current entity inherit from a kind.
+    pub fn kind(self, kind: &str) -> WorldBuilder {
+        self.with("kind", |e| e.kind = Some(kind.to_string()))
+    }
+
+    /// Add an attribute to the current entity.
+    pub fn attribute(self, attribute: Attribute) -> WorldBuilder {
+        self.with("attribute", |e| e.attributes.push(attribute))
+    }
+
+    /// Make the current entity one of a prefab, like a chest or a
+    /// torch (see `prefab`).
+    pub fn prefab(self, prefab: Prefab) -> WorldBuilder {
+        self.with("prefab", |e| prefab.apply(e))
+    }
+
+    /// Put the current room into a region.
+    pub fn in_region(self, region: &str) -> WorldBuilder {
+        self.with("in_region", |e| if let Some(r) = e.room_mut() { r.region = Some(region.to_string()) })
+    }
+
+    /// Give the current room coordinates.
+    pub fn position(self, x: i32, y: i32, z: i32) -> WorldBuilder {
+        self.with("position", |e| if let Some(r) = e.room_mut() { r.position = Some((x, y, z)) })
+    }
+
+    /// Let the current door connect two rooms.
+    pub fn between(mut self, a: &str, b: &str) -> WorldBuilder {
+        match self.current() {
+            Some(d) => self.doors.push((d, a.to_string(), b.to_string(), false)),
+            None => self.errors.push("between without an entity".to_string()),
+        }
+        self
+    }
+
+    /// Let the current door lead from one room to another, but not
+    /// back.
+    pub fn leading(mut self, from: &str, to: &str) -> WorldBuilder {
+        match self.current() {
+            Some(d) => self.doors.push((d, from.to_string(), to.to_string(), true)),
+            None => self.errors.push("leading without an entity".to_string()),
+        }
+        self
+    }
+
+    /// Put the current entity into a room.
+    pub fn in_room(mut self, room: &str) -> WorldBuilder {
+        match self.current() {
+            Some(e) => self.placements.push((e, room.to_string())),
+            None => self.errors.push("in_room without an entity".to_string()),
+        }
+        self
+    }
+
+    /// Define a kind entities can inherit from.
+    pub fn define_kind(mut self, name: &str, kind: Kind) -> WorldBuilder {
+        self.kinds.insert(name.to_string(), kind);
+        self
+    }
+
+    /// Define a region rooms can belong to.
+    pub fn define_region(mut self, name: &str, region: Region) -> WorldBuilder {
+        self.regions.insert(name.to_string(), region);
+        self
+    }
+
+    /// Let players start in the given room.
+    pub fn start(mut self, room: &str) -> WorldBuilder {
+        self.start = Some(room.to_string());
+        self
+    }
+
+    /// Return the index of the entity with the given name, if it is
+    /// declared.
+    fn declared(&mut self, name: &str) -> Result<usize, String> {
+        let id = self.id(name);
+        self.entities.iter().position(|e| e.id == id).ok_or(format!("unknown entity: {}", name))
+    }
+
+    /// Build the world.  Fails if names are used which were never
+    /// declared or the world does not validate.
+    pub fn build(mut self) -> Result<World, String> {
+        if self.errors.len() > 0 {
+            return Err(self.errors.join("; "));
+        }
+        for (door, a, b, one_way) in self.doors.clone() {
+            let (d, ra, rb) = (try!(self.declared(&door)), try!(self.declared(&a)), try!(self.declared(&b)));
+            let endpoints = (self.entities[ra].id, self.entities[rb].id);
+            self.entities[d].attributes.push(Attribute::Doorlike(Connection {
+                endpoints: endpoints,
+                barrier: None,
+                one_way: one_way,
+                teleport: false,
+                transition: None,
+                directions: vec![],
+            }));
+        }
+        for (entity, room) in self.placements.clone() {
+            let (e, r) = (try!(self.declared(&entity)), try!(self.declared(&room)));
+            let id = self.entities[e].id;
+            match self.entities[r].room_mut() {
+                Some(r) => r.entities.push(id),
+                None => return Err(format!("not a room: {}", room)),
+            }
+        }
+        let start = match self.start.clone() {
+            Some(s) => {
+                let i = try!(self.declared(&s));
+                self.entities[i].id
+            },
+            None => try!(self.entities.iter().find(|e| e.room().is_some()).map(|e| e.id).ok_or("no rooms".to_string())),
+        };
+        WorldFile {
+            version: WORLD_VERSION,
+            name: self.name,
+            start_location: start,
+            player: None,
+            kinds: self.kinds,
+            regions: self.regions,
+            entities: self.entities,
+            relations: vec![],
+            respawns: vec![],
+            schedule: vec![],
+            clock: None,
+            scoring: None,
+            quests: BTreeMap::new(),
+            includes: vec![],
+            namespace: None,
+            locale: None,
+            messages: BTreeMap::new(),
+        }.build()
+    }
+}
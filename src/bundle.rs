@@ -0,0 +1,189 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Bundles: finished games packed into a single file, so they can be
+//! shared.  A bundle holds a world file and the files it includes,
+//! message catalogs, other files like snippets of text or walkthrough
+//! scripts, and the title, author and version of the game.  Bundles
+//! are written in RON, and are compressed when their name asks for it
+//! (see `compress`):
+//!
+//! ```text
+//! Bundle(
+//!     version: 1,
+//!     metadata: (title: "Zones", author: "M. G.", version: "1.0"),
+//!     main: "world.ron",
+//!     files: {"world.ron": "...", "camp.ron": "..."},
+//!     messages: {"en": {"taken": "Got it."}},
+//! )
+//! ```
+//!
+//! Files in bundles are named by their paths relative to the main
+//! world file, with `/` between directories.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use ron;
+
+use super::types::*;
+use super::compress::{read_bytes, read_file, write_file};
+use super::load::{read_world_files, load_from_files};
+use super::migrate::{Migration, migrate};
+
+/// The version of the bundle format.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Migrations of bundles from older versions.
+const MIGRATIONS: &'static [Migration] = &[];
+
+/// Information about the game in a bundle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    /// The version of the game, like "1.0".
+    #[serde(default)]
+    pub version: String,
+}
+
+/// The version of a bundle, which is read before the rest.
+#[derive(Deserialize)]
+#[serde(rename = "Bundle")]
+struct Header {
+    version: u32,
+}
+
+/// A game packed into a single file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename = "Bundle")]
+pub struct Bundle {
+    pub version: u32,
+    pub metadata: Metadata,
+    /// The name of the world file the game is played in.
+    pub main: String,
+    /// The files of the bundle, by name.
+    pub files: BTreeMap<String, String>,
+    /// Messages of the game replaced by the bundle, by locale and
+    /// key.  They take precedence over those of the world files.
+    #[serde(default)]
+    pub messages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Return the name of a file in a bundle: its path with `.` and `..`
+/// resolved, and `/` between directories.
+fn bundle_name(path: &Path) -> Result<String, String> {
+    let mut parts: Vec<String> = vec![];
+    for c in path.components() {
+        match c {
+            Component::Normal(p) => parts.push(p.to_string_lossy().into_owned()),
+            Component::CurDir => {},
+            Component::ParentDir if parts.len() > 0 => {
+                parts.pop();
+            },
+            _ => return Err(format!("file outside of the bundle: {}", path.display())),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+impl Bundle {
+    /// Pack the world file at `main` and the files it includes into a
+    /// bundle.  The included files must be in the directory of the
+    /// main file or below.
+    pub fn pack<P: AsRef<Path>>(main: P, metadata: Metadata) -> Result<Bundle, String> {
+        let main = main.as_ref();
+        let files = try!(read_world_files(main));
+        let dir = match files[0].0.parent() {
+            Some(d) => d.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        let mut bundle = Bundle {
+            version: BUNDLE_VERSION,
+            metadata: metadata,
+            main: String::new(),
+            files: BTreeMap::new(),
+            messages: BTreeMap::new(),
+        };
+        for (path, txt) in files {
+            let name = try!(path.strip_prefix(&dir).map_err(|_| format!("file outside of the bundle: {}", path.display()))
+                            .and_then(bundle_name));
+            if bundle.main.len() == 0 {
+                bundle.main = name.clone();
+            }
+            bundle.files.insert(name, txt);
+        }
+        Ok(bundle)
+    }
+
+    /// Add a file, like a snippet of text or a walkthrough script,
+    /// to the bundle.
+    pub fn add_file(&mut self, name: &str, txt: &str) -> Result<(), String> {
+        let name = try!(bundle_name(Path::new(name)));
+        self.files.insert(name, txt.to_string());
+        Ok(())
+    }
+
+    /// Return the text of a file in the bundle.
+    pub fn file(&self, name: &str) -> Option<&str> {
+        bundle_name(Path::new(name)).ok().and_then(|n| self.files.get(&n)).map(|t| t.as_str())
+    }
+
+    /// Read a bundle from the file at `path`.  Bundles of older
+    /// versions are migrated.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Bundle, String> {
+        Bundle::from_str(&try!(read_file(path.as_ref())))
+    }
+
+    /// Read a bundle from the bytes of a bundle file, like `open`.
+    pub fn from_bytes(data: &[u8]) -> Result<Bundle, String> {
+        Bundle::from_str(&try!(read_bytes(data.to_vec()).map_err(|e| format!("invalid bundle: {}", e))))
+    }
+
+    fn from_str(txt: &str) -> Result<Bundle, String> {
+        let header: Header = try!(ron::de::from_str(txt).map_err(|e| format!("invalid bundle: {}", e)));
+        let txt = try!(migrate(txt, header.version, BUNDLE_VERSION, MIGRATIONS));
+        ron::de::from_str(&txt).map_err(|e| format!("invalid bundle: {}", e))
+    }
+
+    /// Write the bundle to the file at `path`, compressed if its name
+    /// ends in `.gz` or `.zst`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let txt = try!(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                       .map_err(|e| format!("cannot write bundle: {}", e)));
+        write_file(path.as_ref(), &txt)
+    }
+
+    /// Load the world of the bundle.
+    pub fn world(&self) -> Result<World, String> {
+        let txt = try!(self.file(&self.main).ok_or(format!("missing file in bundle: {}", self.main)));
+        let main = Path::new(&self.main);
+        let mut world = try!(match main.extension().and_then(|e| e.to_str()) {
+            Some("mud") => World::compile(txt),
+            Some("trizbort") => World::import_trizbort(txt),
+            _ => {
+                let read = |p: &Path| {
+                    let name = try!(bundle_name(p));
+                    match self.files.get(&name) {
+                        Some(t) => Ok((PathBuf::from(name), t.clone())),
+                        None => Err(format!("missing file in bundle: {}", name)),
+                    }
+                };
+                load_from_files(main, txt, &read)
+            },
+        });
+        for (locale, messages) in self.messages.iter() {
+            world.messages.entry(locale.clone()).or_insert(BTreeMap::new())
+                .extend(messages.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        Ok(world)
+    }
+}
+
+impl World {
+    /// Load the world of the bundle at `path`.
+    pub fn load_bundle<P: AsRef<Path>>(path: P) -> Result<World, String> {
+        try!(Bundle::open(path)).world()
+    }
+}
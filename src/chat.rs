@@ -0,0 +1,256 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Chat between players sharing a world (see `session`).  Players
+//! talk to the players in the same room, on channels, or to each
+//! other in private:
+//!
+//! ```text
+//! say Hallo!                 talk to everyone in the room
+//! chat global Who is there?  talk on a channel
+//! tell anna Over here!       talk to one player
+//! channel join traders       join a channel, or start it
+//! channel leave global       leave a channel
+//! channel                    list the channels
+//! ```
+//!
+//! Everyone starts out on the channel `global`.  Talking in a room
+//! publishes a `Said` event (see `events`), so rooms can have
+//! triggers on `say`.
+//!
+//! Everything said passes the moderation hooks first, which may let
+//! it through, change it, or refuse it with a reason:
+//!
+//! ```text
+//! engine.chat.moderate(|_, u| if u.text.contains("spoiler") {
+//!     Moderation::Refuse("No spoilers, please.".to_string())
+//! } else {
+//!     Moderation::Allow
+//! });
+//! ```
+//!
+//! What is said is not shown to the other players right away, but
+//! kept for them until the program running the engine delivers it,
+//! with `Chat::take_deliveries`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+
+/// The channel everyone is on at first.
+pub const DEFAULT_CHANNEL: &'static str = "global";
+
+/// Where something is said.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Channel {
+    /// To everyone in a room.
+    Room(InternalName),
+    /// To everyone on a channel.
+    Named(String),
+    /// To one player.
+    Private(InternalName),
+}
+
+/// Something a player says.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utterance {
+    pub from: InternalName,
+    pub channel: Channel,
+    pub text: String,
+}
+
+/// What a moderation hook decides.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Moderation {
+    Allow,
+    /// Say this instead.
+    Replace(String),
+    /// Say nothing, and tell the player why.
+    Refuse(String),
+}
+
+/// A function deciding whether something may be said.
+pub type Hook = Box<FnMut(&World, &Utterance) -> Moderation + Send>;
+
+/// The channels of a shared world, and what is waiting to be shown
+/// to the players.
+#[derive(Default)]
+pub struct Chat {
+    /// The players on each channel, by channel name.
+    channels: BTreeMap<String, BTreeSet<InternalName>>,
+    hooks: Vec<Hook>,
+    /// Text for players, in the order it was said.
+    deliveries: Vec<(InternalName, String)>,
+}
+
+impl Chat {
+    /// Pass everything said to `hook` before it is said.
+    pub fn moderate<F: FnMut(&World, &Utterance) -> Moderation + Send + 'static>(&mut self, hook: F) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Put a player on a channel.
+    pub fn join(&mut self, player: InternalName, channel: &str) {
+        self.channels.entry(channel.to_string()).or_insert(BTreeSet::new()).insert(player);
+    }
+
+    /// Take a player off a channel.  Returns false if the player was
+    /// not on it.  Channels nobody is on are gone.
+    pub fn leave(&mut self, player: &InternalName, channel: &str) -> bool {
+        let left = self.channels.get_mut(channel).map_or(false, |c| c.remove(player));
+        self.channels.retain(|_, c| !c.is_empty());
+        left
+    }
+
+    /// Take a player off all channels.
+    pub fn leave_all(&mut self, player: &InternalName) {
+        let names: Vec<String> = self.channels.keys().cloned().collect();
+        for n in names {
+            self.leave(player, &n);
+        }
+    }
+
+    /// Return the players on a channel.
+    pub fn members(&self, channel: &str) -> Vec<InternalName> {
+        self.channels.get(channel).map(|c| c.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Return the text waiting to be shown to players, and forget it.
+    pub fn take_deliveries(&mut self) -> Vec<(InternalName, String)> {
+        mem::replace(&mut self.deliveries, vec![])
+    }
+
+    /// Run the moderation hooks on an utterance.  Returns the text to
+    /// say, or the reason it is refused.
+    fn check(&mut self, world: &World, mut u: Utterance) -> Result<Utterance, String> {
+        for h in self.hooks.iter_mut() {
+            match h(world, &u) {
+                Moderation::Allow => {},
+                Moderation::Replace(t) => u.text = t,
+                Moderation::Refuse(why) => return Err(why),
+            }
+        }
+        Ok(u)
+    }
+}
+
+impl Engine {
+    /// Return the character of the player with the given name.
+    fn player_named(&self, name: &str) -> Option<InternalName> {
+        let name = name.to_lowercase();
+        self.world.entities_with_tag("player").into_iter()
+            .find(|p| self.world.entity(p).map_or(false, |e| e.name == vec![name.clone()]))
+    }
+
+    /// Say something, after the moderation hooks have had their say,
+    /// and keep it for the players hearing it.
+    fn utter(&mut self, u: Utterance) {
+        let u = match self.chat.check(&self.world, u) {
+            Ok(u) => u,
+            Err(why) => {
+                say!("{}", why);
+                return;
+            },
+        };
+        let w = &self.world;
+        let name = w.short_description(&u.from);
+        let (hearers, text) = match u.channel {
+            Channel::Room(room) => {
+                say!("{}", msg!(w, "say_self", text = u.text));
+                let hearers = w.entity(&room).and_then(|e| e.room())
+                    .map(|r| r.entities.iter().filter(|e| w.entity(e).map_or(false, |e| e.tags.iter().any(|t| t == "player")))
+                         .cloned().collect())
+                    .unwrap_or_default();
+                (hearers, msg!(w, "says", name = name, text = u.text))
+            },
+            Channel::Named(ref c) => {
+                let text = format!("[{}] {}: {}", c, name, u.text);
+                say!("{}", text);
+                (self.chat.members(c), text)
+            },
+            Channel::Private(to) => {
+                say!("You tell {}: {}", w.short_description(&to), u.text);
+                (vec![to], format!("{} tells you: {}", name, u.text))
+            },
+        };
+        for h in hearers.into_iter().filter(|h| *h != u.from) {
+            self.chat.deliveries.push((h, text.clone()));
+        }
+        if let Channel::Room(room) = u.channel {
+            self.world.publish(Event::Said { who: u.from, room: room, text: u.text });
+            self.world.dispatch();
+        }
+    }
+
+    /// Handle the commands for chatting.  Returns false if the line is
+    /// not one of them.
+    pub fn chat_command(&mut self, session: &Session, line: &str) -> bool {
+        let (word, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        let me = session.player;
+        match word {
+            "say" if rest.is_empty() => say!("Usage: say TEXT"),
+            "say" => {
+                let room = self.world.location_of(&me).unwrap_or(self.world.start_location);
+                self.utter(Utterance { from: me, channel: Channel::Room(room), text: rest.to_string() });
+            },
+            "chat" | "tell" => {
+                let (to, text) = match rest.find(' ') {
+                    Some(i) => (&rest[..i], rest[i + 1..].trim()),
+                    None => {
+                        say!("Usage: {} {} TEXT", word, if word == "chat" { "CHANNEL" } else { "PLAYER" });
+                        return true;
+                    },
+                };
+                let channel = if word == "chat" {
+                    if !self.chat.members(to).contains(&me) {
+                        say!("You are not on the channel {}.", to);
+                        return true;
+                    }
+                    Channel::Named(to.to_string())
+                } else {
+                    match self.player_named(to) {
+                        Some(p) => Channel::Private(p),
+                        None => {
+                            say!("Nobody called {} is playing.", to);
+                            return true;
+                        },
+                    }
+                };
+                self.utter(Utterance { from: me, channel: channel, text: text.to_string() });
+            },
+            "channel" | "channels" => {
+                let mut words = rest.split_whitespace();
+                match (words.next(), words.next()) {
+                    (None, _) => {
+                        let mine: Vec<&String> = self.chat.channels.iter().filter(|&(_, m)| m.contains(&me)).map(|(c, _)| c).collect();
+                        if mine.is_empty() {
+                            say!("You are on no channel.");
+                        }
+                        for c in mine {
+                            say!("{} ({} players)", c, self.chat.members(c).len());
+                        }
+                    },
+                    (Some("join"), Some(c)) => {
+                        self.chat.join(me, c);
+                        say!("You are on the channel {} now.", c);
+                    },
+                    (Some("leave"), Some(c)) =>
+                        if self.chat.leave(&me, c) {
+                            say!("You have left the channel {}.", c);
+                        } else {
+                            say!("You are not on the channel {}.", c);
+                        },
+                    _ => say!("Usage: channel [join NAME | leave NAME]"),
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+}
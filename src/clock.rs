@@ -0,0 +1,72 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Game time.  Each turn takes some minutes of game time, and the
+//! day is divided into phases, like morning or night.  Worlds set up
+//! their clock in the `clock` field:
+//!
+//! ```text
+//! clock: Some(Clock(minutes_per_turn: 5, start: 20, phases: [
+//!     Phase(name: "nacht", hour: 0),
+//!     Phase(name: "tag", hour: 6),
+//!     Phase(name: "nacht", hour: 21),
+//! ])),
+//! ```
+//!
+//! Templates consult the clock with `(time)`, the time of day like
+//! `08:30`, `(day)`, the number of the day starting with 1, and
+//! `(phase)`, the name of the phase of the day, or `(phase "nacht")`,
+//! which is true at night.  That way, descriptions, the barriers of
+//! doors and ambient messages can depend on the time of day:
+//!
+//! ```text
+//! barrier: Some(Barrier(condition: "(phase \"tag\")",
+//!                       refusal: "Nachts ist das Tor verschlossen.")),
+//! ```
+
+use super::types::*;
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+impl Default for Clock {
+    /// Ten minutes per turn, starting at eight in the morning.
+    fn default() -> Clock {
+        let phases = [("nacht", 0), ("morgen", 6), ("tag", 10), ("abend", 18), ("nacht", 22)];
+        Clock {
+            minutes_per_turn: 10,
+            start: 8,
+            phases: phases.iter().map(|&(n, h)| Phase { name: n.to_string(), hour: h }).collect(),
+        }
+    }
+}
+
+impl World {
+    /// Return the minutes of game time passed since midnight of the
+    /// first day.
+    pub fn minutes(&self) -> u64 {
+        self.clock.start as u64 * 60 + self.turn * self.clock.minutes_per_turn as u64
+    }
+
+    /// Return the number of the current day, starting with 1.
+    pub fn day(&self) -> u64 {
+        self.minutes() / MINUTES_PER_DAY + 1
+    }
+
+    /// Return the time of day as hours and minutes.
+    pub fn time_of_day(&self) -> (u32, u32) {
+        let m = self.minutes() % MINUTES_PER_DAY;
+        ((m / 60) as u32, (m % 60) as u32)
+    }
+
+    /// Return the name of the current phase of the day: the phase
+    /// starting last before now, or the latest one if all start
+    /// later.  Without phases, there are no names.
+    pub fn phase(&self) -> &str {
+        let (hour, _) = self.time_of_day();
+        let phases = &self.clock.phases;
+        phases.iter().filter(|p| p.hour <= hour).max_by_key(|p| p.hour)
+            .or_else(|| phases.iter().max_by_key(|p| p.hour))
+            .map(|p| p.name.as_str())
+            .unwrap_or("")
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Combat.  Players and hostile characters fight with the weapons
+//! they carry.  Damage is rolled with the world's random number
+//! generator and reduced by armor.  A character whose health drops
+//! to zero is dead and drops everything it carried.
+
+use std::cmp;
+
+use super::types::*;
+use super::command::Command;
+use super::error::Error;
+use super::print_wrap;
+
+/// Damage rolled by someone fighting without a weapon.
+const UNARMED_DAMAGE: u32 = 2;
+
+impl World {
+    /// Return the damage of the best weapon among the given items.
+    fn best_weapon(&self, items: &[InternalName]) -> u32 {
+        items.iter()
+            .filter_map(|n| self.entity(n).and_then(|e| e.weapon()))
+            .map(|w| w.damage)
+            .max()
+            .unwrap_or(UNARMED_DAMAGE)
+    }
+
+    /// Return the total armor of the wearable things among the given
+    /// items.
+    fn armor_of(&self, items: &[InternalName]) -> i32 {
+        items.iter()
+            .filter_map(|n| self.entity(n).and_then(|e| e.wearable()))
+            .map(|w| w.armor)
+            .sum()
+    }
+
+    /// Roll one attack.  Returns None on a miss, otherwise the damage
+    /// done, which may be zero when armor absorbs the blow.
+    fn roll_attack(&mut self, weapon: u32, strength: i32, armor: i32) -> Option<i32> {
+        if self.rng.roll(10) <= 2 {
+            None
+        } else {
+            Some(cmp::max(0, self.rng.roll(weapon) as i32 + strength / 4 - armor))
+        }
+    }
+
+    /// Handle the death of a character: everything it carried drops
+    /// to the floor of the room it is in.  Returns true if anything
+    /// was dropped.
+    fn drop_loot(&mut self, name: &InternalName) -> bool {
+        let loot = match self.entity_mut(name).and_then(|e| e.character_mut()) {
+            Some(c) => c.inventory.drain(..).collect::<Vec<_>>(),
+            None => return false,
+        };
+        if loot.len() == 0 {
+            return false;
+        }
+        if let Some(room) = self.location_of(name) {
+            if let Some(r) = self.entity_mut(&room).and_then(|e| e.room_mut()) {
+                r.entities.extend(loot);
+            }
+        }
+        true
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return true if the player has died.
+    pub fn is_dead(&self) -> bool {
+        self.me().stats.health <= 0
+    }
+
+    /// Attack the direct object, with the weapon given as indirect
+    /// object or the best weapon the player carries.  A character
+    /// which is attacked becomes hostile.
+    pub fn attack(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "attack_whom"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.room_contents()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "nobody_here"));
+                return Ok(());
+            }
+        };
+        let armor = match self.world.entity(&name).unwrap().character() {
+            Some(c) if c.stats.health > 0 =>
+                self.world.armor_of(&c.inventory),
+            Some(_) => {
+                say!("{}", msg!(self.world, "already_dead"));
+                return Ok(());
+            },
+            None => {
+                say!("{}", msg!(self.world, "pointless_attack"));
+                return Ok(());
+            }
+        };
+        let weapon = match cmd.indirect_object {
+            Some((_, ref w)) =>
+                match self.world.find_entity(w, &self.me().inventory) {
+                    Some(n) if self.world.entity(&n).unwrap().weapon().is_some() =>
+                        self.world.best_weapon(&[n]),
+                    Some(_) => {
+                        say!("{}", msg!(self.world, "no_weapon"));
+                        return Ok(());
+                    },
+                    None => {
+                        say!("{}", msg!(self.world, "not_carried"));
+                        return Ok(());
+                    }
+                },
+            None =>
+                self.world.best_weapon(&self.me().inventory),
+        };
+        let target = self.world.short_description(&name);
+        let damage = self.world.roll_attack(weapon, self.me().stats.strength, armor);
+        let dead = match self.world.entity_mut(&name).and_then(|e| e.character_mut()) {
+            Some(c) => {
+                c.hostile = true;
+                c.stats.health = cmp::max(0, c.stats.health - damage.unwrap_or(0));
+                c.stats.health <= 0
+            },
+            None => false,
+        };
+        match damage {
+            None => print_wrap(&msg!(self.world, "miss", target = target), 72),
+            Some(0) => say!("{}", msg!(self.world, "no_effect")),
+            Some(_) => print_wrap(&msg!(self.world, "hit", target = target), 72),
+        }
+        let me = self.world.short_description(&self.id);
+        let seen = match damage {
+            None => msg!(self.world, "fights_miss", attacker = me, target = target),
+            Some(0) => msg!(self.world, "fights_glance", attacker = me, target = target),
+            Some(_) => msg!(self.world, "fights_hit", attacker = me, target = target),
+        };
+        self.world.witness(&self.id, seen);
+        if dead {
+            let collapsed = msg!(self.world, "collapses", target = target);
+            print_wrap(&collapsed, 72);
+            self.world.witness(&self.id, collapsed);
+            self.world.publish(Event::NpcDied { npc: name, killer: Some(self.id) });
+            if self.world.drop_loot(&name) {
+                say!("{}", msg!(self.world, "something_falls"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Let every living hostile character in the player's location
+    /// attack the player.  Characters played by other players only
+    /// fight when their players attack.
+    pub fn defend(&mut self) {
+        if self.is_dead() {
+            return;
+        }
+        for n in self.room_contents() {
+            if self.is_dead() {
+                break;
+            }
+            let (weapon, strength) = match self.world.entity(&n) {
+                Some(e) if e.tags.iter().any(|t| t == "player") => continue,
+                Some(e) => match e.character() {
+                    Some(c) if c.hostile && c.stats.health > 0 =>
+                        (self.world.best_weapon(&c.inventory), c.stats.strength),
+                    _ => continue,
+                },
+                None => continue,
+            };
+            let attacker = self.world.short_description(&n);
+            let me = self.world.short_description(&self.id);
+            let armor = self.armor();
+            let seen = match self.world.roll_attack(weapon, strength, armor) {
+                None => {
+                    print_wrap(&msg!(self.world, "attacked_miss", attacker = attacker), 72);
+                    msg!(self.world, "fights_miss", attacker = attacker, target = me)
+                },
+                Some(0) => {
+                    print_wrap(&msg!(self.world, "attacked_glance", attacker = attacker), 72);
+                    msg!(self.world, "fights_glance", attacker = attacker, target = me)
+                },
+                Some(d) => {
+                    self.me_mut().stats.health = cmp::max(0, self.me().stats.health - d);
+                    print_wrap(&msg!(self.world, "attacked_hit", attacker = attacker), 72);
+                    msg!(self.world, "fights_hit", attacker = attacker, target = me)
+                }
+            };
+            self.world.witness(&self.id, seen);
+        }
+        if self.is_dead() {
+            say!("{}", msg!(self.world, "died"));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Combination locks.  Locks with a code are opened by entering the
+//! code on a code input, by turning a dial ("turn dial to 7") or
+//! typing on a keypad ("enter 1234 on keypad").
+
+use super::types::*;
+use super::command::{Command, Connector};
+use super::error::Error;
+
+impl<'a> PlayerState<'a> {
+    /// Turn a dial to a digit ("turn dial to 7").
+    pub fn turn(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "turn_what"));
+                return Ok(());
+            }
+        };
+        let input = match cmd.indirect_object {
+            Some((Connector::To, ref v)) => v.concat(),
+            _ => {
+                say!("{}", msg!(self.world, "turn_to_what"));
+                return Ok(());
+            }
+        };
+        self.input_code(words, input);
+        Ok(())
+    }
+
+    /// Enter a code on a keypad ("enter 1234 on keypad").
+    pub fn enter_code(&mut self, cmd: &Command) -> Result<(), Error> {
+        let input = match cmd.direct_object {
+            Some(ref w) => w.concat(),
+            None => {
+                say!("{}", msg!(self.world, "enter_what"));
+                return Ok(());
+            }
+        };
+        match cmd.indirect_object {
+            Some((Connector::Onto, ref w)) | Some((Connector::Into, ref w)) => self.input_code(w, input),
+            _ => say!("{}", msg!(self.world, "enter_on_what")),
+        }
+        Ok(())
+    }
+
+    /// Add input to the code input named by `words`, and open its
+    /// lock when the right code has been entered.
+    fn input_code(&mut self, words: &Name, input: String) {
+        let device = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return;
+            }
+        };
+        if !self.reach(&device) {
+            return;
+        }
+        let (target, entered) = match self.world.entity_mut(&device).and_then(|e| e.code_input_mut()) {
+            Some(c) => {
+                c.entered.push_str(&input);
+                (c.target, c.entered.clone())
+            },
+            None => {
+                say!("{}", msg!(self.world, "no_input"));
+                return;
+            }
+        };
+        let code = match self.world.entity(&target).and_then(|e| e.lock()).and_then(|l| l.code.clone()) {
+            Some(c) => c,
+            None => {
+                say!("{}", msg!(self.world, "nothing_happens"));
+                return;
+            }
+        };
+        if entered.len() < code.len() {
+            say!("{}", msg!(self.world, "click"));
+            return;
+        }
+        if let Some(c) = self.world.entity_mut(&device).and_then(|e| e.code_input_mut()) {
+            c.entered.clear();
+        }
+        if entered == code {
+            if let Some(l) = self.world.entity_mut(&target).and_then(|e| e.lock_mut()) {
+                l.locked = false;
+            }
+            say!("{}", msg!(self.world, "click_open"));
+        } else {
+            say!("{}", msg!(self.world, "click_nothing"));
+        }
+    }
+}
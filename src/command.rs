@@ -27,7 +27,7 @@ pub struct Command {
     pub indirect_object: Option<(Connector, types::Name)>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Verb {
     Get,
     Put,
@@ -37,6 +37,31 @@ pub enum Verb {
     Drink,
     Eat,
     Sleep,
+    Lock,
+    Unlock,
+    Wear,
+    Remove,
+    Read,
+    Ask,
+    Attack,
+    Sell,
+    List,
+    Look,
+    Search,
+    Open,
+    Close,
+    Enter,
+    Leave,
+    Tie,
+    Untie,
+    SwitchOn,
+    SwitchOff,
+    Light,
+    Listen,
+    Smell,
+    Give,
+    Show,
+    Turn,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -45,9 +70,11 @@ pub enum Connector {
     Onto,
     Under,
     Beside,
+    Behind,
     To,
     From,
     With,
+    About,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -56,6 +83,34 @@ pub enum Direction {
     East,
     South,
     West,
+    Northeast,
+    Northwest,
+    Southeast,
+    Southwest,
+    Up,
+    Down,
+}
+
+impl Verb {
+    /// Return the name of the verb: "on" and "off" for switching,
+    /// otherwise the verb's first word in the verb table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Verb::SwitchOn => "on",
+            Verb::SwitchOff => "off",
+            _ => VERBS.iter().find(|&&(_, v)| v == self).map(|&(w, _)| w).unwrap_or(""),
+        }
+    }
+
+    /// Return true if the verb needs a direct object, so the game
+    /// asks for one when it is missing.
+    pub fn needs_object(self) -> bool {
+        match self {
+            Verb::Use | Verb::Move | Verb::Sleep | Verb::List | Verb::Look |
+            Verb::Search | Verb::Leave | Verb::Listen | Verb::Smell => false,
+            _ => true,
+        }
+    }
 }
 
 pub const VERBS: &'static[(&'static str, Verb)] =
@@ -64,16 +119,69 @@ pub const VERBS: &'static[(&'static str, Verb)] =
         ("take", Verb::Get),
         ("acquire", Verb::Get),
         ("put", Verb::Put),
-        ("give", Verb::Put),
+        ("give", Verb::Give),
+        ("show", Verb::Show),
         ("toss", Verb::Put),
         ("drop", Verb::Put),
         ("use", Verb::Use),
         ("move", Verb::Move),
         ("go", Verb::Move),
         ("buy", Verb::Buy),
+        ("sell", Verb::Sell),
+        ("list", Verb::List),
+        ("browse", Verb::List),
+        ("look", Verb::Look),
+        ("examine", Verb::Look),
+        ("search", Verb::Search),
+        ("open", Verb::Open),
+        ("close", Verb::Close),
+        ("shut", Verb::Close),
+        ("turn", Verb::Turn),
+        ("dial", Verb::Turn),
+        ("enter", Verb::Enter),
+        ("type", Verb::Enter),
+        ("board", Verb::Enter),
+        ("mount", Verb::Enter),
+        ("leave", Verb::Leave),
+        ("disembark", Verb::Leave),
+        ("dismount", Verb::Leave),
+        ("tie", Verb::Tie),
+        ("attach", Verb::Tie),
+        ("chain", Verb::Tie),
+        ("untie", Verb::Untie),
+        ("detach", Verb::Untie),
+        ("light", Verb::Light),
+        ("ignite", Verb::Light),
+        ("listen", Verb::Listen),
+        ("hear", Verb::Listen),
+        ("smell", Verb::Smell),
+        ("sniff", Verb::Smell),
         ("drink", Verb::Drink),
         ("eat", Verb::Eat),
         ("sleep", Verb::Sleep),
+        ("lock", Verb::Lock),
+        ("unlock", Verb::Unlock),
+        ("wear", Verb::Wear),
+        ("don", Verb::Wear),
+        ("remove", Verb::Remove),
+        ("doff", Verb::Remove),
+        ("read", Verb::Read),
+        ("ask", Verb::Ask),
+        ("attack", Verb::Attack),
+        ("hit", Verb::Attack),
+        ("fight", Verb::Attack),
+        ("kill", Verb::Attack),
+    ];
+
+/// Verbs consisting of a verb word and a particle.  The particle
+/// either follows the verb word ("turn on lamp") or ends the command
+/// ("turn lamp on").
+pub const PHRASAL_VERBS: &'static[(&'static str, &'static str, Verb)] =
+    &[
+        ("turn", "on", Verb::SwitchOn),
+        ("turn", "off", Verb::SwitchOff),
+        ("switch", "on", Verb::SwitchOn),
+        ("switch", "off", Verb::SwitchOff),
     ];
 
 pub const CONNECTORS: &'static[(&'static str, Connector)] =
@@ -83,9 +191,12 @@ pub const CONNECTORS: &'static[(&'static str, Connector)] =
         ("on", Connector::Onto),
         ("onto", Connector::Onto),
         ("under", Connector::Under),
+        ("behind", Connector::Behind),
+        ("beside", Connector::Beside),
         ("to", Connector::To),
         ("from", Connector::From),
         ("with", Connector::With),
+        ("about", Connector::About),
     ];
 
 
@@ -95,6 +206,12 @@ pub const DIRECTIONS: &'static[(&'static str, Direction)] =
         ("east", Direction::East),
         ("south", Direction::South),
         ("west", Direction::West),
+        ("northeast", Direction::Northeast),
+        ("northwest", Direction::Northwest),
+        ("southeast", Direction::Southeast),
+        ("southwest", Direction::Southwest),
+        ("up", Direction::Up),
+        ("down", Direction::Down),
     ];
 
 pub const IGNORED: &'static [&'static str] =
@@ -102,6 +219,7 @@ pub const IGNORED: &'static [&'static str] =
         "a",
         "an",
         "the",
+        "at",
     ];
 
 
@@ -122,7 +240,31 @@ fn find_verb(s: &str) -> Option<Verb> {
     VERBS.iter().find(|&&(t, _)| s == t).map(|&(_, vrb)| vrb)
 }
 
-/// Find the verb matching string s, or None if there is no match.
+/// Find the phrasal verb of a command, given as list of words.  If
+/// there is one, its particle is removed from the words and the verb
+/// is returned.
+fn find_phrasal_verb(words: &mut Vec<String>) -> Option<Verb> {
+    if words.len() < 2 {
+        return None;
+    }
+    for &(head, particle, verb) in PHRASAL_VERBS.iter() {
+        if words[0] != head {
+            continue;
+        }
+        if words[1] == particle {
+            words.remove(1);
+            return Some(verb);
+        }
+        if words[words.len() - 1] == particle {
+            words.pop();
+            return Some(verb);
+        }
+    }
+    None
+}
+
+/// Find the direction matching string s, or None if there is no
+/// match.
 fn find_direction(s: &str) -> Option<Direction> {
     DIRECTIONS.iter().find(|&&(t, _)| s == t).map(|&(_, dir)| dir)
 }
@@ -130,8 +272,12 @@ fn find_direction(s: &str) -> Option<Direction> {
 /// Parse a string as a MUD-like command.  Return either a command
 /// structure or an error when the string cannot be parsed.
 pub fn parse(s: &str) -> Result<Command, error::Error> {
-    // Convert string slice to iterator over non-empty lowercase words.
-    let mut words = s.split(' ').filter(|s| s.len() > 0).map(|s| s.to_lowercase());
+    // Convert string slice to iterator over non-empty lowercase words,
+    // after taking out the particle of a phrasal verb, so that it is
+    // not mistaken for a connector.
+    let mut all: Vec<String> = s.split(' ').filter(|s| s.len() > 0).map(|s| s.to_lowercase()).collect();
+    let phrasal = find_phrasal_verb(&mut all);
+    let mut words = all.into_iter();
 
     let mut direct_object: Vec<String> = vec![];
 
@@ -139,7 +285,9 @@ pub fn parse(s: &str) -> Result<Command, error::Error> {
     // is wrong.
     let verb_str = try!(words.next().
                         ok_or(error::Error::CommandParse("command expected")));
-    let verb = if let Some(_dir) = find_direction(&verb_str) {
+    let verb = if let Some(vrb) = phrasal {
+        vrb
+    } else if let Some(_dir) = find_direction(&verb_str) {
         direct_object.push(verb_str.clone());
         Verb::Move
     } else {
@@ -0,0 +1,103 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Reading and writing files which may be compressed.  Large worlds
+//! and saved games can be compressed with gzip or zstd when the crate
+//! is built with the `compression` feature.  Files are written
+//! compressed when their name ends in `.gz` or `.zst`, and compressed
+//! files are recognized by their contents when they are read,
+//! whatever they are called.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "compression")]
+use flate2;
+#[cfg(feature = "compression")]
+use zstd;
+
+/// The compression methods, with their file extensions and the first
+/// bytes of files compressed with them.
+const METHODS: &'static [(&'static str, &'static str, &'static [u8])] =
+    &[
+        ("gzip", "gz", &[0x1f, 0x8b]),
+        ("zstd", "zst", &[0x28, 0xb5, 0x2f, 0xfd]),
+    ];
+
+/// Return the compression method for a file name, if any.
+fn method_for_name(path: &Path) -> Option<&'static str> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    METHODS.iter().find(|&&(_, e, _)| e == ext).map(|&(m, _, _)| m)
+}
+
+/// Return the name a file would have if it was not compressed, which
+/// tells its format.
+pub fn uncompressed_name(path: &Path) -> PathBuf {
+    match method_for_name(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+#[cfg(feature = "compression")]
+fn compress(method: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    match method {
+        "gzip" => {
+            let mut enc = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+            try!(enc.write_all(data).map_err(|e| e.to_string()));
+            enc.finish().map_err(|e| e.to_string())
+        },
+        _ => zstd::encode_all(data, 0).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(feature = "compression")]
+fn decompress(method: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut res = vec![];
+    match method {
+        "gzip" => {
+            try!(flate2::read::GzDecoder::new(data).read_to_end(&mut res).map_err(|e| e.to_string()));
+            Ok(res)
+        },
+        _ => zstd::decode_all(data).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress(method: &str, _data: &[u8]) -> Result<Vec<u8>, String> {
+    Err(format!("{} compression is not supported in this build", method))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(method: &str, _data: &[u8]) -> Result<Vec<u8>, String> {
+    Err(format!("the file is compressed with {}, which is not supported in this build", method))
+}
+
+/// Return the text in some bytes, decompressing them if they are
+/// compressed.
+pub fn read_bytes(data: Vec<u8>) -> Result<String, String> {
+    let data = match METHODS.iter().find(|&&(_, _, magic)| data.starts_with(magic)) {
+        Some(&(m, _, _)) => try!(decompress(m, &data)),
+        None => data,
+    };
+    String::from_utf8(data).map_err(|_| "not UTF-8 text".to_string())
+}
+
+/// Read the text of a file, decompressing it if it is compressed.
+pub fn read_file(path: &Path) -> Result<String, String> {
+    let mut data = vec![];
+    try!(File::open(path).and_then(|mut f| f.read_to_end(&mut data))
+         .map_err(|e| format!("cannot read {}: {}", path.display(), e)));
+    read_bytes(data).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+/// Write text to a file, compressed if the file name asks for it.
+pub fn write_file(path: &Path, txt: &str) -> Result<(), String> {
+    let data = match method_for_name(path) {
+        Some(m) => try!(compress(m, txt.as_bytes()).map_err(|e| format!("cannot write {}: {}", path.display(), e))),
+        None => txt.as_bytes().to_vec(),
+    };
+    File::create(path).and_then(|mut f| f.write_all(&data))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
@@ -0,0 +1,149 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Containers.  Things can be put into containers as long as they
+//! fit, and the contents of open containers can be reached like
+//! everything else around the player.
+
+use super::types::*;
+use super::command::{Command, Connector};
+use super::error::Error;
+use super::actions::split_quantity;
+
+impl World {
+    /// Return how much room an entity takes up in a container: its
+    /// bulk, times the number of items for stacks.
+    pub fn bulk(&self, name: &InternalName) -> u32 {
+        let each = self.entity(name).and_then(|e| e.attributes.iter().filter_map(|a| match a {
+            &Attribute::Bulky(b) => Some(b),
+            _ => None,
+        }).next()).unwrap_or(1);
+        each * self.quantity(name)
+    }
+
+    /// Return true if a container is open, or cannot be closed at
+    /// all.
+    pub fn is_open(&self, name: &InternalName) -> bool {
+        self.entity(name).and_then(|e| e.closed()).map(|c| !c).unwrap_or(true)
+    }
+
+    /// Return true if an entity is the given container, or is inside
+    /// it, however deeply nested.
+    pub fn is_within(&self, name: &InternalName, container: &InternalName) -> bool {
+        let mut at = Some(*name);
+        while let Some(n) = at {
+            if n == *container {
+                return true;
+            }
+            at = self.holder_of(&n);
+        }
+        false
+    }
+
+    /// Return the contents of a container, or nothing if it is not an
+    /// open container.
+    pub fn open_contents(&self, name: &InternalName) -> Vec<InternalName> {
+        match self.entity(name).and_then(|e| e.container()) {
+            Some(c) if self.is_open(name) => c.contents.clone(),
+            _ => vec![],
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return the visible contents of all containers in the player's
+    /// location or hands.
+    pub fn container_contents(&self) -> Vec<InternalName> {
+        let mut res = vec![];
+        for n in self.room_contents().iter().chain(self.me().inventory.iter()) {
+            res.extend(self.world.visible_contents(n));
+        }
+        res
+    }
+
+    /// Put a carried item into a container ("put coin into bag"), if
+    /// there is enough room left in it.  Part of a stack can be put
+    /// in, as in "put 3 nails into bag".
+    pub fn put_into(&mut self, cmd: &Command) -> Result<(), Error> {
+        let (count, words) = match cmd.direct_object {
+            Some(ref w) => split_quantity(w),
+            None => {
+                say!("{}", msg!(self.world, "put_what"));
+                return Ok(());
+            }
+        };
+        let target = match cmd.indirect_object {
+            Some((Connector::Into, ref w)) => w,
+            _ => return Err(Error::Unimplemented("putting things anywhere but into containers")),
+        };
+        let item = match self.world.find_entity(&words, &self.me().inventory) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_carried"));
+                return Ok(());
+            }
+        };
+        let container = match self.world.find_entity(target, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&container) {
+            return Ok(());
+        }
+        let (used, capacity) = match self.world.entity(&container).and_then(|e| e.container()) {
+            Some(_) if self.world.is_within(&container, &item) => {
+                say!("{}", msg!(self.world, "impossible"));
+                return Ok(());
+            },
+            Some(_) if !self.world.is_open(&container) => {
+                say!("{}", msg!(self.world, "closed"));
+                return Ok(());
+            },
+            Some(c) => (c.contents.iter().map(|i| self.world.bulk(i)).sum::<u32>(), c.capacity),
+            None => {
+                say!("{}", msg!(self.world, "not_container"));
+                return Ok(());
+            }
+        };
+        let quantity = self.world.quantity(&item);
+        let bulk = self.world.bulk(&item) / quantity * count.unwrap_or(quantity);
+        if capacity.map(|c| used + bulk > c).unwrap_or(false) {
+            say!("{}", msg!(self.world, "does_not_fit"));
+            return Ok(());
+        }
+        let item = match self.take_part(&item, count) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        self.me_mut().inventory.retain(|e| *e != item);
+        let contents = self.world.open_contents(&container);
+        if self.world.merge_stack(&item, &contents).is_none() {
+            if let Some(c) = self.world.entity_mut(&container).and_then(|e| e.container_mut()) {
+                c.contents.push(item);
+            }
+        }
+        say!("{}", msg!(self.world, "put_done"));
+        Ok(())
+    }
+
+    /// List the contents of a container, if they can be seen.
+    pub fn show_contents(&self, name: &InternalName) {
+        let contents = self.world.visible_contents(name);
+        if contents.len() == 0 {
+            return;
+        }
+        say!("{}", msg!(self.world, "contents"));
+        for n in contents.iter() {
+            let e = self.world.entity(n).unwrap();
+            match self.eval_str(e.short_text(self.world.locale())) {
+                Ok(s) =>
+                    say!("  {}", self.world.with_quantity(s, self.world.quantity(n))),
+                Err(e) =>
+                    say!("{}", msg!(self.world, "error", error = e))
+            }
+        }
+    }
+}
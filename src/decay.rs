@@ -0,0 +1,114 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Decay and respawning.  Perishable entities disappear after a
+//! number of turns, and respawn rules bring entities back some turns
+//! after they have been destroyed or killed, so that worlds don't run
+//! dry when played for a long time.
+
+use super::types::*;
+
+impl World {
+    /// Let all perishable entities decay by one turn, destroying those
+    /// which have no turns left.  Returns their messages, each
+    /// together with the room or character which held the entity.
+    pub fn decay(&mut self) -> Vec<(InternalName, String)> {
+        let mut gone = Vec::new();
+        for e in self.entities.iter_mut() {
+            let id = e.id;
+            if let Some(d) = e.decay_mut() {
+                self.changed.insert(id);
+                if d.turns > 0 {
+                    d.turns -= 1;
+                }
+                if d.turns == 0 {
+                    gone.push((id, d.message.clone()));
+                }
+            }
+        }
+        let mut notices = Vec::new();
+        for (id, message) in gone {
+            if let Some(h) = self.holder_of(&id) {
+                notices.push((h, message));
+            }
+            self.destroy(&id);
+        }
+        notices
+    }
+
+    /// Add a rule which brings the given entity back, as it is now, to
+    /// the room it is in now, `delay` turns after it went missing.
+    /// Does nothing if there is no such entity or it is not in a room.
+    pub fn add_respawn(&mut self, name: InternalName, delay: u32, message: String) {
+        let entity = match self.entity(&name) {
+            Some(e) => e.clone(),
+            None => return,
+        };
+        if let Some(room) = self.location_of(&name) {
+            self.respawns.push(Respawn {
+                entity: entity,
+                room: room,
+                delay: delay,
+                countdown: None,
+                message: message,
+            });
+        }
+    }
+
+    /// Return true if the entity is gone for the purpose of
+    /// respawning: destroyed, or a dead character.
+    fn missing(&self, name: &InternalName) -> bool {
+        match self.entity(name) {
+            Some(e) => e.character().map(|c| c.stats.health == 0).unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// Remove everything from the inventory and contents of a
+    /// respawning entity which no longer exists or has been taken
+    /// elsewhere in the meantime, so nothing is held twice.
+    fn reclaim(&self, entity: &mut Entity) {
+        let free = |n: &InternalName| self.entity(n).is_some() && self.holder_of(n).is_none();
+        for a in entity.attributes.iter_mut() {
+            match *a {
+                Attribute::Container(ref mut c) => c.contents.retain(|n| free(n)),
+                Attribute::Characterlike(ref mut c) => {
+                    c.inventory.retain(|n| free(n));
+                    c.worn.retain(|n| free(n));
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Advance all respawn rules by one turn, bringing back entities
+    /// whose time has come.  A dead character is replaced by its
+    /// living self.  Returns the respawn messages, each together with
+    /// the room the entity reappeared in.
+    pub fn respawn(&mut self) -> Vec<(InternalName, String)> {
+        let mut due = Vec::new();
+        for i in 0..self.respawns.len() {
+            let missing = self.missing(&self.respawns[i].entity.id);
+            let r = &mut self.respawns[i];
+            if !missing {
+                r.countdown = None;
+                continue;
+            }
+            let left = r.countdown.unwrap_or(r.delay).saturating_sub(1);
+            if left == 0 {
+                r.countdown = None;
+                due.push((r.entity.clone(), r.room, r.message.clone()));
+            } else {
+                r.countdown = Some(left);
+            }
+        }
+        let mut notices = Vec::new();
+        for (mut entity, room, message) in due {
+            self.destroy(&entity.id);
+            self.reclaim(&mut entity);
+            self.spawn(entity, Some(room));
+            notices.push((room, message));
+        }
+        notices
+    }
+}
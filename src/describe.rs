@@ -0,0 +1,95 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Synthesized descriptions.  Entities without a long description
+//! are described by their attributes and state instead, so that
+//! worlds are playable before all texts are written.
+
+use super::types::*;
+
+impl World {
+    /// Join words into an enumeration: "a", "a und b", "a, b und c".
+    pub fn enumerate(&self, words: &[String]) -> String {
+        match words.len() {
+            0 => String::new(),
+            1 => words[0].clone(),
+            n => msg!(self, "and", list = words[..n - 1].join(", "), last = words[n - 1]),
+        }
+    }
+
+    /// Return a description of an entity made up from its attributes
+    /// and their state, like "Das ist geschlossen und verriegelt."
+    pub fn synthesize_description(&self, name: &InternalName) -> String {
+        let e = match self.entity(name) {
+            Some(e) => e,
+            None => return String::new(),
+        };
+        let mut states = vec![];
+        let mut sentences = vec![];
+        match e.closed() {
+            Some(true) => states.push("state_closed"),
+            Some(false) => states.push("state_open"),
+            None => {},
+        }
+        if e.lock().map(|l| l.locked).unwrap_or(false) {
+            states.push("state_locked");
+        }
+        match e.machine().map(|m| m.state.as_str()) {
+            Some("on") => states.push("state_on"),
+            Some("off") => states.push("state_off"),
+            _ => {},
+        }
+        if e.fuse().map(|f| f.lit).unwrap_or(false) {
+            states.push("state_lit");
+        }
+        if states.len() > 0 {
+            let states: Vec<String> = states.iter().map(|s| msg!(self, s)).collect();
+            sentences.push(msg!(self, "states", states = self.enumerate(&states)));
+        }
+        for a in e.attributes.iter() {
+            let s = match *a {
+                Attribute::Wearable(_) => "desc_wearable",
+                Attribute::Edible(_) => "desc_edible",
+                Attribute::Drinkable(_) => "desc_drinkable",
+                Attribute::Readable(_) => "desc_readable",
+                Attribute::Weapon(_) => "desc_weapon",
+                Attribute::Enterable(_) => "desc_enterable",
+                Attribute::Container(_) => "desc_container",
+                Attribute::Attachable => "desc_attachable",
+                Attribute::CodeEntry(_) => "desc_code_entry",
+                Attribute::Characterlike(ref c) =>
+                    if c.stats.health <= 0 {
+                        "desc_dead"
+                    } else if c.stats.health * 2 < c.stats.max_health {
+                        "desc_badly_hurt"
+                    } else if c.stats.health < c.stats.max_health {
+                        "desc_hurt"
+                    } else {
+                        "desc_healthy"
+                    },
+                _ => continue,
+            };
+            sentences.push(msg!(self, s));
+        }
+        if sentences.len() == 0 {
+            msg!(self, "desc_nothing")
+        } else {
+            sentences.join(" ")
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return the evaluated long description of an entity, or a
+    /// synthesized one if it has none.
+    pub fn long_description(&self, name: &InternalName) -> Result<String, String> {
+        match self.world.entity(name) {
+            Some(e) if e.long_description.len() > 0 =>
+                self.eval_str(e.long_text(self.world.locale())),
+            Some(_) =>
+                Ok(self.world.synthesize_description(name)),
+            None =>
+                Err("no such entity".to_string()),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Differences between worlds.  `diff` compares two worlds and
+//! returns a `Changeset`, which `patch` applies to a world.
+//! Changesets are serializable, so they can be exchanged between
+//! authors working on the same world, or sent to a running server:
+//! the difference between the old and the new version of a world file
+//! can be applied to the world being played.
+//!
+//! Entities are matched by their ids, and changed entities are
+//! replaced as a whole.
+
+use std::collections::BTreeMap;
+
+use ron;
+use serde::Serialize;
+
+use super::types::*;
+
+/// Return true if both values are the same.  Values are compared by
+/// their serialization, as not all parts of worlds can be compared.
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    match (ron::ser::to_string(a), ron::ser::to_string(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Return the entries of `new` which are not in `old` or differ,
+/// and `None` for those which are only in `old`.
+fn diff_map<T: Clone + Serialize>(old: &BTreeMap<String, T>, new: &BTreeMap<String, T>) -> BTreeMap<String, Option<T>> {
+    let mut res = BTreeMap::new();
+    for (k, v) in new.iter() {
+        if old.get(k).map_or(true, |o| !same(o, v)) {
+            res.insert(k.clone(), Some(v.clone()));
+        }
+    }
+    for k in old.keys().filter(|k| !new.contains_key(*k)) {
+        res.insert(k.clone(), None);
+    }
+    res
+}
+
+fn patch_map<T: Clone>(map: &mut BTreeMap<String, T>, changes: &BTreeMap<String, Option<T>>) {
+    for (k, v) in changes.iter() {
+        match *v {
+            Some(ref v) => {
+                map.insert(k.clone(), v.clone());
+            },
+            None => {
+                map.remove(k);
+            },
+        }
+    }
+}
+
+impl Changeset {
+    /// Return true if the changeset changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.start_location.is_none() && self.entities.len() == 0 &&
+            self.kinds.len() == 0 && self.regions.len() == 0 &&
+            self.relations.is_none() && self.respawns.is_none()
+    }
+}
+
+impl World {
+    /// Return the changes which turn this world into `other`.
+    pub fn diff(&self, other: &World) -> Changeset {
+        let mut entities = vec![];
+        for e in self.entities.iter() {
+            match other.entity(&e.id) {
+                Some(o) if !same(e, o) => entities.push(Change::Modified(o.clone())),
+                Some(_) => {},
+                None => entities.push(Change::Removed(e.id)),
+            }
+        }
+        for o in other.entities.iter().filter(|o| self.entity(&o.id).is_none()) {
+            entities.push(Change::Added(o.clone()));
+        }
+        Changeset {
+            name: Some(other.name.clone()).filter(|n| *n != self.name),
+            start_location: Some(other.start_location).filter(|s| *s != self.start_location),
+            entities: entities,
+            kinds: diff_map(&self.kinds, &other.kinds),
+            regions: diff_map(&self.regions, &other.regions),
+            relations: Some(other.relations.clone()).filter(|r| !same(r, &self.relations)),
+            respawns: Some(other.respawns.clone()).filter(|r| !same(r, &self.respawns)),
+        }
+    }
+
+    /// Apply a changeset to this world.  Fails without changing the
+    /// world if entities are to be added which exist already, or
+    /// changed or removed which do not exist.
+    pub fn patch(&mut self, changes: &Changeset) -> Result<(), String> {
+        for c in changes.entities.iter() {
+            match *c {
+                Change::Added(ref e) if self.entity(&e.id).is_some() =>
+                    return Err(format!("entity {} exists already", e.id)),
+                Change::Modified(ref e) if self.entity(&e.id).is_none() =>
+                    return Err(format!("unknown entity: {}", e.id)),
+                Change::Removed(ref id) if self.entity(id).is_none() =>
+                    return Err(format!("unknown entity: {}", id)),
+                _ => {},
+            }
+        }
+        for c in changes.entities.iter() {
+            match *c {
+                Change::Added(ref e) => {
+                    self.spawn(e.clone(), None);
+                },
+                Change::Removed(ref id) => {
+                    self.destroy(id);
+                },
+                Change::Modified(ref e) => {
+                    if let Some(old) = self.entity_mut(&e.id) {
+                        *old = e.clone();
+                    }
+                },
+            }
+        }
+        if let Some(ref name) = changes.name {
+            self.name = name.clone();
+        }
+        if let Some(start) = changes.start_location {
+            self.start_location = start;
+        }
+        patch_map(&mut self.kinds, &changes.kinds);
+        patch_map(&mut self.regions, &changes.regions);
+        if let Some(ref r) = changes.relations {
+            self.relations = r.clone();
+        }
+        if let Some(ref r) = changes.respawns {
+            self.respawns = r.clone();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Exporting the map of a world for Graphviz.  Rooms are nodes and
+//! doors are edges, labelled with their state.  The `dot` command of
+//! the game writes the map to `world.dot`, which can be drawn with
+//! `dot -Tsvg world.dot > world.svg`.
+//!
+//! Doors which can be passed both ways are drawn with two arrow
+//! heads, one-way doors with one, and teleports dashed.  One-way
+//! doors without any way back are drawn red, as they are often a
+//! forgotten return exit.
+
+use super::types::*;
+
+/// Quote a string for use in a DOT file.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+impl World {
+    /// Return the state of a door, as shown in the graph.
+    fn door_state(&self, door: &Entity) -> Vec<&'static str> {
+        let mut state = vec![];
+        if door.lock().map_or(false, |l| l.locked) {
+            state.push("verschlossen");
+        } else if door.closed() == Some(true) {
+            state.push("geschlossen");
+        }
+        if door.connection().map_or(false, |c| c.barrier.is_some()) {
+            state.push("bedingt");
+        }
+        state
+    }
+
+    /// Return true if any door leads from room `a` to room `b`.
+    fn leads_to(&self, a: &InternalName, b: &InternalName) -> bool {
+        self.doors_at(a).iter().any(|d| self.other_side(d, a) == Some(*b))
+    }
+
+    /// Return the map of the world in the DOT language of Graphviz.
+    pub fn to_dot(&self) -> String {
+        let mut out = format!("digraph {} {{\n", quote(&self.name));
+        out.push_str("    node [shape=box];\n");
+        for e in self.entities.iter().filter(|e| e.room().is_some()) {
+            let mut label = self.short_description(&e.id);
+            if let Some(ref a) = e.alias {
+                label = format!("{}\n@{}", label, a);
+            }
+            let style = if e.id == self.start_location { ", style=bold" } else { "" };
+            out.push_str(&format!("    {} [label={}{}];\n", quote(&e.id.to_string()), quote(&label), style));
+        }
+        for e in self.entities.iter() {
+            let c = match e.connection() {
+                Some(c) => c,
+                None => continue,
+            };
+            let (a, b) = c.endpoints;
+            let mut label = self.short_description(&e.id);
+            let state = self.door_state(e);
+            if state.len() > 0 {
+                label = format!("{}\n({})", label, state.join(", "));
+            }
+            let mut attrs = vec![format!("label={}", quote(&label))];
+            if !c.one_way {
+                attrs.push("dir=both".to_string());
+            } else if !self.leads_to(&b, &a) {
+                attrs.push("color=red".to_string());
+            }
+            if c.teleport {
+                attrs.push("style=dashed".to_string());
+            }
+            out.push_str(&format!("    {} -> {} [{}];\n", quote(&a.to_string()), quote(&b.to_string()), attrs.join(", ")));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
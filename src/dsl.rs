@@ -0,0 +1,583 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! A language for writing worlds by hand.  Entities are declared with
+//! a keyword and a symbolic name instead of a UUID, and rooms declare
+//! their exits:
+//!
+//! ```text
+//! world "Example World" { start: small_rock_room; }
+//!
+//! region mine { description: "Die Luft ist kühl."; dark: false; }
+//!
+//! room small_rock_room {
+//!     name: "small rock room";
+//!     short: "Ein kleiner Raum mit Wänden aus rohem Fels";
+//!     region: mine;
+//!     position: 0 0 0;
+//!     contains: iron_key;
+//!     exits: east -> tunnel via metal_door, down -> cellar;
+//! }
+//!
+//! door metal_door { short: "Metalltür"; kind: door; }
+//!
+//! item iron_key {
+//!     short: "Ein kleiner Eisenschlüssel";
+//!     attribute: Keylike(Key(classes: []));
+//! }
+//! ```
+//!
+//! The UUIDs of entities are derived from their symbolic names, like
+//! those of aliased entities in world files.
+//!
+//! Declarations are `world`, `region`, `quest`, `kind`, `room`,
+//! `door`, `item` and `character`.  Each has fields of the form `field: value;`:
+//!
+//! * all entities: `name` (the words it is called by, by default the
+//!   words of its symbolic name), `short`, `long`, `sound`, `smell`
+//!   (templates), `kind`, `tags` and `attribute`, which is an
+//!   attribute in the world file format and can be given repeatedly.
+//!   `translation` gives the descriptions in another language, like
+//!   `("en", (short: "A small iron key"))`, and can be given
+//!   repeatedly, too.  `prefab` makes the entity one of the prefabs
+//!   in `prefab`, like `Chest(keys: [@iron_key], locked: true)`.
+//!   Other entities are referred to as `@symbol` in attributes.
+//! * rooms: `region`, `position` (three numbers), `contains` (the
+//!   entities in the room) and `exits`.  An exit `dir -> room via
+//!   door` makes the door connect both rooms.  An exit without a door
+//!   is a one-way passage called like its direction.
+//! * characters: `stats` (health and strength, unless the character is
+//!   made from an `Npc` prefab), `carries` and `behaviors` (a list
+//!   of behaviors in the world file format).
+//! * items: `owner`, the character owning the item.
+//! * regions: `description`, `ambient` (a list of strings), `dark`
+//!   and `weather` (in the world file format).
+//! * quests: `title`, `start` (a condition), `stage`, a `Stage` in the
+//!   world file format, given once for every stage, and `reward` (a
+//!   list of effects).
+//! * kinds: `parent`, `short`, `long`, `tags` and `attribute`.
+//! * the world: `start`, the room players start in, `locale`, the
+//!   language descriptions are shown in, and `clock` and `scoring`
+//!   (in the world file format).
+//!
+//! Lines starting with `#` are comments.
+
+use std::collections::BTreeMap;
+
+use ron;
+use serde::de::DeserializeOwned;
+
+use super::scanner;
+use super::scanner::Scanner;
+use super::types::*;
+use super::relation;
+use super::prefab::Prefab;
+use super::load::{WorldFile, WORLD_VERSION, substitute_aliases, alias_id};
+
+/// A declaration, with its keyword, its name, and its fields in the
+/// order they were given.  Field values are kept as text and
+/// interpreted depending on the field.
+struct Decl {
+    keyword: String,
+    name: String,
+    fields: Vec<(String, String)>,
+}
+
+/// Skip whitespace and comments.
+fn skip(s: &mut Scanner) {
+    loop {
+        scanner::skip_ws(s);
+        if s.current() != Some('#') {
+            return;
+        }
+        while s.current().map_or(false, |c| c != '\n') {
+            s.next();
+        }
+    }
+}
+
+fn ident(s: &mut Scanner) -> Result<String, String> {
+    let mut res = String::new();
+    while let Some(c) = s.current().filter(|c| c.is_alphanumeric() || *c == '_') {
+        res.push(c);
+        s.next();
+    }
+    if res.len() == 0 {
+        match s.current() {
+            Some(c) => Err(format!("name expected, found {}", c)),
+            None => Err("name expected, found end of file".to_string()),
+        }
+    } else {
+        Ok(res)
+    }
+}
+
+fn expect(s: &mut Scanner, c: char) -> Result<(), String> {
+    match s.current() {
+        Some(d) if d == c => {
+            s.next();
+            Ok(())
+        },
+        Some(d) => Err(format!("{} expected, found {}", c, d)),
+        None => Err(format!("{} expected, found end of file", c)),
+    }
+}
+
+/// Read a string literal.  Backslashes escape quotes, backslashes and
+/// newlines (as `\n`).
+fn string(s: &mut Scanner) -> Result<String, String> {
+    try!(expect(s, '"'));
+    let mut res = String::new();
+    loop {
+        match s.current() {
+            None =>
+                return Err("unexpected end of file in string".to_string()),
+            Some('"') => {
+                s.next();
+                return Ok(res);
+            },
+            Some('\\') => {
+                s.next();
+                match s.current() {
+                    Some('n') => res.push('\n'),
+                    Some(c) => res.push(c),
+                    None => return Err("unexpected end of file in string".to_string()),
+                }
+                s.next();
+            },
+            Some(c) => {
+                res.push(c);
+                s.next();
+            },
+        }
+    }
+}
+
+/// Read the text of a value, up to the `;` ending it.  Semicolons in
+/// strings or brackets don't end the value.
+fn raw_value(s: &mut Scanner) -> Result<String, String> {
+    let mut res = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    loop {
+        let c = match s.current() {
+            Some(c) => c,
+            None => return Err("unexpected end of file in value".to_string()),
+        };
+        s.next();
+        if in_string {
+            res.push(c);
+            if c == '\\' {
+                if let Some(d) = s.current() {
+                    res.push(d);
+                    s.next();
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            ';' if depth == 0 =>
+                return Ok(res.trim().to_string()),
+            '"' =>
+                in_string = true,
+            '(' | '[' | '{' =>
+                depth += 1,
+            ')' | ']' | '}' if depth == 0 =>
+                return Err(format!("unbalanced {} in value", c)),
+            ')' | ']' | '}' =>
+                depth -= 1,
+            _ => {},
+        }
+        res.push(c);
+    }
+}
+
+fn parse_decls(txt: &str) -> Result<Vec<Decl>, String> {
+    let mut s = Scanner::new(txt);
+    let mut decls = vec![];
+    loop {
+        skip(&mut s);
+        if s.current().is_none() {
+            return Ok(decls);
+        }
+        let keyword = try!(ident(&mut s));
+        skip(&mut s);
+        let name = try!(if s.current() == Some('"') { string(&mut s) } else { ident(&mut s) }
+                        .map_err(|e| format!("{}: {}", keyword, e)));
+        let context = format!("{} {}", keyword, name);
+        skip(&mut s);
+        try!(expect(&mut s, '{').map_err(|e| format!("{}: {}", context, e)));
+        let mut fields = vec![];
+        loop {
+            skip(&mut s);
+            if s.current() == Some('}') {
+                s.next();
+                break;
+            }
+            let field = try!(ident(&mut s).map_err(|e| format!("{}: {}", context, e)));
+            skip(&mut s);
+            try!(expect(&mut s, ':').map_err(|e| format!("{}: {}: {}", context, field, e)));
+            let value = try!(raw_value(&mut s).map_err(|e| format!("{}: {}: {}", context, field, e)));
+            fields.push((field, value));
+        }
+        decls.push(Decl { keyword: keyword, name: name, fields: fields });
+    }
+}
+
+/// Return an error if anything but whitespace is left.
+fn end(s: &mut Scanner) -> Result<(), String> {
+    skip(s);
+    match s.current() {
+        None => Ok(()),
+        Some(c) => Err(format!("unexpected {}", c)),
+    }
+}
+
+fn string_value(v: &str) -> Result<String, String> {
+    let mut s = Scanner::new(v);
+    let res = try!(string(&mut s));
+    try!(end(&mut s));
+    Ok(res)
+}
+
+fn strings_value(v: &str) -> Result<Vec<String>, String> {
+    let mut s = Scanner::new(v);
+    let mut res = vec![];
+    loop {
+        skip(&mut s);
+        if s.current().is_none() {
+            return Ok(res);
+        }
+        res.push(try!(string(&mut s)));
+        skip(&mut s);
+        if s.current() == Some(',') {
+            s.next();
+        }
+    }
+}
+
+fn name_value(v: &str) -> Result<String, String> {
+    let mut s = Scanner::new(v);
+    let res = try!(ident(&mut s));
+    try!(end(&mut s));
+    Ok(res)
+}
+
+fn names_value(v: &str) -> Result<Vec<String>, String> {
+    v.split(',').filter(|n| n.trim().len() > 0).map(|n| name_value(n.trim())).collect()
+}
+
+fn bool_value(v: &str) -> Result<bool, String> {
+    match v {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("true or false expected, found {}", v)),
+    }
+}
+
+fn numbers_value(v: &str) -> Result<Vec<i32>, String> {
+    v.split_whitespace().map(|n| n.parse().map_err(|_| format!("number expected, found {}", n))).collect()
+}
+
+/// The symbolic names of entities, with the UUIDs they stand for.
+struct Symbols(BTreeMap<String, InternalName>);
+
+impl Symbols {
+    fn get(&self, name: &str) -> Result<InternalName, String> {
+        self.0.get(name).cloned().ok_or(format!("unknown name: {}", name))
+    }
+
+    /// Parse a value in the world file format, with references of
+    /// the form `@symbol` to entities.
+    fn ron<T: DeserializeOwned>(&self, v: &str) -> Result<T, String> {
+        let txt = try!(substitute_aliases(v, |n| self.get(n)));
+        ron::de::from_str(&txt).map_err(|e| format!("invalid value: {}", e))
+    }
+}
+
+/// Return an entity with the given name and empty descriptions.
+fn entity(id: InternalName, symbol: &str) -> Entity {
+    Entity {
+        id: id,
+        name: symbol.split('_').map(|w| w.to_lowercase()).collect(),
+        alias: Some(symbol.to_string()),
+        kind: None,
+        tags: vec![],
+        properties: BTreeMap::new(),
+        short_description: String::new(),
+        long_description: String::new(),
+        sound_description: None,
+        smell_description: None,
+        translations: BTreeMap::new(),
+        attributes: vec![],
+    }
+}
+
+/// An exit declared by a room: the room, the direction, the room it
+/// leads to and the door it leads through, if any.
+struct Exit {
+    from: InternalName,
+    room: String,
+    direction: String,
+    to: InternalName,
+    door: Option<InternalName>,
+}
+
+fn exits_value(from: InternalName, room: &str, v: &str, symbols: &Symbols) -> Result<Vec<Exit>, String> {
+    let mut exits = vec![];
+    for e in v.split(',').filter(|e| e.trim().len() > 0) {
+        let words: Vec<&str> = e.split_whitespace().collect();
+        let (direction, to, door) = match words.len() {
+            3 if words[1] == "->" => (words[0], words[2], None),
+            5 if words[1] == "->" && words[3] == "via" => (words[0], words[2], Some(words[4])),
+            _ => return Err(format!("exit expected, like \"north -> room via door\", found {}", e.trim())),
+        };
+        exits.push(Exit {
+            from: from,
+            room: room.to_string(),
+            direction: direction.to_string(),
+            to: try!(symbols.get(to)),
+            door: match door {
+                Some(d) => Some(try!(symbols.get(d))),
+                None => None,
+            },
+        });
+    }
+    Ok(exits)
+}
+
+/// Compile the source of a world into its world file contents.
+fn compile(txt: &str) -> Result<WorldFile, String> {
+    let decls = try!(parse_decls(txt));
+    let mut symbols = Symbols(BTreeMap::new());
+    for d in decls.iter() {
+        match d.keyword.as_str() {
+            "room" | "door" | "item" | "character" => {
+                if symbols.0.insert(d.name.clone(), alias_id(&d.name)).is_some() {
+                    return Err(format!("{} {}: declared twice", d.keyword, d.name));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let mut name = None;
+    let mut start = None;
+    let mut locale = None;
+    let mut clock = None;
+    let mut scoring = None;
+    let mut kinds = BTreeMap::new();
+    let mut regions = BTreeMap::new();
+    let mut quests = BTreeMap::new();
+    let mut entities = vec![];
+    let mut exits = vec![];
+    let mut relations = vec![];
+    for d in decls {
+        let context = format!("{} {}", d.keyword, d.name);
+        match d.keyword.as_str() {
+            "world" => {
+                name = Some(d.name.clone());
+                for (f, v) in d.fields {
+                    match f.as_str() {
+                        "start" => start = Some(try!(name_value(&v).and_then(|n| symbols.get(&n)).map_err(|e| format!("{}: {}", context, e)))),
+                        "locale" => locale = Some(try!(string_value(&v).map_err(|e| format!("{}: {}", context, e)))),
+                        "clock" => clock = Some(try!(symbols.ron(&v).map_err(|e| format!("{}: {}", context, e)))),
+                        "scoring" => scoring = Some(try!(symbols.ron(&v).map_err(|e| format!("{}: {}", context, e)))),
+                        _ => return Err(format!("{}: unknown field {}", context, f)),
+                    }
+                }
+            },
+            "region" => {
+                let mut region = Region { description: String::new(), ambient: vec![], dark: false, weather: None };
+                for (f, v) in d.fields {
+                    let res = match f.as_str() {
+                        "description" => string_value(&v).map(|s| region.description = s),
+                        "ambient" => strings_value(&v).map(|s| region.ambient = s),
+                        "dark" => bool_value(&v).map(|b| region.dark = b),
+                        "weather" => symbols.ron(&v).map(|w| region.weather = Some(w)),
+                        _ => Err(format!("unknown field {}", f)),
+                    };
+                    try!(res.map_err(|e| format!("{}: {}", context, e)));
+                }
+                regions.insert(d.name, region);
+            },
+            "quest" => {
+                let mut quest = Quest { title: String::new(), start: None, stages: vec![], reward: vec![], stage: None };
+                for (f, v) in d.fields {
+                    let res = match f.as_str() {
+                        "title" => string_value(&v).map(|s| quest.title = s),
+                        "start" => string_value(&v).map(|s| quest.start = Some(s)),
+                        "stage" => symbols.ron(&v).map(|s| quest.stages.push(s)),
+                        "reward" => symbols.ron(&v).map(|r| quest.reward = r),
+                        _ => Err(format!("unknown field {}", f)),
+                    };
+                    try!(res.map_err(|e| format!("{}: {}", context, e)));
+                }
+                quests.insert(d.name, quest);
+            },
+            "kind" => {
+                let mut kind = Kind {
+                    parent: None,
+                    short_description: String::new(),
+                    long_description: String::new(),
+                    translations: BTreeMap::new(),
+                    tags: vec![],
+                    attributes: vec![],
+                };
+                for (f, v) in d.fields {
+                    let res = match f.as_str() {
+                        "parent" => name_value(&v).map(|n| kind.parent = Some(n)),
+                        "short" => string_value(&v).map(|s| kind.short_description = s),
+                        "long" => string_value(&v).map(|s| kind.long_description = s),
+                        "tags" => names_value(&v).map(|t| kind.tags = t),
+                        "attribute" => symbols.ron(&v).map(|a| kind.attributes.push(a)),
+                        _ => Err(format!("unknown field {}", f)),
+                    };
+                    try!(res.map_err(|e| format!("{}: {}", context, e)));
+                }
+                kinds.insert(d.name, kind);
+            },
+            "room" | "door" | "item" | "character" => {
+                let id = try!(symbols.get(&d.name));
+                let mut e = entity(id, &d.name);
+                let mut room = Room { entities: vec![], region: None, position: None };
+                let mut stats = None;
+                let mut carries = vec![];
+                let mut behaviors = vec![];
+                for (f, v) in d.fields {
+                    let res = match (d.keyword.as_str(), f.as_str()) {
+                        (_, "name") => string_value(&v).map(|s| e.name = s.split_whitespace().map(|w| w.to_lowercase()).collect()),
+                        (_, "short") => string_value(&v).map(|s| e.short_description = s),
+                        (_, "long") => string_value(&v).map(|s| e.long_description = s),
+                        (_, "sound") => string_value(&v).map(|s| e.sound_description = Some(s)),
+                        (_, "smell") => string_value(&v).map(|s| e.smell_description = Some(s)),
+                        (_, "translation") => symbols.ron(&v).map(|(l, t): (String, Translation)| {
+                            e.translations.insert(l, t);
+                        }),
+                        (_, "kind") => name_value(&v).map(|k| e.kind = Some(k)),
+                        (_, "tags") => names_value(&v).map(|t| e.tags = t),
+                        (_, "attribute") => symbols.ron(&v).map(|a| e.attributes.push(a)),
+                        (_, "prefab") => symbols.ron(&v).map(|p: Prefab| p.apply(&mut e)),
+                        ("room", "region") => name_value(&v).map(|r| room.region = Some(r)),
+                        ("room", "position") => numbers_value(&v).and_then(|p| match p.len() {
+                            3 => Ok(room.position = Some((p[0], p[1], p[2]))),
+                            _ => Err("three numbers expected".to_string()),
+                        }),
+                        ("room", "contains") => names_value(&v).and_then(|ns| {
+                            ns.iter().map(|n| symbols.get(n)).collect::<Result<Vec<_>, _>>()
+                        }).map(|ids| room.entities.extend(ids)),
+                        ("room", "exits") => exits_value(id, &d.name, &v, &symbols).map(|es| exits.extend(es)),
+                        ("character", "stats") => numbers_value(&v).and_then(|p| match p.len() {
+                            2 => Ok(stats = Some(Stats { health: p[0], max_health: p[0], strength: p[1] })),
+                            _ => Err("health and strength expected".to_string()),
+                        }),
+                        ("character", "carries") => names_value(&v).and_then(|ns| {
+                            ns.iter().map(|n| symbols.get(n)).collect::<Result<Vec<_>, _>>()
+                        }).map(|ids| carries.extend(ids)),
+                        ("character", "behaviors") => symbols.ron(&v).map(|bs: Vec<Behavior>| behaviors = bs),
+                        ("item", "owner") => name_value(&v).and_then(|n| symbols.get(&n))
+                            .map(|o| relations.push((relation::OWNED_BY.to_string(), id, o))),
+                        _ => Err(format!("unknown field {}", f)),
+                    };
+                    try!(res.map_err(|e| format!("{}: {}", context, e)));
+                }
+                match d.keyword.as_str() {
+                    "room" =>
+                        e.attributes.push(Attribute::Roomlike(room)),
+                    "character" if e.character().is_some() => {
+                        // Made from a prefab.
+                        let c = e.character_mut().unwrap();
+                        if let Some(s) = stats {
+                            c.stats = s;
+                        }
+                        c.inventory.extend(carries);
+                        if behaviors.len() > 0 {
+                            c.behaviors = behaviors;
+                        }
+                    },
+                    "character" => {
+                        let mut c = Character::new(try!(stats.ok_or(format!("{}: stats missing", context))));
+                        c.inventory = carries;
+                        c.behaviors = behaviors;
+                        e.attributes.push(Attribute::Characterlike(c));
+                    },
+                    _ => {},
+                }
+                entities.push(e);
+            },
+            _ => return Err(format!("unknown declaration: {}", d.keyword)),
+        }
+    }
+
+    for exit in exits {
+        match exit.door {
+            Some(door) => {
+                let e = entities.iter_mut().find(|e| e.id == door).unwrap();
+                let alias = e.alias.clone().unwrap_or_default();
+                let connection = e.attributes.iter_mut().filter_map(|a| match *a {
+                    Attribute::Doorlike(ref mut c) => Some(c),
+                    _ => None,
+                }).next();
+                match connection {
+                    Some(ref c) if c.endpoints != (exit.from, exit.to) && c.endpoints != (exit.to, exit.from) =>
+                        return Err(format!("door {} connects other rooms", alias)),
+                    Some(c) =>
+                        c.directions.push((exit.from, exit.direction.clone())),
+                    None =>
+                        e.attributes.push(Attribute::Doorlike(Connection {
+                            endpoints: (exit.from, exit.to),
+                            barrier: None,
+                            one_way: false,
+                            teleport: false,
+                            transition: None,
+                            directions: vec![(exit.from, exit.direction.clone())],
+                        })),
+                }
+            },
+            None => {
+                let mut e = entity(alias_id(&format!("{}/{}", exit.room, exit.direction)), &exit.direction);
+                e.alias = None;
+                e.short_description = exit.direction.clone();
+                e.attributes.push(Attribute::Doorlike(Connection {
+                    endpoints: (exit.from, exit.to),
+                    barrier: None,
+                    one_way: true,
+                    teleport: false,
+                    transition: None,
+                    directions: vec![(exit.from, exit.direction.clone())],
+                }));
+                entities.push(e);
+            },
+        }
+    }
+
+    Ok(WorldFile {
+        version: WORLD_VERSION,
+        name: try!(name.ok_or("world declaration missing".to_string())),
+        start_location: try!(start.ok_or("start location missing".to_string())),
+        player: None,
+        kinds: kinds,
+        regions: regions,
+        entities: entities,
+        relations: relations,
+        respawns: vec![],
+        schedule: vec![],
+        clock: clock,
+        scoring: scoring,
+        quests: quests,
+        includes: vec![],
+        namespace: None,
+        locale: locale,
+        messages: BTreeMap::new(),
+    })
+}
+
+impl World {
+    /// Compile a world written in the authoring language.  The world
+    /// is validated like loaded worlds.
+    pub fn compile(txt: &str) -> Result<World, String> {
+        try!(compile(txt)).build()
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The engine: a world together with the player playing it.  The
+//! engine handles the lines the player types, game commands as well
+//! as commands like `save` or `map`.
+//!
+//! The engine owns the world, and everything changing it goes through
+//! `handle`, which takes the engine mutably.  Game commands are
+//! carried out by a `PlayerState`, which borrows the world mutably
+//! while the command is carried out.  Afterwards the world advances
+//! by one turn: characters move, fuses burn down and the weather
+//! changes (see `PlayerState::execute`).  Shared worlds advance on a
+//! clock instead (see `step`).
+//!
+//! Sessions can be replayed.  A `Script` holds the lines typed in a
+//! session and, if it was read from a transcript, the output each of
+//! them produced.  Replaying a script in a fresh engine, with the
+//! random number generator seeded like in the recorded session,
+//! produces the same output again, so whole puzzles can be tested.
+//! Transcripts must be recorded from the start of a game for that;
+//! the seed is noted when recording starts.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::types::*;
+use super::rng::Rng;
+use super::command;
+use super::output;
+use super::chat::Chat;
+use super::roles::Role;
+use super::session::Session;
+use super::step::SessionId;
+use super::snapshot::{Journal, Snapshot};
+use super::storage::Storage;
+
+/// File games are saved to when no file is given.
+pub const SAVE_FILE: &'static str = "mudstuck.sav";
+
+/// Name games are saved under in storage when no name is given.
+pub const SAVE_GAME: &'static str = "mudstuck";
+
+/// File games are saved to automatically when no file is given.
+pub const AUTOSAVE_FILE: &'static str = "autosave.sav";
+
+/// File transcripts are written to when no file is given.
+pub const TRANSCRIPT_FILE: &'static str = "transcript.txt";
+
+/// File the map is written to by the `dot` command.
+pub const DOT_FILE: &'static str = "world.dot";
+
+/// A world and its player.
+pub struct Engine {
+    pub world: World,
+    /// The player's entity.
+    pub player: InternalName,
+    /// The seed the random number generator started from.
+    pub seed: u64,
+    /// Chat between the players of sessions (see `chat`).
+    pub chat: Chat,
+    /// The roles of players, by name (see `roles`).
+    pub roles: BTreeMap<String, Role>,
+    /// The sessions of players, by number (see `step`).
+    pub sessions: BTreeMap<SessionId, Session>,
+    /// The number the next session gets.
+    pub next_session: SessionId,
+    /// The state before the last game command, for `undo`.
+    pub last: Option<Snapshot>,
+    /// Where snapshots and the lines stepped are kept, if anywhere
+    /// (see `snapshot`).
+    pub journal: Option<Journal>,
+    /// Where the state of the world and saved games are kept, if not
+    /// in files named by the player (see `storage`).
+    pub storage: Option<Arc<Storage>>,
+    /// Whether the state kept in storage is that of the world, but
+    /// for the entities in `world.changed`.
+    pub stored: bool,
+}
+
+/// The first words of commands which are not game commands, but
+/// handled by the engine or the program running it.
+const META_COMMANDS: &'static [&'static str] =
+    &["look", "l", "help", "h", "inventory", "i", "status", "exits", "journal", "quests", "j", "score", "map",
+      "desc", "d", "save", "restore", "checkpoint", "autosave", "transcript", "dot", "stats", "locale", "become",
+      "undo", "quit", "q", "alias", "unalias", "say", "chat", "tell", "channel", "channels"];
+
+/// Return true if the line is a command which is not a game command.
+pub fn is_meta(line: &str) -> bool {
+    line.starts_with('@') || line.split_whitespace().next().map_or(false, |w| META_COMMANDS.contains(&w))
+}
+
+/// Return the file name given after a command, or the default one.
+fn file_arg<'a>(s: &'a str, default: &'a str) -> &'a str {
+    match s.splitn(2, ' ').nth(1).map(|f| f.trim()) {
+        Some(f) if f.len() > 0 => f,
+        _ => default,
+    }
+}
+
+fn show_help() {
+    say!("Commands:");
+    say!("  help or h        show this help");
+    say!("  undo             take back the last command");
+    say!("  quit or q        quit the game");
+    say!("  look or l        describe your surroundings");
+    say!("  inventory or i   list what you are carrying");
+    say!("  status           show your health and condition");
+    say!("  exits            list the ways out of here");
+    say!("  journal or j     list your quests (also: quests)");
+    say!("  score            show your score, rank and achievements");
+    say!("  map              draw a map of the surroundings");
+    say!("  become NAME      take control of another character");
+    say!("  save [FILE]      save the game (default: {})", SAVE_FILE);
+    say!("  restore [FILE]   restore a saved game or a checkpoint");
+    say!("  checkpoint NAME  save the game as a checkpoint");
+    say!("  autosave N [FILE]  save the game every N turns (default: {})", AUTOSAVE_FILE);
+    say!("  autosave off     stop saving the game automatically");
+    say!("  transcript on [FILE]  record a transcript (default: {})", TRANSCRIPT_FILE);
+    say!("  transcript off   stop recording the transcript");
+    say!("  dot [FILE]       write a Graphviz map of the world (default: {})", DOT_FILE);
+    say!("  stats            show statistics about the world");
+    say!("  locale [NAME]    show or choose the language of descriptions");
+    say!("  locale default   show descriptions as written");
+}
+
+impl Engine {
+    /// Start a game in the world, with a new player character made
+    /// from the world's player template at the start location and the
+    /// random number generator seeded with `seed`.
+    pub fn new(mut world: World, seed: u64) -> Engine {
+        world.rng = Rng::new(seed);
+        let id = world.add_starting_player();
+        Engine::from_parts(world, id, seed)
+    }
+
+    /// Start a world shared by players in sessions, with the random
+    /// number generator seeded with `seed`.  Nobody plays until the
+    /// first player joins (see `session`).  Turns pass when the
+    /// program running the engine calls `tick` (see `step`).
+    pub fn shared(mut world: World, seed: u64) -> Engine {
+        world.rng = Rng::new(seed);
+        world.shared = true;
+        let start = world.start_location;
+        Engine::from_parts(world, start, seed)
+    }
+
+    /// Return an engine for a world, playing the given character.
+    fn from_parts(world: World, player: InternalName, seed: u64) -> Engine {
+        Engine {
+            world: world,
+            player: player,
+            seed: seed,
+            chat: Chat::default(),
+            roles: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            next_session: 0,
+            last: None,
+            journal: None,
+            storage: None,
+            stored: false,
+        }
+    }
+
+    /// Do something as the player.  Changes of the player's entity,
+    /// like after restoring a game, are kept.
+    pub fn with_player<R, F: FnOnce(&mut PlayerState) -> R>(&mut self, f: F) -> R {
+        let mut ps = PlayerState::new(&mut self.world, self.player);
+        let res = f(&mut ps);
+        self.player = ps.id;
+        res
+    }
+
+    /// Handle a line typed by the player.  Returns false if the game
+    /// is over.
+    pub fn handle(&mut self, s: &str) -> bool {
+        if s == "undo" {
+            match self.undo() {
+                Ok(()) => self.with_player(|ps| ps.look()),
+                Err(e) => say!("Cannot undo: {}.", e),
+            }
+            return true;
+        }
+        // Shared worlds are not rolled back for one player.
+        let before = if !is_meta(s) && self.sessions.is_empty() { Some(self.snapshot()) } else { None };
+        let seed = self.seed;
+        let storage = self.storage.clone();
+        let alive = self.with_player(|ps| {
+            match s {
+                "look" | "l" => ps.look(),
+                "help" | "h" => show_help(),
+                "inventory" | "i" => ps.show_inventory(),
+                "status" => ps.show_status(),
+                "exits" => ps.show_exits(),
+                "journal" | "quests" | "j" => ps.show_journal(),
+                "score" => ps.show_score(false),
+                "map" => ps.show_map(),
+                "desc" | "d" => ps.describe("rusty.metal.door"),
+                _ if s == "save" || s.starts_with("save ") => {
+                    let res = match storage {
+                        Some(ref st) => ps.save_to_string().and_then(|g| st.save_game(file_arg(s, SAVE_GAME), &g)),
+                        None => ps.save(file_arg(s, SAVE_FILE)),
+                    };
+                    match res {
+                        Ok(()) => say!("Game saved."),
+                        Err(e) => say!("Cannot save the game: {}.", e),
+                    }
+                },
+                _ if s == "restore" || s.starts_with("restore ") => {
+                    let file = file_arg(s, SAVE_FILE);
+                    let res = match storage {
+                        Some(ref st) => {
+                            let name = file_arg(s, SAVE_GAME);
+                            st.load_game(name).and_then(|g| match g {
+                                Some(g) => ps.restore_from_str(&g),
+                                None => Err(format!("no saved game named {}", name)),
+                            })
+                        },
+                        None if Path::new(file).exists() => ps.restore(file),
+                        None => ps.restore_checkpoint(file),
+                    };
+                    match res {
+                        Ok(()) => ps.look(),
+                        Err(e) => say!("Cannot restore the game: {}.", e),
+                    }
+                },
+                _ if s.starts_with("checkpoint ") => {
+                    let name = s["checkpoint ".len()..].trim();
+                    let res = match storage {
+                        Some(ref st) => ps.save_to_string().and_then(|g| st.save_game(name, &g)),
+                        None => ps.checkpoint(name),
+                    };
+                    match res {
+                        Ok(()) => say!("Checkpoint saved."),
+                        Err(e) => say!("Cannot save the checkpoint: {}.", e),
+                    }
+                },
+                "autosave off" => {
+                    ps.world.autosave = None;
+                    say!("Autosave is off.");
+                },
+                _ if s.starts_with("autosave ") => {
+                    let words: Vec<&str> = s.split_whitespace().collect();
+                    match words.get(1).and_then(|w| w.parse().ok()) {
+                        Some(n) if n > 0 => {
+                            let file = words.get(2).cloned().unwrap_or(AUTOSAVE_FILE);
+                            ps.world.set_autosave(n, file);
+                            say!("Saving the game to {} every {} turns.", file, n);
+                        },
+                        _ => say!("Usage: autosave N [FILE] or autosave off"),
+                    }
+                },
+                "transcript off" =>
+                    match output::stop_transcript() {
+                        Some(path) => say!("Transcript written to {}.", path.display()),
+                        None => say!("No transcript is being recorded."),
+                    },
+                _ if s == "transcript on" || s.starts_with("transcript on ") =>
+                    match output::start_transcript(file_arg(&s["transcript".len()..].trim(), TRANSCRIPT_FILE)) {
+                        Ok(()) => say!("Recording a transcript (seed {}).", seed),
+                        Err(e) => say!("Cannot record a transcript: {}.", e),
+                    },
+                _ if s == "dot" || s.starts_with("dot ") =>
+                    match fs::write(file_arg(s, DOT_FILE), ps.world.to_dot()) {
+                        Ok(()) => say!("Map written."),
+                        Err(e) => say!("Cannot write the map: {}.", e),
+                    },
+                "stats" => say!("{}", ps.world.stats()),
+                "locale" => {
+                    let locales: Vec<String> = ps.world.locales().into_iter().collect();
+                    say!("Locale: {} (available: {})", ps.world.locale().unwrap_or("default"),
+                         if locales.is_empty() { "none".to_string() } else { locales.join(", ") });
+                },
+                "locale default" => {
+                    ps.world.locale = None;
+                    ps.look();
+                },
+                _ if s.starts_with("locale ") => {
+                    ps.world.locale = Some(s["locale ".len()..].trim().to_string());
+                    ps.look();
+                },
+                _ if s.starts_with("become ") => {
+                    let words = s.split(' ').skip(1).filter(|w| w.len() > 0).map(|w| w.to_lowercase()).collect();
+                    match ps.possess_named(&words) {
+                        Ok(()) => ps.look(),
+                        Err(e) => say!("You cannot become that: {}.", e),
+                    }
+                },
+                _ if ps.world.extensions.verb(s).is_some() => {
+                    if let Some(v) = ps.world.extensions.verb(s) {
+                        v(ps, s.trim().splitn(2, ' ').nth(1).unwrap_or("").trim());
+                    }
+                    ps.end_turn();
+                    if ps.is_dead() {
+                        ps.show_score(true);
+                        return false;
+                    }
+                },
+                _ =>
+                    match command::parse(s) {
+                        Err(e) => {
+                            say!("{}", msg!(ps.world, "unknown_command"));
+                            say!("({})", e);
+                        },
+                        Ok(cmd) => {
+                            if let Err(e) = ps.execute(&cmd) {
+                                say!("{}", msg!(ps.world, "unknown_command"));
+                                say!("({})", e);
+                            }
+                            if ps.is_dead() {
+                                ps.show_score(true);
+                                return false;
+                            }
+                        },
+                    },
+            }
+            true
+        });
+        if before.is_some() {
+            self.last = before;
+        }
+        alive
+    }
+
+    /// Replay a script, checking that every line produces the output
+    /// recorded for it, if any.  Returns the output of every line, or
+    /// the first line whose output differs.
+    pub fn replay(&mut self, script: &Script) -> Result<Vec<String>, Mismatch> {
+        let mut outputs = vec![];
+        for (i, &(ref input, ref expected)) in script.steps.iter().enumerate() {
+            let (_, actual) = output::capture(|| self.handle(input));
+            if let Some(ref expected) = *expected {
+                if *expected != actual {
+                    return Err(Mismatch {
+                        step: i + 1,
+                        input: input.clone(),
+                        expected: expected.clone(),
+                        actual: actual,
+                    });
+                }
+            }
+            outputs.push(actual);
+        }
+        Ok(outputs)
+    }
+}
+
+/// The lines typed in a session, each with the output it produced if
+/// that is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub steps: Vec<(String, Option<String>)>,
+}
+
+impl Script {
+    /// Read a script from a list of commands, one per line.  Empty
+    /// lines and lines starting with `#` are skipped.
+    pub fn from_commands(txt: &str) -> Script {
+        Script {
+            steps: txt.lines().map(|l| l.trim())
+                .filter(|l| l.len() > 0 && !l.starts_with('#'))
+                .map(|l| (l.to_string(), None))
+                .collect(),
+        }
+    }
+
+    /// Read a script from a transcript (see `output`).  Starting and
+    /// stopping the transcript is not part of the script, and neither
+    /// is output before the first line typed.
+    pub fn from_transcript(txt: &str) -> Script {
+        let mut steps: Vec<(String, Option<String>)> = vec![];
+        let mut skipping = true;
+        for line in txt.lines() {
+            // Drop the time stamp.
+            let line = match line.find("] ") {
+                Some(i) if line.starts_with('[') => &line[i + 2..],
+                _ => line,
+            };
+            if line.starts_with("--- transcript ") {
+                continue;
+            }
+            if line.starts_with("> ") {
+                let input = &line[2..];
+                skipping = input.starts_with("transcript ");
+                if !skipping {
+                    steps.push((input.to_string(), Some(String::new())));
+                }
+            } else if !skipping {
+                if let Some(&mut (_, Some(ref mut out))) = steps.last_mut() {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+        }
+        Script { steps: steps }
+    }
+}
+
+/// A line of a script which produced other output than recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// The number of the line in the script, counting from 1.
+    pub step: usize,
+    pub input: String,
+    pub expected: String,
+    pub actual: String,
+}
@@ -0,0 +1,86 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Events: things which happen in the world, like a character going
+//! into another room or an item being taken.  Commands publish the
+//! events they cause, and listeners subscribe to them, to react to
+//! what happens or to show it in their own way:
+//!
+//! ```text
+//! world.subscribe(|w, e| if let Event::NpcDied { npc, .. } = *e {
+//!     say!("{} ist tot.", w.short_description(&npc));
+//! });
+//! ```
+//!
+//! Entities react to events affecting them with their triggers (see
+//! `trigger`), which run before the listeners.
+//!
+//! Listeners must be `Send`, like everything else in a world, so
+//! worlds can be handed to the thread serving them (see `server`).
+//!
+//! Events are not delivered while the command causing them is carried
+//! out, but when it is done and again at the end of the turn, in the
+//! order they happened.  Listeners may change the world and publish
+//! further events, which are delivered in the same round.
+
+use std::fmt;
+use std::mem;
+
+use super::types::*;
+
+/// How often events published by listeners are delivered in a row,
+/// before the rest is dropped.  Keeps listeners reacting to each
+/// other's events from looping forever.
+const MAX_ROUNDS: usize = 32;
+
+/// A function called with every event delivered.
+pub type Listener = Box<FnMut(&mut World, &Event) + Send>;
+
+/// The events waiting to be delivered, and the listeners they are
+/// delivered to.
+#[derive(Default)]
+pub struct Events {
+    pending: Vec<Event>,
+    listeners: Vec<Listener>,
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Events({} pending, {} listeners)", self.pending.len(), self.listeners.len())
+    }
+}
+
+impl World {
+    /// Publish an event, to be delivered to the listeners later.
+    pub fn publish(&mut self, event: Event) {
+        self.events.pending.push(event);
+    }
+
+    /// Call `listener` with every event delivered from now on.
+    pub fn subscribe<F: FnMut(&mut World, &Event) + Send + 'static>(&mut self, listener: F) {
+        self.events.listeners.push(Box::new(listener));
+    }
+
+    /// Deliver the events published so far to the listeners, and the
+    /// events they publish in turn.
+    pub fn dispatch(&mut self) {
+        for _ in 0..MAX_ROUNDS {
+            let pending = mem::replace(&mut self.events.pending, vec![]);
+            if pending.is_empty() {
+                return;
+            }
+            self.monitor.events(pending.len());
+            let mut listeners = mem::replace(&mut self.events.listeners, vec![]);
+            for e in pending.iter() {
+                self.run_triggers(e);
+                for l in listeners.iter_mut() {
+                    l(self, e);
+                }
+            }
+            // Listeners subscribed by listeners come after the others.
+            listeners.extend(self.events.listeners.drain(..));
+            self.events.listeners = listeners;
+        }
+        self.events.pending.clear();
+    }
+}
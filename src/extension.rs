@@ -0,0 +1,133 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Extensions: game systems in crates of their own, like hunger,
+//! crafting or factions, which register what they add to the engine
+//! when a game starts:
+//!
+//! ```text
+//! struct Hunger;
+//!
+//! impl Extension for Hunger {
+//!     fn name(&self) -> &str { "hunger" }
+//!
+//!     fn register(&self, r: &mut Registry) {
+//!         r.attribute("hunger", Property::Int(0));
+//!         r.verb("fast", |ps, _| say!("Du fastest."));
+//!         r.function("hungry", |w, args| ...);
+//!         r.listener(|w, e| ...);
+//!     }
+//! }
+//!
+//! engine.extend(&Hunger);
+//! ```
+//!
+//! Extensions can add
+//!
+//! * verbs, the first words of commands, carried out instead of the
+//!   built-in verbs, after which the turn ends like after any other
+//!   command,
+//! * functions of the template language, called with the values of
+//!   their arguments,
+//! * attributes, properties with a default value for entities which
+//!   do not have them (see `World::property`), and
+//! * listeners for events (see `events`).
+//!
+//! What extensions add belongs to the world, but is not saved with
+//! it; the program running the game registers its extensions again
+//! when it starts.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::types::*;
+use super::engine::Engine;
+use super::Value;
+
+/// A verb added by an extension, called with the rest of the command.
+pub type VerbHandler = Arc<Fn(&mut PlayerState, &str) + Send + Sync>;
+
+/// A function of the template language added by an extension.
+pub type TemplateFunction = Arc<Fn(&World, &[Value]) -> Result<Value, String> + Send + Sync>;
+
+/// A game system which can be added to engines.
+pub trait Extension {
+    /// The name of the extension, for listing extensions.
+    fn name(&self) -> &str;
+
+    /// Register what the extension adds.
+    fn register(&self, registry: &mut Registry);
+}
+
+/// What extensions added to a world.
+#[derive(Default)]
+pub struct Extensions {
+    /// The names of the extensions, in the order they were added.
+    pub names: Vec<String>,
+    verbs: BTreeMap<String, VerbHandler>,
+    functions: BTreeMap<String, TemplateFunction>,
+    attributes: BTreeMap<String, Property>,
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Extensions({})", self.names.join(", "))
+    }
+}
+
+impl Extensions {
+    /// Return the verb added for the first word of a command, if any.
+    pub fn verb(&self, command: &str) -> Option<VerbHandler> {
+        command.split_whitespace().next().and_then(|w| self.verbs.get(&w.to_lowercase())).cloned()
+    }
+
+    /// Return the template function added with a name, if any.
+    pub fn function(&self, name: &str) -> Option<&TemplateFunction> {
+        self.functions.get(name)
+    }
+
+    /// Return the default value of an attribute, if one was added.
+    pub fn attribute(&self, key: &str) -> Option<&Property> {
+        self.attributes.get(key)
+    }
+}
+
+/// Where an extension registers what it adds to a world.
+pub struct Registry<'a> {
+    pub world: &'a mut World,
+}
+
+impl<'a> Registry<'a> {
+    /// Add a verb.  Commands starting with `word` are carried out by
+    /// `handler`, with the rest of the command.
+    pub fn verb<F: Fn(&mut PlayerState, &str) + Send + Sync + 'static>(&mut self, word: &str, handler: F) {
+        self.world.extensions.verbs.insert(word.to_lowercase(), Arc::new(handler));
+    }
+
+    /// Add a function to the template language.
+    pub fn function<F>(&mut self, name: &str, function: F)
+        where F: Fn(&World, &[Value]) -> Result<Value, String> + Send + Sync + 'static
+    {
+        self.world.extensions.functions.insert(name.to_string(), Arc::new(function));
+    }
+
+    /// Add an attribute: a property which entities without it have
+    /// with the given value.
+    pub fn attribute(&mut self, key: &str, default: Property) {
+        self.world.extensions.attributes.insert(key.to_string(), default);
+    }
+
+    /// Call `listener` with every event delivered from now on.
+    pub fn listener<F: FnMut(&mut World, &Event) + Send + 'static>(&mut self, listener: F) {
+        self.world.subscribe(listener);
+    }
+}
+
+impl Engine {
+    /// Add an extension to the engine's world.
+    pub fn extend(&mut self, extension: &Extension) {
+        extension.register(&mut Registry { world: &mut self.world });
+        self.world.extensions.names.push(extension.name().to_string());
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! A C interface to the engine, for hosting games in programs not
+//! written in Rust, like C programs or Python scripts using ctypes.
+//! Built with the `ffi` feature, the shared library exports these
+//! functions, declared in `include/mudstuck.h`:
+//!
+//! ```text
+//! MudstuckGame *mudstuck_new(const uint8_t *bundle, size_t len, uint64_t seed, char **error);
+//! char *mudstuck_step(MudstuckGame *game, const char *input);
+//! int mudstuck_over(const MudstuckGame *game);
+//! char *mudstuck_save(MudstuckGame *game);
+//! int mudstuck_restore(MudstuckGame *game, const char *saved, char **error);
+//! void mudstuck_free(MudstuckGame *game);
+//! void mudstuck_string_free(char *s);
+//! ```
+//!
+//! Games are started from the bytes of a bundle (see `bundle`).  Each
+//! step returns the text the game shows.  Strings returned by the
+//! library belong to the caller, who frees them with
+//! `mudstuck_string_free`; errors are returned like that through the
+//! `error` argument, unless it is null.
+//!
+//! Panics do not unwind into the caller.  Should the library fail
+//! unexpectedly, functions return null or -1 like on errors, and the
+//! game is best ended.
+
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use super::bundle::Bundle;
+use super::engine::Engine;
+use super::session::Session;
+use super::step::{Output, SessionId};
+
+/// A game hosted through the C interface.
+pub struct MudstuckGame {
+    engine: Engine,
+    session: SessionId,
+}
+
+/// Return a string to C, without the NUL characters C cannot take.
+fn to_c(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "")).map(|c| c.into_raw()).unwrap_or(ptr::null_mut())
+}
+
+/// Report an error through the `error` argument of a function.
+unsafe fn report(error: *mut *mut c_char, e: &str) {
+    if !error.is_null() {
+        *error = to_c(e);
+    }
+}
+
+/// Return the message a panic was started with.
+fn panic_message(payload: &Box<Any + Send>) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s,
+        None => payload.downcast_ref::<String>().map(|s| s.as_str()).unwrap_or("unknown cause"),
+    }
+}
+
+/// Call `f`, and return what it returns, or `failed` if it panics,
+/// with the error reported through `error`.
+pub unsafe fn guarded<T, F: FnOnce() -> T>(error: *mut *mut c_char, failed: T, f: F) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => {
+            report(error, &format!("internal error: {}", panic_message(&payload)));
+            failed
+        },
+    }
+}
+
+/// Start a game of the bundle in `len` bytes at `bundle`, with the
+/// random number generator seeded with `seed`.  Returns null on
+/// errors.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_new(bundle: *const u8, len: usize, seed: u64, error: *mut *mut c_char)
+                                      -> *mut MudstuckGame {
+    if bundle.is_null() {
+        report(error, "no bundle given");
+        return ptr::null_mut();
+    }
+    guarded(error, ptr::null_mut(), || {
+        match Bundle::from_bytes(slice::from_raw_parts(bundle, len)).and_then(|b| b.world()) {
+            Ok(world) => {
+                let mut engine = Engine::new(world, seed);
+                let session = engine.attach(Session::new(engine.player));
+                Box::into_raw(Box::new(MudstuckGame { engine: engine, session: session }))
+            },
+            Err(e) => {
+                report(error, &e);
+                ptr::null_mut()
+            },
+        }
+    })
+}
+
+/// Handle a line typed by the player, and return the text shown.
+/// Returns null if the game or the input is missing.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_step(game: *mut MudstuckGame, input: *const c_char) -> *mut c_char {
+    if game.is_null() || input.is_null() {
+        return ptr::null_mut();
+    }
+    let game = &mut *game;
+    guarded(ptr::null_mut(), ptr::null_mut(), || {
+        let input = CStr::from_ptr(input).to_string_lossy();
+        let text: String = game.engine.step(game.session, &input).into_iter()
+            .filter_map(|e| match e.output {
+                Output::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect();
+        to_c(&text)
+    })
+}
+
+/// Return 1 once the game is over, and 0 before.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_over(game: *const MudstuckGame) -> c_int {
+    guarded(ptr::null_mut(), -1, || {
+        if game.is_null() || (*game).engine.session((*game).session).is_none() { 1 } else { 0 }
+    })
+}
+
+/// Return the game saved to a string (see `save`), or null if it
+/// cannot be saved.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_save(game: *mut MudstuckGame) -> *mut c_char {
+    if game.is_null() {
+        return ptr::null_mut();
+    }
+    guarded(ptr::null_mut(), ptr::null_mut(), || {
+        match (*game).engine.with_player(|ps| ps.save_to_string()) {
+            Ok(s) => to_c(&s),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Restore a game saved with `mudstuck_save`.  Returns 0 on success
+/// and -1 on errors, and the game is left as it was.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_restore(game: *mut MudstuckGame, saved: *const c_char, error: *mut *mut c_char)
+                                          -> c_int {
+    if game.is_null() || saved.is_null() {
+        report(error, "no game or saved game given");
+        return -1;
+    }
+    let game = &mut *game;
+    guarded(error, -1, || {
+        let saved = CStr::from_ptr(saved).to_string_lossy();
+        match game.engine.with_player(|ps| ps.restore_from_str(&saved)) {
+            Ok(()) => {
+                // The saved player may be another character.
+                game.engine.close(game.session);
+                game.session = game.engine.attach(Session::new(game.engine.player));
+                0
+            },
+            Err(e) => {
+                report(error, &e);
+                -1
+            },
+        }
+    })
+}
+
+/// End a game.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_free(game: *mut MudstuckGame) {
+    if !game.is_null() {
+        guarded(ptr::null_mut(), (), || drop(Box::from_raw(game)));
+    }
+}
+
+/// Free a string returned by the library.
+#[no_mangle]
+pub unsafe extern "C" fn mudstuck_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        guarded(ptr::null_mut(), (), || drop(CString::from_raw(s)));
+    }
+}
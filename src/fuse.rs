@@ -0,0 +1,102 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Fuses and burning objects.  Lit fuses burn down once per turn, and
+//! when they run out, their expiry text is shown to the player if the
+//! burning entity is nearby.
+
+use super::types::*;
+use super::command::Command;
+use super::error::Error;
+use super::print_wrap;
+
+impl World {
+    /// Burn all lit fuses down by one turn.  Returns the entities
+    /// whose fuses ran out, together with the room or character which
+    /// held them, if any, and their expiry texts.  Fuses which use up
+    /// their entity have already destroyed it.
+    pub fn burn_fuses(&mut self) -> Vec<(InternalName, Option<InternalName>, String)> {
+        let mut expired = Vec::new();
+        let mut used_up = Vec::new();
+        for e in self.entities.iter_mut() {
+            let id = e.id;
+            if let Some(f) = e.fuse_mut().filter(|f| f.lit) {
+                self.changed.insert(id);
+                if f.turns > 0 {
+                    f.turns -= 1;
+                }
+                if f.turns == 0 {
+                    f.lit = false;
+                    expired.push((id, f.expiry.clone()));
+                    if f.consume {
+                        used_up.push(id);
+                    }
+                }
+            }
+        }
+        let expired: Vec<_> = expired.into_iter().map(|(id, expiry)| (id, self.holder_of(&id), expiry)).collect();
+        for id in used_up {
+            self.destroy(&id);
+        }
+        expired
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Burn down the player's surroundings by one turn, showing the
+    /// expiry texts of fuses running out in the player's hands or
+    /// room.
+    pub fn burn_fuses(&mut self) {
+        let expired = self.world.burn_fuses();
+        self.notice_fuses(&expired);
+    }
+
+    /// Show the expiry texts of fuses which ran out in the player's
+    /// hands or room.
+    pub fn notice_fuses(&mut self, expired: &[(InternalName, Option<InternalName>, String)]) {
+        for &(_, holder, ref expiry) in expired {
+            if holder == Some(self.id) || holder == Some(self.location()) {
+                match self.eval_str(expiry) {
+                    Ok(s) =>
+                        print_wrap(&s, 72),
+                    Err(e) =>
+                        say!("{}", msg!(self.world, "error", error = e))
+                }
+            }
+        }
+    }
+
+    /// Light the direct object, starting its fuse.
+    pub fn light(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "light_what"));
+                return Ok(());
+            }
+        };
+        let name = match self.world.find_entity(words, &self.scope()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if !self.reach(&name) {
+            return Ok(());
+        }
+        match self.world.entity_mut(&name).and_then(|e| e.fuse_mut()) {
+            Some(ref f) if f.lit =>
+                say!("{}", msg!(self.world, "burning")),
+            Some(ref f) if f.turns == 0 =>
+                say!("{}", msg!(self.world, "burnt_out")),
+            Some(f) => {
+                f.lit = true;
+                say!("{}", msg!(self.world, "lit"));
+            },
+            None =>
+                say!("{}", msg!(self.world, "not_burnable")),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Giving and showing items to characters.  Characters decide by
+//! their gift rules whether to refuse, accept or trade what they are
+//! given, and may comment on what they are shown.
+
+use super::types::*;
+use super::command::{Command, Connector};
+use super::error::Error;
+use super::print_wrap;
+
+impl<'a> PlayerState<'a> {
+    /// Find the carried item and the living character of a command
+    /// like "give lamp to guard", telling the player what is wrong if
+    /// there are none.
+    fn item_and_character(&self, cmd: &Command, question: &str) -> Option<(InternalName, InternalName)> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, question));
+                return None;
+            }
+        };
+        let item = match self.world.find_entity(words, &self.me().inventory) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_carried"));
+                return None;
+            }
+        };
+        let who = match cmd.indirect_object {
+            Some((Connector::To, ref w)) => w,
+            _ => {
+                say!("{}", msg!(self.world, "give_to_whom"));
+                return None;
+            }
+        };
+        match self.world.find_entity(who, &self.room_contents()) {
+            Some(n) if self.world.entity(&n).unwrap().character().map(|c| c.stats.health > 0).unwrap_or(false) =>
+                Some((item, n)),
+            Some(_) => {
+                say!("{}", msg!(self.world, "pointless_give"));
+                None
+            },
+            None => {
+                say!("{}", msg!(self.world, "nobody_here"));
+                None
+            }
+        }
+    }
+
+    /// Give a carried item to a character ("give lamp to guard").
+    /// Characters take back what they own; otherwise their first
+    /// matching gift rule applies, and they refuse without one.
+    pub fn give(&mut self, cmd: &Command) -> Result<(), Error> {
+        let (item, npc) = match self.item_and_character(cmd, "give_what") {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let response = {
+            let c = self.world.entity(&npc).unwrap().character().unwrap();
+            match c.gifts.iter().find(|g| g.item.map(|i| i == item).unwrap_or(true)) {
+                Some(g) => g.response.clone(),
+                None if self.world.owner_of(&item) == Some(npc) =>
+                    Response::Accept(msg!(self.world, "returned")),
+                None =>
+                    Response::Refuse(msg!(self.world, "refused", who = self.world.short_description(&npc))),
+            }
+        };
+        let text = match response {
+            Response::Refuse(t) => t,
+            Response::Accept(t) => {
+                self.hand_over(&item, &npc);
+                t
+            },
+            Response::Trade(t, other) => {
+                self.hand_over(&item, &npc);
+                if let Some(c) = self.world.entity_mut(&npc).and_then(|e| e.character_mut()) {
+                    c.inventory.retain(|i| *i != other);
+                }
+                if let Some(s) = self.world.entity_mut(&npc).and_then(|e| e.shop_mut()) {
+                    s.stock.retain(|i| *i != other);
+                }
+                self.world.set_owner(other, None);
+                self.me_mut().inventory.push(other);
+                t
+            },
+        };
+        match self.eval_str(&text) {
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        Ok(())
+    }
+
+    /// Move a carried item into the inventory of a character, who
+    /// becomes its owner.
+    fn hand_over(&mut self, item: &InternalName, npc: &InternalName) {
+        self.me_mut().inventory.retain(|i| i != item);
+        if let Some(c) = self.world.entity_mut(npc).and_then(|e| e.character_mut()) {
+            c.inventory.push(*item);
+        }
+        self.world.set_owner(*item, Some(*npc));
+    }
+
+    /// Show a carried item to a character ("show ring to guard").
+    pub fn show(&mut self, cmd: &Command) -> Result<(), Error> {
+        let (item, npc) = match self.item_and_character(cmd, "show_what") {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let text = match self.world.entity(&npc).unwrap().character().unwrap().shown.get(&item) {
+            Some(t) => t.clone(),
+            None => msg!(self.world, "not_interested", who = self.world.short_description(&npc)),
+        };
+        match self.eval_str(&text) {
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        Ok(())
+    }
+}
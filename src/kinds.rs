@@ -0,0 +1,84 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Entity kinds.  A kind carries default descriptions, tags and
+//! attributes, which the entities of that kind inherit unless they
+//! override them.
+
+use std::mem;
+
+use super::types::*;
+
+impl World {
+    /// Add a kind under the given name, replacing any previous kind
+    /// of that name.
+    pub fn add_kind(&mut self, name: &str, kind: Kind) {
+        self.kinds.insert(name.to_string(), kind);
+    }
+
+    /// Return the chain of kinds starting with `kind` and following
+    /// the parents, or an error message for unknown kinds and cyclic
+    /// inheritance.
+    fn kind_chain(&self, kind: &str) -> Result<Vec<&Kind>, String> {
+        let mut names: Vec<&str> = vec![];
+        let mut chain = vec![];
+        let mut next = Some(kind);
+        while let Some(n) = next {
+            if names.contains(&n) {
+                return Err(format!("cyclic inheritance of kind: {}", n));
+            }
+            let k = try!(self.kinds.get(n).ok_or(format!("unknown kind: {}", n)));
+            names.push(n);
+            chain.push(k);
+            next = k.parent.as_ref().map(|p| p.as_str());
+        }
+        Ok(chain)
+    }
+
+    /// Let every entity inherit from its kind and the kind's parents.
+    /// Empty descriptions are filled in, and attributes are added
+    /// unless the entity already has an attribute of the same sort.
+    /// The nearest kind wins.  Resolving again does not change
+    /// anything.
+    pub fn resolve_kinds(&mut self) -> Result<(), String> {
+        let mut inherited = vec![];
+        for (idx, e) in self.entities.iter().enumerate() {
+            if let Some(ref k) = e.kind {
+                let chain = try!(self.kind_chain(k));
+                let mut ent = e.clone();
+                for kind in chain {
+                    if ent.short_description.len() == 0 {
+                        ent.short_description = kind.short_description.clone();
+                    }
+                    if ent.long_description.len() == 0 {
+                        ent.long_description = kind.long_description.clone();
+                    }
+                    for (locale, t) in kind.translations.iter() {
+                        let tr = ent.translations.entry(locale.clone()).or_insert(Translation::default());
+                        if tr.short.len() == 0 {
+                            tr.short = t.short.clone();
+                        }
+                        if tr.long.len() == 0 {
+                            tr.long = t.long.clone();
+                        }
+                    }
+                    for t in kind.tags.iter() {
+                        if !ent.tags.contains(t) {
+                            ent.tags.push(t.clone());
+                        }
+                    }
+                    for a in kind.attributes.iter() {
+                        if !ent.attributes.iter().any(|b| mem::discriminant(a) == mem::discriminant(b)) {
+                            ent.attributes.push(a.clone());
+                        }
+                    }
+                }
+                inherited.push((idx, ent));
+            }
+        }
+        for (idx, ent) in inherited {
+            self.entities[idx] = ent;
+        }
+        Ok(())
+    }
+}
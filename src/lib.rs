@@ -4,77 +4,350 @@
 //! Mudstuck errors and machinery to make them work with `try!'.
 
 extern crate uuid;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate ron;
+extern crate roxmltree;
+extern crate serde_json;
+extern crate sha2;
+extern crate pbkdf2;
+#[cfg(feature = "compression")]
+extern crate flate2;
+#[cfg(feature = "compression")]
+extern crate zstd;
+#[cfg(feature = "websocket")]
+extern crate tungstenite;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 
 use uuid::Uuid;
-use std::collections::BTreeMap;
+
+/// Show a line of text to the player, like `println!`.  The text goes
+/// through `output::say`, so it can be captured and recorded.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => ($crate::output::say(&format!($($arg)*)));
+}
+
+/// Return a message of the game in the locale of a world, with
+/// parameters filled in, like `msg!(world, "bought", price = 3)`; see
+/// `messages`.
+#[macro_export]
+macro_rules! msg {
+    ($world:expr, $key:expr) => ($world.message($key, &[]));
+    ($world:expr, $key:expr, $($name:ident = $val:expr),+) =>
+        ($world.message($key, &[$((stringify!($name), &$val.to_string()[..])),+]));
+}
 
 pub mod types;
+pub mod rng;
 mod error;
 mod scanner;
 mod template;
+mod actions;
+mod npc;
+mod combat;
+mod shop;
+mod kinds;
+mod movement;
+mod attach;
+mod fuse;
+mod senses;
+mod region;
+mod weather;
+mod machine;
+mod relation;
+mod ownership;
+mod give;
+mod player;
+mod combination;
+mod spawn;
+mod events;
+mod extension;
+mod trigger;
+mod script;
+mod rules;
+mod scheduler;
+mod clock;
+mod quest;
+mod score;
+mod container;
+mod visibility;
+mod describe;
+mod locale;
+mod messages;
+mod translate;
+mod map;
+mod dot;
+mod load;
+mod bundle;
+mod save;
+mod migrate;
+mod compress;
+mod autosave;
+pub mod output;
+mod validate;
+mod stats;
+mod lint;
+mod schema;
+mod reload;
+mod diff;
+mod dsl;
+mod builder;
+mod prefab;
+mod trizbort;
+mod procgen;
+mod decay;
 pub mod command;
+mod engine;
+mod session;
+mod chat;
+mod wizard;
+mod roles;
+mod accounts;
+mod limits;
+mod step;
+mod snapshot;
+mod metrics;
+mod audit;
+mod storage;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod telnet;
+pub mod websocket;
+mod server;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod walkthrough;
+
+pub use player::PlayerSeed;
+pub use builder::WorldBuilder;
+pub use prefab::Prefab;
+pub use engine::{Engine, Script, Mismatch};
+pub use bundle::{Bundle, Metadata};
+pub use walkthrough::{Walkthrough, Step, Failure};
+pub use procgen::{Generator, Layout};
+pub use events::{Events, Listener};
+pub use extension::{Extension, Extensions, Registry};
+pub use server::{Server, ServerConfig, Protocol, Reply, SNAPSHOT_INTERVAL};
+pub use session::Session;
+pub use chat::{Chat, Channel, Utterance, Moderation};
+pub use roles::Role;
+pub use accounts::{Account, Accounts};
+pub use limits::{Limits, Penalty};
+pub use step::{Output, OutputEvent, SessionId};
+pub use snapshot::{Snapshot, Journal};
+pub use metrics::{Metrics, Monitor, Counters};
+pub use audit::{AuditLog, Outcome};
+pub use storage::{Storage, Files};
+#[cfg(feature = "sqlite")]
+pub use sqlite::Sqlite;
+#[cfg(feature = "wasm")]
+pub use wasm::Game;
 
 use template::Ast;
 use types::*;
 
+/// Return the example world, which is built into the game.
 pub fn make_example_world() -> World {
-    let mut entities = vec![];
-
-    let r1_name = Uuid::new_v4();
-    let r2_name = Uuid::new_v4();
-    let d1_name = Uuid::new_v4();
-    let d1 = Entity {
-        id: d1_name,
-        name: vec!["rusty".to_string(), "metal".to_string(), "door".to_string()],
-        alias: Some("metal_door_1".to_string()),
-        short_description: "Metalltür".to_string(),
-        long_description: "Eine verbeulte, rostige Tür aus Metall.#(if (closed rusty.metal.door) \" Die Tür ist geschlossen.\" \"\")".to_string(),
-        attributes: vec![
-            Attribute::Doorlike(Connection { endpoints: (r1_name, r2_name) }),
-            Attribute::Closable(true),
-            Attribute::Lockable(false),
-        ],
-    };
-    let r1 = Entity {
-        id: r1_name,
-        name: vec!["small".to_string(), "rock".to_string(), "room".to_string()],
-        alias: None,
-        short_description: "Ein kleiner Raum mit Wänden aus rohem Fels".to_string(),
-        long_description:  "Der Raum hat eine Größe von etwa sechs Quadratmetern. Der Boden, die Decke und die Wände bestehen aus roh behauenem Fels. Der Boden ist mit Schutt bedeckt.  In einer der Wände befindet sich eine zugemauerte Türöffnung, gegenüber ist eine #(if (closed rusty.metal.door) \"geschlossene\" \"geöffnete\")#(if (locked rusty.metal.door) \" verriegelte\" \"\") Metalltür eingelassen.".to_string(),
-        attributes: vec![
-            Attribute::Roomlike(Room {
-                entities: vec![d1_name]
-            }),
-        ],
-    };
-    let r2 = Entity {
-        id: r2_name,
-        name: vec!["cramped".to_string(), "rock".to_string(), "tunnel".to_string()],
-        alias: None,
-        short_description: "Ein niedriger Felstunnel".to_string(),
-        long_description: "Ein schmaler, niedriger Tunnel, etwa 1,70 Meter hoch und einen Meter breit. Der Tunnel führt leicht bergab und hat an beiden Enden Metalltüren".to_string(),
-        attributes: vec![
-            Attribute::Roomlike(Room {
-                entities: vec![d1_name]
-            }),
-        ],
-    };
-    entities.push(d1);
-    entities.push(r1);
-    entities.push(r2);
-
-    let mut map = BTreeMap::new();
-    for (i, e) in entities.iter().enumerate() {
-        map.insert(e.id, i);
-    }
-
-    let world = World {
-        name: "Example World".to_string(),
-        entities: entities,
-        start_location: r1_name,
-        entity_map: map,
-    };
-    world
+    World::load_from_str(include_str!("../worlds/example.ron")).unwrap()
+}
+
+/// Derive a random seed from the bytes of a UUID.
+fn seed_from(u: &Uuid) -> u64 {
+    u.as_bytes().iter().take(8).fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+impl Entity {
+    /// Return the value of a property, if it is set.
+    pub fn property(&self, key: &str) -> Option<&Property> {
+        self.properties.get(key)
+    }
+
+    /// Set a property, returning its previous value.
+    pub fn set_property(&mut self, key: &str, value: Property) -> Option<Property> {
+        self.properties.insert(key.to_string(), value)
+    }
+
+    /// Return the room part of this entity, if it is roomlike.
+    fn room(&self) -> Option<&Room> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Roomlike(ref r) => Some(r), _ => None }).next()
+    }
+
+    fn room_mut(&mut self) -> Option<&mut Room> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Roomlike(ref mut r) => Some(r), _ => None }).next()
+    }
+
+    /// Return the connection of this entity, if it is doorlike.
+    fn connection(&self) -> Option<&Connection> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Doorlike(ref c) => Some(c), _ => None }).next()
+    }
+
+    /// Return whether this entity is closed, if it can be closed at
+    /// all.
+    fn closed(&self) -> Option<bool> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Closable(c) => Some(c), _ => None }).next()
+    }
+
+    fn closed_mut(&mut self) -> Option<&mut bool> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Closable(ref mut c) => Some(c), _ => None }).next()
+    }
+
+    /// Return the vehicle part of this entity, if it can be entered.
+    fn vehicle(&self) -> Option<&Vehicle> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Enterable(ref v) => Some(v), _ => None }).next()
+    }
+
+    /// Return the state machine of this entity, if it has one.
+    fn machine(&self) -> Option<&Machine> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Stateful(ref m) => Some(m), _ => None }).next()
+    }
+
+    fn machine_mut(&mut self) -> Option<&mut Machine> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Stateful(ref mut m) => Some(m), _ => None }).next()
+    }
+
+    /// Return the triggers of this entity, if it has any.
+    fn triggers(&self) -> Option<&Vec<Trigger>> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Triggered(ref t) => Some(t), _ => None }).next()
+    }
+
+    fn triggers_mut(&mut self) -> Option<&mut Vec<Trigger>> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Triggered(ref mut t) => Some(t), _ => None }).next()
+    }
+
+    /// Return the rules of this entity, if it has any.
+    fn rules(&self) -> Option<&Vec<Rule>> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Ruled(ref r) => Some(r), _ => None }).next()
+    }
+
+    /// Return the fuse of this entity, if it can be lit.
+    fn fuse(&self) -> Option<&Fuse> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Burnable(ref f) => Some(f), _ => None }).next()
+    }
+
+    fn fuse_mut(&mut self) -> Option<&mut Fuse> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Burnable(ref mut f) => Some(f), _ => None }).next()
+    }
+
+    fn decay_mut(&mut self) -> Option<&mut Decay> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Perishable(ref mut d) => Some(d), _ => None }).next()
+    }
+
+    /// Return the code input of this entity, if it is one.
+    fn code_input_mut(&mut self) -> Option<&mut CodeInput> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::CodeEntry(ref mut c) => Some(c), _ => None }).next()
+    }
+
+    /// Return the container of this entity, if it is one.
+    fn container(&self) -> Option<&Container> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Container(ref c) => Some(c), _ => None }).next()
+    }
+
+    fn container_mut(&mut self) -> Option<&mut Container> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Container(ref mut c) => Some(c), _ => None }).next()
+    }
+
+    /// Return the lock of this entity, if it is lockable.
+    fn lock(&self) -> Option<&Lock> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Lockable(ref l) => Some(l), _ => None }).next()
+    }
+
+    fn lock_mut(&mut self) -> Option<&mut Lock> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Lockable(ref mut l) => Some(l), _ => None }).next()
+    }
+
+    /// Return the key part of this entity, if it can be used as a key.
+    fn key(&self) -> Option<&Key> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Keylike(ref k) => Some(k), _ => None }).next()
+    }
+
+    /// Return the wearable part of this entity, if it can be worn.
+    fn wearable(&self) -> Option<&Wearable> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Wearable(ref w) => Some(w), _ => None }).next()
+    }
+
+    /// Return the consumable part of this entity, if it can be eaten
+    /// (`drink` is false) or drunk (`drink` is true).
+    fn consumable_mut(&mut self, drink: bool) -> Option<&mut Consumable> {
+        self.attributes.iter_mut().filter_map(|a| match a {
+            &mut Attribute::Edible(ref mut c) if !drink => Some(c),
+            &mut Attribute::Drinkable(ref mut c) if drink => Some(c),
+            _ => None
+        }).next()
+    }
+
+    /// Return the text written on this entity, if it is readable.
+    fn text(&self) -> Option<&String> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Readable(ref t) => Some(t), _ => None }).next()
+    }
+
+    /// Return the character part of this entity, if it is
+    /// characterlike.
+    fn character(&self) -> Option<&Character> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Characterlike(ref c) => Some(c), _ => None }).next()
+    }
+
+    fn character_mut(&mut self) -> Option<&mut Character> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Characterlike(ref mut c) => Some(c), _ => None }).next()
+    }
+
+    /// Return the price of this entity, if it has one.
+    fn price(&self) -> Option<u32> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Price(p) => Some(p), _ => None }).next()
+    }
+
+    /// Return the amount of money this entity is worth, if it is
+    /// money.
+    fn money(&self) -> Option<u32> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Money(m) => Some(m), _ => None }).next()
+    }
+
+    /// Return the shop of this entity, if it is a shopkeeper.
+    fn shop(&self) -> Option<&Shop> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Shopkeeper(ref s) => Some(s), _ => None }).next()
+    }
+
+    fn shop_mut(&mut self) -> Option<&mut Shop> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Shopkeeper(ref mut s) => Some(s), _ => None }).next()
+    }
+
+    /// Return the concealment of this entity, if it hides anything.
+    fn concealment_mut(&mut self) -> Option<&mut Concealment> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Concealing(ref mut c) => Some(c), _ => None }).next()
+    }
+
+    /// Return the number of items in this entity, if it is a stack.
+    fn stack(&self) -> Option<u32> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Stackable(n) => Some(n), _ => None }).next()
+    }
+
+    fn stack_mut(&mut self) -> Option<&mut u32> {
+        self.attributes.iter_mut().filter_map(|a| match a { &mut Attribute::Stackable(ref mut n) => Some(n), _ => None }).next()
+    }
+
+    /// Return the weapon part of this entity, if it is a weapon.
+    fn weapon(&self) -> Option<&Weapon> {
+        self.attributes.iter().filter_map(|a| match a { &Attribute::Weapon(ref w) => Some(w), _ => None }).next()
+    }
+
+    /// Return true if this entity can be picked up and carried
+    /// around.  Rooms, doors, characters and fixed things stay where
+    /// they are.
+    fn is_portable(&self) -> bool {
+        !self.attributes.iter().any(|a| match a {
+            &Attribute::Roomlike(_) | &Attribute::Doorlike(_) | &Attribute::Characterlike(_) |
+            &Attribute::Fixed => true,
+            _ => false
+        })
+    }
 }
 
 impl World {
@@ -84,6 +357,34 @@ impl World {
         self.entity_map.get(name).and_then(|idx| self.entities.get(*idx))
     }
 
+    /// Return a mutable reference to the entity with the given name,
+    /// if possible.  The entity counts as changed from now on.
+    fn entity_mut(&mut self, name: &InternalName) -> Option<&mut Entity> {
+        match self.entity_map.get(name) {
+            Some(idx) => {
+                self.changed.insert(*name);
+                self.entities.get_mut(*idx)
+            },
+            None => None,
+        }
+    }
+
+    /// Find the entity among `candidates` which is described by the
+    /// given words.  An entity matches if every word is part of its
+    /// name or one of its tags, so "door" finds the rusty metal door,
+    /// or if the only word is its alias.
+    fn find_entity(&self, words: &Name, candidates: &[InternalName]) -> Option<InternalName> {
+        for c in candidates {
+            if let Some(e) = self.entity(c) {
+                if words.iter().all(|w| e.name.contains(w) || e.tags.contains(w)) ||
+                    (words.len() == 1 && e.alias.as_ref() == words.first()) {
+                    return Some(*c);
+                }
+            }
+        }
+        None
+    }
+
     /// Evaluate a string in the context of the world.  The string can
     /// contain expressions (marked with #) which will be evaluated in
     /// the state that the world itself is currently.  Returns the
@@ -91,9 +392,22 @@ impl World {
     /// an error message indicates a syntax or logic error in the
     /// input string.  Correct strings will never return errors.
     fn eval_str(&self, txt: &str) -> Result<String, String> {
+        self.eval_for(txt, None)
+    }
+
+    /// Evaluate a string like `eval_str`, on behalf of a player.  The
+    /// player is available to the expressions as `player`.
+    fn eval_for(&self, txt: &str, player: Option<&PlayerState>) -> Result<String, String> {
+        let started = self.monitor.start();
+        let res = self.eval_template(txt, player);
+        self.monitor.template(started);
+        res
+    }
+
+    fn eval_template(&self, txt: &str, player: Option<&PlayerState>) -> Result<String, String> {
         match template::parse(txt) {
             Ok(ast) => {
-                match self.eval(ast) {
+                match self.eval(ast, player) {
                     Err(e) => Err(e),
                     Ok(Value::Str(s)) => Ok(s),
                     Ok(val) => Err(format!("invalid value: {:?}", val))
@@ -103,6 +417,153 @@ impl World {
         }
     }
 
+    /// Evaluate a condition on behalf of a player.  A condition is a
+    /// single template expression (without the leading #) which must
+    /// evaluate to a boolean.
+    fn eval_condition(&self, txt: &str, player: Option<&PlayerState>) -> Result<bool, String> {
+        let started = self.monitor.start();
+        let res = match template::parse_expression(txt) {
+            Ok(ast) => {
+                match self.eval(ast, player) {
+                    Err(e) => Err(e),
+                    Ok(Value::Bool(b)) => Ok(b),
+                    Ok(val) => Err(format!("invalid condition value: {:?}", val))
+                }
+            },
+            Err(e) => Err(e)
+        };
+        self.monitor.template(started);
+        res
+    }
+
+    /// Return true if the entity `key` fits into `lock`, either
+    /// because it is one of the keys bound to the lock or because it
+    /// is of the lock's key class.
+    fn key_fits(&self, lock: &Lock, key: &InternalName) -> bool {
+        if lock.keys.contains(key) {
+            return true;
+        }
+        match (&lock.key_class, self.entity(key).and_then(|e| e.key())) {
+            (&Some(ref class), Some(k)) => k.classes.contains(class),
+            _ => false,
+        }
+    }
+
+    /// Return the value of a property of the entity with the given
+    /// name, or the default of an attribute added by an extension.
+    pub fn property(&self, name: &InternalName, key: &str) -> Option<&Property> {
+        self.entity(name).and_then(|e| e.property(key).or_else(|| self.extensions.attribute(key)))
+    }
+
+    /// Set a property of the entity with the given name, returning
+    /// the previous value.  Does nothing if there is no such entity.
+    pub fn set_property(&mut self, name: &InternalName, key: &str, value: Property) -> Option<Property> {
+        self.entity_mut(name).and_then(|e| e.set_property(key, value))
+    }
+
+    /// Return the names of all entities carrying the given tag.
+    pub fn entities_with_tag(&self, tag: &str) -> Vec<InternalName> {
+        self.entities.iter().filter(|e| e.tags.iter().any(|t| t == tag)).map(|e| e.id).collect()
+    }
+
+    /// Return the entities the given entity is attached to.
+    fn attached_to(&self, name: &InternalName) -> Vec<InternalName> {
+        self.relations.objects(relation::ATTACHED, name)
+    }
+
+    /// Return the doors which connect the given room to other rooms.
+    /// Doors are not listed as contents of rooms, but are part of
+    /// both rooms they connect, except for one-way doors, which are
+    /// only part of the room they lead out of.
+    fn doors_at(&self, room: &InternalName) -> Vec<InternalName> {
+        self.entities.iter().filter(|e| match e.connection() {
+            Some(c) => c.endpoints.0 == *room || (c.endpoints.1 == *room && !c.one_way),
+            None => false,
+        }).map(|e| e.id).collect()
+    }
+
+    /// Return the room which currently contains the given entity, if
+    /// any.
+    fn location_of(&self, name: &InternalName) -> Option<InternalName> {
+        self.entities.iter().find(|e| match e.room() {
+            Some(r) => r.entities.contains(name),
+            None => false,
+        }).map(|e| e.id)
+    }
+
+    /// Return the evaluated short description of an entity, for use
+    /// in messages.  Falls back to the unevaluated text on errors.
+    fn short_description(&self, name: &InternalName) -> String {
+        match self.entity(name) {
+            Some(e) => self.with_quantity(self.eval_str(e.short_text(self.locale())).unwrap_or(e.short_text(self.locale()).to_string()),
+                                          self.quantity(name)),
+            None => String::new(),
+        }
+    }
+
+    /// Return the number of items an entity stands for: the size of
+    /// a stack, or one for everything else.
+    fn quantity(&self, name: &InternalName) -> u32 {
+        self.entity(name).and_then(|e| e.stack()).unwrap_or(1)
+    }
+
+    /// Add the number of items to a description of a stack.  Single
+    /// items are described as they are.
+    fn with_quantity(&self, desc: String, quantity: u32) -> String {
+        if quantity == 1 {
+            desc
+        } else {
+            msg!(self, "quantity", desc = desc, quantity = quantity)
+        }
+    }
+
+    /// Split `count` items off a stack into a new stack, which is not
+    /// placed anywhere yet.  Returns the name of the new stack, or
+    /// None if the stack does not hold more than `count` items.
+    fn split_stack(&mut self, name: &InternalName, count: u32) -> Option<InternalName> {
+        let mut ent = match self.entity_mut(name) {
+            Some(e) => {
+                match e.stack_mut() {
+                    Some(n) if *n > count => *n -= count,
+                    _ => return None,
+                }
+                e.clone()
+            },
+            None => return None,
+        };
+        ent.id = self.next_id();
+        *ent.stack_mut().unwrap() = count;
+        let id = self.spawn(ent, None);
+        if let Some(owner) = self.relations.object(relation::OWNED_BY, name) {
+            self.relations.relate(relation::OWNED_BY, id, owner);
+        }
+        Some(id)
+    }
+
+    /// Merge a stack into an identical stack among `others`, if there
+    /// is one.  Stacks are identical if they have the same name.
+    /// Returns the stack the items ended up in.
+    fn merge_stack(&mut self, name: &InternalName, others: &[InternalName]) -> Option<InternalName> {
+        let target = match self.entity(name) {
+            Some(e) if e.stack().is_some() =>
+                others.iter().find(|o| *o != name && match self.entity(o) {
+                    Some(x) => x.name == e.name && x.stack().is_some(),
+                    None => false,
+                }).cloned(),
+            _ => None,
+        };
+        let target = match target {
+            Some(t) => t,
+            None => return None,
+        };
+        let count = self.quantity(name);
+        if let Some(n) = self.entity_mut(&target).and_then(|e| e.stack_mut()) {
+            *n += count;
+        }
+        self.destroy(name);
+        Some(target)
+    }
+
     fn get_by_name(&self, name: &Name) -> Option<InternalName> {
         let mut res = None;
         for e in self.entities.iter() {
@@ -115,10 +576,10 @@ impl World {
     
     /// Evaluate a list of expressions into a list of values, or an
     /// error message.
-    fn eval_list(&self, args: Vec<Ast>) -> Result<Vec<Value>, String> {
+    fn eval_list(&self, args: Vec<Ast>, player: Option<&PlayerState>) -> Result<Vec<Value>, String> {
         let mut res = Vec::new();
         for a in args {
-            let ar = try!(self.eval(a));
+            let ar = try!(self.eval(a, player));
             res.push(ar);
         }
         Ok(res)
@@ -133,7 +594,7 @@ impl World {
     }
     
     /// Evaluate an expression into a value, or an error message.
-    fn eval(&self, ast: Ast) -> Result<Value, String> {
+    fn eval(&self, ast: Ast, player: Option<&PlayerState>) -> Result<Value, String> {
         match ast {
             Ast::Empty =>
                 Ok(Value::Str("".to_string())),
@@ -142,13 +603,15 @@ impl World {
             Ast::Str(s) =>
                 Ok(Value::Str(s.clone())),
             Ast::Id(s) => {
+                if let Some(f) = self.function(&s) {
+                    return Ok(f);
+                }
                 match s.as_str() {
-                    "if" => Ok(Value::Fun(Function::If, "if", true, 3, 3)),
-                    "closed" => Ok(Value::Fun(Function::Closed, "closed", false, 1, 1)),
-                    "locked" => Ok(Value::Fun(Function::Locked, "locked", false, 1, 1)),
+                    "player" if player.is_some() => Ok(Value::Reference(player.unwrap().id)),
+                    "here" if player.is_some() => Ok(Value::Reference(player.unwrap().location())),
                     _ => {
                         let sv = self.from_script_name(&s);
-                        match self.get_by_name(&sv) {
+                        match self.by_alias(&s).or_else(|| self.get_by_name(&sv)) {
                             None => Err(format!("undefined identifier: {}", s)),
                             Some(name) => Ok(Value::Reference(name))
                         }
@@ -156,16 +619,20 @@ impl World {
                 }
             },
             Ast::Seq(l, r) => {
-                let lhs = try!(self.eval(*l));
-                let rhs = try!(self.eval(*r));
+                let lhs = try!(self.eval(*l, player));
+                let rhs = try!(self.eval(*r, player));
                 match (lhs, rhs) {
                     (Value::Str(l), Value::Str(r)) =>
                         Ok(Value::Str(format!("{}{}", l, r))),
+                    (Value::Str(l), Value::Int(r)) =>
+                        Ok(Value::Str(format!("{}{}", l, r))),
+                    (Value::Str(l), Value::Reference(r)) =>
+                        Ok(Value::Str(format!("{}{}", l, self.short_description(&r)))),
                     _ => Err("invalid operand for concatenation".to_string())
                 }
             },
             Ast::Call(f, args) => {
-                let fun = try!(self.eval(*f));
+                let fun = try!(self.eval(*f, player));
                 match fun {
                     Value::Fun(_, name, special, min_args, max_args) => {
                         let acnt = args.len();
@@ -178,9 +645,9 @@ impl World {
                         let arguments = if special {
                             args.into_iter().map(|a| Value::Expr(a.clone())).collect()
                         } else {
-                            try!(self.eval_list(args))
+                            try!(self.eval_list(args, player))
                         };
-                        self.apply(fun.clone(), arguments)
+                        self.apply(fun.clone(), arguments, player)
                     },
                     _ =>
                         Err("non-function in function position".to_string())
@@ -189,19 +656,44 @@ impl World {
         }
     }
 
+    /// Return the function of the template language with the given
+    /// name, built in or added by an extension, if there is one.
+    fn function(&self, name: &str) -> Option<Value> {
+        builtin_function(name).or_else(|| self.extensions.function(name).map(|_| {
+            Value::Fun(Function::Extension(name.to_string()), "extension", false, 0, usize::MAX)
+        }))
+    }
+
+    /// Return the entity with the given name, or an error naming it if
+    /// there is none.
+    fn known_entity(&self, name: &InternalName) -> Result<&Entity, String> {
+        self.entity(name).ok_or_else(|| format!("unknown entity: {}", name))
+    }
+
+    /// Return the stats, conditions and wealth of the character that
+    /// a value refers to, if it refers to one.
+    fn character_of(&self, v: &Value) -> Option<(&Stats, &[Condition], u32)> {
+        match *v {
+            Value::Reference(ref name) =>
+                self.entity(name).and_then(|e| e.character()).map(|c| (&c.stats, &c.conditions[..], c.purse)),
+            _ =>
+                None,
+        }
+    }
+
     /// Apply a functional value to a list of argument values.
-    fn apply(&self, f: Value, args: Vec<Value>) -> Result<Value, String> {
+    fn apply(&self, f: Value, args: Vec<Value>, player: Option<&PlayerState>) -> Result<Value, String> {
         match f {
             Value::Fun(fun_id, _,  _, _, _) =>
                 match fun_id {
                     Function::If => {
                         if let &Value::Expr(ref cond) = args.get(0).unwrap() {
-                            let cval = try!(self.eval(cond.clone()));
+                            let cval = try!(self.eval(cond.clone(), player));
                             match cval {
                                 Value::Bool(b) => {
                                     let e = if b { args.get(1).unwrap() } else { args.get(2).unwrap() };
                                     if let &Value::Expr(ref ee) = e {
-                                        self.eval(ee.clone())
+                                        self.eval(ee.clone(), player)
                                     } else {
                                         Err("internal error, if expression already evaluated".to_string())
                                     }
@@ -216,7 +708,7 @@ impl World {
                     },
                     Function::Closed => {
                         if let Some(&Value::Reference(ref name)) = args.get(0) {
-                            let ent = self.entity(name).unwrap();
+                            let ent = try!(self.known_entity(name));
                             match ent.attributes.iter().find(|&a| match a { &Attribute::Closable(_) => true, _ => false }) {
                                 Some(&Attribute::Closable(closed)) =>
                                     Ok(Value::Bool(closed)),
@@ -229,10 +721,10 @@ impl World {
                     },
                     Function::Locked => {
                         if let Some(&Value::Reference(ref name)) = args.get(0) {
-                            let ent = self.entity(name).unwrap();
+                            let ent = try!(self.known_entity(name));
                             match ent.attributes.iter().find(|&a| match a { &Attribute::Lockable(_) => true, _ => false }) {
-                                Some(&Attribute::Lockable(closed)) =>
-                                    Ok(Value::Bool(closed)),
+                                Some(&Attribute::Lockable(ref lock)) =>
+                                    Ok(Value::Bool(lock.locked)),
                                 _ =>
                                     Ok(Value::Bool(false)),
                             }
@@ -240,6 +732,161 @@ impl World {
                             Err("function locked requires a name of an entity".to_string())
                         }
                     },
+                    Function::Health | Function::Strength | Function::Wealth => {
+                        match args.get(0).and_then(|a| self.character_of(a)) {
+                            Some((stats, _, purse)) =>
+                                Ok(Value::Int(match fun_id {
+                                    Function::Health => stats.health as i64,
+                                    Function::Strength => stats.strength as i64,
+                                    _ => purse as i64,
+                                })),
+                            None =>
+                                Err("functions health, strength and wealth require a character".to_string()),
+                        }
+                    },
+                    Function::Alive => {
+                        match args.get(0) {
+                            Some(a @ &Value::Reference(_)) =>
+                                match self.character_of(a) {
+                                    Some((stats, _, _)) => Ok(Value::Bool(stats.health > 0)),
+                                    None => Ok(Value::Bool(false)),
+                                },
+                            _ =>
+                                Err("function alive requires a name of an entity".to_string())
+                        }
+                    },
+                    Function::Condition => {
+                        match (args.get(0), args.get(1)) {
+                            (Some(a), Some(&Value::Str(ref cond))) => {
+                                let cond = try!(condition_by_name(cond).ok_or(format!("unknown condition: {}", cond)));
+                                match self.character_of(a) {
+                                    Some((_, conditions, _)) => Ok(Value::Bool(conditions.contains(&cond))),
+                                    None => Ok(Value::Bool(false)),
+                                }
+                            },
+                            _ =>
+                                Err("function condition requires a character and a condition name".to_string())
+                        }
+                    },
+                    Function::Tagged => {
+                        match (args.get(0), args.get(1)) {
+                            (Some(&Value::Reference(ref name)), Some(&Value::Str(ref tag))) =>
+                                Ok(Value::Bool(try!(self.known_entity(name)).tags.contains(tag))),
+                            _ =>
+                                Err("function tagged requires a name of an entity and a tag".to_string())
+                        }
+                    },
+                    Function::Property => {
+                        match (args.get(0), args.get(1)) {
+                            (Some(&Value::Reference(ref name)), Some(&Value::Str(ref key))) =>
+                                match try!(self.known_entity(name).map(|_| self.property(name, key))) {
+                                    Some(&Property::Bool(b)) => Ok(Value::Bool(b)),
+                                    Some(&Property::Int(i)) => Ok(Value::Int(i)),
+                                    Some(&Property::Str(ref s)) => Ok(Value::Str(s.clone())),
+                                    Some(&Property::Reference(r)) => Ok(Value::Reference(r)),
+                                    None => Err(format!("undefined property: {}", key)),
+                                },
+                            _ =>
+                                Err("function property requires a name of an entity and a property name".to_string())
+                        }
+                    },
+                    Function::Attached => {
+                        match (args.get(0), args.get(1)) {
+                            (Some(&Value::Reference(ref a)), Some(&Value::Reference(ref b))) =>
+                                Ok(Value::Bool(self.relations.related(relation::ATTACHED, a, b))),
+                            _ =>
+                                Err("function attached requires two names of entities".to_string())
+                        }
+                    },
+                    Function::On => {
+                        if let Some(&Value::Reference(ref name)) = args.get(0) {
+                            Ok(Value::Bool(try!(self.known_entity(name)).machine().map(|m| m.state == "on").unwrap_or(false)))
+                        } else {
+                            Err("function on requires a name of an entity".to_string())
+                        }
+                    },
+                    Function::Owner => {
+                        if let Some(&Value::Reference(ref name)) = args.get(0) {
+                            let owner = self.owner_of(name);
+                            match (owner, args.get(1)) {
+                                (Some(o), None) => Ok(Value::Reference(o)),
+                                (None, None) => Ok(Value::Str("".to_string())),
+                                (o, Some(&Value::Reference(ref who))) => Ok(Value::Bool(o == Some(*who))),
+                                (_, Some(_)) => Err("function owner requires a name of an entity as second argument".to_string()),
+                            }
+                        } else {
+                            Err("function owner requires a name of an entity".to_string())
+                        }
+                    },
+                    Function::Related => {
+                        match (args.get(0), args.get(1), args.get(2)) {
+                            (Some(&Value::Str(ref r)), Some(&Value::Reference(ref a)), Some(&Value::Reference(ref b))) =>
+                                Ok(Value::Bool(self.relations.related(r, a, b))),
+                            _ =>
+                                Err("function related requires a relation and two names of entities".to_string())
+                        }
+                    },
+                    Function::State => {
+                        if let Some(&Value::Reference(ref name)) = args.get(0) {
+                            let st = try!(self.known_entity(name)).machine().map(|m| m.state.as_str()).unwrap_or("");
+                            match args.get(1) {
+                                None => Ok(Value::Str(st.to_string())),
+                                Some(&Value::Str(ref s)) => Ok(Value::Bool(st == s)),
+                                Some(_) => Err("function state requires a string as second argument".to_string()),
+                            }
+                        } else {
+                            Err("function state requires a name of an entity".to_string())
+                        }
+                    },
+                    Function::Weather => {
+                        if let Some(&Value::Reference(ref name)) = args.get(0) {
+                            let w = self.weather_at(name).unwrap_or("");
+                            match args.get(1) {
+                                None => Ok(Value::Str(w.to_string())),
+                                Some(&Value::Str(ref s)) => Ok(Value::Bool(w == s)),
+                                Some(_) => Err("function weather requires a string as second argument".to_string()),
+                            }
+                        } else {
+                            Err("function weather requires a name of a room".to_string())
+                        }
+                    },
+                    Function::Time => {
+                        let (h, m) = self.time_of_day();
+                        Ok(Value::Str(format!("{:02}:{:02}", h, m)))
+                    },
+                    Function::Day =>
+                        Ok(Value::Int(self.day() as i64)),
+                    Function::Phase => {
+                        let p = self.phase();
+                        match args.get(0) {
+                            None => Ok(Value::Str(p.to_string())),
+                            Some(&Value::Str(ref s)) => Ok(Value::Bool(p == s)),
+                            Some(_) => Err("function phase requires a string as argument".to_string()),
+                        }
+                    },
+                    Function::Score =>
+                        Ok(Value::Int(self.score() as i64)),
+                    Function::Awarded => {
+                        if let Some(&Value::Str(ref s)) = args.get(0) {
+                            Ok(Value::Bool(self.is_awarded(s)))
+                        } else {
+                            Err("function awarded requires a string as argument".to_string())
+                        }
+                    },
+                    Function::Price => {
+                        if let Some(&Value::Reference(ref name)) = args.get(0) {
+                            Ok(Value::Int(try!(self.known_entity(name)).price().unwrap_or(0) as i64))
+                        } else {
+                            Err("function price requires a name of an entity".to_string())
+                        }
+                    },
+                    Function::OneOf =>
+                        Ok(args[self.rng.roll(args.len() as u32) as usize - 1].clone()),
+                    Function::Extension(ref name) =>
+                        match self.extensions.function(name) {
+                            Some(f) => f(self, &args),
+                            None => Err(format!("unknown function: {}", name)),
+                        },
                 },
             _ =>
                 Err("non-function in function position".to_string()),
@@ -255,6 +902,29 @@ pub enum Function {
     If,
     Closed,
     Locked,
+    Health,
+    Strength,
+    Condition,
+    Alive,
+    Wealth,
+    Price,
+    Tagged,
+    Property,
+    Attached,
+    On,
+    Weather,
+    State,
+    Related,
+    Owner,
+    Time,
+    Day,
+    Phase,
+    Score,
+    Awarded,
+    /// One of the arguments, chosen at random.
+    OneOf,
+    /// A function added by an extension, by name.
+    Extension(String),
 }
 
 #[derive(Debug, Clone)]
@@ -262,71 +932,164 @@ pub enum Value {
     Fun(Function, &'static str, bool, usize, usize),
     Reference(InternalName),
     Str(String),
+    Int(i64),
     Bool(bool),
     Expr(Ast),
 }
 
+/// Return the built-in function of the template language with the
+/// given name, if there is one.
+fn builtin_function(name: &str) -> Option<Value> {
+    Some(match name {
+        "if" => Value::Fun(Function::If, "if", true, 3, 3),
+        "closed" => Value::Fun(Function::Closed, "closed", false, 1, 1),
+        "locked" => Value::Fun(Function::Locked, "locked", false, 1, 1),
+        "health" => Value::Fun(Function::Health, "health", false, 1, 1),
+        "strength" => Value::Fun(Function::Strength, "strength", false, 1, 1),
+        "condition" => Value::Fun(Function::Condition, "condition", false, 2, 2),
+        "alive" => Value::Fun(Function::Alive, "alive", false, 1, 1),
+        "wealth" => Value::Fun(Function::Wealth, "wealth", false, 1, 1),
+        "price" => Value::Fun(Function::Price, "price", false, 1, 1),
+        "tagged" => Value::Fun(Function::Tagged, "tagged", false, 2, 2),
+        "property" => Value::Fun(Function::Property, "property", false, 2, 2),
+        "attached" => Value::Fun(Function::Attached, "attached", false, 2, 2),
+        "on" => Value::Fun(Function::On, "on", false, 1, 1),
+        "weather" => Value::Fun(Function::Weather, "weather", false, 1, 2),
+        "state" => Value::Fun(Function::State, "state", false, 1, 2),
+        "related" => Value::Fun(Function::Related, "related", false, 3, 3),
+        "owner" => Value::Fun(Function::Owner, "owner", false, 1, 2),
+        "time" => Value::Fun(Function::Time, "time", false, 0, 0),
+        "day" => Value::Fun(Function::Day, "day", false, 0, 0),
+        "phase" => Value::Fun(Function::Phase, "phase", false, 0, 1),
+        "score" => Value::Fun(Function::Score, "score", false, 0, 0),
+        "awarded" => Value::Fun(Function::Awarded, "awarded", false, 1, 1),
+        "one_of" => Value::Fun(Function::OneOf, "one_of", false, 1, usize::MAX),
+        _ => return None,
+    })
+}
+
+/// Find the condition with the given name, as used in templates.
+fn condition_by_name(s: &str) -> Option<Condition> {
+    match s {
+        "poisoned" => Some(Condition::Poisoned),
+        "exhausted" => Some(Condition::Exhausted),
+        _ => None,
+    }
+}
+
+/// Apply a change to the given stats and conditions.  Health stays
+/// between zero and the maximum health.
+fn apply_change(stats: &mut Stats, conditions: &mut Vec<Condition>, change: &StatChange) {
+    stats.health = std::cmp::max(0, std::cmp::min(stats.max_health, stats.health + change.health));
+    conditions.retain(|c| !change.cure.contains(c));
+    for c in change.inflict.iter() {
+        if !conditions.contains(c) {
+            conditions.push(*c);
+        }
+    }
+}
+
 fn print_wrap(txt: &str, width: usize) {
+    let mut line = String::new();
     let mut pos = 0;
     for w in txt.split(' ') {
-        let w_len = w.chars().collect::<Vec<_>>().len();
-//        print!("{} {}", pos, w_len);
+        let w_len = w.chars().count();
         if pos + w_len > width {
-            println!("");
+            say!("{}", line);
+            line.clear();
             pos = 0;
         }
         if pos > 0 {
-            print!(" ");
+            line.push(' ');
             pos += 1;
         }
-        print!("{}", w);
+        line.push_str(w);
         pos += w_len;
     }
     if pos > 0 {
-        println!("");
+        say!("{}", line);
     }
 }
 
 impl<'a> PlayerState<'a> {
+    /// Evaluate a string in the context of the world, on behalf of
+    /// this player.
+    fn eval_str(&self, txt: &str) -> Result<String, String> {
+        self.world.eval_for(txt, Some(self))
+    }
+
+    /// Evaluate a condition in the context of the world, on behalf of
+    /// this player.
+    fn eval_condition(&self, txt: &str) -> Result<bool, String> {
+        self.world.eval_condition(txt, Some(self))
+    }
+
     pub fn look(&self) {
-        let w = self.world;
-        let loc = w.entity(&self.location).unwrap();
-        let shrt = w.eval_str(&loc.short_description);
-        let lng = w.eval_str(&loc.long_description);
+        if self.is_dark() {
+            say!("{}", msg!(self.world, "pitch_dark"));
+            return;
+        }
+        let w = &*self.world;
+        let loc = w.entity(&self.location()).unwrap();
+        let shrt = self.eval_str(loc.short_text(w.locale()));
+        let lng = self.long_description(&self.location());
         match shrt {
             Ok(s) =>
                 print_wrap(&s, 72),
             Err(e) =>
-                println!("an error has occurred: {}", e)
+                say!("{}", msg!(self.world, "error", error = e))
         }
         match lng {
             Ok(s) =>
                 print_wrap(&s, 72),
             Err(e) =>
-                println!("an error has occurred: {}", e)
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+        if let Some(r) = w.region_of(&self.location()).filter(|r| r.description.len() > 0) {
+            match self.eval_str(&r.description) {
+                Ok(s) =>
+                    print_wrap(&s, 72),
+                Err(e) =>
+                    say!("{}", msg!(self.world, "error", error = e))
+            }
         }
+        let others: Vec<String> = self.other_players().iter().map(|p| w.short_description(p)).collect();
+        if others.len() > 0 {
+            say!("{}", msg!(self.world, "players_here", players = w.enumerate(&others)));
+        }
+    }
+
+    /// Return the characters of other players in the player's room.
+    pub fn other_players(&self) -> Vec<InternalName> {
+        let w = &*self.world;
+        w.entity(&self.location()).and_then(|e| e.room()).map(|r| {
+            r.entities.iter().filter(|i| **i != self.id)
+                .filter(|i| w.entity(i).map_or(false, |e| e.character().is_some() && e.tags.iter().any(|t| t == "player")))
+                .cloned().collect()
+        }).unwrap_or_default()
     }
+
     pub fn describe(&self, name: &str) {
-        let w = self.world;
+        let w = &*self.world;
         match w.get_by_name(&w.from_script_name(name)) {
             None => {
-                println!("Es gibt nichts, was {} heißt.", name);
+                say!("{}", msg!(self.world, "no_such_entity", name = name));
             },
             Some(n) => {
                 let ent = w.entity(&n).unwrap();
-                let shrt = w.eval_str(&ent.short_description);
-                let lng = w.eval_str(&ent.long_description);
+                let shrt = self.eval_str(ent.short_text(w.locale()));
+                let lng = self.long_description(&n);
                 match shrt {
                     Ok(s) =>
                         print_wrap(&s, 72),
                     Err(e) =>
-                        println!("an error has occurred: {}", e)
+                        say!("{}", msg!(self.world, "error", error = e))
                 }
                 match lng {
                     Ok(s) =>
                         print_wrap(&s, 72),
                     Err(e) =>
-                        println!("an error has occurred: {}", e)
+                        say!("{}", msg!(self.world, "error", error = e))
                 }
             }
         }
@@ -335,7 +1098,2504 @@ impl<'a> PlayerState<'a> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use rng::Rng;
+
+    /// Add a player with the given health to `w` and return its name.
+    fn tester(w: &mut World, health: i32) -> InternalName {
+        w.add_player(vec!["tester".to_string()], Character::new(Stats { health: health, max_health: 10, strength: 5 }))
+    }
+
+    /// Return a new player standing in the start location of `w`.
+    fn player<'a>(w: &'a mut World) -> PlayerState<'a> {
+        let id = tester(w, 10);
+        PlayerState::new(w, id)
+    }
+
+    /// Carry out a command as typed by the player, and return what it
+    /// printed.
+    fn run(ps: &mut PlayerState, cmd: &str) -> String {
+        output::capture(|| ps.execute(&command::parse(cmd).unwrap()).unwrap()).1
+    }
+
+    /// Handle a line of input in a session, and return what it
+    /// printed.
+    fn send(engine: &mut Engine, s: &mut Session, line: &str) -> String {
+        output::capture(|| engine.handle_session(s, line)).1
+    }
+
+    /// Return the small world of the examples directory.
+    fn tiny() -> World {
+        World::compile(include_str!("../worlds/tiny.mud")).unwrap()
+    }
+
+    /// Add a thing called by `words` to `room` and return its name.
+    fn add(w: &mut World, room: InternalName, words: &str, attributes: Vec<Attribute>) -> InternalName {
+        let id = Uuid::new_v4();
+        w.entity_map.insert(id, w.entities.len());
+        w.entities.push(Entity {
+            id: id,
+            name: words.split(' ').map(|s| s.to_string()).collect(),
+            alias: None,
+            kind: None,
+            short_description: words.to_string(),
+            long_description: String::new(),
+            sound_description: None,
+            smell_description: None,
+            tags: vec![],
+            properties: BTreeMap::new(),
+            attributes: attributes,
+            translations: BTreeMap::new(),
+        });
+        w.entity_mut(&room).and_then(|e| e.room_mut()).unwrap().entities.push(id);
+        id
+    }
+
     #[test]
-    fn it_works() {
+    fn keys() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let door = w.get_by_name(&w.from_script_name("rusty.metal.door")).unwrap();
+        add(&mut w, start, "bent key", vec![Attribute::Keylike(Key { classes: vec![] })]);
+        add(&mut w, start, "master key", vec![Attribute::Keylike(Key { classes: vec!["rock_cellar".to_string()] })]);
+        let locked = |w: &World| w.entity(&door).and_then(|e| e.lock()).unwrap().locked;
+        let mut ps = player(&mut w);
+        run(&mut ps, "take bent key");
+        run(&mut ps, "lock door");
+        assert!(!locked(ps.world));
+        run(&mut ps, "take small key");
+        run(&mut ps, "lock door with bent key");
+        assert!(!locked(ps.world));
+        run(&mut ps, "lock door with small key");
+        assert!(locked(ps.world));
+        run(&mut ps, "drop small key");
+        run(&mut ps, "take master key");
+        run(&mut ps, "unlock door");
+        assert!(!locked(ps.world));
+    }
+
+    #[test]
+    fn wearing() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let wearable = |slot, warmth, armor| vec![Attribute::Wearable(Wearable { slot: slot, warmth: warmth, armor: armor })];
+        let helmet = add(&mut w, start, "helmet", wearable(Slot::Head, 1, 2));
+        let hood = add(&mut w, start, "hood", wearable(Slot::Head, 2, 0));
+        let mittens = add(&mut w, start, "mittens", wearable(Slot::Hands, 1, 1));
+        let mut ps = player(&mut w);
+        run(&mut ps, "wear helmet");
+        assert!(ps.me().worn.is_empty());
+        for c in ["take helmet", "take hood", "take mittens", "wear helmet", "wear hood", "wear mittens"].iter() {
+            run(&mut ps, c);
+        }
+        assert_eq!(ps.me().worn, vec![helmet, mittens]);
+        assert_eq!(ps.me().inventory, vec![hood]);
+        assert_eq!((ps.armor(), ps.warmth()), (3, 2));
+        run(&mut ps, "remove helmet");
+        run(&mut ps, "wear hood");
+        assert_eq!(ps.me().worn, vec![mittens, hood]);
+        assert_eq!(ps.me().inventory, vec![helmet]);
+        assert_eq!((ps.armor(), ps.warmth()), (1, 3));
+    }
+
+    #[test]
+    fn eating_and_drinking() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let portions = |n| Consumable { portions: n, effect: "Lecker.".to_string(), change: StatChange::default() };
+        let bread = add(&mut w, start, "bread", vec![Attribute::Edible(portions(2))]);
+        let potion = add(&mut w, start, "potion", vec![Attribute::Drinkable(portions(1))]);
+        let left = |w: &World, n| match w.entity(&n).unwrap().attributes[0] {
+            Attribute::Edible(ref c) | Attribute::Drinkable(ref c) => c.portions,
+            _ => unreachable!(),
+        };
+        let mut ps = player(&mut w);
+        run(&mut ps, "eat potion");
+        assert_eq!(left(ps.world, potion), 1);
+        run(&mut ps, "take bread");
+        run(&mut ps, "drink potion");
+        assert!(!ps.world.entity(&start).and_then(|e| e.room()).unwrap().entities.contains(&potion));
+        run(&mut ps, "eat bread");
+        assert_eq!((left(ps.world, bread), ps.me().inventory.clone()), (1, vec![bread]));
+        run(&mut ps, "eat bread");
+        assert!(ps.me().inventory.is_empty());
+    }
+
+    #[test]
+    fn reading() {
+        let mut w = make_example_world();
+        let note = w.get_by_name(&w.from_script_name("crumpled.paper.note")).unwrap();
+        let text = w.entity(&note).and_then(|e| e.text()).unwrap().clone();
+        assert!(w.eval_str(&text).unwrap().contains("Die Tür ist offen,"));
+        let mut ps = player(&mut w);
+        for c in ["read note", "read door", "take small key", "lock door"].iter() {
+            run(&mut ps, c);
+        }
+        assert!(ps.world.eval_str(&text).unwrap().contains("Die Tür ist verriegelt,"));
+    }
+
+    #[test]
+    fn dialogue() {
+        let cmd = command::parse("ask old guard about the door").unwrap();
+        assert_eq!(cmd.direct_object, Some(vec!["old".to_string(), "guard".to_string()]));
+        match cmd.indirect_object {
+            Some((command::Connector::About, ref topic)) => assert_eq!(topic, &vec!["door".to_string()]),
+            ref o => panic!("unexpected topic {:?}", o),
+        }
+        let mut w = make_example_world();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let door = w.entity(&guard).and_then(|e| e.character()).unwrap().topics["door"].clone();
+        let mut ps = player(&mut w);
+        for c in ["ask guard about door", "ask guard about weather", "ask guard", "ask note about door"].iter() {
+            run(&mut ps, c);
+        }
+        assert!(ps.world.eval_str(&door).unwrap().contains("Die ist nicht verriegelt."));
+    }
+
+    #[test]
+    fn patrols() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        w.entity_mut(&guard).and_then(|e| e.character_mut()).unwrap().patrol =
+            Some(Patrol { route: vec![cell, tunnel], position: 0, pace: 2, wait: 1 });
+        w.regions.get_mut("mine").unwrap().weather = None;
+        let left = "Ein mürrischer alter Wächter geht hinaus.".to_string();
+        let came = "Ein mürrischer alter Wächter kommt herein.".to_string();
+        assert_eq!(w.tick(), vec![(cell, left.clone()), (tunnel, came.clone())]);
+        assert_eq!(w.location_of(&guard), Some(tunnel));
+        assert!(w.tick().is_empty());
+        assert_eq!(w.tick(), vec![(tunnel, left), (cell, came)]);
+        assert_eq!(w.location_of(&guard), Some(cell));
+        let mut ps = player(&mut w);
+        run(&mut ps, "read note");
+        run(&mut ps, "read note");
+        assert_eq!(ps.world.location_of(&guard), Some(tunnel));
+    }
+
+    #[test]
+    fn stats_and_conditions() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let cure = StatChange { health: 5, inflict: vec![], cure: vec![Condition::Poisoned] };
+        add(&mut w, start, "potion", vec![Attribute::Drinkable(Consumable { portions: 1, effect: String::new(), change: cure })]);
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        assert_eq!(w.eval_str("#(health grumpy.old.guard)/#(strength grumpy.old.guard)").unwrap(), "12/4");
+        w.entity_mut(&guard).and_then(|e| e.character_mut()).unwrap().conditions.push(Condition::Poisoned);
+        w.tick();
+        assert_eq!(w.eval_str("#(health grumpy.old.guard)").unwrap(), "11");
+        assert_eq!(w.eval_str("#(if (condition grumpy.old.guard \"poisoned\") \"krank\" \"gesund\")").unwrap(), "krank");
+        let mut ps = player(&mut w);
+        let me = ps.me_mut();
+        apply_change(&mut me.stats, &mut me.conditions,
+                     &StatChange { health: -4, inflict: vec![Condition::Poisoned, Condition::Exhausted], cure: vec![] });
+        assert_eq!(ps.me().stats.health, 6);
+        run(&mut ps, "read note");
+        assert_eq!(ps.me().stats.health, 5);
+        run(&mut ps, "drink potion");
+        assert_eq!(ps.me().stats.health, 10);
+        assert_eq!(ps.me().conditions, vec![Condition::Exhausted]);
+    }
+
+    #[test]
+    fn combat() {
+        let mut w = make_example_world();
+        w.rng = Rng::new(7);
+        let start = w.start_location;
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        add(&mut w, start, "club", vec![Attribute::Weapon(Weapon { damage: 20 })]);
+        let coin = add(&mut w, start, "coin", vec![]);
+        w.entity_mut(&start).and_then(|e| e.room_mut()).unwrap().entities.retain(|e| *e != coin);
+        w.entity_mut(&guard).and_then(|e| e.character_mut()).unwrap().inventory.push(coin);
+        let health = |w: &World| w.entity(&guard).and_then(|e| e.character()).unwrap().stats.health;
+        let mut ps = player(&mut w);
+        ps.me_mut().stats.health = 1000;
+        run(&mut ps, "take club");
+        for _ in 0..10 {
+            run(&mut ps, "attack guard with club");
+        }
+        assert_eq!(health(ps.world), 0);
+        assert!(ps.world.entity(&guard).and_then(|e| e.character()).unwrap().hostile);
+        assert_eq!(ps.world.location_of(&coin), Some(start));
+
+        // Hostile characters fight back until the player dies.
+        let mut w = make_example_world();
+        w.rng = Rng::new(7);
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        w.entity_mut(&guard).and_then(|e| e.character_mut()).unwrap().hostile = true;
+        let mut ps = player(&mut w);
+        ps.me_mut().stats.health = 1;
+        for _ in 0..20 {
+            ps.defend();
+        }
+        assert!(ps.is_dead());
+    }
+
+    #[test]
+    fn shop() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let candle = w.get_by_name(&w.from_script_name("tallow.candle")).unwrap();
+        let club = add(&mut w, start, "club", vec![Attribute::Price(10)]);
+        let keeper = |w: &World| {
+            let e = w.entity(&guard).unwrap();
+            (e.character().unwrap().purse, e.shop().unwrap().stock.clone())
+        };
+        let mut ps = player(&mut w);
+        ps.me_mut().purse = 1;
+        run(&mut ps, "buy candle");
+        assert_eq!((ps.me().purse, keeper(ps.world)), (1, (3, vec![candle])));
+        ps.me_mut().purse = 5;
+        run(&mut ps, "buy candle");
+        assert_eq!((ps.me().purse, keeper(ps.world)), (3, (5, vec![])));
+        assert_eq!(ps.me().inventory, vec![candle]);
+        run(&mut ps, "take club");
+        run(&mut ps, "sell club to guard");
+        assert_eq!((ps.me().purse, keeper(ps.world)), (8, (0, vec![club])));
+        assert_eq!(ps.me().inventory, vec![candle]);
+    }
+
+    #[test]
+    fn searching() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        let in_room = |w: &World| w.entity(&start).and_then(|e| e.room()).unwrap().entities.contains(&ring);
+        {
+            let mut ps = player(&mut w);
+            for c in ["take ring", "look behind rubble", "take rubble"].iter() {
+                run(&mut ps, c);
+            }
+            assert!(ps.me().inventory.is_empty() && !in_room(ps.world));
+            run(&mut ps, "look under rubble");
+            assert!(in_room(ps.world));
+            run(&mut ps, "take ring");
+            run(&mut ps, "search");
+            assert_eq!(ps.me().inventory, vec![ring]);
+        }
+        let mut w = make_example_world();
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        let mut ps = player(&mut w);
+        run(&mut ps, "search");
+        run(&mut ps, "take ring");
+        assert_eq!(ps.me().inventory, vec![ring]);
+    }
+
+    #[test]
+    fn kinds() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let key = w.get_by_name(&w.from_script_name("small.iron.key")).unwrap();
+        {
+            let e = w.entity(&key).unwrap();
+            assert_eq!((&e.short_description[..], &e.long_description[..]),
+                       ("Ein kleiner Eisenschlüssel", "Ein kleiner, angelaufener Schlüssel aus Eisen."));
+            assert_eq!(e.tags, vec!["key".to_string()]);
+            assert!(e.key().is_some());
+        }
+        let attributes = w.entity(&key).unwrap().attributes.len();
+        w.resolve_kinds().unwrap();
+        assert_eq!(w.entity(&key).unwrap().attributes.len(), attributes);
+
+        let kind = |parent: Option<&str>, short: &str, attributes| Kind {
+            parent: parent.map(|p| p.to_string()), short_description: short.to_string(),
+            long_description: String::new(), tags: vec![], attributes: attributes, translations: BTreeMap::new(),
+        };
+        w.add_kind("master_key", kind(Some("key"), "Ein Hauptschlüssel",
+                                      vec![Attribute::Keylike(Key { classes: vec!["rock_cellar".to_string()] })]));
+        let master = add(&mut w, start, "master key", vec![]);
+        w.entity_mut(&master).unwrap().short_description = String::new();
+        w.entity_mut(&master).unwrap().kind = Some("master_key".to_string());
+        w.resolve_kinds().unwrap();
+        {
+            let e = w.entity(&master).unwrap();
+            assert_eq!((&e.short_description[..], &e.long_description[..]), ("Ein Hauptschlüssel", "Ein gewöhnlicher Schlüssel."));
+            assert_eq!(e.key().unwrap().classes, vec!["rock_cellar".to_string()]);
+        }
+        w.add_kind("a", kind(Some("b"), "", vec![]));
+        w.add_kind("b", kind(Some("a"), "", vec![]));
+        w.entity_mut(&master).unwrap().kind = Some("a".to_string());
+        assert!(w.resolve_kinds().unwrap_err().starts_with("cyclic inheritance"));
+        w.entity_mut(&master).unwrap().kind = Some("c".to_string());
+        assert_eq!(w.resolve_kinds(), Err("unknown kind: c".to_string()));
+    }
+
+    #[test]
+    fn doors_of_both_rooms() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let door = w.get_by_name(&w.from_script_name("rusty.metal.door")).unwrap();
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        assert!(w.doors_at(&cell).contains(&door));
+        assert!(w.doors_at(&tunnel).contains(&door));
+        w.entity_mut(&tunnel).and_then(|e| e.room_mut()).unwrap().region = None;
+        let mut ps = player(&mut w);
+        run(&mut ps, "go door");
+        assert_eq!(ps.location(), cell);
+        run(&mut ps, "open door");
+        run(&mut ps, "go door");
+        assert_eq!(ps.location(), tunnel);
+        run(&mut ps, "close door");
+        assert_eq!(ps.world.entity(&door).unwrap().closed(), Some(true));
+        run(&mut ps, "go door");
+        assert_eq!(ps.location(), tunnel);
+    }
+
+    #[test]
+    fn vehicles() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let cart = w.get_by_name(&w.from_script_name("rickety.mine.cart")).unwrap();
+        let barrel = add(&mut w, cell, "barrel", vec![]);
+        w.entity_mut(&cell).and_then(|e| e.room_mut()).unwrap().entities.retain(|e| *e != barrel);
+        if let Some(&mut Attribute::Enterable(ref mut v)) = w.entity_mut(&cart).map(|e| &mut e.attributes[0]) {
+            v.contents.push(barrel);
+        }
+        let mut ps = player(&mut w);
+        run(&mut ps, "open door");
+        run(&mut ps, "board cart");
+        assert_eq!(ps.me().vehicle, Some(cart));
+        run(&mut ps, "go door");
+        assert_eq!((ps.location(), ps.world.location_of(&cart)), (tunnel, Some(tunnel)));
+        assert_eq!(ps.world.entity(&cart).and_then(|e| e.vehicle()).unwrap().contents, vec![barrel]);
+        if let Some(&mut Attribute::Enterable(ref mut v)) = ps.world.entity_mut(&cart).map(|e| &mut e.attributes[0]) {
+            v.rooms = vec![tunnel];
+        }
+        run(&mut ps, "go door");
+        assert_eq!(ps.location(), tunnel);
+        run(&mut ps, "leave");
+        assert_eq!(ps.me().vehicle, None);
+        run(&mut ps, "go door");
+        assert_eq!((ps.location(), ps.world.location_of(&cart)), (cell, Some(tunnel)));
+    }
+
+    #[test]
+    fn tying() {
+        let mut w = make_example_world();
+        let rope = w.get_by_name(&w.from_script_name("coil.hemp.rope")).unwrap();
+        let cart = w.get_by_name(&w.from_script_name("rickety.mine.cart")).unwrap();
+        let tied = "#(if (attached coil.hemp.rope rickety.mine.cart) \"fest\" \"lose\")";
+        let mut ps = player(&mut w);
+        run(&mut ps, "tie key to cart");
+        assert!(ps.world.relations.subjects(relation::ATTACHED, &cart).is_empty());
+        run(&mut ps, "take rope");
+        run(&mut ps, "tie rope to cart");
+        assert_eq!(ps.world.relations.subjects(relation::ATTACHED, &cart), vec![rope]);
+        assert!(ps.me().inventory.is_empty());
+        assert_eq!(ps.world.eval_str(tied).unwrap(), "fest");
+        run(&mut ps, "tie rope to cart");
+        run(&mut ps, "take rope");
+        assert_eq!(ps.world.relations.subjects(relation::ATTACHED, &cart), vec![rope]);
+        assert!(ps.me().inventory.is_empty());
+        run(&mut ps, "untie rope");
+        assert_eq!(ps.world.eval_str(tied).unwrap(), "lose");
+        run(&mut ps, "take rope");
+        assert_eq!(ps.me().inventory, vec![rope]);
+    }
+
+    #[test]
+    fn switching() {
+        let cmd = command::parse("turn the lever on").unwrap();
+        assert_eq!(cmd.direct_object, Some(vec!["lever".to_string()]));
+        assert!(cmd.indirect_object.is_none());
+        let mut w = make_example_world();
+        let on = "#(if (on iron.lever) \"an\" \"aus\")";
+        assert_eq!(w.eval_str(on).unwrap(), "aus");
+        let mut ps = player(&mut w);
+        run(&mut ps, "turn on lever");
+        assert_eq!(ps.world.eval_str(on).unwrap(), "an");
+        run(&mut ps, "turn lever on");
+        assert_eq!(ps.world.eval_str(on).unwrap(), "an");
+        run(&mut ps, "switch off lever");
+        assert_eq!(ps.world.eval_str(on).unwrap(), "aus");
+        run(&mut ps, "turn on key");
+    }
+
+    #[test]
+    fn fuses() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let match_ = w.get_by_name(&w.from_script_name("match")).unwrap();
+        let candle = add(&mut w, start, "candle", vec![Attribute::Burnable(Fuse {
+            lit: false, turns: 3, expiry: "Die Kerze ist heruntergebrannt.".to_string(), consume: false,
+        })]);
+        let fuse = |w: &World, n| { let f = w.entity(&n).and_then(|e| e.fuse()).unwrap(); (f.lit, f.turns) };
+        let mut ps = player(&mut w);
+        run(&mut ps, "take match");
+        run(&mut ps, "light match");
+        assert_eq!(fuse(ps.world, match_), (true, 4));
+        run(&mut ps, "light candle");
+        assert_eq!(fuse(ps.world, candle), (true, 2));
+        assert_eq!(ps.world.burn_fuses(), vec![]);
+        assert_eq!(ps.world.burn_fuses(), vec![(candle, Some(start), "Die Kerze ist heruntergebrannt.".to_string())]);
+        assert_eq!(fuse(ps.world, candle), (false, 0));
+        run(&mut ps, "light candle");
+        assert_eq!(fuse(ps.world, candle), (false, 0));
+        run(&mut ps, "read note");
+        assert!(ps.me().inventory.is_empty());
+        assert_eq!(ps.world.location_of(&match_), None);
+    }
+
+    #[test]
+    fn senses() {
+        let cmd = command::parse("listen to the guard").unwrap();
+        assert!(cmd.direct_object.is_none());
+        match cmd.indirect_object {
+            Some((command::Connector::To, ref who)) => assert_eq!(who, &vec!["guard".to_string()]),
+            ref o => panic!("unexpected object {:?}", o),
+        }
+        let mut w = make_example_world();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        assert!(w.entity(&guard).unwrap().smell_description.is_some());
+        let mut ps = player(&mut w);
+        for c in ["listen", "listen to guard", "smell", "smell guard", "sniff flask", "smell dragon"].iter() {
+            run(&mut ps, c);
+        }
+    }
+
+    #[test]
+    fn regions() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        assert!(w.region_of(&cell).unwrap().description.starts_with("Die Luft ist"));
+        assert!(w.region_of(&tunnel).unwrap().dark);
+        let lamp = add(&mut w, tunnel, "lamp", vec![]);
+        w.entity_mut(&lamp).unwrap().tags.push("light_source".to_string());
+        w.entity_mut(&tunnel).and_then(|e| e.room_mut()).unwrap().entities.retain(|e| *e != lamp);
+        let mut ps = player(&mut w);
+        assert!(!ps.is_dark());
+        ps.world.place(ps.id, tunnel);
+        assert!(ps.is_dark());
+        ps.me_mut().inventory.push(lamp);
+        assert!(!ps.is_dark());
+        for _ in 0..20 {
+            ps.ambient();
+        }
+    }
+
+    #[test]
+    fn weather() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        assert_eq!(w.weather_at(&cell), Some("frisch"));
+        assert_eq!(w.weather_at(&tunnel), None);
+        w.regions.get_mut("mine").unwrap().weather.as_mut().unwrap().change = 1;
+        assert_eq!(w.advance_weather(), vec![(cell, "Die Luft wird stickig und schwer.".to_string())]);
+        assert_eq!(w.weather_at(&cell), Some("stickig"));
+        let ps = player(&mut w);
+        assert_eq!(ps.eval_str("#(weather here)").unwrap(), "stickig");
+        assert_eq!(ps.eval_str("#(if (weather here \"stickig\") \"ja\" \"nein\")").unwrap(), "ja");
+        ps.world.place(ps.id, tunnel);
+        assert_eq!(ps.eval_str("#(weather here)").unwrap(), "");
+    }
+
+    #[test]
+    fn state_machines() {
+        let mut w = make_example_world();
+        let state = "#(state iron.furnace)";
+        assert_eq!(w.eval_str(state).unwrap(), "cold");
+        let mut ps = player(&mut w);
+        run(&mut ps, "light furnace");
+        assert_eq!(ps.world.eval_str(state).unwrap(), "lit");
+        assert_eq!(ps.world.eval_str("#(if (state iron.furnace \"lit\") \"ja\" \"nein\")").unwrap(), "ja");
+        // Without a transition, the verb does what it usually does.
+        run(&mut ps, "light furnace");
+        assert_eq!(ps.world.eval_str(state).unwrap(), "lit");
+        run(&mut ps, "use furnace");
+        assert_eq!(ps.world.eval_str(state).unwrap(), "roaring");
+    }
+
+    #[test]
+    fn relations() {
+        let mut w = make_example_world();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let note = w.get_by_name(&w.from_script_name("crumpled.paper.note")).unwrap();
+        let rope = w.get_by_name(&w.from_script_name("coil.hemp.rope")).unwrap();
+        let cart = w.get_by_name(&w.from_script_name("rickety.mine.cart")).unwrap();
+        let knows = "#(if (related \"knows\" grumpy.old.guard crumpled.paper.note) \"ja\" \"nein\")";
+        assert_eq!(w.eval_str(knows).unwrap(), "nein");
+        w.relations.relate(relation::KNOWS, guard, note);
+        w.relations.relate(relation::KNOWS, guard, rope);
+        w.relations.relate(relation::KNOWS, cart, rope);
+        assert_eq!(w.eval_str(knows).unwrap(), "ja");
+        assert_eq!(w.relations.objects(relation::KNOWS, &guard), vec![note, rope]);
+        assert_eq!(w.relations.subjects(relation::KNOWS, &rope), vec![guard, cart]);
+        // Each item has only one owner.
+        w.relations.relate(relation::OWNED_BY, rope, guard);
+        w.relations.relate(relation::OWNED_BY, rope, cart);
+        assert_eq!(w.relations.objects(relation::OWNED_BY, &rope), vec![cart]);
+        w.relations.define("married", Arity::OneToOne);
+        w.relations.relate("married", guard, cart);
+        w.relations.relate("married", note, cart);
+        assert!(!w.relations.related("married", &guard, &cart));
+        assert_eq!(w.relations.unrelate(relation::KNOWS, &guard, Some(&note)), 1);
+        assert_eq!(w.relations.unrelate(relation::KNOWS, &guard, None), 1);
+        w.relations.forget(&cart);
+        assert!(w.relations.subjects(relation::KNOWS, &rope).is_empty());
+        assert_eq!(w.relations.object(relation::OWNED_BY, &rope), None);
+    }
+
+    #[test]
+    fn ownership() {
+        let mut w = make_example_world();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let flask = w.get_by_name(&w.from_script_name("leather.water.flask")).unwrap();
+        assert_eq!(w.owner_of(&flask), Some(guard));
+        assert_eq!(w.eval_str("#(owner leather.water.flask)").unwrap(), "Ein mürrischer alter Wächter");
+        assert_eq!(w.eval_str("#(if (owner leather.water.flask grumpy.old.guard) \"ja\" \"nein\")").unwrap(), "ja");
+        let mut ps = player(&mut w);
+        run(&mut ps, "take flask");
+        // Ownership does not change hands with the item.
+        assert_eq!(ps.me().inventory, vec![flask]);
+        assert_eq!(ps.world.owner_of(&flask), Some(guard));
+        ps.world.set_owner(flask, None);
+        assert_eq!(ps.world.eval_str("#(owner leather.water.flask)").unwrap(), "");
+    }
+
+    #[test]
+    fn giving_and_showing() {
+        let mut w = make_example_world();
+        let start = w.start_location;
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        let nails = w.get_by_name(&w.from_script_name("rusty.iron.nails")).unwrap();
+        let candle = w.get_by_name(&w.from_script_name("tallow.candle")).unwrap();
+        let flask = w.get_by_name(&w.from_script_name("leather.water.flask")).unwrap();
+        let mittens = add(&mut w, start, "mittens", vec![]);
+        let mut ps = player(&mut w);
+        ps.me_mut().inventory.push(ring);
+        for c in ["take nails", "take mittens", "take flask"].iter() {
+            run(&mut ps, c);
+        }
+        run(&mut ps, "give nails to guard");
+        run(&mut ps, "give mittens to guard");
+        assert!(ps.me().inventory.contains(&nails) && ps.me().inventory.contains(&mittens));
+        run(&mut ps, "show ring to guard");
+        assert!(ps.me().inventory.contains(&ring));
+        run(&mut ps, "give ring to guard");
+        assert!(ps.me().inventory.contains(&candle) && !ps.me().inventory.contains(&ring));
+        assert_eq!(ps.world.owner_of(&ring), Some(guard));
+        assert!(ps.world.entity(&guard).and_then(|e| e.shop()).unwrap().stock.is_empty());
+        run(&mut ps, "give flask to guard");
+        assert!(ps.world.entity(&guard).and_then(|e| e.character()).unwrap().inventory.contains(&flask));
+    }
+
+    #[test]
+    fn players_are_entities() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let anna = w.add_player(vec!["anna".to_string()], Character::new(Stats { health: 10, max_health: 10, strength: 5 }));
+        let bert = w.add_player(vec!["bert".to_string()], Character::new(Stats { health: 10, max_health: 10, strength: 5 }));
+        {
+            let e = w.entity(&anna).unwrap();
+            assert!(e.tags.contains(&"player".to_string()) && e.character().is_some());
+        }
+        assert_eq!(w.location_of(&anna), Some(cell));
+        assert!(w.entity(&cell).and_then(|e| e.room()).unwrap().entities.contains(&bert));
+        w.entity_mut(&anna).and_then(|e| e.character_mut()).unwrap().conditions.push(Condition::Poisoned);
+        w.tick();
+        let mut ps = PlayerState::new(&mut w, anna);
+        assert_eq!(ps.location(), cell);
+        assert_eq!(ps.eval_str("#(health player)").unwrap(), "9");
+        run(&mut ps, "take flask");
+        assert_eq!(ps.me().inventory.len(), 1);
+        assert!(ps.world.entity(&bert).and_then(|e| e.character()).unwrap().inventory.is_empty());
+    }
+
+    #[test]
+    fn becoming_others() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let flask = w.get_by_name(&w.from_script_name("leather.water.flask")).unwrap();
+        w.entity_mut(&guard).and_then(|e| e.character_mut()).unwrap().patrol =
+            Some(Patrol { route: vec![cell, tunnel], position: 0, pace: 1, wait: 1 });
+        let mut ps = player(&mut w);
+        let id = ps.id;
+        assert!(ps.possess(flask).is_err());
+        assert!(ps.possess_named(&vec!["troll".to_string()]).is_err());
+        assert_eq!(ps.id, id);
+        ps.possess_named(&vec!["guard".to_string()]).unwrap();
+        assert_eq!((ps.id, ps.location()), (guard, cell));
+        assert!(ps.world.entity(&guard).unwrap().tags.contains(&"player".to_string()));
+        assert!(!ps.world.entity(&id).unwrap().tags.contains(&"player".to_string()));
+        // Controlled characters don't patrol.
+        ps.world.tick();
+        assert_eq!(ps.location(), cell);
+        ps.possess(id).unwrap();
+        assert_eq!(ps.id, id);
+    }
+
+    #[test]
+    fn combination_locks() {
+        let mut w = make_example_world();
+        let locked = "#(if (locked steel.strongbox) \"zu\" \"offen\")";
+        let mut ps = player(&mut w);
+        run(&mut ps, "turn dial to 3");
+        run(&mut ps, "turn dial to 7");
+        assert_eq!(ps.world.eval_str(locked).unwrap(), "zu");
+        run(&mut ps, "turn dial to 1");
+        assert_eq!(ps.world.eval_str(locked).unwrap(), "zu");
+        for c in ["turn dial to 3", "turn dial to 7", "turn dial to 2"].iter() {
+            run(&mut ps, c);
+        }
+        assert_eq!(ps.world.eval_str(locked).unwrap(), "offen");
+        run(&mut ps, "lock strongbox");
+        assert_eq!(ps.world.eval_str(locked).unwrap(), "zu");
+        run(&mut ps, "enter 372 on dial");
+        assert_eq!(ps.world.eval_str(locked).unwrap(), "offen");
+    }
+
+    #[test]
+    fn spawn_and_destroy() {
+        let mut w = make_example_world();
+        let cell = w.start_location;
+        let tunnel = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let guard = w.get_by_name(&w.from_script_name("grumpy.old.guard")).unwrap();
+        let flask = w.get_by_name(&w.from_script_name("leather.water.flask")).unwrap();
+        assert_eq!(w.owner_of(&flask), Some(guard));
+        let mut copy = w.entity(&flask).unwrap().clone();
+        assert_eq!(w.destroy(&flask).map(|e| e.id), Some(flask));
+        assert!(w.entity(&flask).is_none());
+        assert!(!w.entity_map.contains_key(&flask));
+        assert!(!w.entity(&cell).and_then(|e| e.room()).unwrap().entities.contains(&flask));
+        assert_eq!(w.owner_of(&flask), None);
+        assert!(w.destroy(&flask).is_none());
+        // Entities after the destroyed one are still found.
+        assert_eq!(w.entity(&guard).map(|e| e.id), Some(guard));
+
+        copy.id = Uuid::new_v4();
+        let id = w.spawn(copy, Some(tunnel));
+        assert_eq!(w.entity(&id).map(|e| e.id), Some(id));
+        assert_eq!(w.location_of(&id), Some(tunnel));
+        assert_eq!(w.holder_of(&id), Some(tunnel));
+    }
+
+    #[test]
+    fn visibility() {
+        let mut w = make_example_world();
+        let case = w.get_by_name(&w.from_script_name("glass.case")).unwrap();
+        let map = w.get_by_name(&w.from_script_name("old.map")).unwrap();
+        let lamp = w.get_by_name(&w.from_script_name("mining.lamp")).unwrap();
+        assert_eq!(w.visible_contents(&case), vec![map]);
+        assert!(w.open_contents(&case).is_empty());
+        let mut ps = player(&mut w);
+        assert!(ps.scope().contains(&map));
+        assert!(!ps.can_reach(&map));
+        run(&mut ps, "take map");
+        assert!(ps.me().inventory.is_empty());
+        assert!(ps.scope().contains(&lamp));
+        assert!(!ps.can_reach(&lamp));
+        run(&mut ps, "take lamp");
+        assert!(ps.me().inventory.is_empty());
+        run(&mut ps, "open case");
+        assert!(ps.can_reach(&map));
+        run(&mut ps, "take map");
+        assert_eq!(ps.me().inventory, vec![map]);
+    }
+
+    #[test]
+    fn unknown_entities_in_functions() {
+        let w = make_example_world();
+        let missing = Value::Reference(Uuid::new_v4());
+        let tag = Value::Str("x".to_string());
+        for (f, args) in vec![("tagged", vec![missing.clone(), tag.clone()]), ("property", vec![missing.clone(), tag]),
+                              ("on", vec![missing.clone()]), ("state", vec![missing.clone()]), ("price", vec![missing.clone()]),
+                              ("closed", vec![missing.clone()]), ("locked", vec![missing])] {
+            let res = w.apply(builtin_function(f).unwrap(), args, None);
+            assert!(res.unwrap_err().starts_with("unknown entity"), "{}", f);
+        }
+    }
+
+    #[test]
+    fn it_works() {
+    }
+
+    #[test]
+    fn player_in_templates() {
+        let mut w = make_example_world();
+        let mut me = Character::new(Stats { health: 7, max_health: 10, strength: 5 });
+        me.conditions.push(Condition::Poisoned);
+        me.purse = 12;
+        let id = w.add_player(vec!["hero".to_string()], me);
+        let ps = PlayerState::new(&mut w, id);
+        assert_eq!(ps.eval_str("#(wealth player) Münzen").unwrap(), "12 Münzen");
+        assert_eq!(ps.eval_str("#(if (condition player \"poisoned\") \"krank\" \"gesund\")").unwrap(), "krank");
+        assert!(ps.world.eval_str("#(wealth player)").is_err());
+    }
+
+    #[test]
+    fn properties() {
+        let mut w = make_example_world();
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        assert_eq!(w.set_property(&ring, "polished", Property::Bool(true)), None);
+        assert_eq!(w.property(&ring, "polished"), Some(&Property::Bool(true)));
+        assert_eq!(w.eval_str("#(if (property small.brass.ring \"polished\") \"blank\" \"matt\")").unwrap(), "blank");
+        assert!(w.eval_str("#(property small.brass.ring \"dial\")").is_err());
+    }
+
+    #[test]
+    fn conditions() {
+        let w = make_example_world();
+        assert_eq!(w.eval_condition("(on iron.lever)", None), Ok(false));
+        assert_eq!(w.eval_condition(" (closed rusty.metal.door) ", None), Ok(true));
+        assert!(w.eval_condition("\"blocked\"", None).is_err());
+        assert!(w.eval_condition("(on iron.lever) x", None).is_err());
+    }
+
+    #[test]
+    fn stacks() {
+        let mut w = make_example_world();
+        // Ambient messages and changes of the weather are shown at random.
+        for r in w.regions.values_mut() {
+            r.ambient.clear();
+            r.weather = None;
+        }
+        let nails = w.by_alias("rusty_iron_nails").unwrap();
+        let bag = w.by_alias("small_leather_bag").unwrap();
+        let mut ps = player(&mut w);
+        assert_eq!(run(&mut ps, "take 5 nails"), "Genommen.\n");
+        assert_eq!(ps.world.quantity(&nails), 5);
+        assert_eq!(run(&mut ps, "put 3 nails in bag"), "Hineingelegt.\n");
+        let in_bag = ps.world.entity(&bag).and_then(|e| e.container()).unwrap().contents.clone();
+        assert_eq!(in_bag.iter().map(|n| ps.world.quantity(n)).collect::<Vec<_>>(), vec![3]);
+        let carried = ps.me().inventory.clone();
+        assert_eq!(carried.iter().map(|n| ps.world.quantity(n)).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(run(&mut ps, "put 1 nails in bag"), "Das passt nicht hinein.\n");
+        assert_eq!(run(&mut ps, "drop 3 nails"), "So viele gibt es nicht.\n");
+        run(&mut ps, "drop nails");
+        assert!(ps.me().inventory.is_empty());
+        assert_eq!(ps.world.quantity(&nails), 7);
+    }
+
+    #[test]
+    fn money() {
+        let mut w = World::compile("world \"W\" { start: cell; }\n\
+            room cell { short: \"Eine Zelle\"; contains: coins; }\n\
+            item coins { short: \"Ein paar Münzen\"; attribute: Money(5); attribute: Price(2); }").unwrap();
+        let mut me = Character::new(Stats { health: 10, max_health: 10, strength: 5 });
+        me.purse = 1;
+        let id = w.add_player(vec!["tester".to_string()], me);
+        let coins = w.by_alias("coins").unwrap();
+        let entities = w.entities.len();
+        let mut ps = PlayerState::new(&mut w, id);
+        assert_eq!(ps.eval_str("#(price coins)").unwrap(), "2");
+        let txt = run(&mut ps, "take coins");
+        assert_eq!(txt, "Du steckst 5 Münzen ein.\n");
+        assert_eq!(ps.eval_str("#(wealth player)").unwrap(), "6");
+        assert!(ps.world.entity(&coins).is_none());
+        assert_eq!(ps.world.entities.len(), entities - 1);
+    }
+
+    #[test]
+    fn nested_containers() {
+        let mut w = World::compile("world \"W\" { start: cell; }\n\
+            room cell { short: \"Eine Zelle\"; contains: bag, box, chest; }\n\
+            item bag { short: \"Ein Beutel\"; attribute: Container(Container(contents: [], capacity: None)); }\n\
+            item box { short: \"Eine Schachtel\"; attribute: Container(Container(contents: [], capacity: None)); }\n\
+            item chest { short: \"Eine Truhe\"; attribute: Container(Container(contents: [], capacity: None)); }").unwrap();
+        let id = tester(&mut w, 10);
+        let (bag, box_, chest) = (w.by_alias("bag").unwrap(), w.by_alias("box").unwrap(), w.by_alias("chest").unwrap());
+        let mut ps = PlayerState::new(&mut w, id);
+        run(&mut ps, "take bag");
+        assert_eq!(run(&mut ps, "put bag in bag"), "Das geht nicht.\n");
+        assert_eq!(run(&mut ps, "put bag in box"), "Hineingelegt.\n");
+        run(&mut ps, "take box");
+        assert_eq!(run(&mut ps, "put box in bag"), "Das geht nicht.\n");
+        assert_eq!(ps.world.holder_of(&box_), Some(id));
+        assert_eq!(run(&mut ps, "put box in chest"), "Hineingelegt.\n");
+        assert!(ps.world.is_within(&bag, &chest));
+        assert!(!ps.world.is_within(&chest, &bag));
+    }
+
+    #[test]
+    fn synthesized_descriptions() {
+        let w = make_example_world();
+        let strongbox = w.get_by_name(&w.from_script_name("steel.strongbox")).unwrap();
+        assert_eq!(w.synthesize_description(&strongbox),
+                   "Das ist geschlossen und verriegelt. Man kann etwas hineinlegen.");
+    }
+
+    #[test]
+    fn geometry() {
+        let mut w = make_example_world();
+        assert!(w.check_geometry().is_empty());
+        let r2 = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        w.entity_mut(&r2).and_then(|e| e.room_mut()).unwrap().position = Some((0, 0, 0));
+        assert_eq!(w.check_geometry().len(), 3);
+    }
+
+    #[test]
+    fn decay_and_respawn() {
+        let mut w = make_example_world();
+        let flask = w.get_by_name(&w.from_script_name("leather.water.flask")).unwrap();
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        w.entity_mut(&ring).unwrap().attributes.push(Attribute::Perishable(Decay { turns: 2, message: String::new() }));
+        w.destroy(&flask);
+        for _ in 0..29 {
+            w.tick();
+        }
+        assert!(w.entity(&ring).is_none());
+        assert!(w.entity(&flask).is_none());
+        w.tick();
+        assert!(w.entity(&flask).is_some());
+        assert!(w.location_of(&flask).is_some());
+    }
+
+    #[test]
+    fn loading() {
+        assert!(World::load_from_path("worlds/example.ron").is_ok());
+        assert!(World::load_from_path("worlds/missing.ron").is_err());
+        assert!(World::load_from_str("World(name: \"Empty\")").is_err());
+        let room = "Entity(id: \"936DA01F-9ABD-4D9D-80C7-02AF85C822A8\", name: [\"cell\"], alias: Some(\"cell\"), \
+                    short_description: \"\", long_description: \"\", \
+                    attributes: [Roomlike(Room(entities: [@spoon], region: None, position: None))])";
+        let spoon = "Entity(name: [\"spoon\"], alias: Some(\"spoon\"), short_description: \"\", long_description: \"\", attributes: [])";
+        let w = World::load_from_str(&format!("World(name: \"Cell\", start_location: @cell, entities: [{}, {}])", room, spoon)).unwrap();
+        assert_eq!(w.start_location, Uuid::parse_str("936DA01F-9ABD-4D9D-80C7-02AF85C822A8").unwrap());
+        assert_eq!(w.entity(&w.start_location).and_then(|e| e.room()).unwrap().entities, vec![load::alias_id("spoon")]);
+        assert!(World::load_from_str(&format!("World(name: \"Cell\", start_location: @yard, entities: [{}, {}])", room, spoon)).is_err());
+        assert!(World::load_from_str(&format!("World(version: 1, name: \"Cell\", start_location: @cell, entities: [{}, {}])", room, spoon)).is_ok());
+        let newer = World::load_from_str(&format!("World(version: 99, name: \"Cell\", start_location: @cell, entities: [{}, {}])", room, spoon));
+        assert!(newer.err().unwrap().contains("newer than the supported version"));
+    }
+
+    #[test]
+    fn includes() {
+        let w = World::load_from_path("worlds/zones/world.ron").unwrap();
+        let tent = w.entity(&w.start_location).unwrap();
+        assert_eq!(tent.alias, Some("camp.tent".to_string()));
+        assert_eq!(tent.room().unwrap().entities, vec![load::alias_id("camp.lamp")]);
+        let shaft = w.entity(&load::alias_id("mine.shaft")).unwrap();
+        assert_eq!(shaft.room().unwrap().entities, vec![load::alias_id("mine.lamp")]);
+        let tent = "Entity(name: [\"tent\"], alias: Some(\"camp.tent\"), short_description: \"\", long_description: \"\", attributes: [])";
+        let res = World::load_from_str(&format!("World(name: \"Zones\", start_location: @camp.tent, \
+                                                  includes: [\"worlds/zones/camp.ron\", \"worlds/zones/mine.ron\"], \
+                                                  entities: [{}])", tent));
+        assert!(res.err().map_or(false, |e| e.contains("alias declared twice")));
+    }
+
+    #[test]
+    fn bundles() {
+        let meta = Metadata { title: "Zones".to_string(), author: "Tester".to_string(), version: "1.0".to_string() };
+        let mut bundle = Bundle::pack("worlds/zones/world.ron", meta.clone()).unwrap();
+        assert_eq!(bundle.main, "world.ron");
+        assert_eq!(bundle.files.keys().collect::<Vec<_>>(), vec!["camp.ron", "mine.ron", "world.ron"]);
+        bundle.add_file("notes/intro.txt", "Willkommen!").unwrap();
+        bundle.add_file("zones.walk", "> look\nZelt\n").unwrap();
+        assert!(bundle.run_walkthroughs().is_ok());
+        bundle.messages.insert("de".to_string(), vec![("taken".to_string(), "Eingesteckt.".to_string())].into_iter().collect());
+        let path = std::env::temp_dir().join("mudstuck-test.bundle");
+        bundle.save(&path).unwrap();
+        let opened = Bundle::open(&path).unwrap();
+        assert_eq!(opened.metadata, meta);
+        assert_eq!(opened.file("./notes/intro.txt"), Some("Willkommen!"));
+        let w = World::load_from_path(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(w.entities.len(), World::load_from_path("worlds/zones/world.ron").unwrap().entities.len());
+        assert_eq!(msg!(w, "taken"), "Eingesteckt.");
+        assert!(bundle.add_file("../outside.txt", "").is_err());
+    }
+
+    #[test]
+    fn dot_export() {
+        let w = make_example_world();
+        let dot = w.to_dot();
+        assert!(dot.starts_with("digraph \"Example World\" {"));
+        assert_eq!(dot.matches(" -> ").count(), w.entities.iter().filter(|e| e.connection().is_some()).count());
+        assert!(dot.contains("Metalltür\\n(geschlossen, bedingt)\", dir=both"));
+    }
+
+    #[test]
+    fn trizbort_import() {
+        let w = World::load_from_path("worlds/cave.trizbort").unwrap();
+        assert_eq!(w.name, "Cave");
+        assert_eq!(w.entities.iter().filter(|e| e.room().is_some()).count(), 3);
+        let hall = w.get_by_name(&w.from_script_name("dripping.hall")).unwrap();
+        assert_eq!(w.position_of(&hall), Some((0, 1, 0)));
+        assert_eq!(w.doors_at(&hall).len(), 2);
+        let lantern = w.get_by_name(&w.from_script_name("rusty.lantern")).unwrap();
+        assert_eq!(w.location_of(&lantern), Some(w.start_location));
+        assert!(World::import_trizbort("<trizbort><map/></trizbort>").is_err());
+    }
+
+    #[test]
+    fn diff_and_patch() {
+        let old = make_example_world();
+        let mut new = make_example_world();
+        let ring = new.get_by_name(&new.from_script_name("small.brass.ring")).unwrap();
+        let r1 = new.start_location;
+        let mut copy = new.destroy(&ring).unwrap();
+        copy.id = Uuid::new_v4();
+        new.spawn(copy, Some(r1));
+        new.entity_mut(&r1).unwrap().short_description = "Ein anderer Raum".to_string();
+        new.regions.remove("tunnels");
+        let changes = old.diff(&new);
+        assert!(changes.entities.iter().any(|c| match *c { Change::Removed(id) => id == ring, _ => false }));
+        assert!(changes.entities.iter().any(|c| match *c { Change::Modified(ref e) => e.id == r1, _ => false }));
+        assert!(changes.entities.iter().any(|c| match *c { Change::Added(ref e) => e.name.contains(&"ring".to_string()), _ => false }));
+        assert_eq!(changes.regions.len(), 1);
+        assert!(changes.name.is_none());
+        let mut patched = make_example_world();
+        patched.patch(&ron::de::from_str(&ron::ser::to_string(&changes).unwrap()).unwrap()).unwrap();
+        assert!(patched.diff(&new).is_empty());
+        assert!(patched.patch(&changes).is_err());
+    }
+
+    #[test]
+    fn serialization() {
+        use serde::de::DeserializeSeed;
+        let mut w = make_example_world();
+        let id = tester(&mut w, 3);
+        let saved_world = ron::ser::to_string(&w).unwrap();
+        let saved_player = ron::ser::to_string(&PlayerState::new(&mut w, id)).unwrap();
+        let mut w2: World = ron::de::from_str(&saved_world).unwrap();
+        assert_eq!(w2.entities.len(), w.entities.len());
+        let mut de = ron::de::Deserializer::from_str(&saved_player).unwrap();
+        let ps = PlayerSeed(&mut w2).deserialize(&mut de).unwrap();
+        assert_eq!(ps.id, id);
+        assert_eq!(ps.me().stats.health, 3);
+        let mut de = ron::de::Deserializer::from_str(&saved_world).unwrap();
+        assert!(PlayerSeed(&mut w).deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut w = make_example_world();
+        let id = tester(&mut w, 3);
+        let ring = w.get_by_name(&w.from_script_name("small.brass.ring")).unwrap();
+        let saved = PlayerState::new(&mut w, id).save_to_string().unwrap();
+        w.destroy(&ring);
+        let mut ps = PlayerState::new(&mut w, Uuid::nil());
+        ps.restore_from_str(&saved).unwrap();
+        assert_eq!(ps.id, id);
+        assert!(ps.world.entity(&ring).is_some());
+        assert!(ps.restore_from_str(&saved.replace("Example World", "Other World")).is_err());
+        assert!(saved.starts_with("(version:5,"));
+        assert!(ps.restore_from_str(&saved.replace("version:5,", "version:1,")).is_ok());
+        assert!(ps.restore_from_str(&saved.replace("version:5,", "version:99,")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn compression() {
+        let mut w = make_example_world();
+        let id = tester(&mut w, 3);
+        let mut ps = PlayerState::new(&mut w, id);
+        for name in ["mudstuck-test.sav.gz", "mudstuck-test.sav.zst"].iter() {
+            let path = std::env::temp_dir().join(name);
+            ps.save(&path).unwrap();
+            assert!(ron::de::from_str::<ron::Value>(&String::from_utf8_lossy(&std::fs::read(&path).unwrap())).is_err());
+            ps.restore(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn autosave_and_checkpoints() {
+        let mut w = make_example_world();
+        let id = tester(&mut w, 3);
+        let path = std::env::temp_dir().join("mudstuck-test-autosave.sav");
+        let _ = std::fs::remove_file(&path);
+        w.set_autosave(2, &path);
+        let mut ps = PlayerState::new(&mut w, id);
+        run(&mut ps, "look");
+        assert_eq!(ps.world.turn, 1);
+        assert!(!path.exists());
+        run(&mut ps, "look");
+        assert!(path.exists());
+        run(&mut ps, "look");
+        ps.restore(&path).unwrap();
+        assert_eq!(ps.world.turn, 2);
+        std::fs::remove_file(&path).unwrap();
+        assert!(ps.checkpoint("../escape").is_err());
+        assert!(ps.restore_checkpoint("never_saved").is_err());
+
+        let mut engine = Engine::new(make_example_world(), 42);
+        for line in ["autosave ", "autosave x", "autosave 0"].iter() {
+            assert_eq!(output::capture(|| engine.handle(line)).1, "Usage: autosave N [FILE] or autosave off\n");
+        }
+        assert!(engine.world.autosave.is_none());
+    }
+
+    #[test]
+    fn transcript() {
+        let mut w = make_example_world();
+        let id = tester(&mut w, 3);
+        let ps = PlayerState::new(&mut w, id);
+        let path = std::env::temp_dir().join("mudstuck-test-transcript.txt");
+        let _ = std::fs::remove_file(&path);
+        output::start_transcript(&path).unwrap();
+        output::input("look");
+        let ((), txt) = output::capture(|| ps.look());
+        assert!(txt.starts_with("Ein kleiner Raum mit Wänden aus rohem Fels\n"));
+        assert_eq!(output::stop_transcript(), Some(path.clone()));
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        assert!(recorded.contains("] > look\n"));
+        assert!(recorded.contains("] Ein kleiner Raum mit Wänden aus rohem Fels\n"));
+        std::fs::remove_file(&path).unwrap();
+
+        // Lines of sessions are marked with their numbers.
+        let mut engine = Engine::shared(tiny(), 1);
+        let anna = engine.open("Anna");
+        output::start_transcript(&path).unwrap();
+        engine.step(anna, "inventory");
+        output::stop_transcript();
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        assert!(recorded.contains(&format!(" #{}] > inventory\n", anna)));
+        assert!(recorded.contains(&format!(" #{}] Du trägst nichts bei dir.\n", anna)));
+        assert!(recorded.lines().filter(|l| l.contains("--- transcript")).all(|l| !l.contains(" #")));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn capture_after_panic() {
+        let txt = output::capture(|| {
+            say!("before");
+            let _ = std::panic::catch_unwind(|| output::capture(|| {
+                say!("lost");
+                panic!("failing on purpose");
+            }));
+            say!("after");
+        }).1;
+        assert_eq!(txt, "before\nafter\n");
+    }
+
+    #[test]
+    fn replay() {
+        let path = std::env::temp_dir().join("mudstuck-test-replay.txt");
+        let _ = std::fs::remove_file(&path);
+        let mut engine = Engine::new(make_example_world(), 42);
+        output::capture(|| {
+            output::start_transcript(&path).unwrap();
+            output::input("transcript on");
+            say!("Recording a transcript (seed 42).");
+            for line in ["look", "go door", "exits", "inventory"].iter() {
+                output::input(line);
+                engine.handle(line);
+            }
+            output::input("transcript off");
+            output::stop_transcript();
+        });
+        let script = Script::from_transcript(&std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(script.steps.len(), 4);
+        assert_eq!(script.steps[3].1, Some("Du trägst nichts bei dir.\n".to_string()));
+        assert!(Engine::new(make_example_world(), 42).replay(&script).is_ok());
+        let mut wrong = script.clone();
+        wrong.steps[3].1 = Some("Du trägst alles bei dir.\n".to_string());
+        assert_eq!(Engine::new(make_example_world(), 42).replay(&wrong).unwrap_err().step, 4);
+        let outputs = Engine::new(make_example_world(), 42).replay(&Script::from_commands("# test\nlook\n\ninventory\n")).unwrap();
+        assert_eq!(outputs[1], "Du trägst nichts bei dir.\n");
+    }
+
+    #[test]
+    fn walkthroughs() {
+        Walkthrough::run_file("worlds/tiny.walk").unwrap();
+        let walk = Walkthrough::parse("seed: 7\n> inventory\nDu trägst nichts\n> look\n!Raum\n").unwrap();
+        assert_eq!(walk.seed, 7);
+        let failure = walk.run(make_example_world()).unwrap_err();
+        assert_eq!((failure.step, failure.problem.as_str()), (2, "unexpected: Raum"));
+        assert!(Walkthrough::parse("Genommen.\n> take key\n").is_err());
+    }
+
+    #[test]
+    fn generation() {
+        let summary = |w: &World| {
+            let mut s: Vec<(Option<String>, String)> =
+                w.entities.iter().map(|e| (e.alias.clone(), e.short_description.clone())).collect();
+            s.sort();
+            s
+        };
+        for &layout in [Layout::Cave, Layout::Maze, Layout::Dungeon].iter() {
+            let gen = Generator::new(layout);
+            let w = gen.generate(17).unwrap();
+            assert!(w.validate().is_ok());
+            assert!(w.check_geometry().is_empty());
+            assert_eq!(summary(&w), summary(&gen.generate(17).unwrap()));
+            assert_eq!(w.stats().attributes.get("Keylike"), Some(&2));
+        }
+        let maze = Generator { width: 5, height: 4, ..Generator::new(Layout::Maze) }.generate(3).unwrap();
+        assert_eq!(maze.stats().attributes.get("Roomlike"), Some(&20));
+        assert!(Generator { rooms: 0, ..Generator::new(Layout::Cave) }.generate(3).is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let mut w = make_example_world();
+        assert!(w.validate().is_ok());
+        let r2 = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let key = w.get_by_name(&w.from_script_name("small.iron.key")).unwrap();
+        let door = w.get_by_name(&w.from_script_name("rusty.metal.door")).unwrap();
+        let missing = Uuid::new_v4();
+        w.entity_mut(&r2).and_then(|e| e.room_mut()).unwrap().entities.push(missing);
+        let gloves = w.get_by_name(&w.from_script_name("worn.leather.gloves")).unwrap();
+        w.entity_mut(&gloves).unwrap().alias = Some("iron_key_1".to_string());
+        w.destroy(&door);
+        let problems = w.validate().problems;
+        assert!(problems.contains(&Problem::Dangling { entity: r2, reference: missing }));
+        assert!(problems.contains(&Problem::DuplicateAlias("iron_key_1".to_string(), vec![key, gloves])));
+        assert!(problems.contains(&Problem::Unreachable(r2)));
+    }
+
+    #[test]
+    fn statistics() {
+        let mut w = make_example_world();
+        let stats = w.stats();
+        assert_eq!(stats.entities, w.entities.len());
+        assert!(stats.attributes["Roomlike"] >= 2);
+        assert!(stats.unreachable.is_empty() && stats.unplaced.is_empty());
+        let r1 = w.start_location;
+        let r2 = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let key = w.get_by_name(&w.from_script_name("small.iron.key")).unwrap();
+        let door = w.get_by_name(&w.from_script_name("rusty.metal.door")).unwrap();
+        w.entity_mut(&r1).and_then(|e| e.room_mut()).unwrap().entities.retain(|e| *e != key);
+        w.destroy(&door);
+        let stats = w.stats();
+        assert_eq!(stats.unreachable, vec![r2]);
+        assert_eq!(stats.unplaced, vec![key]);
+    }
+
+    #[test]
+    fn reloading() {
+        let mut w = make_example_world();
+        let key = w.get_by_name(&w.from_script_name("small.iron.key")).unwrap();
+        let mut fresh = make_example_world();
+        fresh.entity_mut(&key).unwrap().short_description = "Ein Schlüssel".to_string();
+        let id = tester(&mut w, 3);
+        let r1 = w.start_location;
+        w.entity_mut(&r1).and_then(|e| e.room_mut()).unwrap().entities.retain(|e| *e != key);
+        w.entity_mut(&id).and_then(|e| e.character_mut()).unwrap().inventory.push(key);
+        w.reload_from(fresh);
+        assert_eq!(w.short_description(&key), "Ein Schlüssel");
+        assert_eq!(w.holder_of(&key), Some(id));
+        assert!(w.entity(&id).is_some());
+    }
+
+    #[test]
+    fn authoring_language() {
+        let w = World::load_from_path("worlds/tiny.mud").unwrap();
+        let cellar = w.start_location;
+        let door = w.get_by_name(&w.from_script_name("heavy.oak.door")).unwrap();
+        let store = w.other_side(&door, &cellar).unwrap();
+        assert_eq!(w.doors_at(&cellar), vec![door]);
+        assert_eq!(w.doors_at(&store).len(), 2);
+        assert_eq!(w.entity(&door).unwrap().closed(), Some(true));
+        let mut tiny = tiny();
+        let mut ps = player(&mut tiny);
+        assert!(run(&mut ps, "north").contains("Eichentür"));
+        run(&mut ps, "open door");
+        run(&mut ps, "north");
+        assert_eq!(ps.location(), store);
+        run(&mut ps, "south");
+        assert_eq!(ps.location(), cellar);
+        run(&mut ps, "north");
+        run(&mut ps, "down");
+        assert_eq!(ps.location(), cellar);
+        assert!(World::compile("world \"W\" { start: nowhere; }").is_err());
+        assert!(World::compile("world \"W\" { start: r; } room r { colour: \"red\"; }").is_err());
+    }
+
+    #[test]
+    fn builder() {
+        let w = WorldBuilder::new("Cells")
+            .room("cell").desc("Eine Zelle").position(0, 0, 0)
+            .room("tunnel").desc("Ein Tunnel").position(1, 0, 0)
+            .door("metal_door").named("metal door").between("cell", "tunnel")
+            .character("jailer", Stats { health: 5, max_health: 5, strength: 3 }).in_room("tunnel")
+            .build()
+            .unwrap();
+        let door = w.get_by_name(&w.from_script_name("metal.door")).unwrap();
+        assert_eq!(w.other_side(&door, &w.start_location).and_then(|r| w.entity(&r)).map(|e| e.short_description.clone()),
+                   Some("Ein Tunnel".to_string()));
+        assert!(w.check_geometry().is_empty());
+        assert!(WorldBuilder::new("Broken").room("cell").door("gate").between("cell", "yard").build().is_err());
+    }
+
+    #[test]
+    fn linting() {
+        assert!(make_example_world().lint().is_empty());
+        let w = WorldBuilder::new("Cells")
+            .room("cell").desc("Eine Zelle").long("#(if (closed nowhere) \"dunkel\" \"hell\")")
+            .item("spoon").desc("Ein Löffel")
+            .build()
+            .unwrap();
+        let findings = w.lint();
+        assert_eq!(findings.len(), 2);
+        assert_eq!((findings[0].severity, findings[0].reference.as_str()), (Severity::Error, "cell"));
+        assert_eq!(findings[0].message, "long: undefined identifier: nowhere");
+        assert_eq!((findings[1].severity, findings[1].reference.as_str()), (Severity::Warning, "spoon"));
+        assert_eq!(w.check_template("#(health)", false), vec!["function health takes 1 to 1 arguments, got 0".to_string()]);
+        assert_eq!(w.check_template("(on", true).len(), 1);
+    }
+
+    #[test]
+    fn schema() {
+        let schema = World::schema().unwrap();
+        assert!(schema.contains("\"$ref\": \"#/definitions/World\""));
+        assert!(schema.contains("\"Lockable\""));
+        assert!(schema.contains("\"format\": \"uuid\""));
+    }
+
+    #[test]
+    fn prefabs() {
+        let mut b = WorldBuilder::new("Cells").room("cell");
+        let coin = b.id("coin");
+        let w = b.item("coin").desc("Eine Münze")
+            .item("chest").prefab(Prefab::Chest { contents: vec![coin], capacity: Some(3), keys: vec![], locked: false })
+            .in_room("cell")
+            .item("torch").desc("Eine Kienfackel").prefab(Prefab::Torch { turns: 10, lit: true }).in_room("cell")
+            .build()
+            .unwrap();
+        let chest = w.get_by_name(&w.from_script_name("chest")).unwrap();
+        let torch = w.get_by_name(&w.from_script_name("torch")).unwrap();
+        assert_eq!(w.entity(&chest).and_then(|e| e.container()).map(|c| c.contents.clone()), Some(vec![coin]));
+        assert_eq!(w.entity(&chest).unwrap().closed(), Some(true));
+        assert_eq!(w.short_description(&torch), "Eine Kienfackel");
+        assert!(w.entity(&torch).unwrap().tags.contains(&"light_source".to_string()));
+
+        let w = World::compile("world \"W\" { start: cell; }\n\
+                                room cell { exits: east -> yard via gate; contains: guard, key; }\n\
+                                room yard { }\n\
+                                door gate { prefab: LockableDoor(keys: [@key], locked: true); }\n\
+                                item key { attribute: Keylike(Key(classes: [])); }\n\
+                                character guard { prefab: Npc(health: 8, strength: 2); short: \"Ein Wächter\"; }").unwrap();
+        let gate = w.get_by_name(&w.from_script_name("gate")).unwrap();
+        let guard = w.get_by_name(&w.from_script_name("guard")).unwrap();
+        assert_eq!(w.entity(&gate).and_then(|e| e.lock()).map(|l| l.locked), Some(true));
+        assert_eq!(w.entity(&guard).and_then(|e| e.character()).map(|c| c.stats.health), Some(8));
+        assert_eq!(w.short_description(&guard), "Ein Wächter");
+    }
+
+    #[test]
+    fn translations() {
+        let mut w = WorldBuilder::new("Cells")
+            .room("cell").desc("Eine Zelle").long("Kahle Wände.").translated("en", "A cell", "")
+            .build()
+            .unwrap();
+        let cell = w.start_location;
+        assert_eq!(w.short_description(&cell), "Eine Zelle");
+        w.locale = Some("en".to_string());
+        assert_eq!(w.short_description(&cell), "A cell");
+        assert_eq!(w.entity(&cell).unwrap().long_text(w.locale()), "Kahle Wände.");
+        w.locale = Some("fr".to_string());
+        assert_eq!(w.short_description(&cell), "Eine Zelle");
+        let tiny = tiny();
+        assert_eq!(tiny.entity(&tiny.start_location).unwrap().short_text(Some("en")), "A damp cellar");
+    }
+
+    #[test]
+    fn messages() {
+        let mut w = make_example_world();
+        assert_eq!(msg!(w, "bought", price = 3), "Gekauft für 3 Münzen.");
+        w.locale = Some("en".to_string());
+        assert_eq!(msg!(w, "bought", price = 3), "Bought for 3 coins.");
+        w.messages.entry("en".to_string()).or_insert(Default::default())
+            .insert("bought".to_string(), "{price} {coins}, {price} coins!".to_string());
+        assert_eq!(msg!(w, "bought", price = 3), "3 {coins}, 3 coins!");
+        w.locale = Some("fr".to_string());
+        assert_eq!(msg!(w, "taken"), "Genommen.");
+        assert_eq!(msg!(w, "no.such.message"), "no.such.message");
+    }
+
+    #[test]
+    fn one_way_exits() {
+        let w = make_example_world();
+        let r1 = w.get_by_name(&w.from_script_name("small.rock.room")).unwrap();
+        let r2 = w.get_by_name(&w.from_script_name("cramped.rock.tunnel")).unwrap();
+        let chute = w.get_by_name(&w.from_script_name("narrow.rock.chute")).unwrap();
+        assert!(w.doors_at(&r2).contains(&chute));
+        assert!(!w.doors_at(&r1).contains(&chute));
+        assert_eq!(w.other_side(&chute, &r2), Some(r1));
+        assert_eq!(w.other_side(&chute, &r1), None);
+    }
+
+    #[test]
+    fn aliases() {
+        let mut w = make_example_world();
+        let door = w.get_by_name(&w.from_script_name("rusty.metal.door")).unwrap();
+        assert_eq!(w.by_alias("metal_door_1"), Some(door));
+        assert_eq!(w.find_entity(&vec!["metal_door_1".to_string()], &[door]), Some(door));
+        assert_eq!(w.eval_str("#(closed metal_door_1)"), w.eval_str("#(closed rusty.metal.door)"));
+        let key = w.by_alias("iron_key_1").unwrap();
+        w.destroy(&key);
+        assert_eq!(w.by_alias("iron_key_1"), None);
+        assert_eq!(w.by_alias("metal_door_1"), Some(door));
+    }
+
+    #[test]
+    fn player_template() {
+        let room = "Entity(name: [\"cell\"], alias: Some(\"cell\"), short_description: \"\", long_description: \"\", \
+                    attributes: [Roomlike(Room(entities: [], region: None, position: None))])";
+        let spoon = "Entity(name: [\"spoon\"], alias: Some(\"spoon\"), short_description: \"\", long_description: \"\", attributes: [])";
+        let player = "player: Some(PlayerTemplate(name: [\"thief\"], short_description: \"Eine Diebin\", \
+                      stats: Stats(health: 6, max_health: 8, strength: 3), inventory: [@spoon], purse: 4))";
+        let w = World::load_from_str(&format!("World(name: \"Cell\", start_location: @cell, {}, entities: [{}, {}])",
+                                              player, room, spoon)).unwrap();
+        let spoon = w.by_alias("spoon").unwrap();
+        assert!(w.stats().unplaced.is_empty());
+        let mut engine = Engine::new(w, 1);
+        let me = engine.player;
+        let c = engine.world.entity(&me).and_then(|e| e.character()).unwrap().clone();
+        assert_eq!((c.inventory, c.stats.health, c.purse, c.conditions.len()), (vec![spoon], 6, 4, 0));
+        assert_eq!(engine.world.short_description(&me), "Eine Diebin");
+        assert_eq!(engine.world.location_of(&me), Some(engine.world.start_location));
+        let other = engine.world.add_starting_player();
+        assert!(engine.world.entity(&other).and_then(|e| e.character()).unwrap().inventory.is_empty());
+        let missing = player.replace("@spoon", "\"936DA01F-9ABD-4D9D-80C7-02AF85C822A8\"");
+        assert!(World::load_from_str(&format!("World(name: \"Cell\", start_location: @cell, {}, entities: [{}])",
+                                             missing, room)).is_err());
+    }
+
+    #[test]
+    fn translation_files() {
+        let mut w = make_example_world();
+        let door = w.by_alias("metal_door_1").unwrap();
+        let po = w.export_translations("en");
+        assert!(po.contains(&format!("#: metal_door_1\nmsgctxt \"{}:short\"\nmsgid \"Metalltür\"\nmsgstr \"\"\n", door)));
+        assert!(po.contains("msgctxt \"message:taken\"\nmsgid \"Genommen.\"\nmsgstr \"Taken.\"\n"));
+        let po = po.replace(&format!("{}:short\"\nmsgid \"Metalltür\"\nmsgstr \"\"", door),
+                            &format!("{}:short\"\nmsgid \"Metalltür\"\nmsgstr \"Metal \"\n\"door\"", door))
+            .replace("msgstr \"Taken.\"", "msgstr \"Got it.\"");
+        assert_eq!(w.import_translations(&po).map(|(l, _)| l), Ok("en".to_string()));
+        w.locale = Some("en".to_string());
+        assert_eq!(w.short_description(&door), "Metal door");
+        assert_eq!(msg!(w, "taken"), "Got it.");
+        let outdated = format!("msgid \"\"\nmsgstr \"Language: fr\\n\"\n\n\
+                                msgctxt \"{0}:short\"\nmsgid \"Holztür\"\nmsgstr \"Porte en bois\"\n\n\
+                                #, fuzzy\nmsgctxt \"{0}:long\"\nmsgid \"Eine verbeulte\"\nmsgstr \"Une porte\"\n", door);
+        assert_eq!(w.import_translations(&outdated), Ok(("fr".to_string(), 0)));
+        assert!(w.import_translations("msgid \"\"\nmsgstr \"\"\n").is_err());
+    }
+
+    #[test]
+    fn events() {
+        use std::sync::{Arc, Mutex};
+        let mut w = tiny();
+        let id = tester(&mut w, 10);
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        w.subscribe(move |_, e| log.lock().unwrap().push(e.clone()));
+        // Listeners may react by publishing events of their own.
+        w.subscribe(move |w, e| if let Event::EntityOpened { who, what } = *e {
+            w.publish(Event::EntityClosed { who: who, what: what });
+        });
+        let cellar = w.start_location;
+        let door = w.by_alias("oak_door").unwrap();
+        let storeroom = w.by_alias("storeroom").unwrap();
+        let mut ps = PlayerState::new(&mut w, id);
+        for c in ["open door", "go door"].iter() {
+            run(&mut ps, c);
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![Event::EntityOpened { who: id, what: door },
+                                               Event::EntityClosed { who: id, what: door },
+                                               Event::PlayerMoved { who: id, from: cellar, to: storeroom }]);
+    }
+
+    #[test]
+    fn triggers() {
+        let mut w = World::compile("world \"W\" { start: cell; }\n\
+            room cell { short: \"Eine Zelle\"; exits: east -> yard via gate; contains: gem; }\n\
+            room yard { short: \"Ein Hof\"; attribute: Triggered([Trigger(on: \"enter\", condition: Some(\"(alive player)\"), \
+                        text: \"Es zieht.\", effects: [Affect(StatChange(health: -2, inflict: [], cure: []))])]); }\n\
+            door gate { short: \"Ein Tor\"; attribute: Closable(false); }\n\
+            item gem { short: \"Ein Edelstein\"; attribute: Triggered([Trigger(on: \"take\", text: \"Ein Alarm schrillt!\", \
+                        effects: [Close(@gate)], once: true)]); }").unwrap();
+        let gate = w.by_alias("gate").unwrap();
+        let mut ps = player(&mut w);
+        assert!(run(&mut ps, "take gem").contains("Ein Alarm schrillt!"));
+        assert_eq!(ps.world.entity(&gate).unwrap().closed(), Some(true));
+        assert!(!run(&mut ps, "drop gem").contains("Alarm"));
+        assert!(!run(&mut ps, "take gem").contains("Alarm"));
+        run(&mut ps, "open gate");
+        let txt = run(&mut ps, "go gate");
+        assert!(txt.contains("Es zieht."), "{}", txt);
+        assert_eq!(ps.me().stats.health, 8);
+    }
+
+    #[test]
+    fn scheduler() {
+        let mut w = tiny();
+        let cellar = w.start_location;
+        let door = w.by_alias("oak_door").unwrap();
+        w.after(2, Task::Say(cellar, "Eine Glocke schlägt.".to_string()));
+        w.every(3, Task::Effects(vec![Effect::Open(door)]));
+        let bells: Vec<usize> = (1..8).filter(|_| w.tick().iter().any(|n| n.1 == "Eine Glocke schlägt.")).collect();
+        assert_eq!(bells, vec![2]);
+        assert_eq!(w.entity(&door).unwrap().closed(), Some(false));
+        *w.entity_mut(&door).unwrap().closed_mut().unwrap() = true;
+        w.tick();
+        w.tick();
+        assert_eq!(w.entity(&door).unwrap().closed(), Some(false));
+        assert_eq!(w.schedule.len(), 1);
+    }
+
+    #[test]
+    fn behaviors() {
+        let mut w = World::compile("world \"W\" { start: cell; }\n\
+            room cell { short: \"Eine Zelle\"; exits: east -> yard via gate; contains: dog; }\n\
+            room yard { short: \"Ein Hof\"; exits: north -> hall via arch; contains: guard; }\n\
+            room hall { short: \"Eine Halle\"; }\n\
+            door gate { short: \"Ein Tor\"; attribute: Closable(false); }\n\
+            door arch { short: \"Ein Bogen\"; }\n\
+            character dog { short: \"Ein Hund\"; stats: 5 1; behaviors: [Follow]; }\n\
+            character guard { short: \"Eine Wache\"; stats: 10 3; behaviors: [Flee(50), Guard(@hall)]; }").unwrap();
+        let id = tester(&mut w, 10);
+        let (cell, yard, hall) = (w.by_alias("cell").unwrap(), w.by_alias("yard").unwrap(), w.by_alias("hall").unwrap());
+        let (dog, guard) = (w.by_alias("dog").unwrap(), w.by_alias("guard").unwrap());
+        w.tick();
+        assert_eq!(w.location_of(&dog), Some(cell));
+        assert_eq!(w.location_of(&guard), Some(hall));
+        w.apply_effect(&Effect::Move(id, yard), None);
+        w.tick();
+        assert_eq!(w.location_of(&dog), Some(yard));
+        w.apply_effect(&Effect::Behave(dog, vec![]), None);
+        w.apply_effect(&Effect::Move(id, hall), None);
+        w.entity_mut(&guard).unwrap().character_mut().unwrap().stats.health = 4;
+        w.tick();
+        assert_eq!(w.location_of(&dog), Some(yard));
+        assert_eq!(w.location_of(&guard), Some(yard));
+    }
+
+    #[test]
+    fn clock() {
+        let mut w = tiny();
+        assert_eq!(w.eval_for("#(day) #(time) #(phase)", None).unwrap(), "1 08:00 morgen");
+        w.turn = 100;
+        assert_eq!(w.eval_for("#(day) #(time) #(phase)", None).unwrap(), "2 00:40 nacht");
+        let mut w = World::compile("world \"W\" { start: cell; clock: Clock(minutes_per_turn: 30, start: 22, \
+            phases: [Phase(name: \"tag\", hour: 6), Phase(name: \"nacht\", hour: 21)]); }\n\
+            room cell { short: \"Eine Zelle\"; }").unwrap();
+        assert_eq!(w.phase(), "nacht");
+        w.turn = 12;
+        assert_eq!((w.day(), w.time_of_day(), w.phase()), (2, (4, 0), "nacht"));
+        w.turn = 16;
+        assert_eq!(w.eval_condition("(phase \"tag\")", None), Ok(true));
+    }
+
+    #[test]
+    fn quests() {
+        let mut w = World::compile(&format!("{}\nquest door {{ title: \"Die Tür\"; start: \"(locked oak_door)\"; \
+            stage: Stage(goal: \"Schließe die Tür.\", condition: \"(closed oak_door)\", text: \"Geschafft.\"); \
+            stage: Stage(goal: \"Lebe.\", condition: \"(alive player)\"); reward: [Give(@brass_key)]; }}",
+                                            include_str!("../worlds/tiny.mud"))).unwrap();
+        let door = w.by_alias("oak_door").unwrap();
+        let key = w.by_alias("brass_key").unwrap();
+        let mut ps = player(&mut w);
+        *ps.world.entity_mut(&door).unwrap().closed_mut().unwrap() = false;
+        let (_, txt) = output::capture(|| { ps.update_quests(); ps.show_journal() });
+        assert_eq!(txt, "Du hast noch keine Aufgaben.\n");
+        ps.world.entity_mut(&door).unwrap().lock_mut().unwrap().locked = true;
+        let (_, txt) = output::capture(|| { ps.update_quests(); ps.show_journal() });
+        assert_eq!(txt, "Neue Aufgabe: Die Tür\n  Die Tür: Schließe die Tür.\n");
+        *ps.world.entity_mut(&door).unwrap().closed_mut().unwrap() = true;
+        let (_, txt) = output::capture(|| ps.update_quests());
+        assert_eq!(txt, "Geschafft.\nAufgabe erfüllt: Die Tür\n");
+        assert!(ps.me().inventory.contains(&key));
+        let saved = ps.save_to_string().unwrap();
+        ps.world.quests.get_mut("door").unwrap().stage = None;
+        ps.restore_from_str(&saved).unwrap();
+        assert!(ps.world.quests["door"].is_done());
+    }
+
+    #[test]
+    fn scoring() {
+        let mut w = World::compile("world \"W\" { start: cell; scoring: Scoring(awards: {\"gem\": Award(points: 5, title: \"Juwelier\"), \
+            \"exit\": Award(points: 10)}, ranks: [Rank(points: 0, title: \"Anfänger\"), Rank(points: 5, title: \"Sammler\")]); }\n\
+            room cell { short: \"Eine Zelle\"; contains: gem; }\n\
+            item gem { short: \"Ein Edelstein\"; attribute: Triggered([Trigger(on: \"take\", text: \"\", effects: [Award(\"gem\")])]); }").unwrap();
+        let mut ps = player(&mut w);
+        assert_eq!(ps.world.rank(), Some("Anfänger"));
+        assert!(run(&mut ps, "take gem").contains("[Deine Punktzahl ist um 5 gestiegen.]"));
+        run(&mut ps, "drop gem");
+        assert!(!run(&mut ps, "take gem").contains("Punktzahl"));
+        assert_eq!((ps.world.score(), ps.world.max_score()), (5, 15));
+        let saved = ps.save_to_string().unwrap();
+        ps.world.scoring.awarded.clear();
+        ps.restore_from_str(&saved).unwrap();
+        assert_eq!(ps.world.eval_condition("(awarded \"gem\")", None), Ok(true));
+        let (_, txt) = output::capture(|| ps.show_score(false));
+        assert_eq!(txt, "Du hast 5 von 15 möglichen Punkten in 3 Zügen erreicht.\nDein Rang: Sammler.\n\
+                         Deine Errungenschaften:\n  Juwelier\n");
+    }
+
+    #[test]
+    fn rules() {
+        let mut w = World::compile("world \"W\" { start: cell; }\n\
+            room cell { short: \"Eine Zelle\"; contains: gem, chest; }\n\
+            item gem { short: \"Ein Edelstein\"; attribute: Ruled([Rule(verb: \"get\", stage: Check, \
+                        condition: Some(\"(closed chest)\"), text: \"Die Truhe bewacht den Stein.\")]); }\n\
+            item chest { short: \"Eine Truhe\"; attribute: Closable(true); attribute: Ruled([\
+                        Rule(verb: \"open\", stage: Report, text: \"Knarrend geht die Truhe auf.\"), \
+                        Rule(verb: \"close\", stage: CarryOut, text: \"Der Deckel klemmt.\")]); }").unwrap();
+        let (gem, chest) = (w.by_alias("gem").unwrap(), w.by_alias("chest").unwrap());
+        let mut ps = player(&mut w);
+        assert_eq!(run(&mut ps, "take gem"), "Die Truhe bewacht den Stein.\n");
+        assert!(!ps.me().inventory.contains(&gem));
+        assert_eq!(run(&mut ps, "open chest"), "Knarrend geht die Truhe auf.\n");
+        assert_eq!(ps.world.entity(&chest).unwrap().closed(), Some(false));
+        run(&mut ps, "take gem");
+        assert!(ps.me().inventory.contains(&gem));
+        assert_eq!(run(&mut ps, "close chest"), "Der Deckel klemmt.\n");
+        assert_eq!(ps.world.entity(&chest).unwrap().closed(), Some(false));
+    }
+
+    #[test]
+    fn telnet_server() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        let mut d = telnet::Decoder::default();
+        assert_eq!(d.feed(b"\xff\xfb\x1flo"), Vec::<String>::new());
+        assert_eq!(d.feed(b"ok\r\n\xff\xfa\x18\x01\xff\xf0i\n"), vec!["look".to_string(), "i".to_string()]);
+        assert_eq!(telnet::encode("a\nb"), b"a\r\nb".to_vec());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The server is set up here, and runs on a thread of its own.
+        let w = tiny();
+        let mut server = Server::new(w, 1);
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let read_until = |c: &mut TcpStream, end: &str| {
+            let mut txt = String::new();
+            let mut buf = [0; 256];
+            while !txt.ends_with(end) {
+                let n = c.read(&mut buf).unwrap();
+                assert!(n > 0, "{}", txt);
+                txt.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+            txt
+        };
+        assert!(read_until(&mut c, "> ").contains("What is your name?"));
+        c.write_all(b"anna\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("Ein feuchter Keller"));
+        c.write_all(b"take lantern\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("Genommen."));
+        c.write_all(b"save\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("not available"));
+        c.write_all(b"quit\r\n").unwrap();
+        assert_eq!(read_until(&mut c, "\n"), "Goodbye.\r\n");
+    }
+
+    #[test]
+    fn websocket_server() {
+        assert_eq!(websocket::encode(&Reply::Prompt), r#"{"type":"prompt"}"#);
+        assert_eq!(websocket::decode(r#"{"type": "input", "text": "look"}"#), Ok("look".to_string()));
+        assert!(websocket::decode(r#"{"type": "shout"}"#).is_err());
+
+        #[cfg(feature = "websocket")]
+        {
+            use std::net::{TcpListener, TcpStream};
+            use tungstenite::Message;
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            std::thread::spawn(move || {
+                let w = tiny();
+                let mut server = Server::new(w, 1);
+                server.listen(listener, Protocol::WebSocket).unwrap();
+                server.run()
+            });
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let (mut ws, _) = tungstenite::client(format!("ws://127.0.0.1:{}/", port), stream).unwrap();
+            let read_until_prompt = |ws: &mut tungstenite::WebSocket<TcpStream>| {
+                let mut replies = vec![];
+                loop {
+                    let v: serde_json::Value = match ws.read().unwrap() {
+                        Message::Text(t) => serde_json::from_str(&t).unwrap(),
+                        _ => continue,
+                    };
+                    if v["type"] == "prompt" {
+                        return replies;
+                    }
+                    replies.push(v);
+                }
+            };
+            read_until_prompt(&mut ws);
+            ws.send(Message::Text(r#"{"type": "input", "text": "anna"}"#.to_string())).unwrap();
+            let replies = read_until_prompt(&mut ws);
+            assert!(replies.iter().any(|r| r["type"] == "room" && r["name"] == "Ein feuchter Keller"));
+            ws.send(Message::Text(r#"{"type": "input", "text": "take lantern"}"#.to_string())).unwrap();
+            let replies = read_until_prompt(&mut ws);
+            assert!(replies[0]["text"].as_str().unwrap().starts_with("Genommen.\n"));
+            ws.send(Message::Text("hello".to_string())).unwrap();
+            assert_eq!(read_until_prompt(&mut ws)[0]["type"], "error");
+            // The server closes the connection with a close frame.
+            ws.send(Message::Text(r#"{"type": "input", "text": "quit"}"#.to_string())).unwrap();
+            let closed = loop {
+                match ws.read() {
+                    Ok(Message::Close(_)) => break true,
+                    Ok(_) => {},
+                    Err(_) => break false,
+                }
+            };
+            assert!(closed);
+
+            // Pings count against the limits like messages.
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            std::thread::spawn(move || {
+                let limits = Limits { penalty: Penalty::Disconnect, burst: 3, ..Limits::default() };
+                let mut server = Server::with_config(tiny(), 1, ServerConfig { limits: limits, ..ServerConfig::default() });
+                server.listen(listener, Protocol::WebSocket).unwrap();
+                server.run()
+            });
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let (mut ws, _) = tungstenite::client(format!("ws://127.0.0.1:{}/", port), stream).unwrap();
+            read_until_prompt(&mut ws);
+            for _ in 0..5 {
+                ws.send(Message::Ping(vec![])).unwrap();
+            }
+            let mut replies = vec![];
+            while let Ok(m) = ws.read() {
+                if let Message::Text(t) = m {
+                    replies.push(serde_json::from_str::<serde_json::Value>(&t).unwrap());
+                }
+            }
+            assert_eq!(replies[0]["text"], "You are typing too fast.");
+            assert_eq!(replies[1]["type"], "goodbye");
+        }
+    }
+
+    #[test]
+    fn sessions() {
+        let w = tiny();
+        let start = w.start_location;
+        let mut engine = Engine::shared(w, 1);
+        let mut anna = engine.join("Anna");
+        let mut bert = engine.join("Bert");
+
+        assert!(send(&mut engine, &mut anna, "look").contains("Außerdem hier: Bert."));
+        assert!(send(&mut engine, &mut anna, "take").contains("Was möchtest du nehmen?"));
+        assert!(send(&mut engine, &mut anna, "lantern").starts_with("Genommen."));
+        assert!(engine.world.entity(&anna.player).unwrap().character().unwrap().inventory.len() == 1);
+        assert!(send(&mut engine, &mut anna, "drop it").contains("Abgelegt"));
+        assert!(send(&mut engine, &mut bert, "take it").contains("So etwas siehst du hier nicht."));
+
+        assert_eq!(send(&mut engine, &mut bert, "alias n go oak door"), "n = go oak door\n");
+        send(&mut engine, &mut bert, "open oak door");
+        assert!(send(&mut engine, &mut bert, "n").contains("Eine Vorratskammer"));
+        assert!(!send(&mut engine, &mut bert, "look").contains("Außerdem hier"));
+        assert!(!send(&mut engine, &mut anna, "look").contains("Außerdem hier"));
+
+        send(&mut engine, &mut anna, "take lantern");
+        let lantern = engine.world.entity(&anna.player).unwrap().character().unwrap().inventory[0];
+        engine.leave(anna);
+        assert_eq!(engine.world.location_of(&lantern), Some(start));
+    }
+
+    #[test]
+    fn chat() {
+        let w = tiny();
+        let mut engine = Engine::shared(w, 1);
+        let mut anna = engine.join("Anna");
+        let mut bert = engine.join("Bert");
+
+        assert_eq!(send(&mut engine, &mut anna, "say Hallo!"), "Du sagst: „Hallo!“\n");
+        assert_eq!(engine.chat.take_deliveries(), vec![(bert.player, "Anna sagt: „Hallo!“".to_string())]);
+        send(&mut engine, &mut bert, "tell anna psst");
+        assert_eq!(engine.chat.take_deliveries(), vec![(anna.player, "Bert tells you: psst".to_string())]);
+        assert!(send(&mut engine, &mut bert, "tell carl psst").contains("Nobody called carl"));
+
+        send(&mut engine, &mut anna, "channel join traders");
+        assert!(send(&mut engine, &mut bert, "chat traders hi").contains("not on the channel"));
+        send(&mut engine, &mut bert, "chat global hi");
+        assert_eq!(engine.chat.take_deliveries(), vec![(anna.player, "[global] Bert: hi".to_string())]);
+        send(&mut engine, &mut anna, "channel leave global");
+        send(&mut engine, &mut bert, "chat global hi");
+        assert!(engine.chat.take_deliveries().is_empty());
+
+        engine.chat.moderate(|_, u| if u.text.contains("spoiler") {
+            Moderation::Refuse("No spoilers, please.".to_string())
+        } else {
+            Moderation::Replace(u.text.to_uppercase())
+        });
+        assert_eq!(send(&mut engine, &mut anna, "say spoiler"), "No spoilers, please.\n");
+        assert!(engine.chat.take_deliveries().is_empty());
+        send(&mut engine, &mut anna, "say hallo");
+        assert_eq!(engine.chat.take_deliveries(), vec![(bert.player, "Anna sagt: „HALLO“".to_string())]);
+
+        engine.leave(anna);
+        assert!(engine.chat.members("traders").is_empty());
+    }
+
+    #[test]
+    fn wizards() {
+        let w = tiny();
+        let mut engine = Engine::shared(w, 1);
+        let mut merlin = engine.join("Merlin");
+        merlin.account = Some("merlin".to_string());
+
+        assert_eq!(send(&mut engine, &mut merlin, "@teleport storeroom"), "Only a builder can do that.\n");
+        engine.grant("Merlin", Role::Wizard);
+        assert!(send(&mut engine, &mut merlin, "@teleport storeroom").contains("Eine Vorratskammer"));
+        assert_eq!(engine.world.location_of(&merlin.player), engine.world.by_alias("storeroom"));
+        assert!(send(&mut engine, &mut merlin, "@teleport lantern").contains("Ein feuchter Keller"));
+
+        assert_eq!(send(&mut engine, &mut merlin, "@clone lantern"), "Cloned Eine Laterne.\n");
+        assert_eq!(engine.world.entities.iter().filter(|e| e.name == vec!["lantern".to_string()]).count(), 2);
+        send(&mut engine, &mut merlin, "@set crate weight 12");
+        let krate = engine.world.by_alias("crate").unwrap();
+        assert!(engine.world.entity(&krate).unwrap().properties.get("weight") == Some(&Property::Int(12)));
+        assert_eq!(send(&mut engine, &mut merlin, "@destroy crate"), "Destroyed Eine Holzkiste.\n");
+        assert!(engine.world.entity(&krate).is_none());
+        assert!(send(&mut engine, &mut merlin, "@destroy merlin").contains("cannot be destroyed"));
+
+        assert_eq!(send(&mut engine, &mut merlin, "@dig east Ein Garten"), "Dug Ein Garten, east of here.\n");
+        assert!(send(&mut engine, &mut merlin, "go east").contains("Ein Garten"));
+        assert!(send(&mut engine, &mut merlin, "go west").contains("Ein feuchter Keller"));
+    }
+
+    #[test]
+    fn roles() {
+        let w = tiny();
+        let mut engine = Engine::shared(w, 1);
+        let mut merlin = engine.join("Merlin");
+        merlin.account = Some("merlin".to_string());
+        let mut anna = engine.join("Anna");
+        anna.account = Some("anna".to_string());
+        assert_eq!(engine.role(&anna), Role::Player);
+        assert!(engine.edit_world(&anna, |w| w.start_location).is_err());
+
+        engine.grant("merlin", Role::Wizard);
+        assert_eq!(send(&mut engine, &mut merlin, "@grant anna builder"), "anna is a builder now.\n");
+        assert_eq!(engine.role(&anna), Role::Builder);
+        assert!(engine.edit_world(&anna, |w| w.start_location).is_ok());
+        assert!(send(&mut engine, &mut anna, "@set crate weight 12").starts_with("Set weight"));
+        assert_eq!(send(&mut engine, &mut anna, "@destroy crate"), "Only a wizard can do that.\n");
+        assert_eq!(send(&mut engine, &mut anna, "@grant anna wizard"), "Only a wizard can do that.\n");
+        assert_eq!(send(&mut engine, &mut merlin, "@grant anna king"), "unknown role: king\n");
+
+        // Roles belong to accounts, and outlast sessions.
+        engine.leave(anna);
+        let mut anna = engine.join("Anna");
+        assert_eq!(engine.role(&anna), Role::Player);
+        anna.account = Some("anna".to_string());
+        assert_eq!(engine.role(&anna), Role::Builder);
+        send(&mut engine, &mut merlin, "@revoke anna");
+        assert_eq!(send(&mut engine, &mut anna, "@teleport storeroom"), "Only a builder can do that.\n");
+
+        // Servers without accounts have no roles.
+        let mut server = Server::new(tiny(), 1);
+        assert!(server.grant("merlin", Role::Wizard).is_err());
+        server.use_accounts(Accounts::default());
+        assert!(server.grant("merlin", Role::Wizard).is_ok());
+    }
+
+    #[test]
+    fn gmcp() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        let mut d = telnet::Decoder::default();
+        assert_eq!(d.feed(b"\xff\xfd\xc9look\n"), vec!["look".to_string()]);
+        assert_eq!(d.take_negotiations(), vec![(telnet::DO, telnet::GMCP)]);
+        assert_eq!(d.take_negotiations(), vec![]);
+        assert_eq!(telnet::gmcp("Char.Vitals", "{}"), b"\xff\xfa\xc9Char.Vitals {}\xff\xf0".to_vec());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let w = tiny();
+        let mut server = Server::new(w, 1);
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let read_until = |c: &mut TcpStream, end: &str| {
+            let mut txt = String::new();
+            let mut buf = [0; 256];
+            while !txt.ends_with(end) {
+                let n = c.read(&mut buf).unwrap();
+                assert!(n > 0, "{}", txt);
+                txt.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+            txt
+        };
+        let connect = || {
+            let c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            c
+        };
+
+        // A client agreeing to GMCP gets the room, vitals and inventory.
+        let mut c = connect();
+        let mut offer = [0; 3];
+        c.read_exact(&mut offer).unwrap();
+        assert_eq!(offer, [telnet::IAC, telnet::WILL, telnet::GMCP]);
+        c.write_all(&[telnet::IAC, telnet::DO, telnet::GMCP]).unwrap();
+        read_until(&mut c, "> ");
+        c.write_all(b"anna\r\n").unwrap();
+        let txt = read_until(&mut c, "> ");
+        assert!(txt.contains(r#"Room.Info {"exits":["#));
+        assert!(txt.contains(r#""name":"Ein feuchter Keller""#));
+        c.write_all(b"take lantern\r\n").unwrap();
+        let txt = read_until(&mut c, "> ");
+        assert!(txt.contains(r#"Char.Items.Inv {"items":["Eine Laterne"]}"#));
+        assert!(txt.contains("Char.Vitals {\"health\":"));
+
+        // Others get the text only.
+        let mut c = connect();
+        read_until(&mut c, "> ");
+        c.write_all(b"bert\r\n").unwrap();
+        assert!(!read_until(&mut c, "> ").contains("Room.Info"));
+    }
+
+    #[test]
+    fn mccp() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        fn read_until<R: Read>(c: &mut R, end: &str) -> String {
+            let mut txt = String::new();
+            let mut buf = [0; 256];
+            while !txt.ends_with(end) {
+                let n = c.read(&mut buf).unwrap();
+                assert!(n > 0, "{}", txt);
+                txt.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+            txt
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let w = tiny();
+        let config = ServerConfig { compression: true, ..ServerConfig::default() };
+        let mut server = Server::with_config(w, 1, config);
+        if !telnet::compression_supported() {
+            assert!(server.listen(listener, Protocol::Telnet).is_err());
+            return;
+        }
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut offers = [0; 6];
+        c.read_exact(&mut offers).unwrap();
+        assert_eq!(offers[3..], [telnet::IAC, telnet::WILL, telnet::MCCP2]);
+        assert!(read_until(&mut c, "> ").contains("What is your name?"));
+
+        c.write_all(&[telnet::IAC, telnet::DO, telnet::MCCP2]).unwrap();
+        c.write_all(b"anna\r\n").unwrap();
+        let mut start = [0; 5];
+        c.read_exact(&mut start).unwrap();
+        assert_eq!(start, [telnet::IAC, telnet::SB, telnet::MCCP2, telnet::IAC, telnet::SE]);
+        #[cfg(feature = "compression")]
+        {
+            let mut z = flate2::read::ZlibDecoder::new(c);
+            assert!(read_until(&mut z, "> ").contains("Ein feuchter Keller"));
+            z.get_mut().write_all(b"quit\r\n").unwrap();
+            assert!(read_until(&mut z, "\n").ends_with("Goodbye.\r\n"));
+        }
+    }
+
+    #[test]
+    fn mssp() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let w = tiny();
+        let mut config = ServerConfig { name: Some("Tiny".to_string()), ..ServerConfig::default() };
+        config.status.insert("CONTACT".to_string(), "admin@example.org".to_string());
+        let mut server = Server::with_config(w, 1, config);
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut offers = [0; 6];
+        c.read_exact(&mut offers).unwrap();
+        assert_eq!(offers[3..], [telnet::IAC, telnet::WILL, telnet::MSSP]);
+
+        c.write_all(&[telnet::IAC, telnet::DO, telnet::MSSP]).unwrap();
+        let mut data = vec![];
+        let mut buf = [0; 256];
+        while !data.ends_with(&[telnet::IAC, telnet::SE]) {
+            let n = c.read(&mut buf).unwrap();
+            assert!(n > 0);
+            data.extend_from_slice(&buf[..n]);
+        }
+        let start = data.windows(3).position(|w| w == [telnet::IAC, telnet::SB, telnet::MSSP]).unwrap();
+        let status = String::from_utf8_lossy(&data[start + 3..data.len() - 2]).into_owned();
+        assert!(status.starts_with("\x01NAME\x02Tiny\x01PLAYERS\x020\x01UPTIME\x02"));
+        assert!(status.ends_with("\x01CONTACT\x02admin@example.org"));
+    }
+
+    #[test]
+    fn accounts() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join("mudstuck-test-accounts.ron");
+        let _ = std::fs::remove_file(&path);
+        let mut accounts = Accounts::load(&path).unwrap();
+        accounts.create("Bert", "hunter2").unwrap();
+        assert!(accounts.create("bert", "other").is_err());
+        assert!(accounts.authenticate("bert", "hunter2"));
+        assert!(!accounts.authenticate("bert", "hunter3"));
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("hunter2"));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let w = tiny();
+        let mut server = Server::new(w, 1);
+        server.use_accounts(Accounts::load(&path).unwrap());
+        server.grant("anna", Role::Builder).unwrap();
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let read_until = |c: &mut TcpStream, end: &str| {
+            let mut txt = String::new();
+            let mut buf = [0; 256];
+            while !txt.ends_with(end) {
+                let n = c.read(&mut buf).unwrap();
+                assert!(n > 0, "{}", txt);
+                txt.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+            txt
+        };
+        let login = |name: &str, password: &str| {
+            let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            read_until(&mut c, "> ");
+            c.write_all(format!("{}\r\n", name).as_bytes()).unwrap();
+            let asked = read_until(&mut c, "> ");
+            c.write_all(format!("{}\r\n", password).as_bytes()).unwrap();
+            let answer = read_until(&mut c, "> ");
+            (c, asked, answer)
+        };
+
+        let (mut c, asked, answer) = login("anna", "secret");
+        assert!(asked.contains("Choose a password"));
+        assert!(answer.contains("Hello, anna!"));
+        c.write_all(b"take lantern\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("Genommen."));
+        c.write_all(b"quit\r\n").unwrap();
+        read_until(&mut c, "\n");
+
+        let (_, asked, answer) = login("anna", "wrong");
+        assert!(asked.contains("What is your password?"));
+        assert!(answer.contains("Wrong password."));
+        // Anna comes back to her character, still carrying the lantern.
+        let (mut c, _, answer) = login("anna", "secret");
+        assert!(answer.contains("Hello, anna!"));
+        c.write_all(b"inventory\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("Laterne"));
+        // Her role is her account's.
+        c.write_all(b"@teleport storeroom\r\n").unwrap();
+        assert!(read_until(&mut c, "> ").contains("Eine Vorratskammer"));
+        assert!(Accounts::load(&path).unwrap().get("anna").unwrap().character.is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn limits() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::{Duration, Instant};
+        use limits::{Limiter, Verdict};
+
+        assert_eq!(limits::sanitize("lo\x07ok\x1b", 10), "look");
+        assert_eq!(limits::sanitize("inventory", 3), "inv");
+        assert_eq!(telnet::Decoder::with_max_line(3).feed(b"looooook\r\n"), vec!["loo".to_string()]);
+
+        let t = Instant::now();
+        let mut l = Limiter::new(Limits { max_line: 10, lines_per_second: 1, burst: 2, penalty: Penalty::Drop });
+        assert_eq!(l.check("look", t), Verdict::Accept("look".to_string()));
+        assert_eq!(l.check("look", t), Verdict::Accept("look".to_string()));
+        assert!(match l.check("look", t) { Verdict::Reject(_) => true, _ => false });
+        assert_eq!(l.check("look", t), Verdict::Ignore);
+        assert_eq!(l.check("look", t + Duration::from_secs(1)), Verdict::Accept("look".to_string()));
+        assert!(match l.check("look", t + Duration::from_secs(1)) { Verdict::Reject(_) => true, _ => false });
+        let mut l = Limiter::new(Limits { max_line: 10, lines_per_second: 1, burst: 1, penalty: Penalty::Mute(10) });
+        l.check("look", t);
+        assert!(match l.check("look", t) { Verdict::Reject(_) => true, _ => false });
+        assert_eq!(l.check("look", t + Duration::from_secs(5)), Verdict::Ignore);
+        assert_eq!(l.check("look", t + Duration::from_secs(11)), Verdict::Accept("look".to_string()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let w = tiny();
+        let limits = Limits { penalty: Penalty::Disconnect, burst: 3, ..Limits::default() };
+        let mut server = Server::with_config(w, 1, ServerConfig { limits: limits, ..ServerConfig::default() });
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        c.write_all(b"anna\r\n").unwrap();
+        c.write_all(b"look\r\n".repeat(10).as_slice()).unwrap();
+        let mut data = vec![];
+        let _ = c.read_to_end(&mut data);
+        assert!(String::from_utf8_lossy(&data).contains("You are typing too fast.\r\nGoodbye.\r\n"));
+
+        // Negotiations are limited like lines.
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        c.write_all([telnet::IAC, telnet::DO, telnet::MSSP].repeat(10).as_slice()).unwrap();
+        let mut data = vec![];
+        let _ = c.read_to_end(&mut data);
+        let txt = String::from_utf8_lossy(&data);
+        assert_eq!(txt.matches("PLAYERS").count(), 3);
+        assert!(txt.contains("You are typing too fast.\r\nGoodbye.\r\n"));
+
+        // Clients whose lines are dropped are told once.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let limits = Limits { burst: 3, ..Limits::default() };
+        let mut server = Server::with_config(tiny(), 1, ServerConfig { limits: limits, ..ServerConfig::default() });
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        c.write_all(b"anna\r\n").unwrap();
+        c.write_all(b"look\r\n".repeat(20).as_slice()).unwrap();
+        let mut data = vec![];
+        let _ = c.read_to_end(&mut data);
+        assert_eq!(String::from_utf8_lossy(&data).matches("You are typing too fast").count(), 1);
+    }
+
+    #[test]
+    fn step() {
+        let w = tiny();
+        let mut engine = Engine::shared(w, 1);
+        let anna = engine.open("Anna");
+        let bert = engine.open("Bert");
+        let text = |e: &OutputEvent| match e.output {
+            Output::Text { ref text } => text.clone(),
+            _ => String::new(),
+        };
+
+        // Nothing is shown, everything is returned.
+        let (events, shown) = output::capture(|| engine.step(anna, "take lantern"));
+        assert_eq!(shown, "");
+        assert_eq!(events[0].session, anna);
+        assert!(text(&events[0]).starts_with("Genommen."));
+        assert!(events.iter().any(|e| e.output == Output::Inventory { items: vec!["Eine Laterne".to_string()] }));
+        assert!(events.iter().any(|e| match e.output { Output::Room { ref name, .. } => name == "Ein feuchter Keller", _ => false }));
+
+        let events = engine.step(anna, "say Hallo");
+        assert!(events.iter().any(|e| e.session == bert && text(e).contains("Hallo")));
+        let s = engine.close(bert).unwrap();
+        engine.leave(s);
+        assert_eq!(engine.step(bert, "look"), vec![]);
+    }
+
+    #[test]
+    fn shared_turns() {
+        let mut w = tiny();
+        let cellar = w.start_location;
+        let storeroom = w.by_alias("storeroom").unwrap();
+        let caretaker = w.by_alias("caretaker").unwrap();
+        w.entity_mut(&caretaker).and_then(|e| e.character_mut()).unwrap().patrol =
+            Some(Patrol { route: vec![cellar, storeroom], position: 0, pace: 1, wait: 1 });
+        let mut engine = Engine::shared(w, 1);
+        let anna = engine.open("Anna");
+        let bert = engine.open("Bert");
+        let text = |e: &OutputEvent| match e.output {
+            Output::Text { ref text } => text.clone(),
+            _ => String::new(),
+        };
+
+        // Turns pass on the clock, and everyone in the room notices.
+        engine.step(anna, "look");
+        assert_eq!(engine.world.turn, 0);
+        let events = engine.tick();
+        assert_eq!(engine.world.turn, 1);
+        assert_eq!(engine.world.location_of(&caretaker), Some(storeroom));
+        for s in &[anna, bert] {
+            assert!(events.iter().any(|e| e.session == *s && text(e).contains("Ein alter Hausmeister geht hinaus.")));
+        }
+
+        // Fights are seen by the others in the room, and players
+        // attacked do not strike back on their own.
+        let events = engine.step(anna, "attack bert");
+        assert!(events.iter().any(|e| e.session == bert && text(e).starts_with("Anna greift Bert an")));
+        assert!(!events.iter().any(|e| e.session == anna && text(e).contains("Anna greift")));
+        let events = engine.step(anna, "look");
+        assert!(!events.iter().any(|e| text(e).contains("Bert greift")));
+
+        let s = engine.close(anna).unwrap();
+        engine.leave(s);
+        let s = engine.close(bert).unwrap();
+        engine.leave(s);
+        assert!(engine.tick().is_empty());
+        assert_eq!(engine.world.turn, 1);
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn wasm() {
+        let w = tiny();
+        let mut game = Game::start(w, 1);
+        let outputs: serde_json::Value = serde_json::from_str(&game.step("look")).unwrap();
+        assert_eq!(outputs[0]["type"], "text");
+        assert!(outputs.as_array().unwrap().iter().any(|o| o["type"] == "room" && o["name"] == "Ein feuchter Keller"));
+        assert!(!game.over());
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn scripts() {
+        let mut w = World::compile("world \"W\" { start: cell; scoring: Scoring(awards: {\"coin\": Award(points: 1)}); }\n\
+            room cell { short: \"Eine Zelle\"; contains: coin, chest, stone; }\n\
+            item coin { short: \"Eine Münze\"; attribute: Triggered([Trigger(on: \"take\", text: \"\", effects: [Script(\"\
+                let n = property(entity, `taken`) ?? 0; set_property(entity, `taken`, n + 1); say(`Zum ${n + 1}. Mal.`); \
+                if carries(player, entity) && location(player) == room { award(`coin`) }\")])]); }\n\
+            item chest { short: \"Eine Truhe\"; attribute: Closable(true); attribute: Ruled([Rule(verb: \"open\", stage: CarryOut, \
+                        text: \"\", effects: [Script(\"open(entity); after(1, `say(name(find(\\\"chest\\\")))`)\")])]); }\n\
+            item stone { short: \"Ein Stein\"; attribute: Ruled([Rule(verb: \"get\", stage: CarryOut, text: \"\", \
+                        effects: [Script(\"loop {}\"), Script(\"destroy(``)\")])]); }").unwrap();
+        let coin = w.by_alias("coin").unwrap();
+        let chest = w.by_alias("chest").unwrap();
+        let mut ps = player(&mut w);
+
+        // Effects are carried out after the script.
+        assert!(run(&mut ps, "take coin").contains("Zum 1. Mal."));
+        assert_eq!(ps.world.property(&coin, "taken"), Some(&Property::Int(1)));
+        assert_eq!(ps.world.score(), 1);
+        run(&mut ps, "drop coin");
+        assert!(run(&mut ps, "take coin").contains("Zum 2. Mal."));
+
+        // Scripts take over verbs, and schedule scripts, here for the
+        // end of the turn.
+        assert_eq!(run(&mut ps, "open chest"), "Eine Truhe\n");
+        assert_eq!(ps.world.entity(&chest).and_then(|e| e.closed()), Some(false));
+
+        // Scripts running too long are stopped.
+        let txt = run(&mut ps, "take stone");
+        assert!(txt.contains("Too many operations"));
+        assert!(txt.contains("no such entity"));
+    }
+
+    #[test]
+    fn extensions() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Hunger(Arc<AtomicUsize>);
+
+        impl Extension for Hunger {
+            fn name(&self) -> &str { "hunger" }
+
+            fn register(&self, r: &mut Registry) {
+                r.attribute("hunger", Property::Int(3));
+                r.verb("fast", |_, rest| say!("Du fastest {}.", rest));
+                r.function("double", |_, args| match args.get(0) {
+                    Some(&Value::Int(i)) => Ok(Value::Int(2 * i)),
+                    _ => Err("function double requires a number".to_string()),
+                });
+                let taken = self.0.clone();
+                r.listener(move |_, e| if let Event::ItemTaken { .. } = *e {
+                    taken.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+
+        let taken = Arc::new(AtomicUsize::new(0));
+        let mut engine = Engine::new(tiny(), 1);
+        engine.extend(&Hunger(taken.clone()));
+        assert_eq!(engine.world.extensions.names, vec!["hunger".to_string()]);
+
+        let turn = engine.world.turn;
+        let (_, txt) = output::capture(|| engine.handle("Fast lange"));
+        assert!(txt.starts_with("Du fastest lange.\n"));
+        assert_eq!(engine.world.turn, turn + 1);
+
+        let player = engine.player;
+        assert_eq!(engine.world.property(&player, "hunger"), Some(&Property::Int(3)));
+        engine.world.set_property(&player, "hunger", Property::Int(5));
+        assert_eq!(engine.world.eval_str("#(double (property lantern \"hunger\"))"), Ok("6".to_string()));
+        assert_eq!(engine.world.eval_str("#(double \"x\")"), Err("function double requires a number".to_string()));
+
+        output::capture(|| engine.handle("take lantern"));
+        assert_eq!(taken.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi() {
+        use std::collections::BTreeMap;
+        use std::ffi::{CStr, CString};
+        use std::ptr;
+
+        let mut files = BTreeMap::new();
+        files.insert("tiny.mud".to_string(), include_str!("../worlds/tiny.mud").to_string());
+        let bundle = Bundle { version: 1, metadata: Metadata::default(), main: "tiny.mud".to_string(), files: files,
+                              messages: BTreeMap::new() };
+        let bytes = ron::ser::to_string(&bundle).unwrap().into_bytes();
+        unsafe {
+            let text = |s: *mut std::os::raw::c_char| {
+                let res = CStr::from_ptr(s).to_string_lossy().into_owned();
+                ffi::mudstuck_string_free(s);
+                res
+            };
+            let mut error = ptr::null_mut();
+            assert!(ffi::mudstuck_new(b"Bundle(".as_ptr(), 7, 1, &mut error).is_null());
+            assert!(text(error).starts_with("invalid bundle"));
+
+            let game = ffi::mudstuck_new(bytes.as_ptr(), bytes.len(), 1, ptr::null_mut());
+            assert!(!game.is_null());
+            let take = CString::new("take lantern").unwrap();
+            assert!(text(ffi::mudstuck_step(game, take.as_ptr())).starts_with("Genommen."));
+            let saved = ffi::mudstuck_save(game);
+            let drop = CString::new("drop lantern").unwrap();
+            text(ffi::mudstuck_step(game, drop.as_ptr()));
+            assert_eq!(ffi::mudstuck_restore(game, saved, ptr::null_mut()), 0);
+            ffi::mudstuck_string_free(saved);
+            let inventory = CString::new("i").unwrap();
+            assert!(text(ffi::mudstuck_step(game, inventory.as_ptr())).contains("Laterne"));
+            assert_eq!(ffi::mudstuck_over(game), 0);
+            ffi::mudstuck_free(game);
+
+            // Panics do not unwind into C.
+            let mut error = ptr::null_mut();
+            assert_eq!(ffi::guarded(&mut error, -1, || -> i32 { panic!("boom") }), -1);
+            assert_eq!(text(error), "internal error: boom");
+            assert_eq!(ffi::guarded(ptr::null_mut(), 0, || 1), 1);
+        }
+    }
+
+    #[test]
+    fn randomness() {
+        let play = |seed| {
+            let mut engine = Engine::shared(tiny(), seed);
+            let anna = engine.open("Anna");
+            let picks: Vec<String> = (0..20).map(|_| engine.world.eval_str("#(one_of \"a\" \"b\" \"c\")").unwrap()).collect();
+            (engine.world.start_location, engine.session(anna).unwrap().player, picks)
+        };
+        let (start, anna, picks) = play(7);
+        assert_eq!(play(7), (start, anna, picks.clone()));
+        assert!(picks.iter().all(|p| p == "a" || p == "b" || p == "c"));
+        assert!(picks.iter().any(|p| *p != picks[0]));
+        let (start2, anna2, picks2) = play(8);
+        assert_eq!(start2, start);
+        assert!(anna2 != anna && picks2 != picks);
+    }
+
+    #[test]
+    fn snapshots() {
+        let tiny = || tiny();
+        let carries = |engine: &Engine, player| engine.world.entity(&player).and_then(|e| e.character())
+            .map_or(false, |c| c.inventory.iter().any(|i| engine.world.short_description(i).contains("Laterne")));
+
+        let mut engine = Engine::new(tiny(), 1);
+        let player = engine.player;
+        output::capture(|| engine.handle("take lantern"));
+        assert!(carries(&engine, player));
+        output::capture(|| engine.handle("undo"));
+        assert!(!carries(&engine, player));
+        let (_, txt) = output::capture(|| engine.handle("undo"));
+        assert!(txt.contains("nothing to undo"));
+        let took = engine.speculate(|e| {
+            output::capture(|| e.handle("take lantern"));
+            carries(e, player)
+        });
+        assert!(took && !carries(&engine, player));
+
+        let path = std::env::temp_dir().join("mudstuck-test-journal.ron");
+        let mut engine = Engine::shared(tiny(), 1);
+        let anna = engine.open("Anna");
+        let player = engine.session(anna).unwrap().player;
+        engine.keep_journal(&path, std::time::Duration::from_secs(3600)).unwrap();
+        engine.step(anna, "take lantern");
+        let turn = engine.world.turn;
+        // The engine crashes.
+        drop(engine);
+        let mut engine = Engine::shared(tiny(), 1);
+        assert_eq!(engine.recover(&path), Ok(1));
+        assert!(carries(&engine, player));
+        assert_eq!(engine.world.turn, turn);
+        assert_eq!(engine.session(anna).map(|s| s.player), Some(player));
+    }
+
+    #[test]
+    fn metrics() {
+        use std::sync::Arc;
+
+        let counters = Arc::new(Counters::new());
+        let mut engine = Engine::shared(tiny(), 1);
+        engine.observe(counters.clone());
+        let anna = engine.open("Anna");
+        engine.open("Bert");
+        assert_eq!(counters.sessions(), 2);
+        engine.step(anna, "look");
+        engine.step(anna, "take lantern");
+        assert_eq!(counters.commands(), 2);
+        assert!(counters.templates() > 0);
+        assert!(counters.most_events() > 0);
+        engine.close(anna);
+        assert_eq!(counters.sessions(), 1);
+    }
+
+    #[test]
+    fn audit() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join("mudstuck-test-audit.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut server = Server::new(tiny(), 1);
+        server.audit(&path).unwrap();
+        server.listen(listener, Protocol::Telnet).unwrap();
+        std::thread::spawn(move || server.run());
+        let mut c = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        // Type a line, if any, and wait for the next prompt.
+        let prompt = |c: &mut TcpStream, line: &str| {
+            if !line.is_empty() {
+                c.write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+            }
+            let mut txt = String::new();
+            let mut buf = [0; 256];
+            while !txt.ends_with("> ") {
+                let n = c.read(&mut buf).unwrap();
+                assert!(n > 0, "{}", txt);
+                txt.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+        };
+        prompt(&mut c, "");
+        prompt(&mut c, "anna");
+        prompt(&mut c, "take lantern");
+        prompt(&mut c, "save");
+
+        let log: Vec<serde_json::Value> = std::fs::read_to_string(&path).unwrap().lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let commands: Vec<&str> = log.iter().map(|e| e["command"].as_str().unwrap()).collect();
+        assert_eq!(commands, vec!["look", "take lantern", "save"]);
+        assert!(log.iter().all(|e| e["player"] == "anna" && e["timestamp"].as_u64().unwrap() > 0));
+        let taken = &log[1]["outcome"];
+        assert_eq!(taken["result"], "done");
+        assert!(taken["text"].as_str().unwrap().starts_with("Genommen."));
+        assert_eq!(taken["events"][0]["type"], "item_taken");
+        assert_eq!(log[2]["outcome"]["result"], "refused");
+    }
+
+    #[test]
+    fn storage() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Memory {
+            games: Mutex<BTreeMap<String, String>>,
+        }
+
+        impl Storage for Memory {
+            fn load_state(&self) -> Result<Option<Snapshot>, String> { Ok(None) }
+            fn save_state(&self, _: &Snapshot) -> Result<(), String> { Err("read-only".to_string()) }
+            fn load_accounts(&self) -> Result<BTreeMap<String, Account>, String> { Ok(BTreeMap::new()) }
+            fn save_accounts(&self, _: &BTreeMap<String, Account>) -> Result<(), String> { Ok(()) }
+            fn load_game(&self, name: &str) -> Result<Option<String>, String> {
+                Ok(self.games.lock().unwrap().get(name).cloned())
+            }
+            fn save_game(&self, name: &str, saved: &str) -> Result<(), String> {
+                self.games.lock().unwrap().insert(name.to_string(), saved.to_string());
+                Ok(())
+            }
+        }
+
+        let tiny = || tiny();
+        let memory = Arc::new(Memory::default());
+        let mut engine = Engine::new(tiny(), 1);
+        engine.use_storage(memory.clone());
+        output::capture(|| engine.handle("take lantern"));
+        assert_eq!(output::capture(|| engine.handle("save")).1, "Game saved.\n");
+        assert!(memory.games.lock().unwrap().contains_key(engine::SAVE_GAME));
+        output::capture(|| engine.handle("drop lantern"));
+        output::capture(|| engine.handle("restore"));
+        assert!(output::capture(|| engine.handle("i")).1.contains("Laterne"));
+        assert!(output::capture(|| engine.handle("restore nothing")).1.contains("no saved game named nothing"));
+
+        let dir = std::env::temp_dir().join("mudstuck-test-storage");
+        let _ = std::fs::remove_dir_all(&dir);
+        let files = Arc::new(Files::new(&dir));
+        let mut engine = Engine::shared(tiny(), 1);
+        engine.use_storage(files.clone());
+        assert_eq!(engine.load_state(), Ok(false));
+        let anna = engine.open("Anna");
+        engine.step(anna, "take lantern");
+        engine.save_state().unwrap();
+        let mut accounts = Accounts::open(files.clone()).unwrap();
+        accounts.create("Anna", "secret").unwrap();
+
+        let mut engine = Engine::shared(tiny(), 1);
+        engine.use_storage(files.clone());
+        assert_eq!(engine.load_state(), Ok(true));
+        assert!(engine.step(anna, "i").iter().any(|e| match e.output {
+            Output::Inventory { ref items } => items.iter().any(|i| i.contains("Laterne")),
+            _ => false,
+        }));
+        assert!(Accounts::open(files).unwrap().authenticate("anna", "secret"));
+        // Accounts kept elsewhere get their directory made too.
+        let elsewhere = dir.join("players").join("accounts.ron");
+        let mut accounts = Accounts::open(Arc::new(Files::new(&dir).accounts_file(&elsewhere))).unwrap();
+        accounts.create("Bert", "secret").unwrap();
+        assert!(elsewhere.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite() {
+        use std::sync::Arc;
+
+        let path = std::env::temp_dir().join("mudstuck-test.db");
+        let _ = std::fs::remove_file(&path);
+        let tiny = || tiny();
+        let db = Arc::new(Sqlite::open(&path).unwrap());
+        let mut engine = Engine::shared(tiny(), 1);
+        engine.use_storage(db.clone());
+        assert_eq!(engine.load_state(), Ok(false));
+        let anna = engine.open("Anna");
+        let player = engine.session(anna).unwrap().player;
+        engine.save_state().unwrap();
+        engine.step(anna, "take lantern");
+        engine.save_state().unwrap();
+        assert_eq!(db.entity(&player).unwrap().unwrap().character().unwrap().inventory.len(), 1);
+        // Only the entity which changed is written again.
+        let lantern = engine.world.by_alias("lantern").unwrap();
+        let written = db.entities_written();
+        engine.world.set_property(&lantern, "polished", Property::Bool(true));
+        engine.save_state().unwrap();
+        assert_eq!(db.entities_written(), written + 1);
+        assert!(engine.world.changed.is_empty());
+        engine.save_state().unwrap();
+        assert_eq!(db.entities_written(), written + 1);
+        db.save_game("mine", "saved").unwrap();
+        let mut accounts = Accounts::open(db.clone()).unwrap();
+        accounts.create("Anna", "secret").unwrap();
+        let ids: Vec<InternalName> = engine.world.entities.iter().map(|e| e.id).collect();
+        drop(accounts);
+        drop(db);
+
+        let db = Arc::new(Sqlite::open(&path).unwrap());
+        let mut engine = Engine::shared(tiny(), 1);
+        engine.use_storage(db.clone());
+        assert_eq!(engine.load_state(), Ok(true));
+        assert_eq!(engine.world.entities.iter().map(|e| e.id).collect::<Vec<_>>(), ids);
+        assert_eq!(engine.session(anna).map(|s| s.player), Some(player));
+        let session = engine.close(anna).unwrap();
+        engine.leave(session);
+        engine.save_state().unwrap();
+        assert!(db.entity(&player).unwrap().is_none());
+        assert_eq!(db.load_game("mine"), Ok(Some("saved".to_string())));
+        assert!(Accounts::open(db).unwrap().authenticate("anna", "secret"));
+        std::fs::remove_file(&path).unwrap();
     }
 }
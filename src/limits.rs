@@ -0,0 +1,116 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Limits on what clients of the server send, so that a client cannot
+//! wedge the server by flooding it.  Each connection may send only so
+//! many lines a second, with some more in a burst, and clients going
+//! over the limit are punished as configured: their lines are
+//! dropped, they are ignored for a while, or they are disconnected.
+//!
+//! Lines are also cut off after a maximum length, and have control
+//! characters taken out, before they reach the parser.  All of this
+//! happens on the threads reading from the connections (see
+//! `server`), so the server thread never sees the flood: clients are
+//! told only once each time they go over the limit.
+
+use std::time::{Duration, Instant};
+
+/// What happens to clients sending more than they may.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Penalty {
+    /// The lines over the limit are dropped, and the client is told
+    /// so once.
+    Drop,
+    /// Everything the client sends is ignored for the given number of
+    /// seconds.
+    Mute(u64),
+    /// The connection is closed.
+    Disconnect,
+}
+
+/// The limits on what clients send.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The longest line passed on, in characters.
+    pub max_line: usize,
+    /// The number of lines a client may send per second.
+    pub lines_per_second: u32,
+    /// The number of lines a client may send at once, after being
+    /// quiet for a while.
+    pub burst: u32,
+    pub penalty: Penalty,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits { max_line: 512, lines_per_second: 5, burst: 20, penalty: Penalty::Drop }
+    }
+}
+
+/// What becomes of a line a client sends.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    /// The line is passed on, as it is now.
+    Accept(String),
+    /// The line is dropped, and the client told why.
+    Reject(String),
+    /// The line is dropped without a word.
+    Ignore,
+    /// The client is disconnected.
+    Disconnect,
+}
+
+/// Return a line without control characters, cut off after `max`
+/// characters.
+pub fn sanitize(line: &str, max: usize) -> String {
+    line.chars().filter(|c| !c.is_control()).take(max).collect()
+}
+
+/// Keeps track of the lines one client sends.  Lines use up tokens,
+/// which come back at the rate allowed, up to the burst.
+pub struct Limiter {
+    limits: Limits,
+    tokens: f64,
+    last: Instant,
+    /// Until when the client is ignored.
+    muted_until: Option<Instant>,
+    /// Whether the client was told that its lines are dropped, since
+    /// it last sent one within the limits.
+    warned: bool,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        Limiter { limits: limits, tokens: limits.burst as f64, last: Instant::now(), muted_until: None, warned: false }
+    }
+
+    /// Judge a line the client sent at the given time.
+    pub fn check(&mut self, line: &str, now: Instant) -> Verdict {
+        let elapsed = now.duration_since(self.last);
+        self.last = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.limits.lines_per_second as f64)
+            .min(self.limits.burst as f64);
+        match self.muted_until {
+            Some(t) if now < t => return Verdict::Ignore,
+            Some(_) => self.muted_until = None,
+            None => {},
+        }
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.warned = false;
+            return Verdict::Accept(sanitize(line, self.limits.max_line));
+        }
+        match self.limits.penalty {
+            Penalty::Drop if self.warned => Verdict::Ignore,
+            Penalty::Drop => {
+                self.warned = true;
+                Verdict::Reject("You are typing too fast; that was ignored.".to_string())
+            },
+            Penalty::Mute(secs) => {
+                self.muted_until = Some(now + Duration::from_secs(secs));
+                Verdict::Reject(format!("You are typing too fast, and are ignored for {} seconds.", secs))
+            },
+            Penalty::Disconnect => Verdict::Disconnect,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Linting worlds.  Besides the problems validation finds, the
+//! linter checks all templates for syntax errors, unknown names and
+//! wrong numbers of arguments, and looks for things which are
+//! probably mistakes: items which never show up, locks nothing
+//! opens, and rooms players cannot get back from.  Run it with
+//!
+//! ```text
+//! mudstuck lint world.ron
+//! ```
+//!
+//! Worlds are loaded with `World::load_unchecked` for linting, so all
+//! validation problems are reported, not only the first failure.
+
+use std::fmt;
+
+use super::types::*;
+use super::template;
+use super::template::Ast;
+use super::Value;
+
+/// Return the templates of an entity, with the fields they are in and
+/// whether they are conditions.
+fn templates(e: &Entity) -> Vec<(String, &str, bool)> {
+    let mut res = vec![("short".to_string(), e.short_description.as_str(), false),
+                       ("long".to_string(), e.long_description.as_str(), false)];
+    res.extend(e.sound_description.iter().map(|s| ("sound".to_string(), s.as_str(), false)));
+    res.extend(e.smell_description.iter().map(|s| ("smell".to_string(), s.as_str(), false)));
+    for (l, t) in e.translations.iter() {
+        for &(field, text) in [("short", &t.short), ("long", &t.long), ("sound", &t.sound), ("smell", &t.smell)].iter() {
+            res.push((format!("{} ({})", field, l), text.as_str(), false));
+        }
+    }
+    for a in e.attributes.iter() {
+        match *a {
+            Attribute::Readable(ref t) => res.push(("text".to_string(), t, false)),
+            Attribute::Edible(ref c) | Attribute::Drinkable(ref c) => res.push(("effect".to_string(), &c.effect, false)),
+            Attribute::Concealing(ref c) => res.push(("reveal".to_string(), &c.reveal, false)),
+            Attribute::Stateful(ref m) =>
+                res.extend(m.transitions.iter().filter_map(|t| t.effect.as_ref())
+                           .map(|t| ("effect".to_string(), t.as_str(), false))),
+            Attribute::Burnable(ref f) => res.push(("expiry".to_string(), &f.expiry, false)),
+            Attribute::Perishable(ref d) => res.push(("message".to_string(), &d.message, false)),
+            Attribute::Doorlike(ref c) => {
+                if let Some(ref b) = c.barrier {
+                    res.push(("condition".to_string(), &b.condition, true));
+                    res.push(("refusal".to_string(), &b.refusal, false));
+                }
+                res.extend(c.transition.iter().map(|t| ("transition".to_string(), t.as_str(), false)));
+            },
+            Attribute::Triggered(ref ts) =>
+                for t in ts.iter() {
+                    res.extend(t.condition.iter().map(|c| (format!("trigger {} condition", t.on), c.as_str(), true)));
+                    res.push((format!("trigger {}", t.on), &t.text, false));
+                },
+            Attribute::Ruled(ref rs) =>
+                for r in rs.iter() {
+                    res.extend(r.condition.iter().map(|c| (format!("rule {} condition", r.verb), c.as_str(), true)));
+                    res.push((format!("rule {}", r.verb), &r.text, false));
+                },
+            Attribute::Characterlike(ref c) => {
+                res.extend(c.topics.iter().map(|(k, t)| (format!("topic {}", k), t.as_str(), false)));
+                res.push(("fallback".to_string(), &c.fallback, false));
+                for g in c.gifts.iter() {
+                    let text = match g.response {
+                        Response::Refuse(ref t) | Response::Accept(ref t) | Response::Trade(ref t, _) => t,
+                    };
+                    res.push(("gift response".to_string(), text, false));
+                }
+            },
+            _ => {},
+        }
+    }
+    res
+}
+
+impl World {
+    /// Return a readable reference to an entity: its alias, or else
+    /// its name as used in templates.
+    pub fn reference(&self, id: &InternalName) -> String {
+        match self.entity(id) {
+            Some(e) => e.alias.clone().unwrap_or(e.name.join(".")),
+            None => id.to_string(),
+        }
+    }
+
+    fn finding(&self, severity: Severity, entity: Option<InternalName>, message: String) -> Finding {
+        Finding {
+            severity: severity,
+            entity: entity,
+            reference: entity.map(|e| self.reference(&e)).unwrap_or_default(),
+            message: message,
+        }
+    }
+
+    /// Check a template expression, adding the problems found.
+    fn check_ast(&self, ast: &Ast, problems: &mut Vec<String>) {
+        match *ast {
+            Ast::Id(ref s) =>
+                if self.function(s).is_none() && s != "player" && s != "here" && self.by_alias(s).is_none() &&
+                    self.get_by_name(&self.from_script_name(s)).is_none() {
+                    problems.push(format!("undefined identifier: {}", s));
+                },
+            Ast::Seq(ref l, ref r) => {
+                self.check_ast(l, problems);
+                self.check_ast(r, problems);
+            },
+            Ast::Call(ref f, ref args) => {
+                match **f {
+                    Ast::Id(ref name) => match self.function(name) {
+                        Some(Value::Fun(_, _, _, min, max)) if args.len() < min || args.len() > max =>
+                            problems.push(format!("function {} takes {} to {} arguments, got {}", name, min, max, args.len())),
+                        Some(_) => {},
+                        None => problems.push(format!("unknown function: {}", name)),
+                    },
+                    _ => problems.push("non-function in function position".to_string()),
+                }
+                for a in args.iter() {
+                    self.check_ast(a, problems);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Check a template, or a condition, returning the problems
+    /// found.
+    pub fn check_template(&self, txt: &str, condition: bool) -> Vec<String> {
+        let parsed = if condition { template::parse_expression(txt) } else { template::parse(txt) };
+        match parsed {
+            Ok(ast) => {
+                let mut problems = vec![];
+                self.check_ast(&ast, &mut problems);
+                problems
+            },
+            Err(e) => vec![e],
+        }
+    }
+
+    /// Return true if the lock can be opened: one of its keys or a
+    /// key of its class is somewhere in the world, or it has a code.
+    fn can_unlock(&self, lock: &Lock) -> bool {
+        lock.code.is_some() ||
+            lock.keys.iter().any(|k| self.is_placed(k)) ||
+            self.entities.iter().any(|e| self.key_fits(lock, &e.id) && self.is_placed(&e.id))
+    }
+
+    /// Lint the world, returning what was found, errors first.
+    pub fn lint(&self) -> Vec<Finding> {
+        let mut res = vec![];
+        for p in self.validate().problems {
+            let entity = match p {
+                Problem::Dangling { entity, .. } => Some(entity),
+                Problem::BadStart(_) | Problem::BadInventory(_) => None,
+                Problem::DuplicateAlias(_, ref ids) => ids.first().cloned(),
+                Problem::Unreachable(r) => Some(r),
+            };
+            res.push(self.finding(Severity::Error, entity, p.to_string()));
+        }
+
+        for e in self.entities.iter() {
+            for (field, txt, condition) in templates(e) {
+                for p in self.check_template(txt, condition) {
+                    res.push(self.finding(Severity::Error, Some(e.id), format!("{}: {}", field, p)));
+                }
+            }
+        }
+        for (name, r) in self.regions.iter() {
+            let mut texts = vec![("description", &r.description)];
+            texts.extend(r.ambient.iter().map(|a| ("ambient", a)));
+            texts.extend(r.weather.iter().flat_map(|w| w.states.iter().map(|s| ("weather", &s.message))));
+            for (field, txt) in texts {
+                for p in self.check_template(txt, false) {
+                    res.push(self.finding(Severity::Error, None, format!("region {}: {}: {}", name, field, p)));
+                }
+            }
+        }
+
+        for (name, q) in self.quests.iter() {
+            let mut texts: Vec<(&str, &str, bool)> = q.start.iter().map(|c| ("start", c.as_str(), true)).collect();
+            for st in q.stages.iter() {
+                texts.push(("goal", &st.goal, false));
+                texts.push(("condition", &st.condition, true));
+                texts.push(("text", &st.text, false));
+            }
+            for (field, txt, condition) in texts {
+                for p in self.check_template(txt, condition) {
+                    res.push(self.finding(Severity::Error, None, format!("quest {}: {}: {}", name, field, p)));
+                }
+            }
+        }
+
+        for i in self.stats().unplaced {
+            res.push(self.finding(Severity::Warning, Some(i), "is never placed anywhere".to_string()));
+        }
+        for e in self.entities.iter() {
+            if let Some(l) = e.lock() {
+                if l.locked && !self.can_unlock(l) {
+                    res.push(self.finding(Severity::Warning, Some(e.id), "is locked, but no key for it is placed".to_string()));
+                }
+            }
+        }
+        let reached = self.reachable_rooms();
+        for e in self.entities.iter().filter(|e| reached.contains(&e.id)) {
+            if !self.reachable_from(&e.id).contains(&self.start_location) {
+                res.push(self.finding(Severity::Warning, Some(e.id), "there is no way back to the start location".to_string()));
+            }
+        }
+        res.sort_by(|a, b| b.severity.cmp(&a.severity));
+        res
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.reference.is_empty() {
+            write!(f, "{}: {}", self.severity, self.message)
+        } else {
+            write!(f, "{}: {}: {}", self.severity, self.reference, self.message)
+        }
+    }
+}
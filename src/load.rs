@@ -0,0 +1,486 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Loading worlds from data files.  Worlds are written in RON (Rusty
+//! Object Notation), as a `World` structure with these fields:
+//!
+//! * `version`: the version of the format (optional).  Files of
+//!   older versions are migrated when they are loaded (see `migrate`).
+//! * `name`: the name of the world.
+//! * `start_location`: the UUID of the room new players start in.
+//!   Both may be left out in included files.
+//! * `player`: the `PlayerTemplate` new players are made from, with
+//!   their `name`, `stats`, and optionally descriptions,
+//!   `conditions`, `inventory` and `purse` (optional).  Without it,
+//!   players start as exhausted adventurers carrying nothing.
+//! * `includes`: a list of further world files, relative to this
+//!   one, whose contents are added to the world (optional).
+//! * `namespace`: the namespace of the aliases in this file
+//!   (optional).
+//! * `locale`: the language descriptions are shown in (optional, see
+//!   `locale`).
+//! * `messages`: messages of the game replaced by the world, by
+//!   locale and key (optional, see `messages`).
+//! * `kinds`: a map from kind names to `Kind`s (optional).
+//! * `regions`: a map from region names to `Region`s (optional).
+//! * `entities`: the list of all `Entity`s, rooms included.  Each
+//!   entity has an `id` (a UUID), a `name` (a list of words), an
+//!   optional `alias`, `kind`, `tags` and `properties`, a
+//!   `short_description` and `long_description` (templates), optional
+//!   `sound_description` and `smell_description`, optional
+//!   `translations` of the descriptions by locale, and a list of
+//!   `attributes`, written like `Lockable(Lock(...))`.
+//! * `relations`: a list of `(relation, a, b)` triples, like
+//!   `("owned_by", flask, guard)` (optional).
+//! * `respawns`: a list of `(entity, delay, message)` triples, each
+//!   bringing back the entity as it is in the file (optional).
+//! * `schedule`: a list of `Job`s, tasks carried out in later turns
+//!   (optional, see `scheduler`).
+//! * `quests`: a map from quest names to `Quest`s (optional, see
+//!   `quest`).
+//! * `clock`: the `Clock`, how game time passes (optional, see
+//!   `clock`).
+//! * `scoring`: the `Scoring`, what players get points for (optional,
+//!   see `score`).
+//!
+//! Field names and variants are those of the types in `types`.
+//! Wherever an entity is expected, it can be given by its alias as
+//! `@alias` instead of by its UUID.  Entities with an alias need no
+//! `id`; their UUID is derived from the alias, so it is the same
+//! whenever the world is loaded.  See
+//! `worlds/example.ron` for a complete world.
+//!
+//! Worlds can be split into several files, like one per zone.  Only
+//! the name, start location and player of the first file count.  The aliases
+//! of a file with a namespace are prefixed with it, like
+//! `mine.lamp`.  In the file itself, `@lamp` refers to `mine.lamp`,
+//! and other entities are referred to by their full alias.  Aliases,
+//! kinds and regions must not be declared in several files.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use uuid;
+use uuid::Uuid;
+use ron;
+
+use super::types::*;
+use super::rng::Rng;
+use super::seed_from;
+use super::compress::{read_file, uncompressed_name};
+use super::migrate::{Migration, migrate, unchanged};
+
+/// The version of the world file format.
+pub const WORLD_VERSION: u32 = 1;
+
+/// Migrations of world files from older versions.
+const MIGRATIONS: &'static [Migration] =
+    &[
+        // Version 1 added the version.
+        Migration { from: 0, migrate: unchanged },
+    ];
+
+/// The version of a world file, which is read before the rest.
+#[derive(Deserialize)]
+#[serde(rename = "World")]
+struct Header {
+    #[serde(default)]
+    version: u32,
+}
+
+thread_local! {
+    /// Set while loading worlds which are not to be validated.
+    static UNCHECKED: Cell<bool> = Cell::new(false);
+}
+
+/// The contents of a world file.
+#[derive(Deserialize)]
+#[serde(rename = "World")]
+pub struct WorldFile {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub start_location: InternalName,
+    #[serde(default)]
+    pub player: Option<PlayerTemplate>,
+    #[serde(default)]
+    pub includes: Vec<String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub messages: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub kinds: BTreeMap<String, Kind>,
+    #[serde(default)]
+    pub regions: BTreeMap<String, Region>,
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    #[serde(default)]
+    pub relations: Vec<(String, InternalName, InternalName)>,
+    #[serde(default)]
+    pub respawns: Vec<(InternalName, u32, String)>,
+    #[serde(default)]
+    pub schedule: Vec<Job>,
+    #[serde(default)]
+    pub clock: Option<Clock>,
+    #[serde(default)]
+    pub scoring: Option<Scoring>,
+    #[serde(default)]
+    pub quests: BTreeMap<String, Quest>,
+}
+
+impl WorldFile {
+    /// Build the world described by the file.  The world is
+    /// validated, and any problems are reported as errors, unless it
+    /// is loaded with `World::load_unchecked`.
+    pub fn build(self) -> Result<World, String> {
+        let file = self;
+        let mut map = BTreeMap::new();
+        for (i, e) in file.entities.iter().enumerate() {
+            if map.insert(e.id, i).is_some() {
+                return Err(format!("duplicate entity: {}", e.id));
+            }
+        }
+        let mut world = World {
+            name: file.name,
+            entities: file.entities,
+            kinds: file.kinds,
+            regions: file.regions,
+            quests: file.quests,
+            relations: Relations::standard(),
+            respawns: vec![],
+            start_location: file.start_location,
+            player: file.player.unwrap_or_default(),
+            entity_map: map,
+            alias_map: BTreeMap::new(),
+            rng: Rng::new(seed_from(&new_id())),
+            turn: 0,
+            schedule: file.schedule,
+            clock: file.clock.unwrap_or_default(),
+            scoring: file.scoring.unwrap_or_default(),
+            autosave: None,
+            locale: file.locale,
+            messages: file.messages,
+            events: Default::default(),
+            extensions: Default::default(),
+            monitor: Default::default(),
+            changed: BTreeSet::new(),
+            shared: false,
+            witnessed: vec![],
+        };
+        world.reindex();
+        try!(world.resolve_kinds());
+        for (r, a, b) in file.relations {
+            world.relations.relate(&r, a, b);
+        }
+        for (id, delay, message) in file.respawns {
+            world.add_respawn(id, delay, message);
+        }
+        let report = world.validate();
+        if !report.is_ok() && !UNCHECKED.with(|u| u.get()) {
+            return Err(format!("invalid world: {}", report));
+        }
+        Ok(world)
+    }
+}
+
+/// Replace references of the form `@alias` outside of strings by the
+/// UUIDs `id` returns for the aliases.
+pub fn substitute_aliases<F>(txt: &str, mut id: F) -> Result<String, String>
+    where F: FnMut(&str) -> Result<InternalName, String>
+{
+    let mut res = String::new();
+    let mut chars = txt.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if c == '@' && !in_string {
+            let mut alias = String::new();
+            while let Some(&a) = chars.peek().filter(|a| a.is_alphanumeric() || **a == '_' || **a == '.') {
+                alias.push(a);
+                chars.next();
+            }
+            if alias.len() == 0 {
+                return Err("alias expected after @".to_string());
+            }
+            res.push_str(&format!("\"{}\"", try!(id(&alias))));
+            continue;
+        }
+        res.push(c);
+        if c == '"' {
+            in_string = !in_string;
+        } else if c == '\\' && in_string {
+            if let Some(d) = chars.next() {
+                res.push(d);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Return the UUID standing for an alias in entities without an id.
+pub fn alias_id(alias: &str) -> InternalName {
+    Uuid::new_v5(&uuid::NAMESPACE_OID, &format!("mudstuck/{}", alias))
+}
+
+/// Return a new UUID, for a new entity.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn new_id() -> InternalName {
+    Uuid::new_v4()
+}
+
+/// Return a new UUID, for a new entity.  There are no random numbers
+/// of the system on the web, so new entities are counted instead.
+#[cfg(target_arch = "wasm32")]
+pub fn new_id() -> InternalName {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::SeqCst);
+    Uuid::new_v5(&uuid::NAMESPACE_OID, &format!("mudstuck/new/{}", n))
+}
+
+fn parse(txt: &str) -> Result<WorldFile, String> {
+    let file: WorldFile = try!(ron::de::from_str(txt).map_err(|e| format!("invalid world file: {}", e)));
+    if file.version > WORLD_VERSION {
+        return Err(format!("world file version {} is newer than the supported version {}",
+                           file.version, WORLD_VERSION));
+    }
+    Ok(file)
+}
+
+/// A world file which has been read, but not yet loaded.
+struct Source {
+    /// Directory the file's includes are relative to.
+    dir: PathBuf,
+    txt: String,
+    namespace: Option<String>,
+    includes: Vec<String>,
+    /// Aliases declared in the file, with the ids of their entities if
+    /// those are given.
+    aliases: Vec<(String, Option<InternalName>)>,
+}
+
+/// Return the full alias of an alias declared in a namespace.
+fn qualify(namespace: &Option<String>, alias: &str) -> String {
+    match *namespace {
+        Some(ref ns) => format!("{}.{}", ns, alias),
+        None => alias.to_string(),
+    }
+}
+
+/// Return the UUID an alias used in a namespace stands for.
+fn resolve(ids: &BTreeMap<String, InternalName>, namespace: &Option<String>, alias: &str) -> Result<InternalName, String> {
+    if !alias.contains('.') {
+        if let Some(id) = ids.get(&qualify(namespace, alias)) {
+            return Ok(*id);
+        }
+    }
+    ids.get(alias).cloned().ok_or(format!("unknown alias: {}", alias))
+}
+
+impl Source {
+    /// Read the declarations of a world file, without resolving its
+    /// aliases.
+    fn new(txt: String, dir: PathBuf) -> Result<Source, String> {
+        let header: Header = try!(substitute_aliases(&txt, |_| Ok(Uuid::nil()))
+                                  .and_then(|t| ron::de::from_str(&t).map_err(|e| format!("invalid world file: {}", e))));
+        let txt = try!(migrate(&txt, header.version, WORLD_VERSION, MIGRATIONS));
+        let file = try!(parse(&try!(substitute_aliases(&txt, |_| Ok(Uuid::nil())))));
+        let aliases = file.entities.iter()
+            .filter_map(|e| e.alias.as_ref().map(|a| (qualify(&file.namespace, a), Some(e.id).filter(|i| !i.is_nil()))))
+            .collect();
+        Ok(Source {
+            dir: dir,
+            txt: txt,
+            namespace: file.namespace,
+            includes: file.includes,
+            aliases: aliases,
+        })
+    }
+}
+
+/// Read a world file from disk.  Returns the canonical path of the
+/// file, which is the same however the file is referred to, and its
+/// text.
+fn read_from_disk(path: &Path) -> Result<(PathBuf, String), String> {
+    let canonical = try!(path.canonicalize().map_err(|e| format!("cannot read {}: {}", path.display(), e)));
+    Ok((canonical, try!(read_file(path))))
+}
+
+/// Add a world file and the files it includes to `sources`, reading
+/// them with `read`, which returns a file's canonical name and text.
+/// Files which have already been read are skipped.
+fn read_sources(source: Source, seen: &mut Vec<PathBuf>, sources: &mut Vec<Source>,
+                read: &Fn(&Path) -> Result<(PathBuf, String), String>) -> Result<(), String> {
+    let includes: Vec<PathBuf> = source.includes.iter().map(|i| source.dir.join(i)).collect();
+    sources.push(source);
+    for path in includes {
+        let (canonical, txt) = try!(read(&path));
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+        let dir = path.parent().map(|d| d.to_path_buf()).unwrap_or(PathBuf::new());
+        let source = try!(Source::new(txt, dir).map_err(|e| format!("{}: {}", path.display(), e)));
+        try!(read_sources(source, seen, sources, read));
+    }
+    Ok(())
+}
+
+/// Return the canonical paths and texts of a world file and of all
+/// the files it includes.
+pub fn read_world_files(path: &Path) -> Result<Vec<(PathBuf, String)>, String> {
+    let (canonical, txt) = try!(read_from_disk(path));
+    let dir = path.parent().map(|d| d.to_path_buf()).unwrap_or(PathBuf::new());
+    let root = try!(Source::new(txt.clone(), dir).map_err(|e| format!("{}: {}", path.display(), e)));
+    let files = RefCell::new(vec![(canonical.clone(), txt)]);
+    let read = |p: &Path| read_from_disk(p).map(|(c, t)| {
+        files.borrow_mut().push((c.clone(), t.clone()));
+        (c, t)
+    });
+    try!(read_sources(root, &mut vec![canonical], &mut vec![], &read));
+    Ok(files.into_inner())
+}
+
+impl WorldFile {
+    /// Add the contents of another file to this one.
+    fn merge(mut self, other: WorldFile) -> Result<WorldFile, String> {
+        for (name, kind) in other.kinds {
+            if self.kinds.insert(name.clone(), kind).is_some() {
+                return Err(format!("kind declared twice: {}", name));
+            }
+        }
+        for (name, region) in other.regions {
+            if self.regions.insert(name.clone(), region).is_some() {
+                return Err(format!("region declared twice: {}", name));
+            }
+        }
+        for (name, quest) in other.quests {
+            if self.quests.insert(name.clone(), quest).is_some() {
+                return Err(format!("quest declared twice: {}", name));
+            }
+        }
+        for (locale, messages) in other.messages {
+            self.messages.entry(locale).or_insert(BTreeMap::new()).extend(messages);
+        }
+        self.entities.extend(other.entities);
+        self.relations.extend(other.relations);
+        self.respawns.extend(other.respawns);
+        self.schedule.extend(other.schedule);
+        if other.clock.is_some() {
+            if self.clock.is_some() {
+                return Err("clock declared twice".to_string());
+            }
+            self.clock = other.clock;
+        }
+        if other.scoring.is_some() {
+            if self.scoring.is_some() {
+                return Err("scoring declared twice".to_string());
+            }
+            self.scoring = other.scoring;
+        }
+        Ok(self)
+    }
+}
+
+/// Load a world from a world file and the files it includes, which
+/// are read with `read`.  `seen` are the files which are not to be
+/// read again.
+fn load_sources(root: Source, mut seen: Vec<PathBuf>,
+                read: &Fn(&Path) -> Result<(PathBuf, String), String>) -> Result<World, String> {
+    let mut sources = vec![];
+    try!(read_sources(root, &mut seen, &mut sources, read));
+
+    // Aliases stand for the UUIDs derived from them, unless their
+    // entities have an id.
+    let mut ids = BTreeMap::new();
+    for s in sources.iter() {
+        for &(ref a, id) in s.aliases.iter() {
+            if ids.insert(a.clone(), id.unwrap_or_else(|| alias_id(a))).is_some() {
+                return Err(format!("alias declared twice: {}", a));
+            }
+        }
+    }
+    let mut merged: Option<WorldFile> = None;
+    // Entities without alias or id are numbered, so they get the same
+    // UUIDs every time the world is loaded.
+    let mut anonymous = 0;
+    for s in sources {
+        let mut file = try!(parse(&try!(substitute_aliases(&s.txt, |a| resolve(&ids, &s.namespace, a)))));
+        for e in file.entities.iter_mut() {
+            match e.alias.take() {
+                Some(a) => {
+                    let a = qualify(&s.namespace, &a);
+                    e.id = ids[&a];
+                    e.alias = Some(a);
+                },
+                None if e.id.is_nil() => {
+                    anonymous += 1;
+                    e.id = alias_id(&format!("#{}", anonymous));
+                },
+                None => {},
+            }
+        }
+        merged = Some(match merged {
+            Some(m) => try!(m.merge(file)),
+            None => file,
+        });
+    }
+    merged.unwrap().build()
+}
+
+/// Load a world from the text of a world file whose includes are read
+/// with `read`, which returns the canonical name and the text of a
+/// file.  The file itself is called `name`.
+pub fn load_from_files(name: &Path, txt: &str, read: &Fn(&Path) -> Result<(PathBuf, String), String>) -> Result<World, String> {
+    let dir = name.parent().map(|d| d.to_path_buf()).unwrap_or(PathBuf::new());
+    let root = try!(Source::new(txt.to_string(), dir).map_err(|e| format!("{}: {}", name.display(), e)));
+    load_sources(root, vec![name.to_path_buf()], read)
+}
+
+impl World {
+    /// Load a world from a string in the world file format.  Included
+    /// files are relative to the current directory.  The world is
+    /// validated, and any problems are reported as errors.
+    pub fn load_from_str(txt: &str) -> Result<World, String> {
+        load_sources(try!(Source::new(txt.to_string(), PathBuf::new())), vec![], &read_from_disk)
+    }
+
+    /// Load a world from the file at `path`.  Files ending in `.mud`
+    /// are written in the authoring language (see `dsl`), files
+    /// ending in `.trizbort` are Trizbort maps (see `trizbort`), files
+    /// ending in `.bundle` are bundles (see `bundle`), all others are
+    /// in the world file format.  Compressed files are
+    /// read if compression is supported (see `compress`).
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<World, String> {
+        let path = path.as_ref();
+        let format = uncompressed_name(path);
+        if format.extension().map_or(false, |e| e == "bundle") {
+            return World::load_bundle(path);
+        }
+        let txt = try!(read_file(path));
+        if format.extension().map_or(false, |e| e == "mud") {
+            return World::compile(&txt);
+        }
+        if format.extension().map_or(false, |e| e == "trizbort") {
+            return World::import_trizbort(&txt);
+        }
+        let dir = path.parent().map(|d| d.to_path_buf()).unwrap_or(PathBuf::new());
+        let root = try!(Source::new(txt, dir).map_err(|e| format!("{}: {}", path.display(), e)));
+        load_sources(root, path.canonicalize().into_iter().collect(), &read_from_disk)
+    }
+
+    /// Load a world like `load_from_path`, but without validating it,
+    /// so that its problems can be reported one by one (see `lint`).
+    pub fn load_unchecked<P: AsRef<Path>>(path: P) -> Result<World, String> {
+        UNCHECKED.with(|u| u.set(true));
+        let res = World::load_from_path(path);
+        UNCHECKED.with(|u| u.set(false));
+        res
+    }
+}
+
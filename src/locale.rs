@@ -0,0 +1,60 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Descriptions in several languages.  Besides the descriptions as
+//! written, entities may have translations for other locales, like
+//! "en".  The world's locale, which can be given in the world file and
+//! changed for a game, selects which of them are shown.  Descriptions
+//! which have not been translated are shown as written.
+
+use std::collections::BTreeSet;
+
+use super::types::*;
+
+/// Return `text` unless it is empty.
+fn translated(text: &String) -> Option<&str> {
+    if text.len() > 0 { Some(text) } else { None }
+}
+
+impl Entity {
+    /// Return the translation of this entity's descriptions for the
+    /// locale, if there is one.
+    fn translation(&self, locale: Option<&str>) -> Option<&Translation> {
+        locale.and_then(|l| self.translations.get(l))
+    }
+
+    /// Return the short description in the given locale.
+    pub fn short_text(&self, locale: Option<&str>) -> &str {
+        self.translation(locale).and_then(|t| translated(&t.short)).unwrap_or(&self.short_description)
+    }
+
+    /// Return the long description in the given locale.
+    pub fn long_text(&self, locale: Option<&str>) -> &str {
+        self.translation(locale).and_then(|t| translated(&t.long)).unwrap_or(&self.long_description)
+    }
+
+    /// Return what can be heard of this entity, in the given locale.
+    pub fn sound_text(&self, locale: Option<&str>) -> Option<&str> {
+        self.sound_description.as_ref()
+            .map(|s| self.translation(locale).and_then(|t| translated(&t.sound)).unwrap_or(s))
+    }
+
+    /// Return what can be smelled of this entity, in the given
+    /// locale.
+    pub fn smell_text(&self, locale: Option<&str>) -> Option<&str> {
+        self.smell_description.as_ref()
+            .map(|s| self.translation(locale).and_then(|t| translated(&t.smell)).unwrap_or(s))
+    }
+}
+
+impl World {
+    /// Return the locale descriptions are shown in, if any.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_ref().map(|l| l.as_str())
+    }
+
+    /// Return all locales entities have translations for.
+    pub fn locales(&self) -> BTreeSet<String> {
+        self.entities.iter().flat_map(|e| e.translations.keys().cloned()).collect()
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! State machines.  Commands whose direct object has a state machine
+//! with a matching transition from its current state cause that
+//! transition, instead of the verb's usual action.
+
+use super::types::*;
+use super::command::Command;
+use super::print_wrap;
+
+impl<'a> PlayerState<'a> {
+    /// Carry out the transition triggered by the command, if its
+    /// direct object has one from its current state.  Returns true if
+    /// there was such a transition.
+    pub fn transition(&mut self, cmd: &Command) -> bool {
+        let name = match cmd.direct_object {
+            Some(ref w) =>
+                match self.world.find_entity(w, &self.scope()) {
+                    Some(n) => n,
+                    None => return false,
+                },
+            None => return false,
+        };
+        if !self.can_reach(&name) {
+            return false;
+        }
+        let trig = cmd.verb.name();
+        let effect = match self.world.entity_mut(&name).and_then(|e| e.machine_mut()) {
+            Some(m) => {
+                let t = match m.transitions.iter().find(|t| t.from == m.state && t.trigger == trig) {
+                    Some(t) => t.clone(),
+                    None => return false,
+                };
+                m.state = t.to;
+                t.effect
+            },
+            None => return false,
+        };
+        if let Some(e) = effect {
+            match self.eval_str(&e) {
+                Ok(s) =>
+                    print_wrap(&s, 72),
+                Err(e) =>
+                    say!("{}", msg!(self.world, "error", error = e))
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Map coordinates.  Rooms can have coordinates, which are checked
+//! against the doors connecting them and used for drawing maps.
+
+use std::collections::BTreeMap;
+
+use super::types::*;
+
+impl World {
+    /// Return the coordinates of a room, if it has any.
+    pub fn position_of(&self, room: &InternalName) -> Option<(i32, i32, i32)> {
+        self.entity(room).and_then(|e| e.room()).and_then(|r| r.position)
+    }
+
+    /// Check the coordinates of all rooms for impossible geometry:
+    /// rooms sharing a position, and doors connecting rooms which are
+    /// not next to each other (teleports excepted).  Returns a description of each
+    /// problem found.
+    pub fn check_geometry(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut seen: BTreeMap<(i32, i32, i32), InternalName> = BTreeMap::new();
+        for e in self.entities.iter() {
+            if let Some(p) = e.room().and_then(|r| r.position) {
+                if let Some(other) = seen.get(&p) {
+                    problems.push(format!("rooms {} and {} are both at {:?}", other, e.id, p));
+                    continue;
+                }
+                seen.insert(p, e.id);
+            }
+        }
+        for e in self.entities.iter() {
+            if let Some(c) = e.connection().filter(|c| !c.teleport) {
+                let (a, b) = c.endpoints;
+                if let (Some(p), Some(q)) = (self.position_of(&a), self.position_of(&b)) {
+                    let dist = (p.0 - q.0).abs() + (p.1 - q.1).abs() + (p.2 - q.2).abs();
+                    if dist != 1 {
+                        problems.push(format!("door {} connects rooms at {:?} and {:?}, which are not next to each other", e.id, p, q));
+                    }
+                }
+            }
+        }
+        problems
+    }
+
+    /// Return true if there is a door between the rooms at the two
+    /// positions, in either direction.  Teleports are not drawn.
+    fn connected(&self, rooms: &BTreeMap<(i32, i32, i32), InternalName>, p: (i32, i32, i32), q: (i32, i32, i32)) -> bool {
+        let leads = |a: &InternalName, b: &InternalName| self.doors_at(a).iter().any(|d| {
+            self.entity(d).and_then(|e| e.connection()).map_or(false, |c| !c.teleport) && self.other_side(d, a) == Some(*b)
+        });
+        match (rooms.get(&p), rooms.get(&q)) {
+            (Some(a), Some(b)) =>
+                leads(a, b) || leads(b, a),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Draw a map of the rooms on the player's level, with the
+    /// player's room marked.
+    pub fn show_map(&self) {
+        let here = match self.world.position_of(&self.location()) {
+            Some(p) => p,
+            None => {
+                say!("{}", msg!(self.world, "lost"));
+                return;
+            }
+        };
+        let rooms: BTreeMap<(i32, i32, i32), InternalName> = self.world.entities.iter()
+            .filter_map(|e| e.room().and_then(|r| r.position).map(|p| (p, e.id)))
+            .filter(|&(p, _)| p.2 == here.2)
+            .collect();
+        let (min_x, max_x) = (rooms.keys().map(|p| p.0).min().unwrap(), rooms.keys().map(|p| p.0).max().unwrap());
+        let (min_y, max_y) = (rooms.keys().map(|p| p.1).min().unwrap(), rooms.keys().map(|p| p.1).max().unwrap());
+        for y in (min_y..max_y + 1).rev() {
+            let mut line = String::new();
+            let mut below = String::new();
+            for x in min_x..max_x + 1 {
+                let p = (x, y, here.2);
+                line.push_str(match rooms.get(&p) {
+                    Some(_) if p == here => "[*]",
+                    Some(_) => "[ ]",
+                    None => "   ",
+                });
+                line.push(if self.world.connected(&rooms, p, (x + 1, y, here.2)) { '-' } else { ' ' });
+                below.push_str(if self.world.connected(&rooms, p, (x, y - 1, here.2)) { " | " } else { "   " });
+                below.push(' ');
+            }
+            say!("{}", line.trim_end());
+            if y > min_y {
+                say!("{}", below.trim_end());
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Messages of the game, like "Genommen." or "Das ist verriegelt.",
+//! kept in a catalog instead of the code, so they can be shown in the
+//! world's locale.  Messages are looked up by key, and may have
+//! parameters, written `{name}`, which are filled in when they are
+//! shown.  The game comes with German and English messages; German
+//! ones are shown when the world has no locale.
+//!
+//! World files can replace messages or add other languages in their
+//! `messages` field, by locale and key:
+//!
+//! ```text
+//! messages: {"en": {"taken": "Got it."}},
+//! ```
+//!
+//! Messages missing in a locale are shown in German, and messages
+//! missing altogether as their key.  Commands outside the game, like
+//! `save`, answer in English.
+
+use super::types::*;
+
+/// The locale of messages when the world has none, and of messages
+/// which are not available in the world's locale.
+pub const DEFAULT_LOCALE: &'static str = "de";
+
+const GERMAN: &'static [(&'static str, &'static str)] = &[
+    ("not_here", "So etwas siehst du hier nicht."),
+    ("not_carried", "So etwas trägst du nicht bei dir."),
+    ("nobody_here", "So jemanden siehst du hier nicht."),
+    ("nothing_for_sale", "Hier wird nichts verkauft."),
+    ("impossible", "Das geht nicht."),
+    ("for_sale", "Zu kaufen gibt es:"),
+    ("turn_to_what", "Worauf möchtest du es drehen?"),
+    ("enter_on_what", "Worauf möchtest du das eingeben?"),
+    ("tie_to_what", "Woran möchtest du es festbinden?"),
+    ("ask_about_what", "Wonach möchtest du fragen?"),
+    ("look_where", "Wohin möchtest du schauen?"),
+    ("go_where", "Wohin möchtest du gehen?"),
+    ("ask_whom", "Wen möchtest du fragen?"),
+    ("attack_whom", "Wen möchtest du angreifen?"),
+    ("give_to_whom", "Wem denn?"),
+    ("open_what", "Was möchtest du öffnen?"),
+    ("lock_what", "Was möchtest du verriegeln?"),
+    ("sell_what", "Was möchtest du verkaufen?"),
+    ("drink_what", "Was möchtest du trinken?"),
+    ("close_what", "Was möchtest du schließen?"),
+    ("take_what", "Was möchtest du nehmen?"),
+    ("untie_what", "Was möchtest du losbinden?"),
+    ("read_what", "Was möchtest du lesen?"),
+    ("buy_what", "Was möchtest du kaufen?"),
+    ("put_what", "Was möchtest du hineinlegen?"),
+    ("tie_what", "Was möchtest du festbinden?"),
+    ("eat_what", "Was möchtest du essen?"),
+    ("unlock_what", "Was möchtest du entriegeln?"),
+    ("switch_on_what", "Was möchtest du einschalten?"),
+    ("enter_what", "Was möchtest du eingeben?"),
+    ("turn_what", "Was möchtest du drehen?"),
+    ("board_what", "Was möchtest du betreten?"),
+    ("remove_what", "Was möchtest du ausziehen?"),
+    ("switch_off_what", "Was möchtest du ausschalten?"),
+    ("light_what", "Was möchtest du anzünden?"),
+    ("wear_what", "Was möchtest du anziehen?"),
+    ("drop_what", "Was möchtest du ablegen?"),
+    ("locked_done", "Verriegelt."),
+    ("not_that_many", "So viele gibt es nicht."),
+    ("no_money", "So viel Geld ist nicht vorhanden."),
+    ("not_sold_here", "So etwas wird hier nicht verkauft."),
+    ("not_worn", "So etwas hast du nicht angezogen."),
+    ("no_such_exit", "So einen Ausgang gibt es hier nicht."),
+    ("nothing_happens", "Nichts geschieht."),
+    ("untied", "Losgebunden."),
+    ("click", "Klick."),
+    ("click_nothing", "Klick. Nichts geschieht."),
+    ("click_open", "Klick. Das Schloss springt auf."),
+    ("no_way", "In diese Richtung führt kein Weg."),
+    ("put_done", "Hineingelegt."),
+    ("nobody_buys", "Hier kauft dir niemand etwas ab."),
+    ("opened", "Geöffnet."),
+    ("closed_done", "Geschlossen."),
+    ("taken", "Genommen."),
+    ("tied", "Festgebunden."),
+    ("something_falls", "Etwas fällt zu Boden."),
+    ("pitch_dark", "Es ist stockdunkel. Du kannst nichts sehen."),
+    ("sold_out", "Es gibt nichts mehr zu kaufen."),
+    ("no_exits", "Es gibt keinen sichtbaren Ausgang."),
+    ("lost", "Du weißt nicht, wo du bist."),
+    ("carrying", "Du trägst:"),
+    ("carrying_nothing", "Du trägst nichts bei dir."),
+    ("out_of_reach", "Du siehst es, aber du kommst nicht heran."),
+    ("smell_nothing", "Du riechst nichts Besonderes."),
+    ("leave_first", "Du musst zuerst aussteigen."),
+    ("hear_nothing", "Du hörst nichts Besonderes."),
+    ("no_fitting_key", "Du hast keinen passenden Schlüssel."),
+    ("eaten", "Du hast es dir schmecken lassen."),
+    ("wearing", "Du hast angezogen:"),
+    ("find_nothing", "Du findest nichts."),
+    ("poisoned", "Du bist vergiftet."),
+    ("not_aboard", "Du bist nirgendwo eingestiegen."),
+    ("died", "Du bist gestorben."),
+    ("exhausted", "Du bist erschöpft."),
+    ("nothing_there", "Dort findest du nichts."),
+    ("nauseous", "Dir ist übel."),
+    ("leads_nowhere", "Dieser Weg führt nirgendwo hin."),
+    ("wrong_key", "Der Schlüssel passt nicht."),
+    ("no_effect", "Dein Schlag zeigt keine Wirkung."),
+    ("nothing_left", "Davon ist nichts mehr übrig."),
+    ("pointless_give", "Das würde nichts bringen."),
+    ("pointless_attack", "Das wäre sinnlos."),
+    ("nobody_wants", "Das will niemand haben."),
+    ("does_not_fit", "Das passt nicht hinein."),
+    ("remove_first", "Das musst du zuerst ausziehen."),
+    ("not_tieable", "Das lässt sich nirgends festbinden."),
+    ("not_closable", "Das lässt sich nicht öffnen und schließen."),
+    ("not_switchable", "Das lässt sich nicht schalten."),
+    ("not_takeable", "Das lässt sich nicht mitnehmen."),
+    ("not_burnable", "Das lässt sich nicht anzünden."),
+    ("not_drinkable", "Das kann man nicht trinken."),
+    ("not_edible", "Das kann man nicht essen."),
+    ("not_wearable", "Das kann man nicht anziehen."),
+    ("locked", "Das ist verriegelt."),
+    ("not_locked", "Das ist nicht verriegelt."),
+    ("already_dead", "Das ist nicht mehr nötig."),
+    ("not_tied", "Das ist nicht festgebunden."),
+    ("no_weapon", "Das ist keine Waffe."),
+    ("closed", "Das ist geschlossen."),
+    ("tied_up", "Das ist festgebunden."),
+    ("already_tied", "Das ist dort bereits festgebunden."),
+    ("already_locked", "Das ist bereits verriegelt."),
+    ("already_open", "Das ist bereits offen."),
+    ("already_closed", "Das ist bereits geschlossen."),
+    ("already_on", "Das ist bereits eingeschaltet."),
+    ("already_off", "Das ist bereits ausgeschaltet."),
+    ("burnt_out", "Das ist bereits abgebrannt."),
+    ("no_lock", "Das hat kein Schloss."),
+    ("already_worn", "Das hast du bereits angezogen."),
+    ("burning", "Das brennt bereits."),
+    ("contents", "Darin liegt:"),
+    ("nothing_written", "Darauf steht nichts."),
+    ("no_input", "Darauf lässt sich nichts eingeben."),
+    ("no_answer", "Darauf bekommst du keine Antwort."),
+    ("needs_code", "Dafür brauchst du den richtigen Code."),
+    ("not_enterable", "Da kannst du nicht einsteigen."),
+    ("not_container", "Da kann man nichts hineinlegen."),
+    ("exits", "Ausgänge:"),
+    ("removed", "Ausgezogen."),
+    ("lit", "Angezündet."),
+    ("worn", "Angezogen."),
+    ("dropped", "Abgelegt."),
+    ("health", "Gesundheit: {health}/{max}"),
+    ("strength", "Stärke: {strength}"),
+    ("purse", "Geld: {coins} Münzen"),
+    ("coins_taken", "Du steckst {coins} Münzen ein."),
+    ("locked_with", "Verriegelt mit: {key}."),
+    ("unlocked_with", "Entriegelt mit: {key}."),
+    ("slot_taken", "Du trägst bereits etwas {slot}."),
+    ("slot_head", "auf dem Kopf"),
+    ("slot_hands", "an den Händen"),
+    ("slot_torso", "am Körper"),
+    ("miss", "Du verfehlst: {target}."),
+    ("hit", "Du triffst: {target}."),
+    ("collapses", "{target} bricht zusammen."),
+    ("attacked_miss", "{attacker} greift dich an, verfehlt dich aber."),
+    ("attacked_glance", "{attacker} greift dich an, aber der Schlag prallt ab."),
+    ("attacked_hit", "{attacker} greift dich an und verletzt dich."),
+    ("fights_miss", "{attacker} greift {target} an, verfehlt aber."),
+    ("fights_glance", "{attacker} greift {target} an, aber der Schlag prallt ab."),
+    ("fights_hit", "{attacker} greift {target} an und trifft."),
+    ("give_what", "Was möchtest du hergeben?"),
+    ("show_what", "Was möchtest du zeigen?"),
+    ("returned", "„Das gehört mir. Danke, dass du es zurückbringst.“"),
+    ("refused", "{who} will das nicht haben."),
+    ("not_interested", "{who} zeigt kein Interesse."),
+    ("quantity", "{desc} ({quantity} Stück)"),
+    ("no_such_entity", "Es gibt nichts, was {name} heißt."),
+    ("door_closed", "{door} ist geschlossen."),
+    ("cannot_take_vehicle", "Dorthin kommst du nicht mit: {vehicle}."),
+    ("boarded", "Du steigst ein: {vehicle}."),
+    ("left_vehicle", "Du steigst aus: {vehicle}."),
+    ("exit_to", "  {door} (nach: {room})"),
+    ("too_expensive", "Das kostet {price} Münzen, du hast aber nur {coins}."),
+    ("price", "  {item} ({price} Münzen)"),
+    ("bought", "Gekauft für {price} Münzen."),
+    ("sold", "Verkauft für {price} Münzen."),
+    ("autosave_failed", "Das Spiel konnte nicht automatisch gespeichert werden: {error}."),
+    ("leaves", "{who} geht hinaus."),
+    ("arrives", "{who} kommt herein."),
+    ("quest_started", "Neue Aufgabe: {title}"),
+    ("quest_done", "Aufgabe erfüllt: {title}"),
+    ("journal_empty", "Du hast noch keine Aufgaben."),
+    ("journal_active", "  {title}: {goal}"),
+    ("journal_done", "  {title} (erfüllt)"),
+    ("score_up", "[Deine Punktzahl ist um {points} gestiegen.]"),
+    ("score", "Du hast {score} von {max} möglichen Punkten in {turns} Zügen erreicht."),
+    ("rank", "Dein Rang: {rank}."),
+    ("achievements", "Deine Errungenschaften:"),
+    ("no_score", "Hier gibt es keine Punkte."),
+    ("players_here", "Außerdem hier: {players}."),
+    ("say_self", "Du sagst: „{text}“"),
+    ("says", "{name} sagt: „{text}“"),
+    ("owner_shouts", "{owner} ruft: „He, das gehört mir!“"),
+    ("and", "{list} und {last}"),
+    ("error", "Ein Fehler ist aufgetreten: {error}"),
+    ("unknown_command", "Das verstehe ich nicht."),
+    ("state_closed", "geschlossen"),
+    ("state_open", "offen"),
+    ("state_locked", "verriegelt"),
+    ("state_on", "eingeschaltet"),
+    ("state_off", "ausgeschaltet"),
+    ("state_lit", "angezündet"),
+    ("states", "Das ist {states}."),
+    ("desc_wearable", "Das kann man anziehen."),
+    ("desc_edible", "Das ist essbar."),
+    ("desc_drinkable", "Das kann man trinken."),
+    ("desc_readable", "Darauf steht etwas geschrieben."),
+    ("desc_weapon", "Das taugt als Waffe."),
+    ("desc_enterable", "Man kann einsteigen."),
+    ("desc_container", "Man kann etwas hineinlegen."),
+    ("desc_attachable", "Das lässt sich festbinden."),
+    ("desc_code_entry", "Darauf lässt sich ein Code eingeben."),
+    ("desc_dead", "Regt sich nicht mehr."),
+    ("desc_badly_hurt", "Wirkt schwer verletzt."),
+    ("desc_hurt", "Wirkt verletzt."),
+    ("desc_healthy", "Wirkt gesund."),
+    ("desc_nothing", "Daran ist nichts Besonderes."),
+];
+
+const ENGLISH: &'static [(&'static str, &'static str)] = &[
+    ("not_here", "You see no such thing here."),
+    ("not_carried", "You are not carrying that."),
+    ("nobody_here", "You see no one like that here."),
+    ("nothing_for_sale", "Nothing is sold here."),
+    ("impossible", "That is not possible."),
+    ("for_sale", "For sale:"),
+    ("turn_to_what", "What do you want to turn it to?"),
+    ("enter_on_what", "What do you want to enter that on?"),
+    ("tie_to_what", "What do you want to tie it to?"),
+    ("ask_about_what", "What do you want to ask about?"),
+    ("look_where", "Where do you want to look?"),
+    ("go_where", "Where do you want to go?"),
+    ("ask_whom", "Whom do you want to ask?"),
+    ("attack_whom", "Whom do you want to attack?"),
+    ("give_to_whom", "To whom?"),
+    ("open_what", "What do you want to open?"),
+    ("lock_what", "What do you want to lock?"),
+    ("sell_what", "What do you want to sell?"),
+    ("drink_what", "What do you want to drink?"),
+    ("close_what", "What do you want to close?"),
+    ("take_what", "What do you want to take?"),
+    ("untie_what", "What do you want to untie?"),
+    ("read_what", "What do you want to read?"),
+    ("buy_what", "What do you want to buy?"),
+    ("put_what", "What do you want to put in?"),
+    ("tie_what", "What do you want to tie?"),
+    ("eat_what", "What do you want to eat?"),
+    ("unlock_what", "What do you want to unlock?"),
+    ("switch_on_what", "What do you want to switch on?"),
+    ("enter_what", "What do you want to enter?"),
+    ("turn_what", "What do you want to turn?"),
+    ("board_what", "What do you want to board?"),
+    ("remove_what", "What do you want to take off?"),
+    ("switch_off_what", "What do you want to switch off?"),
+    ("light_what", "What do you want to light?"),
+    ("wear_what", "What do you want to wear?"),
+    ("drop_what", "What do you want to drop?"),
+    ("locked_done", "Locked."),
+    ("not_that_many", "There are not that many."),
+    ("no_money", "There is not that much money."),
+    ("not_sold_here", "That is not sold here."),
+    ("not_worn", "You are not wearing that."),
+    ("no_such_exit", "There is no such exit here."),
+    ("nothing_happens", "Nothing happens."),
+    ("untied", "Untied."),
+    ("click", "Click."),
+    ("click_nothing", "Click. Nothing happens."),
+    ("click_open", "Click. The lock springs open."),
+    ("no_way", "There is no way in that direction."),
+    ("put_done", "Done."),
+    ("nobody_buys", "Nobody here buys anything from you."),
+    ("opened", "Opened."),
+    ("closed_done", "Closed."),
+    ("taken", "Taken."),
+    ("tied", "Tied."),
+    ("something_falls", "Something falls to the ground."),
+    ("pitch_dark", "It is pitch dark. You cannot see anything."),
+    ("sold_out", "There is nothing left to buy."),
+    ("no_exits", "There is no visible exit."),
+    ("lost", "You don't know where you are."),
+    ("carrying", "You are carrying:"),
+    ("carrying_nothing", "You are carrying nothing."),
+    ("out_of_reach", "You can see it, but you cannot reach it."),
+    ("smell_nothing", "You smell nothing special."),
+    ("leave_first", "You have to get out first."),
+    ("hear_nothing", "You hear nothing special."),
+    ("no_fitting_key", "You have no key that fits."),
+    ("eaten", "You enjoyed it."),
+    ("wearing", "You are wearing:"),
+    ("find_nothing", "You find nothing."),
+    ("poisoned", "You are poisoned."),
+    ("not_aboard", "You are not in anything."),
+    ("died", "You have died."),
+    ("exhausted", "You are exhausted."),
+    ("nothing_there", "You find nothing there."),
+    ("nauseous", "You feel sick."),
+    ("leads_nowhere", "This way leads nowhere."),
+    ("wrong_key", "The key does not fit."),
+    ("no_effect", "Your blow has no effect."),
+    ("nothing_left", "There is nothing left of it."),
+    ("pointless_give", "That would not help."),
+    ("pointless_attack", "That would be pointless."),
+    ("nobody_wants", "Nobody wants that."),
+    ("does_not_fit", "That does not fit."),
+    ("remove_first", "You have to take that off first."),
+    ("not_tieable", "That cannot be tied to anything."),
+    ("not_closable", "That cannot be opened or closed."),
+    ("not_switchable", "That cannot be switched."),
+    ("not_takeable", "You cannot take that."),
+    ("not_burnable", "That cannot be lit."),
+    ("not_drinkable", "You cannot drink that."),
+    ("not_edible", "You cannot eat that."),
+    ("not_wearable", "You cannot wear that."),
+    ("locked", "That is locked."),
+    ("not_locked", "That is not locked."),
+    ("already_dead", "That is no longer necessary."),
+    ("not_tied", "That is not tied."),
+    ("no_weapon", "That is no weapon."),
+    ("closed", "That is closed."),
+    ("tied_up", "That is tied up."),
+    ("already_tied", "That is already tied there."),
+    ("already_locked", "That is already locked."),
+    ("already_open", "That is already open."),
+    ("already_closed", "That is already closed."),
+    ("already_on", "That is already switched on."),
+    ("already_off", "That is already switched off."),
+    ("burnt_out", "That has burnt down already."),
+    ("no_lock", "That has no lock."),
+    ("already_worn", "You are already wearing that."),
+    ("burning", "That is burning already."),
+    ("contents", "Inside is:"),
+    ("nothing_written", "Nothing is written on it."),
+    ("no_input", "You cannot enter anything on that."),
+    ("no_answer", "You get no answer to that."),
+    ("needs_code", "You need the right code for that."),
+    ("not_enterable", "You cannot get into that."),
+    ("not_container", "You cannot put anything in there."),
+    ("exits", "Exits:"),
+    ("removed", "Taken off."),
+    ("lit", "Lit."),
+    ("worn", "Worn."),
+    ("dropped", "Dropped."),
+    ("health", "Health: {health}/{max}"),
+    ("strength", "Strength: {strength}"),
+    ("purse", "Money: {coins} coins"),
+    ("coins_taken", "You pocket {coins} coins."),
+    ("locked_with", "Locked with: {key}."),
+    ("unlocked_with", "Unlocked with: {key}."),
+    ("slot_taken", "You are already wearing something {slot}."),
+    ("slot_head", "on your head"),
+    ("slot_hands", "on your hands"),
+    ("slot_torso", "on your body"),
+    ("miss", "You miss: {target}."),
+    ("hit", "You hit: {target}."),
+    ("collapses", "{target} collapses."),
+    ("attacked_miss", "{attacker} attacks you, but misses."),
+    ("attacked_glance", "{attacker} attacks you, but the blow glances off."),
+    ("attacked_hit", "{attacker} attacks you and wounds you."),
+    ("fights_miss", "{attacker} attacks {target}, but misses."),
+    ("fights_glance", "{attacker} attacks {target}, but the blow glances off."),
+    ("fights_hit", "{attacker} attacks {target} and hits."),
+    ("give_what", "What do you want to give?"),
+    ("show_what", "What do you want to show?"),
+    ("returned", "“That is mine. Thank you for bringing it back.”"),
+    ("refused", "{who} does not want that."),
+    ("not_interested", "{who} shows no interest."),
+    ("quantity", "{desc} ({quantity} pieces)"),
+    ("no_such_entity", "There is nothing called {name}."),
+    ("door_closed", "{door} is closed."),
+    ("cannot_take_vehicle", "You cannot go there with it: {vehicle}."),
+    ("boarded", "You get in: {vehicle}."),
+    ("left_vehicle", "You get out: {vehicle}."),
+    ("exit_to", "  {door} (to: {room})"),
+    ("too_expensive", "That costs {price} coins, but you only have {coins}."),
+    ("price", "  {item} ({price} coins)"),
+    ("bought", "Bought for {price} coins."),
+    ("sold", "Sold for {price} coins."),
+    ("autosave_failed", "The game could not be saved automatically: {error}."),
+    ("leaves", "{who} leaves."),
+    ("arrives", "{who} comes in."),
+    ("quest_started", "New quest: {title}"),
+    ("quest_done", "Quest completed: {title}"),
+    ("journal_empty", "You have no quests yet."),
+    ("journal_active", "  {title}: {goal}"),
+    ("journal_done", "  {title} (completed)"),
+    ("score_up", "[Your score has gone up by {points} points.]"),
+    ("score", "You have scored {score} out of {max} points in {turns} turns."),
+    ("rank", "Your rank: {rank}."),
+    ("achievements", "Your achievements:"),
+    ("no_score", "There are no points to be scored here."),
+    ("players_here", "Also here: {players}."),
+    ("say_self", "You say: “{text}”"),
+    ("says", "{name} says: “{text}”"),
+    ("owner_shouts", "{owner} shouts: “Hey, that is mine!”"),
+    ("and", "{list} and {last}"),
+    ("error", "An error has occurred: {error}"),
+    ("unknown_command", "I don't know how to do that."),
+    ("state_closed", "closed"),
+    ("state_open", "open"),
+    ("state_locked", "locked"),
+    ("state_on", "switched on"),
+    ("state_off", "switched off"),
+    ("state_lit", "lit"),
+    ("states", "It is {states}."),
+    ("desc_wearable", "It can be worn."),
+    ("desc_edible", "It is edible."),
+    ("desc_drinkable", "It can be drunk."),
+    ("desc_readable", "Something is written on it."),
+    ("desc_weapon", "It would make a weapon."),
+    ("desc_enterable", "You can get into it."),
+    ("desc_container", "You can put things into it."),
+    ("desc_attachable", "It can be tied to things."),
+    ("desc_code_entry", "A code can be entered on it."),
+    ("desc_dead", "Does not move any more."),
+    ("desc_badly_hurt", "Looks badly hurt."),
+    ("desc_hurt", "Looks hurt."),
+    ("desc_healthy", "Looks healthy."),
+    ("desc_nothing", "There is nothing special about it."),
+];
+
+/// The messages coming with the game, by locale.
+const CATALOG: &'static [(&'static str, &'static [(&'static str, &'static str)])] =
+    &[
+        ("de", GERMAN),
+        ("en", ENGLISH),
+    ];
+
+/// Return the keys of all messages of the game.
+pub fn keys() -> Vec<&'static str> {
+    GERMAN.iter().map(|&(k, _)| k).collect()
+}
+
+/// Return the built-in message with the given key in a locale.
+pub fn builtin(locale: &str, key: &str) -> Option<&'static str> {
+    CATALOG.iter().find(|&&(l, _)| l == locale)
+        .and_then(|&(_, table)| table.iter().find(|&&(k, _)| k == key))
+        .map(|&(_, m)| m)
+}
+
+/// Fill in the parameters of a message.  Unknown parameters are left
+/// as they are.
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut res = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let arg = rest.find('}').and_then(|end| {
+            args.iter().find(|&&(n, _)| n == &rest[1..end]).map(|&(_, v)| (v, end))
+        });
+        match arg {
+            Some((v, end)) => {
+                res.push_str(v);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                res.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+impl World {
+    /// Return the message with the given key in the world's locale,
+    /// with the parameters filled in.  Usually called by way of the
+    /// `msg!` macro.
+    pub fn message(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let locale = self.locale().unwrap_or(DEFAULT_LOCALE);
+        for l in [locale, DEFAULT_LOCALE].iter() {
+            let own = self.messages.get(*l).and_then(|m| m.get(key)).map(|m| m.as_str());
+            if let Some(m) = own.or_else(|| builtin(l, key)) {
+                return fill(m, args);
+            }
+        }
+        fill(key, args)
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Metrics: how busy the engine is, for the operators of servers.
+//! The engine reports what it does to an implementation of `Metrics`
+//! given to `Engine::observe`, which passes it on to wherever the
+//! operator collects metrics, like Prometheus or a log:
+//!
+//! ```text
+//! struct Log;
+//!
+//! impl Metrics for Log {
+//!     fn command(&self, _: SessionId, took: Duration) {
+//!         eprintln!("command took {:?}", took);
+//!     }
+//! }
+//!
+//! engine.observe(Log);
+//! ```
+//!
+//! The engine reports
+//!
+//! * every line stepped in a session (see `step`), with the time it
+//!   took, from which commands per second follow,
+//! * every template evaluated, with the time it took,
+//! * the number of events waiting to be delivered, before each round
+//!   of delivering them (see `events`), and
+//! * the number of sessions, whenever one is opened or closed.
+//!
+//! `Counters` adds all of this up, for operators who only look at
+//! totals now and then.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use super::engine::Engine;
+use super::step::SessionId;
+
+/// Where the engine reports what it does.  All methods do nothing
+/// unless implemented.  They are called on the thread running the
+/// engine, in the middle of its work, so they should be quick.
+pub trait Metrics: Send + Sync {
+    /// A line was stepped in a session, taking `took`.
+    fn command(&self, _session: SessionId, _took: Duration) {}
+
+    /// A template was evaluated, taking `took`.
+    fn template(&self, _took: Duration) {}
+
+    /// `pending` events are about to be delivered.
+    fn events(&self, _pending: usize) {}
+
+    /// There are `sessions` sessions now.
+    fn players(&self, _sessions: usize) {}
+}
+
+/// The metrics a world reports to, if any.
+#[derive(Clone, Default)]
+pub struct Monitor(Option<Arc<Metrics>>);
+
+impl Monitor {
+    /// Return the time now if metrics are reported, for timing what
+    /// is done next.  Nothing is timed otherwise, so the clock is not
+    /// read where there is none, like in browsers.
+    pub fn start(&self) -> Option<Instant> {
+        self.0.as_ref().map(|_| Instant::now())
+    }
+
+    /// Report a line stepped in a session since `started`.
+    pub fn command(&self, session: SessionId, started: Option<Instant>) {
+        if let (Some(m), Some(s)) = (self.0.as_ref(), started) {
+            m.command(session, s.elapsed());
+        }
+    }
+
+    /// Report a template evaluated since `started`.
+    pub fn template(&self, started: Option<Instant>) {
+        if let (Some(m), Some(s)) = (self.0.as_ref(), started) {
+            m.template(s.elapsed());
+        }
+    }
+
+    /// Report the number of events waiting to be delivered.
+    pub fn events(&self, pending: usize) {
+        if let Some(ref m) = self.0 {
+            m.events(pending);
+        }
+    }
+
+    /// Report the number of sessions.
+    pub fn players(&self, sessions: usize) {
+        if let Some(ref m) = self.0 {
+            m.players(sessions);
+        }
+    }
+}
+
+/// Metrics added up since they were made.
+pub struct Counters {
+    started: Instant,
+    commands: AtomicUsize,
+    command_micros: AtomicUsize,
+    templates: AtomicUsize,
+    template_micros: AtomicUsize,
+    most_events: AtomicUsize,
+    players: AtomicUsize,
+}
+
+impl Counters {
+    /// Start counting.
+    pub fn new() -> Counters {
+        Counters {
+            started: Instant::now(),
+            commands: AtomicUsize::new(0),
+            command_micros: AtomicUsize::new(0),
+            templates: AtomicUsize::new(0),
+            template_micros: AtomicUsize::new(0),
+            most_events: AtomicUsize::new(0),
+            players: AtomicUsize::new(0),
+        }
+    }
+
+    /// Return the number of lines stepped.
+    pub fn commands(&self) -> usize {
+        self.commands.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of lines stepped per second, on average.
+    pub fn commands_per_second(&self) -> f64 {
+        let secs = self.started.elapsed().as_secs_f64();
+        if secs > 0.0 { self.commands() as f64 / secs } else { 0.0 }
+    }
+
+    /// Return the time all lines stepped took.
+    pub fn command_time(&self) -> Duration {
+        Duration::from_micros(self.command_micros.load(Ordering::Relaxed) as u64)
+    }
+
+    /// Return the number of templates evaluated.
+    pub fn templates(&self) -> usize {
+        self.templates.load(Ordering::Relaxed)
+    }
+
+    /// Return the time evaluating templates took.
+    pub fn template_time(&self) -> Duration {
+        Duration::from_micros(self.template_micros.load(Ordering::Relaxed) as u64)
+    }
+
+    /// Return the most events which were waiting to be delivered at
+    /// once.
+    pub fn most_events(&self) -> usize {
+        self.most_events.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of sessions last reported.
+    pub fn sessions(&self) -> usize {
+        self.players.load(Ordering::Relaxed)
+    }
+}
+
+impl Metrics for Counters {
+    fn command(&self, _session: SessionId, took: Duration) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+        self.command_micros.fetch_add(took.as_micros() as usize, Ordering::Relaxed);
+    }
+
+    fn template(&self, took: Duration) {
+        self.templates.fetch_add(1, Ordering::Relaxed);
+        self.template_micros.fetch_add(took.as_micros() as usize, Ordering::Relaxed);
+    }
+
+    fn events(&self, pending: usize) {
+        self.most_events.fetch_max(pending, Ordering::Relaxed);
+    }
+
+    fn players(&self, sessions: usize) {
+        self.players.store(sessions, Ordering::Relaxed);
+    }
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn command(&self, session: SessionId, took: Duration) {
+        (**self).command(session, took)
+    }
+
+    fn template(&self, took: Duration) {
+        (**self).template(took)
+    }
+
+    fn events(&self, pending: usize) {
+        (**self).events(pending)
+    }
+
+    fn players(&self, sessions: usize) {
+        (**self).players(sessions)
+    }
+}
+
+impl Engine {
+    /// Report what the engine does to `metrics` from now on, instead
+    /// of to the metrics given before, if any.  Give an `Arc` to keep
+    /// a handle on the metrics, like on `Counters`.
+    pub fn observe<M: Metrics + 'static>(&mut self, metrics: M) {
+        self.world.monitor = Monitor(Some(Arc::new(metrics)));
+        self.world.monitor.players(self.sessions.len());
+    }
+}
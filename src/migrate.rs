@@ -0,0 +1,42 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Migrating files written in older versions of a file format.
+//! Saved games and world files carry the version of their format.
+//! When a format changes, its version is increased and a migration is
+//! added which turns files of the previous version into files of the
+//! new one, so that old files keep working.  Files without a version
+//! are of version 0, from before formats were versioned.
+//!
+//! Migrations work on the text of files, before it is parsed, as old
+//! files may not parse as the current types.
+
+/// A change of a file format.
+pub struct Migration {
+    /// The version this migration starts from.  It produces files of
+    /// the next version.
+    pub from: u32,
+    pub migrate: fn(&str) -> Result<String, String>,
+}
+
+/// Migration for changes which old files don't need to be adapted to,
+/// like new optional fields.
+pub fn unchanged(txt: &str) -> Result<String, String> {
+    Ok(txt.to_string())
+}
+
+/// Bring a file of the given version up to the `current` version,
+/// applying `migrations` in order.  Fails for files of newer
+/// versions, which cannot be read.
+pub fn migrate(txt: &str, version: u32, current: u32, migrations: &[Migration]) -> Result<String, String> {
+    if version > current {
+        return Err(format!("file format version {} is newer than the supported version {}", version, current));
+    }
+    let mut txt = txt.to_string();
+    for v in version..current {
+        let m = try!(migrations.iter().find(|m| m.from == v)
+                     .ok_or(format!("no migration from file format version {}", v)));
+        txt = try!((m.migrate)(&txt).map_err(|e| format!("cannot migrate from file format version {}: {}", v, e)));
+    }
+    Ok(txt)
+}
@@ -0,0 +1,187 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Moving between rooms.  Rooms are connected by doors, which belong
+//! to both rooms they connect, so every door is an exit of both.
+//! Doors can be gone through by name, or by the direction they lead
+//! in from the player's room, if they have one.  Players in a vehicle
+//! take it along when they move.
+
+use super::types::*;
+use super::command::{Command, DIRECTIONS};
+use super::error::Error;
+use super::print_wrap;
+
+impl World {
+    /// Return the room on the other side of a door, seen from the
+    /// given room.
+    pub fn other_side(&self, door: &InternalName, room: &InternalName) -> Option<InternalName> {
+        match self.entity(door).and_then(|e| e.connection()) {
+            Some(c) if c.endpoints.0 == *room => Some(c.endpoints.1),
+            Some(c) if c.endpoints.1 == *room && !c.one_way => Some(c.endpoints.0),
+            _ => None,
+        }
+    }
+
+    /// Return the door leading out of the given room in a direction,
+    /// like "north", if there is one.
+    pub fn exit_towards(&self, room: &InternalName, direction: &str) -> Option<InternalName> {
+        self.doors_at(room).into_iter().find(|d| match self.entity(d).and_then(|e| e.connection()) {
+            Some(c) => c.directions.iter().any(|&(ref r, ref dir)| r == room && dir == direction),
+            None => false,
+        })
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Print the exits of the player's location and where they lead.
+    pub fn show_exits(&self) {
+        let doors = self.world.doors_at(&self.location());
+        if doors.len() == 0 {
+            say!("{}", msg!(self.world, "no_exits"));
+            return;
+        }
+        say!("{}", msg!(self.world, "exits"));
+        for d in doors.iter() {
+            match self.world.other_side(d, &self.location()) {
+                Some(r) =>
+                    say!("{}", msg!(self.world, "exit_to", door = self.world.short_description(d), room = self.world.short_description(&r))),
+                None =>
+                    say!("  {}", self.world.short_description(d)),
+            }
+        }
+    }
+
+    /// Check the barrier of a door, if any.  Prints the refusal and
+    /// returns true if the way is blocked.
+    fn blocked(&self, door: &InternalName) -> bool {
+        let barrier = match self.world.entity(door).and_then(|e| e.connection()).and_then(|c| c.barrier.clone()) {
+            Some(b) => b,
+            None => return false,
+        };
+        match self.eval_condition(&barrier.condition) {
+            Ok(false) => false,
+            Ok(true) => {
+                match self.eval_str(&barrier.refusal) {
+                    Ok(s) => print_wrap(&s, 72),
+                    Err(e) => say!("{}", msg!(self.world, "error", error = e)),
+                }
+                true
+            },
+            Err(e) => {
+                say!("{}", msg!(self.world, "error", error = e));
+                true
+            }
+        }
+    }
+
+    /// Go through the door given as direct object into the room on
+    /// the other side.  Closed doors must be opened first.
+    pub fn go(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "go_where"));
+                return Ok(());
+            }
+        };
+        let by_direction = match words.len() {
+            1 => self.world.exit_towards(&self.location(), &words[0]),
+            _ => None,
+        };
+        let door = match by_direction.or_else(|| self.world.find_entity(words, &self.world.doors_at(&self.location()))) {
+            Some(d) => d,
+            None => {
+                if words.len() == 1 && DIRECTIONS.iter().any(|&(d, _)| d == words[0]) {
+                    say!("{}", msg!(self.world, "no_way"));
+                } else {
+                    say!("{}", msg!(self.world, "no_such_exit"));
+                }
+                return Ok(());
+            }
+        };
+        if self.world.entity(&door).unwrap().closed() == Some(true) {
+            print_wrap(&msg!(self.world, "door_closed", door = self.world.short_description(&door)), 72);
+            return Ok(());
+        }
+        if self.blocked(&door) {
+            return Ok(());
+        }
+        let to = match self.world.other_side(&door, &self.location()) {
+            Some(r) => r,
+            None => {
+                say!("{}", msg!(self.world, "leads_nowhere"));
+                return Ok(());
+            }
+        };
+        if let Some(v) = self.me().vehicle {
+            let allowed = match self.world.entity(&v).and_then(|e| e.vehicle()) {
+                Some(vehicle) => vehicle.rooms.len() == 0 || vehicle.rooms.contains(&to),
+                None => true,
+            };
+            if !allowed {
+                print_wrap(&msg!(self.world, "cannot_take_vehicle", vehicle = self.world.short_description(&v)), 72);
+                return Ok(());
+            }
+            let from = self.location();
+            if let Some(r) = self.world.entity_mut(&from).and_then(|e| e.room_mut()) {
+                r.entities.retain(|e| *e != v);
+            }
+            if let Some(r) = self.world.entity_mut(&to).and_then(|e| e.room_mut()) {
+                r.entities.push(v);
+            }
+        }
+        let transition = self.world.entity(&door).and_then(|e| e.connection()).and_then(|c| c.transition.clone());
+        if let Some(text) = transition {
+            match self.eval_str(&text) {
+                Ok(s) => print_wrap(&s, 72),
+                Err(e) => say!("{}", msg!(self.world, "error", error = e)),
+            }
+        }
+        let from = self.location();
+        self.world.place(self.id, to);
+        self.world.publish(Event::PlayerMoved { who: self.id, from: from, to: to });
+        self.look();
+        Ok(())
+    }
+
+    /// Enter or board the vehicle given as direct object.
+    pub fn enter(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "board_what"));
+                return Ok(());
+            }
+        };
+        if self.me().vehicle.is_some() {
+            say!("{}", msg!(self.world, "leave_first"));
+            return Ok(());
+        }
+        let name = match self.world.find_entity(words, &self.room_contents()) {
+            Some(n) => n,
+            None => {
+                say!("{}", msg!(self.world, "not_here"));
+                return Ok(());
+            }
+        };
+        if self.world.entity(&name).unwrap().vehicle().is_none() {
+            say!("{}", msg!(self.world, "not_enterable"));
+            return Ok(());
+        }
+        self.me_mut().vehicle = Some(name);
+        print_wrap(&msg!(self.world, "boarded", vehicle = self.world.short_description(&name)), 72);
+        Ok(())
+    }
+
+    /// Leave the vehicle the player is in.
+    pub fn leave(&mut self, _cmd: &Command) -> Result<(), Error> {
+        match self.me_mut().vehicle.take() {
+            Some(v) =>
+                print_wrap(&msg!(self.world, "left_vehicle", vehicle = self.world.short_description(&v)), 72),
+            None =>
+                say!("{}", msg!(self.world, "not_aboard")),
+        }
+        Ok(())
+    }
+}
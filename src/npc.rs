@@ -0,0 +1,190 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Non-player characters.  Characters are moved around the world
+//! once per turn, and the rooms they leave and enter are told about
+//! it.
+//!
+//! Characters walk along their patrol route, or do what their
+//! behaviors tell them.  Behaviors are listed most urgent first, and
+//! the first one which applies decides where the character goes:
+//!
+//! ```text
+//! behaviors: [Flee(25), Guard(@gate_house)],
+//! ```
+//!
+//! A character with behaviors only patrols in turns none of them
+//! applies.  Triggers and scheduled tasks change behaviors with the
+//! `Behave` effect, like a guard starting to follow the player after
+//! the alarm went off.  Characters only go through open doors.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::types::*;
+
+impl World {
+    /// Return the rooms characters can go to from a room, through
+    /// doors which are not closed.
+    fn open_neighbours(&self, room: &InternalName) -> Vec<InternalName> {
+        self.doors_at(room).iter().filter(|d| self.is_open(d))
+            .filter_map(|d| self.other_side(d, room)).collect()
+    }
+
+    /// Return the first room on the shortest way from one room to
+    /// another through open doors, if there is a way.
+    fn step_towards(&self, from: &InternalName, to: &InternalName) -> Option<InternalName> {
+        // The first step of the way to each room seen.
+        let mut steps = BTreeMap::new();
+        let mut todo = VecDeque::new();
+        steps.insert(*from, *from);
+        todo.push_back(*from);
+        while let Some(room) = todo.pop_front() {
+            if room == *to {
+                return steps.get(&room).cloned();
+            }
+            for n in self.open_neighbours(&room) {
+                if !steps.contains_key(&n) {
+                    let step = if room == *from { n } else { steps[&room] };
+                    steps.insert(n, step);
+                    todo.push_back(n);
+                }
+            }
+        }
+        None
+    }
+
+    /// Return the rooms players who are alive are in.
+    fn player_rooms(&self) -> Vec<InternalName> {
+        self.entities.iter()
+            .filter(|e| e.tags.iter().any(|t| t == "player") && e.character().map_or(false, |c| c.stats.health > 0))
+            .filter_map(|e| self.location_of(&e.id))
+            .collect()
+    }
+
+    /// Return one of the rooms at random, if there are any.
+    fn pick_room(&mut self, rooms: &[InternalName]) -> Option<InternalName> {
+        match rooms.len() {
+            0 => None,
+            n => Some(rooms[self.rng.roll(n as u32) as usize - 1]),
+        }
+    }
+
+    /// Decide where a character goes, following the first of its
+    /// behaviors which applies.  Returns `None` if none applies, and
+    /// `Some(None)` if the character stays where it is.
+    fn behave(&mut self, id: &InternalName, behaviors: &[Behavior], players: &[InternalName]) -> Option<Option<InternalName>> {
+        let here = match self.location_of(id) {
+            Some(r) => r,
+            None => return None,
+        };
+        let stats = match self.entity(id).and_then(|e| e.character()) {
+            Some(c) => c.stats.clone(),
+            None => return None,
+        };
+        let neighbours = self.open_neighbours(&here);
+        for b in behaviors.iter() {
+            match *b {
+                Behavior::Wander(n) =>
+                    if self.rng.roll(n) == 1 {
+                        if let Some(to) = self.pick_room(&neighbours) {
+                            return Some(Some(to));
+                        }
+                    },
+                Behavior::Guard(ref post) => {
+                    if here == *post {
+                        return Some(None);
+                    }
+                    if let Some(to) = self.step_towards(&here, post) {
+                        return Some(Some(to));
+                    }
+                },
+                Behavior::Follow =>
+                    if !players.contains(&here) {
+                        let near: Vec<_> = neighbours.iter().filter(|n| players.contains(n)).cloned().collect();
+                        if let Some(to) = self.pick_room(&near) {
+                            return Some(Some(to));
+                        }
+                    },
+                Behavior::Flee(p) =>
+                    if stats.health * 100 <= stats.max_health * p as i32 && players.contains(&here) {
+                        let safe: Vec<_> = neighbours.iter().filter(|n| !players.contains(n)).cloned().collect();
+                        if let Some(to) = self.pick_room(if safe.is_empty() { &neighbours } else { &safe }) {
+                            return Some(Some(to));
+                        }
+                    },
+            }
+        }
+        None
+    }
+
+    /// Advance the world by one turn.  Returns the messages caused by
+    /// the turn, each together with the room or character by which it
+    /// can be noticed.
+    pub fn tick(&mut self) -> Vec<(InternalName, String)> {
+        let mut moves = Vec::new();
+        for e in self.entities.iter_mut() {
+            let id = e.id;
+            if let Some(c) = e.character_mut() {
+                if c.conditions.contains(&Condition::Poisoned) && c.stats.health > 0 {
+                    c.stats.health -= 1;
+                    self.changed.insert(id);
+                }
+            }
+            // Characters controlled by players don't walk on their own.
+            let alive = e.character().map(|c| c.stats.health > 0).unwrap_or(false) &&
+                !e.tags.iter().any(|t| t == "player");
+            if let Some(p) = e.character_mut().and_then(|c| c.patrol.as_mut()).filter(|_| alive) {
+                if p.route.len() == 0 {
+                    continue;
+                }
+                self.changed.insert(id);
+                if p.wait > 0 {
+                    p.wait -= 1;
+                }
+                if p.wait == 0 {
+                    p.position = (p.position + 1) % p.route.len();
+                    p.wait = p.pace;
+                    moves.push((id, p.route[p.position]));
+                }
+            }
+        }
+
+        let players = self.player_rooms();
+        let actors: Vec<(InternalName, Vec<Behavior>)> = self.entities.iter()
+            .filter(|e| !e.tags.iter().any(|t| t == "player"))
+            .filter_map(|e| e.character().filter(|c| c.stats.health > 0 && c.behaviors.len() > 0)
+                        .map(|c| (e.id, c.behaviors.clone())))
+            .collect();
+        for (id, behaviors) in actors {
+            if let Some(to) = self.behave(&id, &behaviors, &players) {
+                moves.retain(|&(m, _)| m != id);
+                moves.extend(to.map(|t| (id, t)));
+            }
+        }
+
+        let mut notices = Vec::new();
+        for (id, to) in moves {
+            let from = self.location_of(&id);
+            if from == Some(to) {
+                continue;
+            }
+            let who = self.short_description(&id);
+            if let Some(f) = from {
+                if let Some(r) = self.entity_mut(&f).and_then(|e| e.room_mut()) {
+                    r.entities.retain(|e| *e != id);
+                }
+                notices.push((f, msg!(self, "leaves", who = who)));
+            }
+            if let Some(r) = self.entity_mut(&to).and_then(|e| e.room_mut()) {
+                r.entities.push(id);
+            }
+            notices.push((to, msg!(self, "arrives", who = who)));
+        }
+        notices.extend(self.advance_weather());
+        notices.extend(self.decay());
+        notices.extend(self.respawn());
+        self.turn += 1;
+        notices.extend(self.run_schedule());
+        notices
+    }
+}
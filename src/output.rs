@@ -0,0 +1,170 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Output of the game.  Everything the game shows the player goes
+//! through `say` (usually by way of the `say!` macro), which prints
+//! it, or keeps it if output is being captured.
+//!
+//! Sessions can be recorded in a transcript: a file with everything
+//! the player typed (as given to `input`) and everything the game
+//! showed, each line with the time it happened, for bug reports and
+//! for authors reviewing playtests.  Input lines are marked with
+//! `> `.  Lines of a session kept by the engine carry its number
+//! after the time, as in `[2016-05-01 12:34:56 #2] > look`, so that
+//! the players of a shared world can be told apart.
+//!
+//! Output is handled for each thread separately.
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A transcript being recorded.
+struct Transcript {
+    path: PathBuf,
+    file: File,
+}
+
+struct Output {
+    /// Text captured instead of being printed, innermost capture
+    /// last.
+    captures: Vec<String>,
+    transcript: Option<Transcript>,
+    /// Number of the session whose input is being handled, if any.
+    session: Option<usize>,
+}
+
+thread_local! {
+    static OUTPUT: RefCell<Output> = RefCell::new(Output { captures: vec![], transcript: None, session: None });
+}
+
+/// Return the current time as UTC in the form `2016-05-01 12:34:56`.
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+/// Write a line to the transcript, if one is being recorded.  A
+/// transcript which cannot be written to is stopped.
+fn record(out: &mut Output, prefix: &str, line: &str) {
+    let stamp = match out.session {
+        Some(s) => format!("{} #{}", timestamp(), s),
+        None => timestamp(),
+    };
+    let failed = match out.transcript {
+        Some(ref mut t) => writeln!(t.file, "[{}] {}{}", stamp, prefix, line).is_err(),
+        None => false,
+    };
+    if failed {
+        out.transcript = None;
+    }
+}
+
+/// Show a line of text to the player.
+pub fn say(line: &str) {
+    OUTPUT.with(|o| {
+        let mut out = o.borrow_mut();
+        match out.captures.last_mut() {
+            Some(c) => {
+                c.push_str(line);
+                c.push('\n');
+            },
+            None => println!("{}", line),
+        }
+        for l in line.split('\n') {
+            record(&mut out, "", l);
+        }
+    })
+}
+
+/// Note a line the player typed, for the transcript.
+pub fn input(line: &str) {
+    OUTPUT.with(|o| record(&mut o.borrow_mut(), "> ", line))
+}
+
+/// Run `f`, and return its result together with the text it showed
+/// instead of printing that text.
+pub fn capture<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+    OUTPUT.with(|o| o.borrow_mut().captures.push(String::new()));
+    let captured = Captured;
+    let res = f();
+    let txt = OUTPUT.with(|o| o.borrow_mut().captures.last_mut().map(|c| mem::replace(c, String::new())).unwrap_or_default());
+    drop(captured);
+    (res, txt)
+}
+
+/// Ends a capture when dropped, so that a panic while capturing does
+/// not swallow the output of the rest of the thread.
+struct Captured;
+
+impl Drop for Captured {
+    fn drop(&mut self) {
+        let _ = OUTPUT.try_with(|o| o.borrow_mut().captures.pop());
+    }
+}
+
+/// Mark the lines recorded in the transcript with the number of a
+/// session, until the returned value is dropped.
+pub fn in_session(id: usize) -> InSession {
+    let previous = OUTPUT.with(|o| o.borrow_mut().session.replace(id));
+    InSession { previous: previous }
+}
+
+/// The lines of a session being recorded, see `in_session`.
+pub struct InSession {
+    previous: Option<usize>,
+}
+
+impl Drop for InSession {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        let _ = OUTPUT.try_with(|o| o.borrow_mut().session = previous);
+    }
+}
+
+/// Show text captured before, which is in the transcript already,
+/// after all.
+pub fn release(txt: &str) {
+    OUTPUT.with(|o| match o.borrow_mut().captures.last_mut() {
+        Some(c) => c.push_str(txt),
+        None => print!("{}", txt),
+    })
+}
+
+/// Start recording a transcript, appending to the file at `path`.
+/// A transcript which is already being recorded is stopped.
+pub fn start_transcript<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    let file = try!(OpenOptions::new().create(true).append(true).open(path)
+                    .map_err(|e| format!("cannot write {}: {}", path.display(), e)));
+    OUTPUT.with(|o| {
+        let mut out = o.borrow_mut();
+        out.transcript = Some(Transcript { path: path.to_path_buf(), file: file });
+        record(&mut out, "", "--- transcript started ---");
+    });
+    Ok(())
+}
+
+/// Stop recording the transcript.  Returns the file it was written
+/// to, if one was being recorded.
+pub fn stop_transcript() -> Option<PathBuf> {
+    OUTPUT.with(|o| {
+        let mut out = o.borrow_mut();
+        record(&mut out, "", "--- transcript stopped ---");
+        out.transcript.take().map(|t| t.path)
+    })
+}
@@ -0,0 +1,42 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Ownership of items.  Items can be owned by a character, no matter
+//! who carries them, and owners notice when their things are taken.
+//! Templates can ask for the owner with `(owner item)`.
+
+use super::types::*;
+use super::relation::OWNED_BY;
+use super::print_wrap;
+
+impl World {
+    /// Return the owner of an item, if it has one.
+    pub fn owner_of(&self, name: &InternalName) -> Option<InternalName> {
+        self.relations.object(OWNED_BY, name)
+    }
+
+    /// Make a character the owner of an item, or nobody if `owner`
+    /// is not given.
+    pub fn set_owner(&mut self, name: InternalName, owner: Option<InternalName>) {
+        match owner {
+            Some(o) => self.relations.relate(OWNED_BY, name, o),
+            None => { self.relations.unrelate(OWNED_BY, &name, None); },
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Let the owner of an item the player has just taken protest, if
+    /// the owner is alive and watching.
+    pub fn notice_theft(&self, name: &InternalName) {
+        let owner = match self.world.owner_of(name) {
+            Some(o) => o,
+            None => return,
+        };
+        let watching = self.room_contents().contains(&owner) &&
+            self.world.entity(&owner).and_then(|e| e.character()).map(|c| c.stats.health > 0).unwrap_or(false);
+        if watching {
+            print_wrap(&msg!(self.world, "owner_shouts", owner = self.world.short_description(&owner)), 72);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Players.  A player is a character entity of the world, which is
+//! controlled through a `PlayerState` handle.
+
+use std::collections::BTreeMap;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{DeserializeSeed, Error};
+
+use super::types::*;
+
+/// The serialized form of a player state.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "PlayerState")]
+struct SavedPlayer {
+    id: InternalName,
+}
+
+impl<'a> Serialize for PlayerState<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedPlayer { id: self.id }.serialize(serializer)
+    }
+}
+
+/// Deserializes a player state for the world it holds.  Fails if the
+/// player's entity is not a character of that world.
+pub struct PlayerSeed<'a>(pub &'a mut World);
+
+impl<'a, 'de> DeserializeSeed<'de> for PlayerSeed<'a> {
+    type Value = PlayerState<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<PlayerState<'a>, D::Error> {
+        let saved = try!(SavedPlayer::deserialize(deserializer));
+        if self.0.entity(&saved.id).and_then(|e| e.character()).is_none() {
+            return Err(D::Error::custom(format!("player is not a character: {}", saved.id)));
+        }
+        Ok(PlayerState::new(self.0, saved.id))
+    }
+}
+
+impl Default for PlayerTemplate {
+    /// An exhausted adventurer, carrying nothing.
+    fn default() -> PlayerTemplate {
+        PlayerTemplate {
+            name: vec!["adventurer".to_string()],
+            short_description: String::new(),
+            long_description: String::new(),
+            stats: Stats { health: 10, max_health: 10, strength: 5 },
+            conditions: vec![Condition::Exhausted],
+            inventory: vec![],
+            purse: 0,
+        }
+    }
+}
+
+impl Character {
+    /// Return a character with the given stats, who carries and knows
+    /// nothing.
+    pub fn new(stats: Stats) -> Character {
+        Character {
+            inventory: vec![],
+            topics: BTreeMap::new(),
+            fallback: String::new(),
+            patrol: None,
+            stats: stats,
+            conditions: vec![],
+            hostile: false,
+            purse: 0,
+            worn: vec![],
+            vehicle: None,
+            gifts: vec![],
+            shown: BTreeMap::new(),
+            behaviors: vec![],
+        }
+    }
+}
+
+impl World {
+    /// Add a player character with the given name to the world, at
+    /// the start location.  Returns the name of the player's entity.
+    pub fn add_player(&mut self, name: Name, character: Character) -> InternalName {
+        let id = self.next_id();
+        let player = Entity {
+            id: id,
+            short_description: name.join(" "),
+            name: name,
+            alias: None,
+            kind: None,
+            tags: vec!["player".to_string()],
+            properties: BTreeMap::new(),
+            long_description: String::new(),
+            sound_description: None,
+            smell_description: None,
+            translations: BTreeMap::new(),
+            attributes: vec![Attribute::Characterlike(character)],
+        };
+        let start = self.start_location;
+        self.spawn(player, Some(start))
+    }
+
+    /// Add a player character made from the world's player template
+    /// to the world, at the start location.  Returns the name of the
+    /// player's entity.
+    pub fn add_starting_player(&mut self) -> InternalName {
+        let t = self.player.clone();
+        let mut me = Character::new(t.stats);
+        me.conditions = t.conditions;
+        me.purse = t.purse;
+        me.inventory = t.inventory.into_iter()
+            .filter(|i| self.entity(i).is_some() && self.holder_of(i).is_none())
+            .collect();
+        let id = self.add_player(t.name, me);
+        if let Some(e) = self.entity_mut(&id) {
+            if !t.short_description.is_empty() {
+                e.short_description = t.short_description;
+            }
+            e.long_description = t.long_description;
+        }
+        id
+    }
+
+    /// Return the names of all characters of the world, including
+    /// the players'.
+    pub fn characters(&self) -> Vec<InternalName> {
+        self.entities.iter().filter(|e| e.character().is_some()).map(|e| e.id).collect()
+    }
+
+    /// Move an entity from the room it is in, if any, into another
+    /// room.
+    pub fn place(&mut self, name: InternalName, room: InternalName) {
+        if let Some(from) = self.location_of(&name) {
+            if let Some(r) = self.entity_mut(&from).and_then(|e| e.room_mut()) {
+                r.entities.retain(|e| *e != name);
+            }
+        }
+        if let Some(r) = self.entity_mut(&room).and_then(|e| e.room_mut()) {
+            r.entities.push(name);
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return a handle for playing as the given character.
+    pub fn new(world: &'a mut World, id: InternalName) -> PlayerState<'a> {
+        PlayerState {
+            world: world,
+            id: id,
+        }
+    }
+
+    /// Take control of another character, for games with several
+    /// protagonists or for possessing characters while debugging.
+    /// Controlled characters are tagged "player" and don't patrol.
+    pub fn possess(&mut self, id: InternalName) -> Result<(), String> {
+        if self.world.entity(&id).and_then(|e| e.character()).is_none() {
+            return Err("not a character".to_string());
+        }
+        if self.world.location_of(&id).is_none() {
+            return Err("character is not in a room".to_string());
+        }
+        if let Some(e) = self.world.entity_mut(&self.id) {
+            e.tags.retain(|t| t != "player");
+        }
+        if let Some(e) = self.world.entity_mut(&id) {
+            e.tags.push("player".to_string());
+        }
+        self.id = id;
+        Ok(())
+    }
+
+    /// Take control of the character with the given name ("become
+    /// guard"), anywhere in the world.
+    pub fn possess_named(&mut self, words: &Name) -> Result<(), String> {
+        let candidates = self.world.characters();
+        match self.world.find_entity(words, &candidates) {
+            Some(id) => self.possess(id),
+            None => Err("no such character".to_string()),
+        }
+    }
+
+    /// Return the room the player is in.
+    pub fn location(&self) -> InternalName {
+        self.world.location_of(&self.id).expect("player is not in a room")
+    }
+
+    /// Return the player's character.
+    pub fn me(&self) -> &Character {
+        self.world.entity(&self.id).and_then(|e| e.character()).expect("player is not a character")
+    }
+
+    pub fn me_mut(&mut self) -> &mut Character {
+        self.world.entity_mut(&self.id).and_then(|e| e.character_mut()).expect("player is not a character")
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Prefabs: the entities most worlds need, like doors, chests and
+//! torches, made from a few parameters instead of being written out
+//! attribute by attribute.  A prefab is applied to an entity, with
+//! `WorldBuilder::prefab` or the `prefab` field of the authoring
+//! language:
+//!
+//! ```text
+//! door cellar_door { prefab: LockableDoor(keys: [@iron_key], locked: true); }
+//! item old_chest { prefab: Chest(contents: [@coin], capacity: Some(5)); }
+//! item torch { prefab: Torch(turns: 20); }
+//! item sign { prefab: Sign(text: "Zum Bergwerk"); }
+//! character guard { prefab: Npc(health: 10, strength: 3, hostile: true); }
+//! ```
+//!
+//! Prefabs give entities descriptions only if they have none yet, so
+//! descriptions given before or after the prefab are kept.
+
+use std::collections::BTreeMap;
+
+use super::types::*;
+
+/// A common kind of entity, with its parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Prefab {
+    /// A closed door, which can be locked with any of the keys.
+    LockableDoor {
+        #[serde(default)]
+        keys: Vec<InternalName>,
+        #[serde(default)]
+        locked: bool,
+    },
+    /// A closed chest holding the contents, which cannot be taken.
+    /// Chests with keys can be locked.
+    Chest {
+        #[serde(default)]
+        contents: Vec<InternalName>,
+        #[serde(default)]
+        capacity: Option<u32>,
+        #[serde(default)]
+        keys: Vec<InternalName>,
+        #[serde(default)]
+        locked: bool,
+    },
+    /// A torch giving light while it burns, for the given number of
+    /// turns.
+    Torch {
+        turns: u32,
+        #[serde(default)]
+        lit: bool,
+    },
+    /// A sign fixed in place, with text to read.
+    Sign {
+        text: String,
+    },
+    /// A character to talk to or to fight.
+    Npc {
+        health: i32,
+        strength: i32,
+        #[serde(default)]
+        hostile: bool,
+        #[serde(default)]
+        topics: BTreeMap<String, String>,
+        #[serde(default)]
+        fallback: String,
+    },
+}
+
+/// Return a lock for the keys, unless there are none and it is not
+/// locked.
+fn lock(keys: &[InternalName], locked: bool) -> Option<Attribute> {
+    if keys.is_empty() && !locked {
+        None
+    } else {
+        Some(Attribute::Lockable(Lock { locked: locked, keys: keys.to_vec(), key_class: None, code: None }))
+    }
+}
+
+impl Prefab {
+    /// Return the short and long descriptions entities made from this
+    /// prefab get by default.
+    fn descriptions(&self) -> (&'static str, &'static str) {
+        match *self {
+            Prefab::LockableDoor { .. } =>
+                ("Eine Tür", "Eine schwere Holztür mit einem eisernen Schloss."),
+            Prefab::Chest { .. } =>
+                ("Eine Truhe", "Eine alte Truhe aus Holz, mit Eisen beschlagen."),
+            Prefab::Torch { .. } =>
+                ("Eine Fackel", "Ein Stück Holz, an einem Ende mit Pech getränkt."),
+            Prefab::Sign { .. } =>
+                ("Ein Schild", "Ein verwittertes Holzschild mit einer Aufschrift."),
+            Prefab::Npc { .. } =>
+                ("Eine Gestalt", "Eine unauffällige Gestalt."),
+        }
+    }
+
+    /// Make the entity one of this prefab.  Characters keep their
+    /// inventory, but get the prefab's stats.
+    pub fn apply(&self, e: &mut Entity) {
+        let (short, long) = self.descriptions();
+        if e.short_description.is_empty() {
+            e.short_description = short.to_string();
+        }
+        if e.long_description.is_empty() {
+            e.long_description = long.to_string();
+        }
+        match *self {
+            Prefab::LockableDoor { ref keys, locked } => {
+                e.attributes.push(Attribute::Closable(true));
+                e.attributes.extend(lock(keys, locked));
+            },
+            Prefab::Chest { ref contents, capacity, ref keys, locked } => {
+                e.attributes.push(Attribute::Container(Container { contents: contents.clone(), capacity: capacity }));
+                e.attributes.push(Attribute::Closable(true));
+                e.attributes.extend(lock(keys, locked));
+                e.attributes.push(Attribute::Fixed);
+            },
+            Prefab::Torch { turns, lit } => {
+                if !e.tags.iter().any(|t| t == "light_source") {
+                    e.tags.push("light_source".to_string());
+                }
+                e.attributes.push(Attribute::Burnable(Fuse {
+                    lit: lit,
+                    turns: turns,
+                    expiry: "Die Fackel ist heruntergebrannt.".to_string(),
+                    consume: true,
+                }));
+            },
+            Prefab::Sign { ref text } => {
+                e.attributes.push(Attribute::Readable(text.clone()));
+                e.attributes.push(Attribute::Fixed);
+            },
+            Prefab::Npc { health, strength, hostile, ref topics, ref fallback } => {
+                let stats = Stats { health: health, max_health: health, strength: strength };
+                if e.character().is_none() {
+                    e.attributes.push(Attribute::Characterlike(Character::new(stats.clone())));
+                }
+                let c = e.character_mut().unwrap();
+                c.stats = stats;
+                c.hostile = hostile;
+                c.topics.extend(topics.iter().map(|(k, v)| (k.clone(), v.clone())));
+                c.fallback = fallback.clone();
+            },
+        }
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Generating worlds.  A `Generator` lays out rooms on a grid, with
+//! passages named like their directions between neighbouring rooms,
+//! locked doors whose keys can be found without passing them, and
+//! items scattered about.  The same parameters and seed always give
+//! the same world, so generated worlds can be used for games as well
+//! as for testing the engine with large worlds.
+//!
+//! There are three layouts:
+//!
+//! * caves, carved by wandering around the grid,
+//! * mazes, which fill the whole grid and have exactly one way
+//!   between any two rooms, and
+//! * dungeons, chambers joined by corridors.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use super::types::*;
+use super::rng::Rng;
+use super::builder::WorldBuilder;
+
+/// The layouts of generated worlds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Cave,
+    Maze,
+    Dungeon,
+}
+
+/// Parameters for generating worlds.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub layout: Layout,
+    /// Size of the grid rooms are laid out on.
+    pub width: i32,
+    pub height: i32,
+    /// Number of rooms of caves, and of chambers of dungeons.  Mazes
+    /// fill the grid.
+    pub rooms: usize,
+    /// Number of locked doors.
+    pub locked_doors: usize,
+    /// Number of items scattered about.
+    pub items: usize,
+}
+
+/// Directions between neighbouring positions.
+const DIRECTIONS: &'static [(&'static str, (i32, i32))] =
+    &[("north", (0, 1)), ("east", (1, 0)), ("south", (0, -1)), ("west", (-1, 0))];
+
+/// Locked doors: the words they are called by, their description and
+/// the description of their key.
+const DOORS: &'static [(&'static str, &'static str, &'static str)] =
+    &[
+        ("iron", "Eine Eisentür", "Ein Eisenschlüssel"),
+        ("oak", "Eine Eichentür", "Ein Eichenholzschlüssel"),
+        ("brass", "Eine Messingtür", "Ein Messingschlüssel"),
+        ("copper", "Eine Kupfertür", "Ein Kupferschlüssel"),
+        ("silver", "Eine Silbertür", "Ein Silberschlüssel"),
+        ("golden", "Eine goldene Tür", "Ein goldener Schlüssel"),
+        ("bone", "Eine Tür aus Knochen", "Ein Knochenschlüssel"),
+        ("crystal", "Eine Kristalltür", "Ein Kristallschlüssel"),
+    ];
+
+/// Items scattered about: the words they are called by and their
+/// description.
+const ITEMS: &'static [(&'static str, &'static str)] =
+    &[
+        ("rusty nail", "Ein rostiger Nagel"),
+        ("torch", "Eine Fackel"),
+        ("rope", "Ein Stück Seil"),
+        ("bone", "Ein Knochen"),
+        ("pebble", "Ein glatter Kiesel"),
+        ("coin", "Eine alte Münze"),
+        ("bottle", "Eine leere Flasche"),
+        ("candle", "Ein Kerzenstummel"),
+    ];
+
+type Pos = (i32, i32);
+
+/// Return a number between 0 and `n - 1`.
+fn pick(rng: &mut Rng, n: usize) -> usize {
+    (rng.roll(n as u32) as usize).saturating_sub(1)
+}
+
+/// Return the passage between two rooms, in the order it is stored.
+fn edge(a: Pos, b: Pos) -> (Pos, Pos) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Return the direction from one position to a neighbouring one.
+fn direction(from: Pos, to: Pos) -> &'static str {
+    DIRECTIONS.iter().find(|&&(_, d)| (from.0 + d.0, from.1 + d.1) == to).map_or("", |&(n, _)| n)
+}
+
+/// Return the alias of the room at a position.
+fn room_alias(p: Pos) -> String {
+    format!("room_{}_{}", p.0, p.1)
+}
+
+impl Generator {
+    /// Return a generator for worlds of the given layout, with
+    /// parameters for small worlds.
+    pub fn new(layout: Layout) -> Generator {
+        Generator { layout: layout, width: 8, height: 8, rooms: 20, locked_doors: 2, items: 10 }
+    }
+
+    fn inside(&self, p: Pos) -> bool {
+        p.0 >= 0 && p.1 >= 0 && p.0 < self.width && p.1 < self.height
+    }
+
+    fn neighbours(&self, p: Pos) -> Vec<Pos> {
+        DIRECTIONS.iter().map(|&(_, d)| (p.0 + d.0, p.1 + d.1)).filter(|q| self.inside(*q)).collect()
+    }
+
+    /// Carve a cave by wandering from the middle of the grid until
+    /// enough rooms are found.  Neighbouring rooms are sometimes
+    /// joined, too, so caves have loops.
+    fn cave(&self, rng: &mut Rng) -> (Vec<Pos>, BTreeSet<(Pos, Pos)>) {
+        let target = self.rooms.min((self.width * self.height) as usize);
+        let mut here = (self.width / 2, self.height / 2);
+        let mut rooms = vec![here];
+        let mut edges = BTreeSet::new();
+        while rooms.len() < target {
+            let ns = self.neighbours(here);
+            let next = ns[pick(rng, ns.len())];
+            edges.insert(edge(here, next));
+            if !rooms.contains(&next) {
+                rooms.push(next);
+            }
+            here = next;
+        }
+        for &r in rooms.iter() {
+            for n in self.neighbours(r) {
+                if rooms.contains(&n) && rng.roll(4) == 1 {
+                    edges.insert(edge(r, n));
+                }
+            }
+        }
+        (rooms, edges)
+    }
+
+    /// Carve a maze through the whole grid, going back when there is
+    /// no unvisited neighbour left.
+    fn maze(&self, rng: &mut Rng) -> (Vec<Pos>, BTreeSet<(Pos, Pos)>) {
+        let mut rooms = vec![(0, 0)];
+        let mut edges = BTreeSet::new();
+        let mut stack = vec![(0, 0)];
+        while let Some(&here) = stack.last() {
+            let ns: Vec<Pos> = self.neighbours(here).into_iter().filter(|n| !rooms.contains(n)).collect();
+            if ns.is_empty() {
+                stack.pop();
+                continue;
+            }
+            let next = ns[pick(rng, ns.len())];
+            edges.insert(edge(here, next));
+            rooms.push(next);
+            stack.push(next);
+        }
+        (rooms, edges)
+    }
+
+    /// Place chambers on the grid and join each to the next one by a
+    /// corridor.  Returns the chambers, too.
+    fn dungeon(&self, rng: &mut Rng) -> (Vec<Pos>, BTreeSet<(Pos, Pos)>, BTreeSet<Pos>) {
+        let target = self.rooms.min((self.width * self.height) as usize);
+        let mut chambers = vec![];
+        while chambers.len() < target {
+            let p = (pick(rng, self.width as usize) as i32, pick(rng, self.height as usize) as i32);
+            if !chambers.contains(&p) {
+                chambers.push(p);
+            }
+        }
+        let mut rooms = vec![chambers[0]];
+        let mut edges = BTreeSet::new();
+        for w in chambers.windows(2) {
+            let (mut here, to) = (w[0], w[1]);
+            // Corridors go along one axis first, then along the
+            // other, chosen at random.
+            let x_first = rng.roll(2) == 1;
+            while here != to {
+                let next = if (x_first && here.0 != to.0) || here.1 == to.1 {
+                    (here.0 + (to.0 - here.0).signum(), here.1)
+                } else {
+                    (here.0, here.1 + (to.1 - here.1).signum())
+                };
+                edges.insert(edge(here, next));
+                if !rooms.contains(&next) {
+                    rooms.push(next);
+                }
+                here = next;
+            }
+        }
+        (rooms, edges, chambers.into_iter().collect())
+    }
+
+    /// Generate a world from the given seed.
+    pub fn generate(&self, seed: u64) -> Result<World, String> {
+        if self.width < 1 || self.height < 1 || self.rooms < 1 {
+            return Err("the world must have room for at least one room".to_string());
+        }
+        let mut rng = Rng::new(seed);
+        let (rooms, edges, chambers) = match self.layout {
+            Layout::Cave => {
+                let (r, e) = self.cave(&mut rng);
+                (r, e, BTreeSet::new())
+            },
+            Layout::Maze => {
+                let (r, e) = self.maze(&mut rng);
+                (r, e, BTreeSet::new())
+            },
+            Layout::Dungeon => self.dungeon(&mut rng),
+        };
+        let start = rooms[0];
+        let (name, region) = match self.layout {
+            Layout::Cave => ("Höhlen", "Die Luft ist feucht und kühl."),
+            Layout::Maze => ("Labyrinth", "Die Gänge sehen alle gleich aus."),
+            Layout::Dungeon => ("Verlies", "Es riecht nach altem Stein."),
+        };
+        let mut builder = WorldBuilder::new(&format!("{} {}", name, seed))
+            .define_region("generated", Region { description: region.to_string(), ambient: vec![], dark: false, weather: None });
+        for &p in rooms.iter() {
+            let (words, short) = match self.layout {
+                Layout::Cave => ("cave", "Eine Höhle"),
+                Layout::Maze => ("passage", "Ein Gang im Labyrinth"),
+                Layout::Dungeon if chambers.contains(&p) => ("chamber", "Eine Kammer"),
+                Layout::Dungeon => ("corridor", "Ein Korridor"),
+            };
+            builder = builder.room(&room_alias(p)).named(words).desc(short)
+                .position(p.0, p.1, 0).in_region("generated");
+        }
+
+        // Locked doors are chosen among all passages, and their keys
+        // are put in rooms which can be reached from the start without
+        // passing any locked door, so the world can always be solved.
+        let edges: Vec<(Pos, Pos)> = edges.into_iter().collect();
+        let mut locked = BTreeMap::new();
+        while locked.len() < self.locked_doors.min(DOORS.len()).min(edges.len()) {
+            let e = edges[pick(&mut rng, edges.len())];
+            let n = locked.len();
+            locked.entry(e).or_insert(n);
+        }
+        let mut free = BTreeSet::new();
+        let mut queue: VecDeque<Pos> = vec![start].into_iter().collect();
+        while let Some(p) = queue.pop_front() {
+            if !free.insert(p) {
+                continue;
+            }
+            for &(a, b) in edges.iter().filter(|e| !locked.contains_key(e)) {
+                if a == p {
+                    queue.push_back(b);
+                } else if b == p {
+                    queue.push_back(a);
+                }
+            }
+        }
+        let free: Vec<Pos> = free.into_iter().collect();
+
+        for &(a, b) in edges.iter() {
+            match locked.get(&(a, b)) {
+                Some(&n) => {
+                    let (words, door, key) = DOORS[n];
+                    let door_alias = format!("{}_door", words);
+                    let key_alias = format!("{}_key", words);
+                    let key_id = builder.id(&key_alias);
+                    builder = builder.door(&door_alias).named(&format!("{} door", words)).desc(door)
+                        .attribute(Attribute::Closable(true))
+                        .attribute(Attribute::Lockable(Lock { locked: true, keys: vec![key_id], key_class: None, code: None }))
+                        .between(&room_alias(a), &room_alias(b))
+                        .item(&key_alias).named(&format!("{} key", words)).desc(key)
+                        .attribute(Attribute::Keylike(Key { classes: vec![] }))
+                        .in_room(&room_alias(free[pick(&mut rng, free.len())]));
+                },
+                None =>
+                    for &(from, to) in [(a, b), (b, a)].iter() {
+                        let dir = direction(from, to);
+                        builder = builder.door(&format!("{}_{}", room_alias(from), dir)).named(dir).desc(dir)
+                            .leading(&room_alias(from), &room_alias(to));
+                    },
+            }
+        }
+        for i in 0..self.items {
+            let (words, short) = ITEMS[pick(&mut rng, ITEMS.len())];
+            builder = builder.item(&format!("item_{}", i)).named(words).desc(short)
+                .in_room(&room_alias(rooms[pick(&mut rng, rooms.len())]));
+        }
+        builder.start(&room_alias(start)).build()
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Quests: goals for the player, reached in stages.  A quest starts
+//! when its start condition holds, or right away without one.  Each
+//! stage has a goal, shown in the journal, and a condition which
+//! completes it.  When the last stage is completed, the rewards are
+//! given, as effects like those of triggers (see `trigger`):
+//!
+//! ```text
+//! quests: {
+//!     "rat": Quest(
+//!         title: "Die Ratte im Keller",
+//!         start: Some("(on lantern)"),
+//!         stages: [
+//!             Stage(goal: "Finde die Ratte.", condition: "(state rat \"seen\")"),
+//!             Stage(goal: "Vertreibe die Ratte.", condition: "(state rat \"gone\")",
+//!                   text: "Endlich Ruhe im Keller."),
+//!         ],
+//!         reward: [Give(@brass_key)],
+//!     ),
+//! },
+//! ```
+//!
+//! Conditions, goals and texts are templates, evaluated on behalf of
+//! the player.  Quests are checked at the end of every turn, and the
+//! journal lists the quests started so far.
+
+use super::types::*;
+use super::print_wrap;
+
+impl Quest {
+    /// Return true if the quest has been started.
+    pub fn is_started(&self) -> bool {
+        self.stage.is_some()
+    }
+
+    /// Return true if all stages of the quest are completed.
+    pub fn is_done(&self) -> bool {
+        self.stage.map_or(false, |s| s >= self.stages.len())
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Evaluate a condition of a quest, showing errors.
+    fn quest_condition(&self, condition: &str) -> bool {
+        match self.eval_condition(condition) {
+            Ok(b) => b,
+            Err(e) => {
+                say!("{}", msg!(self.world, "error", error = e));
+                false
+            },
+        }
+    }
+
+    /// Start the quests whose start condition holds, and complete the
+    /// stages whose condition holds, giving the rewards of quests
+    /// completed.
+    pub fn update_quests(&mut self) {
+        let names: Vec<String> = self.world.quests.keys().cloned().collect();
+        for name in names {
+            loop {
+                let q = self.world.quests[&name].clone();
+                let next = match q.stage {
+                    None if q.start.as_ref().map_or(true, |c| self.quest_condition(c)) => {
+                        say!("{}", msg!(self.world, "quest_started", title = q.title));
+                        0
+                    },
+                    Some(i) if i < q.stages.len() && self.quest_condition(&q.stages[i].condition) => {
+                        match self.eval_str(&q.stages[i].text) {
+                            Ok(ref s) if s.is_empty() => {},
+                            Ok(s) => print_wrap(&s, 72),
+                            Err(e) => say!("{}", msg!(self.world, "error", error = e)),
+                        }
+                        i + 1
+                    },
+                    _ => break,
+                };
+                self.world.quests.get_mut(&name).unwrap().stage = Some(next);
+                if next == q.stages.len() {
+                    say!("{}", msg!(self.world, "quest_done", title = q.title));
+                    for effect in q.reward.iter() {
+                        self.world.apply_effect(effect, Some(self.id));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print the quests started so far, with the goal of the current
+    /// stage of those not done yet.
+    pub fn show_journal(&self) {
+        let started: Vec<&Quest> = self.world.quests.values().filter(|q| q.is_started()).collect();
+        if started.is_empty() {
+            say!("{}", msg!(self.world, "journal_empty"));
+            return;
+        }
+        for q in started {
+            if q.is_done() {
+                say!("{}", msg!(self.world, "journal_done", title = q.title));
+                continue;
+            }
+            let goal = match self.eval_str(&q.stages[q.stage.unwrap()].goal) {
+                Ok(s) => s,
+                Err(e) => msg!(self.world, "error", error = e),
+            };
+            say!("{}", msg!(self.world, "journal_active", title = q.title, goal = goal));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Regions.  Rooms belonging to a region share its description,
+//! ambient messages and darkness.
+
+use super::types::*;
+use super::print_wrap;
+
+/// One in this many turns, players are shown an ambient message of
+/// their region.
+const AMBIENT_CHANCE: u32 = 4;
+
+impl World {
+    /// Return the region the given room belongs to, if any.
+    pub fn region_of(&self, room: &InternalName) -> Option<&Region> {
+        self.entity(room)
+            .and_then(|e| e.room())
+            .and_then(|r| r.region.as_ref())
+            .and_then(|n| self.regions.get(n))
+    }
+
+    /// Return true if the entity gives light: it is a light source
+    /// and, if it can be switched or lit, is not switched off and is
+    /// burning.
+    fn gives_light(&self, name: &InternalName) -> bool {
+        match self.entity(name) {
+            Some(e) if e.tags.iter().any(|t| t == "light_source") =>
+                e.machine().map(|m| m.state != "off").unwrap_or(true) &&
+                e.fuse().map(|f| f.lit).unwrap_or(true),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return true if the player's location is in a dark region and
+    /// there is no light source giving light in the room or in the
+    /// player's hands.
+    pub fn is_dark(&self) -> bool {
+        if !self.world.region_of(&self.location()).map(|r| r.dark).unwrap_or(false) {
+            return false;
+        }
+        !self.room_contents().iter()
+            .chain(self.me().inventory.iter())
+            .chain(self.me().worn.iter())
+            .any(|n| self.world.gives_light(n))
+    }
+
+    /// Show an ambient message of the player's region from time to
+    /// time.
+    pub fn ambient(&mut self) {
+        let count = match self.world.region_of(&self.location()) {
+            Some(r) if r.ambient.len() > 0 => r.ambient.len(),
+            _ => return,
+        };
+        if self.world.rng.roll(AMBIENT_CHANCE) != 1 {
+            return;
+        }
+        let idx = self.world.rng.roll(count as u32) as usize - 1;
+        let msg = self.world.region_of(&self.location()).unwrap().ambient[idx].clone();
+        match self.eval_str(&msg) {
+            // Messages may only apply at times, like at night.
+            Ok(ref s) if s.is_empty() => {},
+            Ok(s) =>
+                print_wrap(&s, 72),
+            Err(e) =>
+                say!("{}", msg!(self.world, "error", error = e))
+        }
+    }
+}
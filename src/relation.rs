@@ -0,0 +1,108 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Relations between entities.  Relationships like attachment,
+//! ownership or knowledge are kept as named binary relations instead
+//! of fields on the entities, and can be queried from templates with
+//! `(related "knows" guard note)`.
+
+use std::collections::BTreeMap;
+
+use super::types::*;
+
+/// The first entity is tied or attached to the second.
+pub const ATTACHED: &'static str = "attached";
+/// The first entity is owned by the second.
+pub const OWNED_BY: &'static str = "owned_by";
+/// The first entity knows about the second.
+pub const KNOWS: &'static str = "knows";
+
+impl Relations {
+    /// Return an empty set of relations, without any relation
+    /// defined.
+    pub fn new() -> Relations {
+        Relations {
+            arities: BTreeMap::new(),
+            pairs: BTreeMap::new(),
+        }
+    }
+
+    /// Return an empty set of relations with the relations used by
+    /// the game itself defined.
+    pub fn standard() -> Relations {
+        let mut r = Relations::new();
+        r.define(ATTACHED, Arity::ManyToMany);
+        r.define(OWNED_BY, Arity::ManyToOne);
+        r.define(KNOWS, Arity::ManyToMany);
+        r
+    }
+
+    /// Define a relation with the given arity.  Pairs of a relation
+    /// defined before are kept.
+    pub fn define(&mut self, relation: &str, arity: Arity) {
+        self.arities.insert(relation.to_string(), arity);
+        self.pairs.entry(relation.to_string()).or_insert(vec![]);
+    }
+
+    /// Relate a to b.  Pairs which would violate the arity of the
+    /// relation are removed.  Undefined relations are many-to-many.
+    pub fn relate(&mut self, relation: &str, a: InternalName, b: InternalName) {
+        let arity = self.arities.get(relation).cloned().unwrap_or(Arity::ManyToMany);
+        let pairs = self.pairs.entry(relation.to_string()).or_insert(vec![]);
+        match arity {
+            Arity::ManyToMany =>
+                pairs.retain(|&(x, y)| !(x == a && y == b)),
+            Arity::ManyToOne =>
+                pairs.retain(|&(x, _)| x != a),
+            Arity::OneToOne =>
+                pairs.retain(|&(x, y)| x != a && y != b),
+        }
+        pairs.push((a, b));
+    }
+
+    /// Remove the pairs relating a to b, or to anything if b is not
+    /// given.  Returns the number of pairs removed.
+    pub fn unrelate(&mut self, relation: &str, a: &InternalName, b: Option<&InternalName>) -> usize {
+        match self.pairs.get_mut(relation) {
+            Some(pairs) => {
+                let before = pairs.len();
+                pairs.retain(|&(ref x, ref y)| !(x == a && b.map(|b| y == b).unwrap_or(true)));
+                before - pairs.len()
+            },
+            None => 0,
+        }
+    }
+
+    /// Return true if a is related to b.
+    pub fn related(&self, relation: &str, a: &InternalName, b: &InternalName) -> bool {
+        self.pairs.get(relation).map(|p| p.iter().any(|&(ref x, ref y)| x == a && y == b)).unwrap_or(false)
+    }
+
+    /// Return everything a is related to.
+    pub fn objects(&self, relation: &str, a: &InternalName) -> Vec<InternalName> {
+        self.pairs.get(relation)
+            .map(|p| p.iter().filter(|&&(ref x, _)| x == a).map(|&(_, y)| y).collect())
+            .unwrap_or(vec![])
+    }
+
+    /// Return the one thing a is related to, if any.  Meant for
+    /// many-to-one and one-to-one relations.
+    pub fn object(&self, relation: &str, a: &InternalName) -> Option<InternalName> {
+        self.objects(relation, a).into_iter().next()
+    }
+
+    /// Return everything related to b.
+    pub fn subjects(&self, relation: &str, b: &InternalName) -> Vec<InternalName> {
+        self.pairs.get(relation)
+            .map(|p| p.iter().filter(|&&(_, ref y)| y == b).map(|&(x, _)| x).collect())
+            .unwrap_or(vec![])
+    }
+
+    /// Remove every pair an entity takes part in, for example when
+    /// it is destroyed.
+    pub fn forget(&mut self, name: &InternalName) {
+        for pairs in self.pairs.values_mut() {
+            pairs.retain(|&(ref x, ref y)| x != name && y != name);
+        }
+    }
+}
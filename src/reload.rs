@@ -0,0 +1,76 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Reloading worlds while they are played.  Authors can change the
+//! world file and see their changes without restarting: the authored
+//! data is taken from the changed file, while the state of the game
+//! is kept where possible.
+
+use std::path::Path;
+
+use super::types::*;
+
+impl World {
+    /// Take the authored data of `fresh` into this world: kinds,
+    /// regions, quests, scoring, and the names, tags and descriptions of
+    /// entities.  The attributes of existing entities, which hold
+    /// their state, are kept, as are the current weather, the
+    /// progress of quests and the awards given.  Entities which are new
+    /// in `fresh` are added, in their room if they are in one.
+    /// Entities missing from `fresh` are kept.
+    pub fn reload_from(&mut self, fresh: World) {
+        let mut added = vec![];
+        for e in fresh.entities.iter() {
+            match self.entity_mut(&e.id) {
+                Some(old) => {
+                    old.name = e.name.clone();
+                    old.alias = e.alias.clone();
+                    old.kind = e.kind.clone();
+                    old.tags = e.tags.iter().cloned()
+                        .chain(old.tags.iter().filter(|t| *t == "player").cloned())
+                        .collect();
+                    old.short_description = e.short_description.clone();
+                    old.long_description = e.long_description.clone();
+                    old.sound_description = e.sound_description.clone();
+                    old.smell_description = e.smell_description.clone();
+                    old.translations = e.translations.clone();
+                },
+                None =>
+                    added.push((e.clone(), fresh.location_of(&e.id))),
+            }
+        }
+        self.reindex();
+        for (e, room) in added {
+            let room = room.filter(|r| self.entity(r).and_then(|e| e.room()).is_some());
+            self.spawn(e, room);
+        }
+        let mut regions = fresh.regions;
+        for (name, region) in regions.iter_mut() {
+            let current = self.regions.get(name).and_then(|r| r.weather.as_ref()).map(|w| w.current);
+            if let (Some(w), Some(c)) = (region.weather.as_mut(), current) {
+                if c < w.states.len() {
+                    w.current = c;
+                }
+            }
+        }
+        self.regions = regions;
+        let mut quests = fresh.quests;
+        for (name, quest) in quests.iter_mut() {
+            quest.stage = self.quests.get(name).and_then(|q| q.stage).map(|s| s.min(quest.stages.len()));
+        }
+        self.quests = quests;
+        let awarded = self.scoring.awarded.clone();
+        self.scoring = fresh.scoring;
+        self.scoring.awarded = awarded;
+        self.kinds = fresh.kinds;
+        self.name = fresh.name;
+    }
+
+    /// Load the world file at `path` again and take its authored
+    /// data into this world, like `reload_from`.
+    pub fn reload_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let fresh = try!(World::load_from_path(path));
+        self.reload_from(fresh);
+        Ok(())
+    }
+}
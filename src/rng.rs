@@ -0,0 +1,63 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Random numbers.  A small xorshift generator, which is good enough
+//! for dice rolls and keeps the sequence reproducible from a seed.
+//!
+//! Everything random in a game comes from the generator of the world,
+//! which the engine seeds (see `Engine::new`) and saved games keep:
+//! combat, characters wandering, ambient messages, `one_of` in
+//! templates and the ids of entities made while playing.  So a game
+//! played again from the same seed with the same commands goes the
+//! same way, which replays and walkthroughs rely on.
+
+use std::cell::Cell;
+
+use uuid::{self, Uuid};
+
+use types::{InternalName, World};
+
+/// A pseudo-random number generator.  Numbers can be drawn from a
+/// shared generator, so templates can draw them while evaluating.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rng {
+    state: Cell<u64>,
+}
+
+impl Rng {
+    /// Create a generator from a seed.  A seed of zero is replaced,
+    /// because xorshift would only ever produce zeros from it.
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            state: Cell::new(if seed == 0 { 0x2545f4914f6cdd1d } else { seed })
+        }
+    }
+
+    /// Return the next number of the sequence.
+    pub fn next_u64(&self) -> u64 {
+        let mut x = self.state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state.set(x);
+        x.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Roll a die with the given number of sides, returning a number
+    /// between 1 and `sides`.  A die without sides always rolls 0.
+    pub fn roll(&self, sides: u32) -> u32 {
+        if sides == 0 {
+            0
+        } else {
+            (self.next_u64() % sides as u64) as u32 + 1
+        }
+    }
+}
+
+impl World {
+    /// Return a new id for an entity made while playing, drawn from
+    /// the world's generator.
+    pub fn next_id(&mut self) -> InternalName {
+        Uuid::new_v5(&uuid::NAMESPACE_OID, &format!("mudstuck/id/{:016x}", self.rng.next_u64()))
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Roles of players in shared worlds, and what they may do.  Every
+//! player is a `player` at first, who can only play.  Builders may
+//! also change the world, with most wizard commands (see `wizard`) or
+//! with `Engine::edit_world`, and wizards may do everything, including
+//! destroying things and giving roles to others:
+//!
+//! ```text
+//! @grant anna builder
+//! @revoke anna
+//! ```
+//!
+//! Roles belong to the accounts of players (see `accounts`), so
+//! players keep them when they leave and come back, and nobody gets
+//! them by giving the name of a wizard.  Players who have not logged
+//! in with an account are players.  The program running the engine
+//! names the first wizards, like `mudstuck serve --accounts FILE
+//! --wizard NAME`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+
+/// What a player may do, each role allowing more than the one
+/// before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Role {
+    Player,
+    Builder,
+    Wizard,
+}
+
+impl Default for Role {
+    fn default() -> Role {
+        Role::Player
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Role::Player => write!(f, "player"),
+            Role::Builder => write!(f, "builder"),
+            Role::Wizard => write!(f, "wizard"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Role, String> {
+        match s {
+            "player" => Ok(Role::Player),
+            "builder" => Ok(Role::Builder),
+            "wizard" => Ok(Role::Wizard),
+            _ => Err(format!("unknown role: {}", s)),
+        }
+    }
+}
+
+impl Engine {
+    /// Return the name of the player of a session.
+    pub fn player_name(&self, session: &Session) -> String {
+        self.world.entity(&session.player).map(|e| e.name.join(" ")).unwrap_or_default()
+    }
+
+    /// Return the role of the player of a session, given to the
+    /// account the player logged in with.
+    pub fn role(&self, session: &Session) -> Role {
+        session.account.as_ref().and_then(|a| self.roles.get(a)).cloned().unwrap_or_default()
+    }
+
+    /// Give a role to the account with the given name.
+    pub fn grant(&mut self, name: &str, role: Role) {
+        let name = name.to_lowercase();
+        if role == Role::Player {
+            self.roles.remove(&name);
+        } else {
+            self.roles.insert(name, role);
+        }
+    }
+
+    /// Return an error unless the player of a session has at least the
+    /// given role.
+    pub fn check_role(&self, session: &Session, role: Role) -> Result<(), String> {
+        if self.role(session) >= role {
+            Ok(())
+        } else {
+            Err(format!("Only a {} can do that.", role))
+        }
+    }
+
+    /// Change the world on behalf of the player of a session, who
+    /// must be a builder at least.
+    pub fn edit_world<R, F: FnOnce(&mut World) -> R>(&mut self, session: &Session, f: F) -> Result<R, String> {
+        try!(self.check_role(session, Role::Builder));
+        Ok(f(&mut self.world))
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Rules: changes to how commands are carried out, written in the
+//! world.  Commands are carried out in three stages, and rules can
+//! step in at each of them:
+//!
+//! * `Check`: before anything happens.  A rule whose condition holds
+//!   refuses the command, showing its text.
+//! * `CarryOut`: instead of the command's usual action.  A rule whose
+//!   condition holds shows its text and carries out its effects.
+//! * `Report`: after the usual action.  A rule whose condition holds,
+//!   evaluated after the action, shows its text instead of the usual
+//!   messages.
+//!
+//! Rules belong to entities, with the `Ruled` attribute, and apply to
+//! commands whose direct or indirect object is the entity, or which
+//! are given in the room:
+//!
+//! ```text
+//! Ruled([
+//!     Rule(verb: "get", stage: Check, condition: Some("(alive dragon)"),
+//!          text: "Der Drache faucht dich an."),
+//!     Rule(verb: "open", stage: Report, text: "Knarrend schwingt die Truhe auf."),
+//! ])
+//! ```
+//!
+//! Verbs are named by their first word in the verb table, like "get"
+//! for `get` and `take`, and "on" and "off" for switching.  The first
+//! rule of a stage which applies is followed, looking at the direct
+//! object first, then at the indirect object and the room.  Effects
+//! are those of triggers (see `trigger`).
+
+use super::types::*;
+use super::command::Command;
+use super::error::Error;
+use super::output;
+use super::print_wrap;
+
+impl<'a> PlayerState<'a> {
+    /// Return the entities whose rules apply to a command.
+    fn ruling_entities(&self, cmd: &Command) -> Vec<InternalName> {
+        let scope = self.scope();
+        let mut res: Vec<InternalName> = cmd.direct_object.iter()
+            .chain(cmd.indirect_object.iter().map(|&(_, ref n)| n))
+            .filter_map(|n| self.world.find_entity(n, &scope))
+            .collect();
+        res.push(self.location());
+        res
+    }
+
+    /// Follow the first rule for the command at the given stage whose
+    /// condition holds: show its text and carry out its effects.
+    /// Returns true if there was such a rule.
+    fn follow_rule(&mut self, cmd: &Command, stage: ActionStage) -> bool {
+        let verb = cmd.verb.name();
+        let rules: Vec<(InternalName, Rule)> = self.ruling_entities(cmd).iter()
+            .filter_map(|n| self.world.entity(n).and_then(|e| e.rules()).map(|rs| (*n, rs)))
+            .flat_map(|(n, rs)| rs.iter().filter(|r| r.stage == stage && r.verb == verb).map(move |r| (n, r.clone())))
+            .collect();
+        for (owner, r) in rules {
+            match r.condition.as_ref().map_or(Ok(true), |c| self.eval_condition(c)) {
+                Ok(true) => {},
+                Ok(false) => continue,
+                Err(e) => {
+                    say!("{}", msg!(self.world, "error", error = e));
+                    continue;
+                },
+            }
+            match self.eval_str(&r.text) {
+                Ok(ref s) if s.is_empty() => {},
+                Ok(s) => print_wrap(&s, 72),
+                Err(e) => say!("{}", msg!(self.world, "error", error = e)),
+            }
+            for effect in r.effects.iter() {
+                self.world.apply_effect_for(effect, Some(self.id), Some(owner));
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Carry out a command in its stages: check it, carry it out and
+    /// report the outcome, following the rules which apply.
+    pub fn perform(&mut self, cmd: &Command) -> Result<(), Error> {
+        if self.follow_rule(cmd, ActionStage::Check) || self.follow_rule(cmd, ActionStage::CarryOut) {
+            return Ok(());
+        }
+        let (res, report) = output::capture(|| self.carry_out(cmd));
+        if res.is_err() || !self.follow_rule(cmd, ActionStage::Report) {
+            output::release(&report);
+        }
+        res
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Saving and restoring games.  A saved game holds only what changes
+//! during play: the entities, their relations, respawn rules, the
+//! weather, the random number generator, the turn, the scheduled
+//! tasks, the progress of quests and the awards given, together
+//! with the player's entity.  Kinds and the
+//! descriptions of regions are authored data and are taken from the
+//! world the game is restored into, which must be the same world the
+//! game was saved from.
+//!
+//! Saved games carry the version of their format, and games saved in
+//! older versions are migrated when they are restored (see
+//! `migrate`).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use ron;
+
+use super::types::*;
+use super::rng::Rng;
+use super::compress::{read_file, write_file};
+use super::migrate::{Migration, migrate, unchanged};
+
+/// The version of the format of saved games.
+pub const SAVE_VERSION: u32 = 5;
+
+/// Migrations of saved games from older versions.
+const MIGRATIONS: &'static [Migration] =
+    &[
+        // Version 1 added the version.
+        Migration { from: 0, migrate: unchanged },
+        // Version 2 added the turn, which is 0 for older games.
+        Migration { from: 1, migrate: unchanged },
+        // Version 3 added the schedule, which is empty for older
+        // games.
+        Migration { from: 2, migrate: unchanged },
+        // Version 4 added the progress of quests, which have not been
+        // started in older games.
+        Migration { from: 3, migrate: unchanged },
+        // Version 5 added the awards given, which are none in older
+        // games.
+        Migration { from: 4, migrate: unchanged },
+    ];
+
+/// The version of a saved game, which is read before the rest.
+#[derive(Deserialize)]
+#[serde(rename = "SavedGame")]
+struct Header {
+    #[serde(default)]
+    version: u32,
+}
+
+/// A saved game, for writing.
+#[derive(Serialize)]
+#[serde(rename = "SavedGame")]
+struct SavedGameRef<'a> {
+    version: u32,
+    world: &'a str,
+    player: InternalName,
+    entities: &'a Vec<Entity>,
+    relations: &'a Relations,
+    respawns: &'a Vec<Respawn>,
+    weather: BTreeMap<String, usize>,
+    rng: &'a Rng,
+    turn: u64,
+    schedule: &'a Vec<Job>,
+    quests: BTreeMap<String, usize>,
+    awarded: &'a Vec<String>,
+}
+
+/// A saved game, for reading.
+#[derive(Deserialize)]
+#[serde(rename = "SavedGame")]
+struct SavedGame {
+    world: String,
+    player: InternalName,
+    entities: Vec<Entity>,
+    relations: Relations,
+    respawns: Vec<Respawn>,
+    weather: BTreeMap<String, usize>,
+    rng: Rng,
+    #[serde(default)]
+    turn: u64,
+    #[serde(default)]
+    schedule: Vec<Job>,
+    #[serde(default)]
+    quests: BTreeMap<String, usize>,
+    #[serde(default)]
+    awarded: Vec<String>,
+}
+
+impl World {
+    /// Return the current weather of every region which has weather.
+    fn weather_states(&self) -> BTreeMap<String, usize> {
+        self.regions.iter()
+            .filter_map(|(n, r)| r.weather.as_ref().map(|w| (n.clone(), w.current)))
+            .collect()
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return the state of the game as a string.
+    pub fn save_to_string(&self) -> Result<String, String> {
+        let saved = SavedGameRef {
+            version: SAVE_VERSION,
+            world: &self.world.name,
+            player: self.id,
+            entities: &self.world.entities,
+            relations: &self.world.relations,
+            respawns: &self.world.respawns,
+            weather: self.world.weather_states(),
+            rng: &self.world.rng,
+            turn: self.world.turn,
+            schedule: &self.world.schedule,
+            quests: self.world.quests.iter().filter_map(|(n, q)| q.stage.map(|s| (n.clone(), s))).collect(),
+            awarded: &self.world.scoring.awarded,
+        };
+        ron::ser::to_string(&saved).map_err(|e| format!("cannot save: {}", e))
+    }
+
+    /// Restore the game state from a string written by
+    /// `save_to_string`.  The player takes control of the saved
+    /// player's entity.  Nothing is changed if the saved game does
+    /// not fit the world.
+    pub fn restore_from_str(&mut self, txt: &str) -> Result<(), String> {
+        let header: Header = try!(ron::de::from_str(txt).map_err(|e| format!("invalid saved game: {}", e)));
+        let txt = try!(migrate(txt, header.version, SAVE_VERSION, MIGRATIONS));
+        let saved: SavedGame = try!(ron::de::from_str(&txt).map_err(|e| format!("invalid saved game: {}", e)));
+        if saved.world != self.world.name {
+            return Err(format!("saved game is for another world: {}", saved.world));
+        }
+        if !saved.entities.iter().any(|e| e.id == saved.player && e.character().is_some()) {
+            return Err(format!("player is not a character: {}", saved.player));
+        }
+        self.world.replace_entities(saved.entities);
+        self.world.relations = saved.relations;
+        self.world.respawns = saved.respawns;
+        self.world.rng = saved.rng;
+        self.world.turn = saved.turn;
+        self.world.schedule = saved.schedule;
+        self.world.scoring.awarded = saved.awarded;
+        for (name, q) in self.world.quests.iter_mut() {
+            q.stage = saved.quests.get(name).map(|s| *s.min(&q.stages.len()));
+        }
+        for (name, current) in saved.weather {
+            if let Some(w) = self.world.regions.get_mut(&name).and_then(|r| r.weather.as_mut()) {
+                if current < w.states.len() {
+                    w.current = current;
+                }
+            }
+        }
+        self.id = saved.player;
+        Ok(())
+    }
+
+    /// Save the game to the file at `path`, compressed if its name
+    /// ends in `.gz` or `.zst` (see `compress`).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let txt = try!(self.save_to_string());
+        write_file(path.as_ref(), &txt)
+    }
+
+    /// Restore the game from the file at `path`.
+    pub fn restore<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let txt = try!(read_file(path.as_ref()));
+        self.restore_from_str(&txt)
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The scheduler: tasks carried out later, once after a number of
+//! turns or again and again every so many turns.  Tasks show a
+//! message or carry out effects, like those of triggers (see
+//! `trigger`).  World files schedule tasks in their `schedule` field,
+//! triggers with the `After` effect, and the engine with `after` and
+//! `every`:
+//!
+//! ```text
+//! schedule: [
+//!     Job(at: 5, task: Say(@hall, "Eine Glocke schlägt.")),
+//!     Job(at: 10, every: Some(10), task: Effects([Open(@gate)])),
+//! ],
+//! ```
+//!
+//! Scheduled tasks are carried out at the end of the turn they are
+//! due in, after characters have moved, the weather has changed and
+//! things have decayed and respawned.
+
+use std::mem;
+
+use super::types::*;
+
+impl World {
+    /// Carry out a task in `turns` turns.
+    pub fn after(&mut self, turns: u64, task: Task) {
+        let at = self.turn + turns;
+        self.schedule.push(Job { at: at, every: None, task: task });
+    }
+
+    /// Carry out a task every `turns` turns, starting in `turns`
+    /// turns.
+    pub fn every(&mut self, turns: u64, task: Task) {
+        let at = self.turn + turns;
+        self.schedule.push(Job { at: at, every: Some(turns), task: task });
+    }
+
+    /// Carry out the tasks which are due, and schedule those which
+    /// recur again.  Returns the messages shown, each together with
+    /// the room or character by which it can be noticed.
+    pub fn run_schedule(&mut self) -> Vec<(InternalName, String)> {
+        let turn = self.turn;
+        let (due, waiting): (Vec<Job>, Vec<Job>) =
+            mem::replace(&mut self.schedule, vec![]).into_iter().partition(|j| j.at <= turn);
+        self.schedule = waiting;
+        let mut notices = vec![];
+        for mut job in due {
+            match job.task {
+                Task::Say(place, ref text) =>
+                    match self.eval_for(text, None) {
+                        Ok(s) => notices.push((place, s)),
+                        Err(e) => notices.push((place, msg!(self, "error", error = e))),
+                    },
+                Task::Effects(ref effects) =>
+                    for e in effects.iter() {
+                        self.apply_effect(e, None);
+                    },
+            }
+            if let Some(n) = job.every.filter(|n| *n > 0) {
+                job.at = turn + n;
+                self.schedule.push(job);
+            }
+        }
+        notices
+    }
+}
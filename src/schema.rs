@@ -0,0 +1,682 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! A JSON Schema for world files, so editors and validators can be
+//! written for them without reading this code.
+//!
+//! The schema is not written by hand, but traced from the types world
+//! files are read into: they are deserialized from a `Tracer`, which
+//! makes up values and notes what is asked for.  Deserializing once
+//! does not show everything, since only one variant of each enum can
+//! be chosen, so deserializing is repeated until nothing new turns
+//! up.  Whether a field may be left out is found the same way, by
+//! leaving it out and seeing whether deserializing fails.
+//!
+//! The schema describes world files as JSON; world files in RON have
+//! the same structure, except that RON writes enum variants as
+//! `Variant(...)` instead of `{"Variant": ...}`, and options as
+//! `Some(...)` and `None`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::error;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor, SeqAccess, MapAccess, EnumAccess, VariantAccess,
+                IntoDeserializer};
+
+use super::types::*;
+use super::load::WorldFile;
+
+/// The format of a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Format {
+    /// Not found out yet.
+    Unknown,
+    Bool,
+    Unsigned,
+    Integer,
+    Float,
+    Char,
+    Str,
+    Uuid,
+    Unit,
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    Tuple(Vec<Format>),
+    Map(Box<Format>, Box<Format>),
+    /// A struct or enum, by name.
+    Named(&'static str),
+}
+
+/// A field of a struct, with its format and whether it may be left
+/// out, once that is known.
+struct Field {
+    name: &'static str,
+    format: Format,
+    required: Option<bool>,
+}
+
+enum Variant {
+    Unit,
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    Struct(Vec<Field>),
+}
+
+enum Definition {
+    Struct(Vec<Field>),
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    /// The variants, as far as they are known, and for each whether
+    /// it has been traced completely.
+    Enum(Vec<(&'static str, Option<Variant>, bool)>),
+}
+
+/// Where the fields of a struct are kept: in the definition of a
+/// struct or in a variant of an enum.
+#[derive(Clone, Copy)]
+enum Place {
+    Struct(&'static str),
+    Variant(&'static str, usize),
+}
+
+/// What has been found out about the types.  `learned` counts the
+/// findings, so tracing knows when to stop.  `active` are the enums
+/// being traced, to recognize recursive ones.
+struct Registry {
+    definitions: BTreeMap<&'static str, Definition>,
+    learned: usize,
+    active: Vec<&'static str>,
+}
+
+impl Registry {
+    fn fields(&mut self, place: Place) -> &mut Vec<Field> {
+        match place {
+            Place::Struct(name) => {
+                match *self.definitions.entry(name).or_insert(Definition::Struct(vec![])) {
+                    Definition::Struct(ref mut fields) => fields,
+                    _ => panic!("{} is not a struct", name),
+                }
+            },
+            Place::Variant(name, i) => {
+                match self.definitions.get_mut(name) {
+                    Some(&mut Definition::Enum(ref mut vs)) => {
+                        if vs[i].1.is_none() {
+                            vs[i].1 = Some(Variant::Struct(vec![]));
+                        }
+                        match vs[i].1 {
+                            Some(Variant::Struct(ref mut fields)) => fields,
+                            _ => panic!("variant {} of {} is not a struct variant", i, name),
+                        }
+                    },
+                    _ => panic!("{} is not an enum", name),
+                }
+            },
+        }
+    }
+
+    /// Define a type unless it is already defined.
+    fn define(&mut self, name: &'static str, definition: Definition) {
+        if !self.definitions.contains_key(name) {
+            self.definitions.insert(name, definition);
+            self.learned += 1;
+        }
+    }
+}
+
+/// Errors while tracing.  Errors because of fields left out carry
+/// the index of the field.
+#[derive(Debug)]
+struct Error(String, Option<usize>);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error(msg.to_string(), None)
+    }
+
+    fn invalid_length(len: usize, exp: &de::Expected) -> Error {
+        Error(format!("invalid length {}, expected {}", len, exp), Some(len))
+    }
+}
+
+/// Shows what a visitor expects.
+struct Expecting<'a, V: 'a>(&'a V);
+
+impl<'a, 'de, V: Visitor<'de>> fmt::Display for Expecting<'a, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+}
+
+/// A deserializer making up values, which notes the format of the
+/// value asked for in `out`.
+struct Tracer<'a> {
+    registry: &'a RefCell<Registry>,
+    out: &'a mut Format,
+}
+
+/// Trace the value of a seed, returning it with its format.
+fn trace<'de, T: DeserializeSeed<'de>>(registry: &RefCell<Registry>, seed: T) -> Result<(T::Value, Format), Error> {
+    let mut format = Format::Unknown;
+    let v = try!(seed.deserialize(Tracer { registry: registry, out: &mut format }));
+    Ok((v, format))
+}
+
+/// Trace a struct or struct variant.  Fields are first all given, to
+/// find their formats, and afterwards those not known to be required
+/// are left out.  Options are never required.
+fn trace_struct<'de, V: Visitor<'de>>(registry: &RefCell<Registry>, place: Place, names: &'static [&'static str],
+                                      visitor: V) -> Result<V::Value, Error> {
+    let left_out: Vec<usize> = {
+        let mut r = registry.borrow_mut();
+        let new = r.fields(place).is_empty() && !names.is_empty();
+        if new {
+            *r.fields(place) = names.iter().map(|n| Field { name: *n, format: Format::Unknown, required: None }).collect();
+            r.learned += 1;
+        }
+        r.fields(place).iter().enumerate()
+            .filter(|&(_, f)| f.required.is_none() && f.format != Format::Unknown)
+            .map(|(i, _)| i).collect()
+    };
+    let res = visitor.visit_seq(StructFields { registry: registry, place: place, index: 0, left_out: &left_out });
+    let mut r = registry.borrow_mut();
+    match res {
+        Ok(v) => {
+            for &i in left_out.iter() {
+                r.fields(place)[i].required = Some(false);
+                r.learned += 1;
+            }
+            Ok(v)
+        },
+        Err(Error(msg, Some(i))) if left_out.contains(&i) => {
+            r.fields(place)[i].required = Some(true);
+            r.learned += 1;
+            Err(Error(msg, None))
+        },
+        Err(Error(msg, _)) => Err(Error(msg, None)),
+    }
+}
+
+/// The fields of a struct, given as a sequence.
+struct StructFields<'a> {
+    registry: &'a RefCell<Registry>,
+    place: Place,
+    index: usize,
+    left_out: &'a [usize],
+}
+
+impl<'a, 'de> SeqAccess<'de> for StructFields<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        let i = self.index;
+        self.index += 1;
+        if self.left_out.contains(&i) {
+            return Ok(None);
+        }
+        let (v, format) = try!(trace(self.registry, seed));
+        let mut r = self.registry.borrow_mut();
+        let optional = match format {
+            Format::Option(_) => true,
+            _ => false,
+        };
+        let learned = match r.fields(self.place).get_mut(i) {
+            Some(f) if f.format == Format::Unknown => {
+                f.format = format;
+                if optional {
+                    f.required = Some(false);
+                }
+                true
+            },
+            _ => false,
+        };
+        if learned {
+            r.learned += 1;
+        }
+        Ok(Some(v))
+    }
+}
+
+/// The elements of a tuple, or the single element of a sequence.
+struct Elements<'a> {
+    registry: &'a RefCell<Registry>,
+    len: usize,
+    formats: Vec<Format>,
+}
+
+impl<'a, 'de> SeqAccess<'de> for Elements<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.formats.len() == self.len {
+            return Ok(None);
+        }
+        let (v, format) = try!(trace(self.registry, seed));
+        self.formats.push(format);
+        Ok(Some(v))
+    }
+}
+
+/// A map with a single entry.
+struct Entry<'a> {
+    registry: &'a RefCell<Registry>,
+    key: Option<Format>,
+    value: Option<Format>,
+}
+
+impl<'a, 'de> MapAccess<'de> for Entry<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.key.is_some() {
+            return Ok(None);
+        }
+        let (k, format) = try!(trace(self.registry, seed));
+        self.key = Some(format);
+        Ok(Some(k))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (v, format) = try!(trace(self.registry, seed));
+        self.value = Some(format);
+        Ok(v)
+    }
+}
+
+/// A variant of an enum, chosen by the tracer.
+struct Choice<'a> {
+    registry: &'a RefCell<Registry>,
+    name: &'static str,
+    index: usize,
+}
+
+impl<'a> Choice<'a> {
+    fn record(&self, variant: Variant) {
+        let mut r = self.registry.borrow_mut();
+        let new = match r.definitions.get_mut(self.name) {
+            Some(&mut Definition::Enum(ref mut vs)) if vs[self.index].1.is_none() => {
+                vs[self.index].1 = Some(variant);
+                true
+            },
+            _ => false,
+        };
+        if new {
+            r.learned += 1;
+        }
+    }
+}
+
+impl<'a, 'de> EnumAccess<'de> for Choice<'a> {
+    type Error = Error;
+    type Variant = Choice<'a>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<(T::Value, Choice<'a>), Error> {
+        let v = try!(seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.index as u32)));
+        Ok((v, self))
+    }
+}
+
+impl<'a, 'de> VariantAccess<'de> for Choice<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.record(Variant::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let (v, format) = try!(trace(self.registry, seed));
+        self.record(Variant::Newtype(format));
+        Ok(v)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut elements = Elements { registry: self.registry, len: len, formats: vec![] };
+        let v = try!(visitor.visit_seq(&mut elements));
+        self.record(Variant::Tuple(elements.formats));
+        Ok(v)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        trace_struct(self.registry, Place::Variant(self.name, self.index), fields, visitor)
+    }
+}
+
+/// A UUID, for strings which are expected to be UUIDs.
+const NIL: &'static str = "00000000-0000-0000-0000-000000000000";
+
+macro_rules! trace_number {
+    ($method:ident, $visit:ident, $format:expr, $zero:expr) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            *self.out = $format;
+            visitor.$visit($zero)
+        }
+    }
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Tracer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        Err(Error(format!("cannot trace {}", Expecting(&visitor)), None))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Format::Bool;
+        visitor.visit_bool(false)
+    }
+
+    trace_number!(deserialize_i8, visit_i8, Format::Integer, 0);
+    trace_number!(deserialize_i16, visit_i16, Format::Integer, 0);
+    trace_number!(deserialize_i32, visit_i32, Format::Integer, 0);
+    trace_number!(deserialize_i64, visit_i64, Format::Integer, 0);
+    trace_number!(deserialize_u8, visit_u8, Format::Unsigned, 0);
+    trace_number!(deserialize_u16, visit_u16, Format::Unsigned, 0);
+    trace_number!(deserialize_u32, visit_u32, Format::Unsigned, 0);
+    trace_number!(deserialize_u64, visit_u64, Format::Unsigned, 0);
+    trace_number!(deserialize_f32, visit_f32, Format::Float, 0.0);
+    trace_number!(deserialize_f64, visit_f64, Format::Float, 0.0);
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Format::Char;
+        visitor.visit_char('a')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = if Expecting(&visitor).to_string().contains("UUID") { Format::Uuid } else { Format::Str };
+        visitor.visit_str(NIL)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut inner = Format::Unknown;
+        let v = try!(visitor.visit_some(Tracer { registry: self.registry, out: &mut inner }));
+        *self.out = Format::Option(Box::new(inner));
+        Ok(v)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Format::Unit;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        let mut inner = Format::Unknown;
+        let v = try!(visitor.visit_newtype_struct(Tracer { registry: self.registry, out: &mut inner }));
+        self.registry.borrow_mut().define(name, Definition::Newtype(inner));
+        *self.out = Format::Named(name);
+        Ok(v)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut elements = Elements { registry: self.registry, len: 1, formats: vec![] };
+        let v = try!(visitor.visit_seq(&mut elements));
+        *self.out = Format::Seq(Box::new(elements.formats.pop().unwrap_or(Format::Unknown)));
+        Ok(v)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut elements = Elements { registry: self.registry, len: len, formats: vec![] };
+        let v = try!(visitor.visit_seq(&mut elements));
+        *self.out = Format::Tuple(elements.formats);
+        Ok(v)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, len: usize, visitor: V)
+                                                 -> Result<V::Value, Error> {
+        let mut elements = Elements { registry: self.registry, len: len, formats: vec![] };
+        let v = try!(visitor.visit_seq(&mut elements));
+        self.registry.borrow_mut().define(name, Definition::Tuple(elements.formats));
+        *self.out = Format::Named(name);
+        Ok(v)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut entry = Entry { registry: self.registry, key: None, value: None };
+        let v = try!(visitor.visit_map(&mut entry));
+        *self.out = Format::Map(Box::new(entry.key.unwrap_or(Format::Unknown)),
+                                Box::new(entry.value.unwrap_or(Format::Unknown)));
+        Ok(v)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V)
+                                           -> Result<V::Value, Error> {
+        *self.out = Format::Named(name);
+        trace_struct(self.registry, Place::Struct(name), fields, visitor)
+    }
+
+    /// Enums choose the first variant which has not been traced
+    /// completely.  A variant is traced completely when nothing new is
+    /// found while tracing it.  Enums contained in themselves choose a
+    /// variant traced completely instead, or the first one, so tracing
+    /// ends.
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V)
+                                         -> Result<V::Value, Error> {
+        *self.out = Format::Named(name);
+        let (index, before) = {
+            let mut r = self.registry.borrow_mut();
+            r.define(name, Definition::Enum(variants.iter().map(|v| (*v, None, false)).collect()));
+            let recursive = r.active.contains(&name);
+            let index = match r.definitions.get(name) {
+                Some(&Definition::Enum(ref vs)) if recursive => vs.iter().position(|v| v.2).unwrap_or(0),
+                Some(&Definition::Enum(ref vs)) => vs.iter().position(|v| !v.2).unwrap_or(0),
+                _ => return Err(Error(format!("{} is not an enum", name), None)),
+            };
+            r.active.push(name);
+            (index, r.learned)
+        };
+        let res = visitor.visit_enum(Choice { registry: self.registry, name: name, index: index });
+        self.registry.borrow_mut().active.pop();
+        let v = try!(res);
+        let mut r = self.registry.borrow_mut();
+        if r.learned == before {
+            if let Some(&mut Definition::Enum(ref mut vs)) = r.definitions.get_mut(name) {
+                if !vs[index].2 {
+                    vs[index].2 = true;
+                    r.learned += 1;
+                }
+            }
+        }
+        Ok(v)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Trace the type until nothing new is found.
+fn trace_type<T: DeserializeOwned>() -> Result<(Format, Registry), String> {
+    let registry = RefCell::new(Registry { definitions: BTreeMap::new(), learned: 0, active: vec![] });
+    loop {
+        let before = registry.borrow().learned;
+        let mut format = Format::Unknown;
+        let res = T::deserialize(Tracer { registry: &registry, out: &mut format });
+        let learned = registry.borrow().learned;
+        match res {
+            Ok(_) if learned == before => return Ok((format, registry.into_inner())),
+            Err(e) if learned == before => return Err(e.to_string()),
+            _ => {},
+        }
+    }
+}
+
+/// JSON values, for writing the schema.
+enum Json {
+    Bool(bool),
+    Str(String),
+    Int(usize),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn string(s: &str) -> Json {
+    Json::Str(s.to_string())
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    try!(write!(f, "\""));
+    for c in s.chars() {
+        match c {
+            '"' => try!(write!(f, "\\\"")),
+            '\\' => try!(write!(f, "\\\\")),
+            '\n' => try!(write!(f, "\\n")),
+            c if (c as u32) < 0x20 => try!(write!(f, "\\u{:04x}", c as u32)),
+            c => try!(write!(f, "{}", c)),
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Json {
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match *self {
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Str(ref s) => write_string(f, s),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::Array(ref items) if items.is_empty() => write!(f, "[]"),
+            Json::Array(ref items) => {
+                try!(write!(f, "["));
+                for (i, item) in items.iter().enumerate() {
+                    try!(write!(f, "{}\n{:2$}", if i > 0 { "," } else { "" }, "", indent + 2));
+                    try!(item.write(f, indent + 2));
+                }
+                write!(f, "\n{:1$}]", "", indent)
+            },
+            Json::Object(ref members) if members.is_empty() => write!(f, "{{}}"),
+            Json::Object(ref members) => {
+                try!(write!(f, "{{"));
+                for (i, &(ref k, ref v)) in members.iter().enumerate() {
+                    try!(write!(f, "{}\n{:2$}", if i > 0 { "," } else { "" }, "", indent + 2));
+                    try!(write_string(f, k));
+                    try!(write!(f, ": "));
+                    try!(v.write(f, indent + 2));
+                }
+                write!(f, "\n{:1$}}}", "", indent)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+fn format_schema(format: &Format) -> Json {
+    match *format {
+        Format::Unknown => object(vec![]),
+        Format::Bool => object(vec![("type", string("boolean"))]),
+        Format::Unsigned => object(vec![("type", string("integer")), ("minimum", Json::Int(0))]),
+        Format::Integer => object(vec![("type", string("integer"))]),
+        Format::Float => object(vec![("type", string("number"))]),
+        Format::Char => object(vec![("type", string("string")), ("minLength", Json::Int(1)), ("maxLength", Json::Int(1))]),
+        Format::Str => object(vec![("type", string("string"))]),
+        Format::Uuid => object(vec![("type", string("string")), ("format", string("uuid"))]),
+        Format::Unit => object(vec![("type", string("null"))]),
+        Format::Option(ref f) => object(vec![("anyOf", Json::Array(vec![format_schema(f), object(vec![("type", string("null"))])]))]),
+        Format::Seq(ref f) => object(vec![("type", string("array")), ("items", format_schema(f))]),
+        Format::Tuple(ref fs) => tuple_schema(fs),
+        Format::Map(ref k, ref v) => {
+            let mut members = vec![("type", string("object")), ("additionalProperties", format_schema(v))];
+            if **k == Format::Uuid {
+                members.push(("propertyNames", format_schema(k)));
+            }
+            object(members)
+        },
+        Format::Named(name) => object(vec![("$ref", Json::Str(format!("#/definitions/{}", name)))]),
+    }
+}
+
+fn tuple_schema(formats: &[Format]) -> Json {
+    object(vec![
+        ("type", string("array")),
+        ("items", Json::Array(formats.iter().map(format_schema).collect())),
+        ("minItems", Json::Int(formats.len())),
+        ("maxItems", Json::Int(formats.len())),
+    ])
+}
+
+fn struct_schema(fields: &[Field]) -> Json {
+    object(vec![
+        ("type", string("object")),
+        ("properties", Json::Object(fields.iter().map(|f| (f.name.to_string(), format_schema(&f.format))).collect())),
+        ("required", Json::Array(fields.iter().filter(|f| f.required != Some(false)).map(|f| string(f.name)).collect())),
+        ("additionalProperties", Json::Bool(false)),
+    ])
+}
+
+fn variant_schema(name: &str, variant: &Option<Variant>) -> Json {
+    let content = match *variant {
+        Some(Variant::Unit) | None => return object(vec![("const", string(name))]),
+        Some(Variant::Newtype(ref f)) => format_schema(f),
+        Some(Variant::Tuple(ref fs)) => tuple_schema(fs),
+        Some(Variant::Struct(ref fields)) => struct_schema(fields),
+    };
+    object(vec![
+        ("type", string("object")),
+        ("properties", object(vec![(name, content)])),
+        ("required", Json::Array(vec![string(name)])),
+        ("additionalProperties", Json::Bool(false)),
+    ])
+}
+
+fn definition_schema(definition: &Definition) -> Json {
+    match *definition {
+        Definition::Struct(ref fields) => struct_schema(fields),
+        Definition::Newtype(ref f) => format_schema(f),
+        Definition::Tuple(ref fs) => tuple_schema(fs),
+        Definition::Enum(ref vs) =>
+            object(vec![("oneOf", Json::Array(vs.iter().map(|&(n, ref v, _)| variant_schema(n, v)).collect()))]),
+    }
+}
+
+impl World {
+    /// Return a JSON Schema describing world files.
+    pub fn schema() -> Result<String, String> {
+        let (format, registry) = try!(trace_type::<WorldFile>());
+        let mut members = vec![
+            ("$schema", string("http://json-schema.org/draft-07/schema#")),
+            ("title", string("mudstuck world file")),
+        ];
+        if let Format::Named(name) = format {
+            members.push(("$ref", Json::Str(format!("#/definitions/{}", name))));
+        }
+        members.push(("definitions", Json::Object(registry.definitions.iter()
+                                                    .map(|(n, d)| (n.to_string(), definition_schema(d))).collect())));
+        Ok(object(members).to_string())
+    }
+}
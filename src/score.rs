@@ -0,0 +1,92 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Scoring.  Worlds name the things players get points for, each
+//! awarded only once, and the ranks players reach with their points:
+//!
+//! ```text
+//! scoring: Some(Scoring(
+//!     awards: {
+//!         "door": Award(points: 5, title: "Türöffner"),
+//!         "treasure": Award(points: 20, title: "Schatzsucher"),
+//!     },
+//!     ranks: [
+//!         Rank(points: 0, title: "Anfänger"),
+//!         Rank(points: 25, title: "Meisterdieb"),
+//!     ],
+//! )),
+//! ```
+//!
+//! Points are awarded with the `Award` effect, by triggers, quest
+//! rewards or scheduled tasks.  The titles of the awards given are
+//! the player's achievements.  The `score` command shows the score,
+//! the rank and the achievements, and so does the end of the game.
+//! Templates can ask for the score with `(score)`, and whether
+//! something has been awarded with `(awarded "door")`.
+
+use super::types::*;
+
+impl World {
+    /// Return the points awarded so far.
+    pub fn score(&self) -> u32 {
+        self.scoring.awarded.iter().filter_map(|a| self.scoring.awards.get(a)).map(|a| a.points).sum()
+    }
+
+    /// Return the points which can be scored in the world.
+    pub fn max_score(&self) -> u32 {
+        self.scoring.awards.values().map(|a| a.points).sum()
+    }
+
+    /// Return the title of the highest rank reached with the current
+    /// score, if any.
+    pub fn rank(&self) -> Option<&str> {
+        let score = self.score();
+        self.scoring.ranks.iter().filter(|r| r.points <= score).max_by_key(|r| r.points).map(|r| r.title.as_str())
+    }
+
+    /// Return true if the award has been given.
+    pub fn is_awarded(&self, name: &str) -> bool {
+        self.scoring.awarded.iter().any(|a| a == name)
+    }
+
+    /// Give an award, unless it has been given before, telling the
+    /// player about the points.  Returns the points awarded.
+    pub fn award(&mut self, name: &str) -> u32 {
+        let points = match self.scoring.awards.get(name) {
+            Some(a) if !self.is_awarded(name) => a.points,
+            _ => return 0,
+        };
+        self.scoring.awarded.push(name.to_string());
+        say!("{}", msg!(self, "score_up", points = points));
+        points
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Print the score, the rank reached and the achievements.  The
+    /// end of the game shows them only if the world has scoring.
+    pub fn show_score(&self, at_end: bool) {
+        if self.world.scoring.awards.is_empty() {
+            if !at_end {
+                say!("{}", msg!(self.world, "no_score"));
+            }
+            return;
+        }
+        say!("{}", msg!(self.world, "score", score = self.world.score(), max = self.world.max_score(),
+                        turns = self.world.turn));
+        if let Some(r) = self.world.rank() {
+            say!("{}", msg!(self.world, "rank", rank = r));
+        }
+        let titles: Vec<&str> = self.world.scoring.awarded.iter()
+            .filter_map(|a| self.world.scoring.awards.get(a))
+            .map(|a| a.title.as_str())
+            .filter(|t| t.len() > 0)
+            .collect();
+        if titles.len() > 0 {
+            say!("{}", msg!(self.world, "achievements"));
+            for t in titles {
+                say!("  {}", t);
+            }
+        }
+    }
+}
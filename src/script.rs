@@ -0,0 +1,291 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Scripts: logic too complex for templates, written in Rhai.  Scripts
+//! are effects (see `trigger`), so they run when triggers fire on
+//! events and when rules take over verbs (see `rules`):
+//!
+//! ```text
+//! Triggered([
+//!     Trigger(on: "take", effects: [Script("
+//!         let n = property(entity, \"taken\") ?? 0;
+//!         set_property(entity, \"taken\", n + 1);
+//!         if n >= 2 { say(\"Der Boden bebt.\"); close(find(\"gate\")); }
+//!     ")]),
+//! ])
+//! ```
+//!
+//! Scripts see the world as it was when they started, through these
+//! functions, with entities named by their ids as strings:
+//!
+//! * `find(NAME)`: the entity with an alias, or called by words,
+//!   anywhere; `""` if there is none,
+//! * `name(ID)`, `location(ID)`, `holder(ID)`, `property(ID, KEY)`,
+//!   `state(ID)`, `locked(ID)`, `closed(ID)`, `carries(ID, ITEM)` and
+//!   `turn()`.
+//!
+//! They change it only with effects, carried out in order after the
+//! script is finished: `lock`, `unlock`, `open`, `close`, `destroy`,
+//! `give`, `move_to(ID, ROOM)`, `set_property(ID, KEY, VALUE)`,
+//! `set_state(ID, STATE)`, `award(NAME)` and `after(TURNS, SCRIPT)`,
+//! which schedules another script.  `say(TEXT)` shows text right away.
+//! The constants `player`, `room` and `entity` are the character causing
+//! the script to run, its room, and the entity whose trigger or rule
+//! it belongs to; they are `""` for scheduled scripts.
+//!
+//! Scripts cannot reach anything but the world, and are stopped when
+//! they run too long or build too large values.  Scripts need the
+//! `scripting` feature; without it, they show an error when run.
+
+use super::types::*;
+
+#[cfg(feature = "scripting")]
+use std::cell::RefCell;
+#[cfg(feature = "scripting")]
+use std::collections::BTreeMap;
+#[cfg(feature = "scripting")]
+use std::rc::Rc;
+
+#[cfg(feature = "scripting")]
+use rhai::{self, Dynamic, EvalAltResult, Scope};
+
+/// The most operations a script may carry out.
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 100000;
+
+/// What scripts see of an entity.
+#[cfg(feature = "scripting")]
+struct Entry {
+    name: Name,
+    tags: Vec<String>,
+    short: String,
+    holder: Option<InternalName>,
+    properties: BTreeMap<String, Property>,
+    state: Option<String>,
+    locked: Option<bool>,
+    closed: Option<bool>,
+    carried: Vec<InternalName>,
+}
+
+/// What scripts see of the world.
+#[cfg(feature = "scripting")]
+struct View {
+    entries: BTreeMap<InternalName, Entry>,
+    /// The entities, in the order of the world, for `find`.
+    order: Vec<InternalName>,
+    aliases: BTreeMap<String, InternalName>,
+    turn: u64,
+}
+
+#[cfg(feature = "scripting")]
+impl View {
+    fn new(world: &World) -> View {
+        let mut holders = BTreeMap::new();
+        for e in world.entities.iter() {
+            for a in e.attributes.iter() {
+                let held: Vec<&InternalName> = match *a {
+                    Attribute::Roomlike(ref r) => r.entities.iter().collect(),
+                    Attribute::Container(ref c) => c.contents.iter().collect(),
+                    Attribute::Characterlike(ref c) => c.inventory.iter().chain(c.worn.iter()).collect(),
+                    _ => vec![],
+                };
+                for h in held {
+                    holders.entry(*h).or_insert(e.id);
+                }
+            }
+        }
+        let entries = world.entities.iter().map(|e| (e.id, Entry {
+            name: e.name.clone(),
+            tags: e.tags.clone(),
+            short: world.short_description(&e.id),
+            holder: holders.get(&e.id).cloned(),
+            properties: e.properties.clone(),
+            state: e.machine().map(|m| m.state.clone()),
+            locked: e.lock().map(|l| l.locked),
+            closed: e.closed(),
+            carried: e.character().map(|c| c.inventory.iter().chain(c.worn.iter()).cloned().collect()).unwrap_or_default(),
+        })).collect();
+        View {
+            entries: entries,
+            order: world.entities.iter().map(|e| e.id).collect(),
+            aliases: world.alias_map.clone(),
+            turn: world.turn,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<InternalName> {
+        let words: Name = name.split_whitespace().map(|w| w.to_lowercase()).collect();
+        self.aliases.get(name).cloned().or_else(|| self.order.iter().find(|id| {
+            let e = &self.entries[id];
+            !words.is_empty() && words.iter().all(|w| e.name.contains(w) || e.tags.contains(w))
+        }).cloned())
+    }
+
+    /// Return the room an entity is in, directly or held by others.
+    fn location(&self, id: &InternalName) -> Option<InternalName> {
+        let mut id = *id;
+        while let Some(h) = self.entries.get(&id).and_then(|e| e.holder) {
+            if self.entries[&h].holder.is_none() {
+                return Some(h);
+            }
+            id = h;
+        }
+        None
+    }
+}
+
+/// Return the id of an entity as scripts see it.
+#[cfg(feature = "scripting")]
+fn show_id(id: Option<InternalName>) -> String {
+    id.map(|i| i.to_string()).unwrap_or_default()
+}
+
+/// Return the entity a script named by its id.
+#[cfg(feature = "scripting")]
+fn entity_id(id: &str) -> Result<InternalName, Box<EvalAltResult>> {
+    id.parse().map_err(|_| format!("no such entity: {:?}", id).into())
+}
+
+#[cfg(feature = "scripting")]
+fn to_dynamic(p: &Property) -> Dynamic {
+    match *p {
+        Property::Bool(b) => b.into(),
+        Property::Int(i) => i.into(),
+        Property::Str(ref s) => s.clone().into(),
+        Property::Reference(id) => id.to_string().into(),
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn to_property(d: Dynamic) -> Result<Property, Box<EvalAltResult>> {
+    if let Ok(b) = d.as_bool() {
+        Ok(Property::Bool(b))
+    } else if let Ok(i) = d.as_int() {
+        Ok(Property::Int(i))
+    } else if d.is_string() {
+        Ok(Property::Str(d.into_string().unwrap_or_default()))
+    } else {
+        Err(format!("invalid property value: {}", d).into())
+    }
+}
+
+/// Return the engine running scripts against a view of the world,
+/// collecting their effects.
+#[cfg(feature = "scripting")]
+fn engine(view: Rc<View>, effects: Rc<RefCell<Vec<Effect>>>) -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(10000);
+    engine.set_max_array_size(1000);
+    engine.set_max_map_size(1000);
+    engine.disable_symbol("eval");
+    engine.on_print(|s| say!("{}", s));
+    engine.on_debug(|_, _, _| {});
+    engine.register_fn("say", |s: &str| say!("{}", s));
+
+    let v = view.clone();
+    engine.register_fn("find", move |name: &str| show_id(v.find(name)));
+    let v = view.clone();
+    engine.register_fn("name", move |id: &str| {
+        entity_id(id).map(|i| v.entries.get(&i).map(|e| e.short.clone()).unwrap_or_default())
+    });
+    let v = view.clone();
+    engine.register_fn("location", move |id: &str| entity_id(id).map(|i| show_id(v.location(&i))));
+    let v = view.clone();
+    engine.register_fn("holder", move |id: &str| {
+        entity_id(id).map(|i| show_id(v.entries.get(&i).and_then(|e| e.holder)))
+    });
+    let v = view.clone();
+    engine.register_fn("property", move |id: &str, key: &str| {
+        entity_id(id).map(|i| v.entries.get(&i).and_then(|e| e.properties.get(key)).map_or(Dynamic::UNIT, to_dynamic))
+    });
+    let v = view.clone();
+    engine.register_fn("state", move |id: &str| {
+        entity_id(id).map(|i| v.entries.get(&i).and_then(|e| e.state.clone()).unwrap_or_default())
+    });
+    let v = view.clone();
+    engine.register_fn("locked", move |id: &str| {
+        entity_id(id).map(|i| v.entries.get(&i).and_then(|e| e.locked).unwrap_or(false))
+    });
+    let v = view.clone();
+    engine.register_fn("closed", move |id: &str| {
+        entity_id(id).map(|i| v.entries.get(&i).and_then(|e| e.closed).unwrap_or(false))
+    });
+    let v = view.clone();
+    engine.register_fn("carries", move |who: &str, item: &str| -> Result<bool, Box<EvalAltResult>> {
+        let (w, i) = (try!(entity_id(who)), try!(entity_id(item)));
+        Ok(v.entries.get(&w).map_or(false, |e| e.carried.contains(&i)))
+    });
+    let v = view.clone();
+    engine.register_fn("turn", move || v.turn as i64);
+
+    macro_rules! effect {
+        ($name:expr, $effect:path) => {
+            let fx = effects.clone();
+            engine.register_fn($name, move |id: &str| -> Result<(), Box<EvalAltResult>> {
+                fx.borrow_mut().push($effect(try!(entity_id(id))));
+                Ok(())
+            });
+        };
+    }
+    effect!("lock", Effect::Lock);
+    effect!("unlock", Effect::Unlock);
+    effect!("open", Effect::Open);
+    effect!("close", Effect::Close);
+    effect!("destroy", Effect::Destroy);
+    effect!("give", Effect::Give);
+    let fx = effects.clone();
+    engine.register_fn("move_to", move |id: &str, room: &str| -> Result<(), Box<EvalAltResult>> {
+        fx.borrow_mut().push(Effect::Move(try!(entity_id(id)), try!(entity_id(room))));
+        Ok(())
+    });
+    let fx = effects.clone();
+    engine.register_fn("set_property", move |id: &str, key: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+        fx.borrow_mut().push(Effect::SetProperty(try!(entity_id(id)), key.to_string(), try!(to_property(value))));
+        Ok(())
+    });
+    let fx = effects.clone();
+    engine.register_fn("set_state", move |id: &str, state: &str| -> Result<(), Box<EvalAltResult>> {
+        fx.borrow_mut().push(Effect::SetState(try!(entity_id(id)), state.to_string()));
+        Ok(())
+    });
+    let fx = effects.clone();
+    engine.register_fn("award", move |name: &str| fx.borrow_mut().push(Effect::Award(name.to_string())));
+    let fx = effects.clone();
+    engine.register_fn("after", move |turns: i64, script: &str| {
+        fx.borrow_mut().push(Effect::After(turns.max(0) as u64, vec![Effect::Script(script.to_string())]));
+    });
+    engine
+}
+
+impl World {
+    /// Run a script on behalf of the character `who`, if any, for the
+    /// entity `this`, if any, and carry out its effects.  Errors are
+    /// shown, and the effects up to the error are carried out.
+    #[cfg(feature = "scripting")]
+    pub fn run_script(&mut self, source: &str, who: Option<InternalName>, this: Option<InternalName>) {
+        let view = Rc::new(View::new(self));
+        let effects = Rc::new(RefCell::new(vec![]));
+        let mut scope = Scope::new();
+        scope.push_constant("player", show_id(who));
+        scope.push_constant("room", show_id(who.and_then(|w| view.location(&w))));
+        scope.push_constant("entity", show_id(this));
+        let res = engine(view, effects.clone()).run_with_scope(&mut scope, source);
+        if let Err(e) = res {
+            say!("{}", msg!(self, "error", error = e));
+        }
+        let effects = effects.replace(vec![]);
+        for effect in effects.iter() {
+            self.apply_effect_for(effect, who, this);
+        }
+    }
+
+    /// Run a script: without the `scripting` feature, this shows an
+    /// error.
+    #[cfg(not(feature = "scripting"))]
+    pub fn run_script(&mut self, _source: &str, _who: Option<InternalName>, _this: Option<InternalName>) {
+        say!("{}", msg!(self, "error", error = "scripts are not supported"));
+    }
+}
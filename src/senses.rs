@@ -0,0 +1,77 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Hearing and smelling.  Entities may have sound and smell
+//! descriptions, which players can notice by listening or sniffing,
+//! either at a single entity or at their whole surroundings.
+
+use super::types::*;
+use super::command::{Command, Connector};
+use super::error::Error;
+use super::print_wrap;
+
+/// Senses besides sight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sense {
+    Hearing,
+    Smell,
+}
+
+impl Entity {
+    /// Return the description of this entity for the given sense, if
+    /// it has one.
+    fn sense_description(&self, sense: Sense, locale: Option<&str>) -> Option<&str> {
+        match sense {
+            Sense::Hearing => self.sound_text(locale),
+            Sense::Smell => self.smell_text(locale),
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Listen or sniff.  With an object ("listen to guard", "smell
+    /// flask"), only that entity is sensed, otherwise the room and
+    /// everything in it and in the player's hands.
+    pub fn sense(&mut self, cmd: &Command, sense: Sense) -> Result<(), Error> {
+        let words = match (&cmd.direct_object, &cmd.indirect_object) {
+            (&Some(ref w), _) => Some(w),
+            (&None, &Some((Connector::To, ref w))) => Some(w),
+            _ => None,
+        };
+        let sources = match words {
+            Some(w) =>
+                match self.world.find_entity(w, &self.scope()) {
+                    Some(n) => vec![n],
+                    None => {
+                        say!("{}", msg!(self.world, "not_here"));
+                        return Ok(());
+                    }
+                },
+            None => {
+                let mut s = vec![self.location()];
+                s.extend(self.room_contents());
+                s.extend(self.me().inventory.iter().cloned());
+                s
+            }
+        };
+        let texts: Vec<String> = sources.iter()
+            .filter_map(|n| self.world.entity(n).and_then(|e| e.sense_description(sense, self.world.locale())).map(|s| s.to_string()))
+            .collect();
+        if texts.len() == 0 {
+            match sense {
+                Sense::Hearing => say!("{}", msg!(self.world, "hear_nothing")),
+                Sense::Smell => say!("{}", msg!(self.world, "smell_nothing")),
+            }
+            return Ok(());
+        }
+        for t in texts {
+            match self.eval_str(&t) {
+                Ok(s) =>
+                    print_wrap(&s, 72),
+                Err(e) =>
+                    say!("{}", msg!(self.world, "error", error = e))
+            }
+        }
+        Ok(())
+    }
+}
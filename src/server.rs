@@ -0,0 +1,935 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The server: a world played by many players at once, who connect
+//! with telnet clients or browsers.  Start it with
+//!
+//! ```text
+//! mudstuck serve world.ron --telnet 4000 --websocket 4001
+//! ```
+//!
+//! Players are asked for their names first.  Every connection gets a
+//! session with a character of its own, made from the world's player
+//! template and called like the player, and plays it like in the
+//! game on the console (see `session`).  Commands which would change
+//! the game for all players, like `save` or `locale`, are not
+//! available.  When a player leaves with `quit` or the connection is
+//! lost, the character leaves the world, and drops everything it
+//! carries.
+//!
+//! What clients send is limited (see `limits`): lines are cut off and
+//! cleaned before they reach the parser, and clients sending too many
+//! are punished as configured.
+//!
+//! Servers with accounts (see `accounts`) also ask for passwords, and
+//! make accounts for players new to them.  Players with accounts
+//! keep their characters, and everything they carry, when they leave.
+//!
+//! The server keeps no game state of its own: it carries out what
+//! players type with the engine's sessions (see `step`), and passes
+//! the output of each step on to the players it is for.  Every few
+//! seconds, a turn passes, and the players are told what happened
+//! around them meanwhile.
+//!
+//! The server tells players what happens with replies, which each
+//! protocol sends in its own way: telnet clients get the text and a
+//! prompt, browsers get JSON messages (see `websocket`).  After each
+//! command, the server also replies with the room the player is in,
+//! the player's vitals and inventory.  Browsers get these as JSON
+//! messages too, and MUD clients which agree to GMCP when the server
+//! offers it (see `telnet`) get them as GMCP messages:
+//!
+//! ```text
+//! Room.Info {"id": "936da01f-...", "name": "Ein feuchter Keller", "exits": ["eine Tür"]}
+//! Char.Vitals {"health": 10, "max_health": 10}
+//! Char.Items.Inv {"items": ["Eine Laterne"]}
+//! ```
+//!
+//! Servers configured to (see `ServerConfig`) also offer MCCP2 to
+//! telnet clients, and compress what they send to those which agree.
+//! Crawlers listing MUDs get the server's status with MSSP: its name,
+//! the number of players, when it was started, and the fields
+//! configured.
+//!
+//! Servers can keep an audit log of the commands players type, and
+//! what came of them (see `audit`).
+//!
+//! Servers keeping a journal (see `snapshot`) lose at most the line
+//! being carried out when they crash.  Started again with the same
+//! journal, they pick up where they stopped; the players who were
+//! connected have left, like after losing their connections.
+//!
+//! The world is never shared between threads.  It belongs to the
+//! server, and only the thread running the server touches it, one
+//! command after the other; there are no locks around it.  Each
+//! connection has a thread of its own reading from it, which passes
+//! what it reads on to the server thread as messages over a channel.
+//! The server thread answers by writing to telnet connections,
+//! disconnecting clients which do not take what it writes in time, and
+//! by passing messages for browsers back to the threads serving them,
+//! which alone write to their WebSockets.  The
+//! output of the game is captured per thread (see `output`), so it
+//! is captured where the commands are carried out.
+//!
+//! For that, the server and everything it owns can be sent to another
+//! thread, so a server can be set up on one thread and run on
+//! another; this is checked when the crate is compiled.  Worlds are
+//! not `Sync`: listeners subscribed to events may change their own
+//! state when called, so a world must not be used from two threads
+//! at once.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+use super::step::{Output, OutputEvent, SessionId};
+use super::roles::Role;
+use super::accounts::Accounts;
+use super::metrics::Metrics;
+use super::audit::{AuditLog, Outcome};
+use super::storage::Storage;
+use super::limits::{Limiter, Limits, Verdict};
+use super::output;
+use super::telnet;
+use super::websocket::{self, Frame, Outgoing};
+
+/// Commands of the game on the console which are not available on
+/// the server, because they would change the game for all players
+/// or write files on the server.
+const LOCAL_COMMANDS: &'static [&'static str] =
+    &["save", "restore", "undo", "checkpoint", "autosave", "transcript", "dot", "locale", "become"];
+
+/// How often servers keeping a journal take snapshots.
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often a turn passes in the world of a server (see `step`).
+pub const TURN_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long writing to a client may take before the client is
+/// disconnected, so clients which stop reading cannot hold up the
+/// server thread.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The protocols players can connect with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Telnet,
+    WebSocket,
+}
+
+/// How a server serves its world.
+#[derive(Clone, Debug, Default)]
+pub struct ServerConfig {
+    /// Whether telnet clients are offered compression with MCCP2,
+    /// which needs the `compression` feature.
+    pub compression: bool,
+    /// The name crawlers list the server by, if not the world's.
+    pub name: Option<String>,
+    /// More fields of the status reported to crawlers, like
+    /// `CONTACT` or `WEBSITE`.
+    pub status: BTreeMap<String, String>,
+    /// The limits on what clients send.
+    pub limits: Limits,
+}
+
+/// Something the server tells a player.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Reply {
+    /// Text shown by the game.
+    Text { text: String },
+    /// The room the player is in, with the doors leading out of it.
+    Room { id: String, name: String, exits: Vec<String> },
+    /// The player's health.
+    Vitals { health: i32, max_health: i32 },
+    /// What the player carries.
+    Inventory { items: Vec<String> },
+    /// The server waits for the next command.
+    Prompt,
+    /// A message from the player was not understood.
+    Error { text: String },
+    /// The server closes the connection.
+    Goodbye,
+}
+
+impl From<Output> for Reply {
+    fn from(output: Output) -> Reply {
+        match output {
+            Output::Text { text } => Reply::Text { text: text },
+            Output::Room { id, name, exits } => Reply::Room { id: id, name: name, exits: exits },
+            Output::Vitals { health, max_health } => Reply::Vitals { health: health, max_health: max_health },
+            Output::Inventory { items } => Reply::Inventory { items: items },
+            Output::Over => Reply::Goodbye,
+        }
+    }
+}
+
+/// Something happening on a connection, reported to the server
+/// thread.
+enum Message {
+    Connected(usize, Writer, Protocol),
+    Input(usize, String),
+    Invalid(usize, String),
+    /// A telnet client agreed to an option, or refused it.
+    Agreed(usize, u8, bool),
+    /// A client sent too much, and is disconnected.
+    Flooded(usize),
+    Disconnected(usize),
+}
+
+/// Where the server thread writes what it sends a client.
+enum Writer {
+    /// Telnet connections are written to directly.
+    Stream(TcpStream),
+    /// Messages for browsers go to the thread serving the connection.
+    WebSocket(Sender<Outgoing>),
+}
+
+/// A connected player.
+struct Client {
+    /// The player's session, once the player has given a name.
+    session: Option<SessionId>,
+    writer: Writer,
+    protocol: Protocol,
+    /// Whether the client gets GMCP messages.
+    gmcp: bool,
+    /// Compresses what is sent to the client, once it agreed to it.
+    compressor: Option<telnet::Compressor>,
+    /// The name the player has given, while the server asks for the
+    /// password.
+    login: Option<String>,
+}
+
+/// A world served to many players.
+pub struct Server {
+    engine: Engine,
+    config: ServerConfig,
+    accounts: Option<Accounts>,
+    clients: BTreeMap<usize, Client>,
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+    /// The number of connections accepted so far, on all listeners.
+    accepted: Arc<AtomicUsize>,
+    /// When the server was started, in seconds since the epoch.
+    started: u64,
+    /// Where the commands of players are logged, if anywhere.
+    audit: Option<AuditLog>,
+}
+
+/// Fails to compile if one of the types the server thread owns
+/// cannot be sent to it.
+#[allow(dead_code)]
+fn assert_send() {
+    fn send<T: Send>() {}
+    send::<World>();
+    send::<Engine>();
+    send::<Session>();
+    send::<Server>();
+}
+
+/// Pass what a client sent on to the server thread, if the limits
+/// allow it.  Returns false if the connection is to be closed.
+fn pass_on(id: usize, input: Result<String, String>, limiter: &mut Limiter, tx: &Sender<Message>) -> bool {
+    let verdict = limiter.check(input.as_ref().map(|l| l.as_str()).unwrap_or(""), Instant::now());
+    let msg = match (verdict, input) {
+        (Verdict::Accept(line), Ok(_)) => Message::Input(id, line),
+        (Verdict::Accept(_), Err(e)) => Message::Invalid(id, e),
+        (Verdict::Reject(why), _) => Message::Invalid(id, why),
+        (Verdict::Ignore, _) => return true,
+        (Verdict::Disconnect, _) => {
+            let _ = tx.send(Message::Flooded(id));
+            return false;
+        },
+    };
+    tx.send(msg).is_ok()
+}
+
+/// Read lines from a telnet connection, passing them on to the
+/// server thread, until the connection is closed.
+fn read_lines(id: usize, mut stream: TcpStream, limits: Limits, tx: &Sender<Message>) {
+    // Characters take up to four bytes.
+    let mut decoder = telnet::Decoder::with_max_line(limits.max_line * 4);
+    let mut limiter = Limiter::new(limits);
+    let mut buf = [0; 1024];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let lines = decoder.feed(&buf[..n]);
+                for (command, option) in decoder.take_negotiations() {
+                    let on = match command {
+                        telnet::DO => true,
+                        telnet::DONT => false,
+                        _ => continue,
+                    };
+                    // The server answers negotiations, some with its
+                    // whole status, so they are limited like lines.
+                    match count(id, &mut limiter, tx) {
+                        Some(true) => if tx.send(Message::Agreed(id, option, on)).is_err() {
+                            return;
+                        },
+                        Some(false) => {},
+                        None => return,
+                    }
+                }
+                for line in lines {
+                    if !pass_on(id, Ok(line), &mut limiter, tx) {
+                        return;
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Count something a client sent which holds no line, like a ping or
+/// a telnet negotiation, against the limits.  Returns whether it is
+/// to be answered, or nothing if the connection is to be closed.
+fn count(id: usize, limiter: &mut Limiter, tx: &Sender<Message>) -> Option<bool> {
+    match limiter.check("", Instant::now()) {
+        Verdict::Accept(_) => Some(true),
+        Verdict::Ignore => Some(false),
+        Verdict::Reject(why) => tx.send(Message::Invalid(id, why)).ok().map(|_| false),
+        Verdict::Disconnect => {
+            let _ = tx.send(Message::Flooded(id));
+            None
+        },
+    }
+}
+
+/// Serve a browser: send it what the server thread passes on, and
+/// pass the lines typed on to the server thread, until the
+/// connection is closed.
+fn serve_browser(id: usize, mut conn: websocket::Connection, limits: Limits, tx: &Sender<Message>,
+                 outgoing: Receiver<Outgoing>) {
+    let mut limiter = Limiter::new(limits);
+    loop {
+        loop {
+            match outgoing.try_recv() {
+                Ok(Outgoing::Text(msg)) =>
+                    if !conn.send(msg) {
+                        return;
+                    },
+                Ok(Outgoing::Close) | Err(TryRecvError::Disconnected) => return conn.close(),
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        let open = match conn.read() {
+            Frame::Text(msg) => pass_on(id, websocket::decode(&msg), &mut limiter, tx),
+            Frame::Other => count(id, &mut limiter, tx).is_some(),
+            Frame::Idle => true,
+            Frame::Closed => return,
+        };
+        if !open {
+            break;
+        }
+    }
+    // Send the server's goodbye before closing.
+    while let Ok(Outgoing::Text(msg)) = outgoing.recv() {
+        if !conn.send(msg) {
+            return;
+        }
+    }
+    conn.close();
+}
+
+/// Serve a connection: greet the client in its protocol, then read
+/// from it until it is closed.
+fn serve_connection(id: usize, stream: TcpStream, protocol: Protocol, limits: Limits, tx: Sender<Message>) {
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    match protocol {
+        Protocol::Telnet => {
+            let writer = match stream.try_clone() {
+                Ok(w) => w,
+                Err(_) => return,
+            };
+            if tx.send(Message::Connected(id, Writer::Stream(writer), protocol)).is_err() {
+                return;
+            }
+            read_lines(id, stream, limits, &tx);
+        },
+        Protocol::WebSocket => {
+            // Nothing may be sent before the handshake is answered.
+            // Messages hold more than the line typed.
+            let conn = match websocket::Connection::accept(stream, limits.max_line * 4 + 1024) {
+                Ok(c) => c,
+                Err(_) => return,
+            };
+            let (out, outgoing) = channel();
+            if tx.send(Message::Connected(id, Writer::WebSocket(out), protocol)).is_err() {
+                return;
+            }
+            serve_browser(id, conn, limits, &tx, outgoing);
+        },
+    }
+    let _ = tx.send(Message::Disconnected(id));
+}
+
+/// Return true if the line is a command which is not available on
+/// the server.
+fn is_local(line: &str) -> bool {
+    let word = line.split_whitespace().next().unwrap_or("");
+    LOCAL_COMMANDS.contains(&word)
+}
+
+/// Return the GMCP package of a reply, if it is sent as GMCP
+/// message.
+fn gmcp_package(reply: &Reply) -> Option<&'static str> {
+    match *reply {
+        Reply::Room { .. } => Some("Room.Info"),
+        Reply::Vitals { .. } => Some("Char.Vitals"),
+        Reply::Inventory { .. } => Some("Char.Items.Inv"),
+        _ => None,
+    }
+}
+
+/// Return a reply as sent to a telnet client, which gets GMCP
+/// messages if `gmcp` is true.  Their data is the reply as sent to
+/// browsers, without its type.
+fn telnet_encode(reply: &Reply, gmcp: bool) -> Vec<u8> {
+    if let Some(package) = gmcp_package(reply) {
+        if !gmcp {
+            return vec![];
+        }
+        let mut data = serde_json::to_value(reply).unwrap_or_default();
+        if let Some(m) = data.as_object_mut() {
+            m.remove("type");
+        }
+        return telnet::gmcp(package, &data.to_string());
+    }
+    match *reply {
+        Reply::Text { ref text } => telnet::encode(text),
+        Reply::Prompt => telnet::encode("> "),
+        Reply::Error { ref text } => telnet::encode(&format!("{}\n", text)),
+        Reply::Goodbye => telnet::encode("Goodbye.\n"),
+        _ => vec![],
+    }
+}
+
+impl Server {
+    /// Serve the world, with the random number generator seeded with
+    /// `seed`.
+    pub fn new(world: World, seed: u64) -> Server {
+        Server::with_config(world, seed, ServerConfig::default())
+    }
+
+    /// Serve the world as configured.
+    pub fn with_config(world: World, seed: u64, config: ServerConfig) -> Server {
+        let (tx, rx) = channel();
+        Server {
+            engine: Engine::shared(world, seed),
+            config: config,
+            accounts: None,
+            clients: BTreeMap::new(),
+            tx: tx,
+            rx: rx,
+            accepted: Arc::new(AtomicUsize::new(0)),
+            started: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            audit: None,
+        }
+    }
+
+    /// Give a role to the account with the given name, like to the
+    /// first wizards (see `roles`).  Only servers with accounts have
+    /// roles.
+    pub fn grant(&mut self, name: &str, role: Role) -> Result<(), String> {
+        if self.accounts.is_none() {
+            return Err("roles need accounts".to_string());
+        }
+        self.engine.grant(name, role);
+        Ok(())
+    }
+
+    /// Report what the engine does to `metrics` (see `metrics`).
+    pub fn observe<M: Metrics + 'static>(&mut self, metrics: M) {
+        self.engine.observe(metrics);
+    }
+
+    /// Log the commands of players, and what came of them, to the file
+    /// at `path` (see `audit`).
+    pub fn audit<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.audit = Some(try!(AuditLog::open(path, &mut self.engine.world)));
+        Ok(())
+    }
+
+    /// Stop logging the commands of players.
+    pub fn stop_audit(&mut self) {
+        self.audit = None;
+    }
+
+    /// Let players log in with the given accounts, and make accounts
+    /// for players new to them.
+    pub fn use_accounts(&mut self, accounts: Accounts) {
+        self.accounts = Some(accounts);
+    }
+
+    /// Keep the world's state, and the accounts, in `storage` (see
+    /// `storage`).  If a state is kept there already, the world is put
+    /// back into it, and the players of its sessions leave.  The state
+    /// is kept whenever players come or go.
+    pub fn use_storage(&mut self, storage: Arc<Storage>) -> Result<(), String> {
+        self.accounts = Some(try!(Accounts::open(storage.clone())));
+        self.engine.use_storage(storage);
+        if try!(self.engine.load_state()) {
+            let stale: Vec<SessionId> = self.engine.sessions.keys().cloned().collect();
+            for id in stale {
+                self.leave(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep a journal of the world at `path`, taking snapshots every
+    /// `every`.  If there is a journal at `path` already, the world is
+    /// recovered from it first, and the players of its sessions leave.
+    /// Returns the number of lines stepped again.
+    pub fn keep_journal<P: Into<PathBuf>>(&mut self, path: P, every: Duration) -> Result<usize, String> {
+        let path = path.into();
+        let mut stepped = 0;
+        if path.exists() {
+            stepped = try!(self.engine.recover(&path));
+            let stale: Vec<SessionId> = self.engine.sessions.keys().cloned().collect();
+            for id in stale {
+                self.leave(id);
+            }
+        }
+        try!(self.engine.keep_journal(path, every));
+        Ok(stepped)
+    }
+
+    /// Accept connections of clients speaking the protocol on the
+    /// listener, in the background.  The clients are served once the
+    /// server runs.
+    pub fn listen(&mut self, listener: TcpListener, protocol: Protocol) -> io::Result<()> {
+        if protocol == Protocol::WebSocket && !websocket::supported() {
+            return Err(io::Error::new(io::ErrorKind::Other, "WebSocket is not supported in this build"));
+        }
+        if protocol == Protocol::Telnet && self.config.compression && !telnet::compression_supported() {
+            return Err(io::Error::new(io::ErrorKind::Other, "compression is not supported in this build"));
+        }
+        let tx = self.tx.clone();
+        let accepted = self.accepted.clone();
+        let limits = self.config.limits;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let id = accepted.fetch_add(1, Ordering::SeqCst);
+                let tx = tx.clone();
+                thread::spawn(move || serve_connection(id, stream, protocol, limits, tx));
+            }
+        });
+        Ok(())
+    }
+
+    /// Serve the world to the clients connecting, letting a turn pass
+    /// every `TURN_INTERVAL`.  Never returns.
+    pub fn run(&mut self) {
+        let mut next_turn = Instant::now() + TURN_INTERVAL;
+        loop {
+            let now = Instant::now();
+            if now >= next_turn {
+                self.turn();
+                next_turn = now + TURN_INTERVAL;
+            }
+            let msg = match self.rx.recv_timeout(next_turn - now) {
+                Ok(m) => m,
+                Err(RecvTimeoutError::Timeout) => continue,
+                // Cannot happen, the server keeps a sender itself.
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            match msg {
+                Message::Connected(id, writer, protocol) => self.connect(id, writer, protocol),
+                Message::Input(id, line) => self.input(id, line.trim()),
+                Message::Invalid(id, error) => {
+                    self.send(id, &Reply::Error { text: error });
+                    self.send(id, &Reply::Prompt);
+                },
+                Message::Agreed(id, option, on) => self.agreed(id, option, on),
+                Message::Flooded(id) => {
+                    self.send(id, &Reply::Error { text: "You are typing too fast.".to_string() });
+                    self.send(id, &Reply::Goodbye);
+                    self.disconnect(id);
+                },
+                Message::Disconnected(id) => self.disconnect(id),
+            }
+        }
+    }
+
+    /// Let a turn pass, and tell the players what they noticed.
+    fn turn(&mut self) {
+        let events = self.engine.tick();
+        let mut told = vec![];
+        for event in events {
+            let session = event.session;
+            if !told.contains(&session) {
+                told.push(session);
+            }
+            // Prompts are sent once per player below.
+            self.emit(event, session);
+        }
+        for session in told {
+            let id = self.clients.iter().find(|&(_, c)| c.session == Some(session)).map(|(id, _)| *id);
+            if let Some(id) = id {
+                self.send(id, &Reply::Prompt);
+            }
+        }
+    }
+
+    /// Send a reply to a client.
+    fn send(&mut self, id: usize, reply: &Reply) {
+        let failed = match self.clients.get(&id) {
+            Some(&Client { writer: Writer::WebSocket(ref out), .. }) =>
+                out.send(Outgoing::Text(websocket::encode(reply))).is_err(),
+            Some(c) => {
+                let data = telnet_encode(reply, c.gmcp);
+                return self.write(id, data);
+            },
+            None => return,
+        };
+        if failed {
+            self.disconnect(id);
+        }
+    }
+
+    /// Write data to a telnet client, compressed if the client asked
+    /// for it.  A client which cannot be written to in time is
+    /// disconnected.
+    fn write(&mut self, id: usize, mut data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        let failed = match self.clients.get_mut(&id) {
+            Some(&mut Client { writer: Writer::Stream(ref mut stream), ref mut compressor, .. }) => {
+                if let Some(ref mut z) = *compressor {
+                    data = z.compress(&data);
+                }
+                stream.write_all(&data).is_err()
+            },
+            _ => false,
+        };
+        if failed {
+            self.disconnect(id);
+        }
+    }
+
+    /// Send output of the engine to the client playing the session it
+    /// is for.  Output for other sessions than the one of the command,
+    /// like what was said to them, gets a prompt of its own.
+    fn emit(&mut self, event: OutputEvent, current: SessionId) {
+        let id = match self.clients.iter().find(|&(_, c)| c.session == Some(event.session)) {
+            Some((id, _)) => *id,
+            None => return,
+        };
+        match event.output {
+            // The engine has closed the session.
+            Output::Over => self.disconnect(id),
+            output => {
+                self.send(id, &Reply::from(output));
+                if event.session != current {
+                    self.send(id, &Reply::Prompt);
+                }
+            },
+        }
+    }
+
+    /// Send text shown by the game to a client, if there is any.
+    fn send_text(&mut self, id: usize, text: String) {
+        if !text.is_empty() {
+            self.send(id, &Reply::Text { text: text });
+        }
+    }
+
+    /// Welcome a new player, and ask for a name.
+    fn connect(&mut self, id: usize, writer: Writer, protocol: Protocol) {
+        let client = Client {
+            session: None,
+            writer: writer,
+            protocol: protocol,
+            gmcp: false,
+            compressor: None,
+            login: None,
+        };
+        self.clients.insert(id, client);
+        if protocol == Protocol::Telnet {
+            let mut offers = vec![telnet::IAC, telnet::WILL, telnet::GMCP];
+            if self.config.compression {
+                offers.extend_from_slice(&[telnet::IAC, telnet::WILL, telnet::MCCP2]);
+            }
+            offers.extend_from_slice(&[telnet::IAC, telnet::WILL, telnet::MSSP]);
+            self.write(id, offers);
+        }
+        let txt = format!("Welcome to {}!\nWhat is your name?\n", self.engine.world.name);
+        self.send_text(id, txt);
+        self.send(id, &Reply::Prompt);
+    }
+
+    /// Return the status of the server reported to crawlers, as MSSP
+    /// variables and their values.
+    fn status(&self) -> Vec<(String, String)> {
+        let name = self.config.name.clone().unwrap_or(self.engine.world.name.clone());
+        let players = self.clients.values().filter(|c| c.session.is_some()).count();
+        let mut res = vec![
+            ("NAME".to_string(), name),
+            ("PLAYERS".to_string(), players.to_string()),
+            ("UPTIME".to_string(), self.started.to_string()),
+        ];
+        res.extend(self.config.status.iter().map(|(k, v)| (k.clone(), v.clone())));
+        res
+    }
+
+    /// Turn an option a telnet client agreed to on, or one it refused
+    /// off.
+    fn agreed(&mut self, id: usize, option: u8, on: bool) {
+        if option == telnet::MSSP && on {
+            let status = telnet::mssp(&self.status());
+            return self.write(id, status);
+        }
+        let compression = self.config.compression;
+        let c = match self.clients.get_mut(&id) {
+            Some(c) if c.protocol == Protocol::Telnet => c,
+            _ => return,
+        };
+        match option {
+            telnet::GMCP => c.gmcp = on,
+            telnet::MCCP2 if on && compression && c.compressor.is_none() => {
+                // Everything after this subnegotiation is compressed.
+                self.write(id, vec![telnet::IAC, telnet::SB, telnet::MCCP2, telnet::IAC, telnet::SE]);
+                if let Some(c) = self.clients.get_mut(&id) {
+                    c.compressor = Some(telnet::Compressor::new());
+                }
+            },
+            telnet::MCCP2 if !on =>
+                if let Some(z) = c.compressor.take() {
+                    self.write(id, z.finish());
+                },
+            _ => {},
+        }
+    }
+
+    /// Return the error in a name a player has given, if there is one.
+    /// Names are single words, and no two players can have the same.
+    fn check_name(&self, name: &str) -> Option<&'static str> {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphabetic()) {
+            return Some("Names are single words, made of letters only.");
+        }
+        let taken = self.clients.values().filter_map(|c| c.session)
+            .filter_map(|s| self.engine.session(s))
+            .filter_map(|s| self.engine.world.entity(&s.player))
+            .any(|e| e.name == vec![name.to_lowercase()]);
+        if taken {
+            return Some("Someone of that name is already playing.");
+        }
+        None
+    }
+
+    /// Handle a line typed by a player who has not joined yet: the
+    /// player's name, and the password if the server has accounts.
+    fn login(&mut self, id: usize, line: &str) {
+        let name = match self.clients.get_mut(&id).and_then(|c| c.login.take()) {
+            Some(n) => n,
+            None if self.accounts.is_none() => return self.join(id, line),
+            None => {
+                if let Some(e) = self.check_name(line) {
+                    self.send(id, &Reply::Error { text: e.to_string() });
+                    return self.send(id, &Reply::Prompt);
+                }
+                let known = self.accounts.as_ref().map_or(false, |a| a.get(line).is_some());
+                let txt = if known {
+                    "What is your password?\n".to_string()
+                } else {
+                    format!("Nobody called {} has played here before.\nChoose a password:\n", line)
+                };
+                if let Some(c) = self.clients.get_mut(&id) {
+                    c.login = Some(line.to_string());
+                }
+                self.send_text(id, txt);
+                self.echo(id, false);
+                return self.send(id, &Reply::Prompt);
+            },
+        };
+        self.echo(id, true);
+        let res = match self.accounts {
+            Some(ref a) if a.get(&name).is_some() =>
+                if a.authenticate(&name, line) { Ok(()) } else { Err("Wrong password.".to_string()) },
+            Some(ref mut a) => a.create(&name, line),
+            None => Ok(()),
+        };
+        match res {
+            Ok(()) => self.join(id, &name),
+            Err(e) => {
+                self.send(id, &Reply::Error { text: e });
+                self.send_text(id, "What is your name?\n".to_string());
+                self.send(id, &Reply::Prompt);
+            },
+        }
+    }
+
+    /// Stop telnet clients from showing what the player types, while
+    /// passwords are typed, or let them show it again.
+    fn echo(&mut self, id: usize, on: bool) {
+        if self.clients.get(&id).map_or(false, |c| c.protocol == Protocol::Telnet) {
+            let command = if on { telnet::WONT } else { telnet::WILL };
+            self.write(id, vec![telnet::IAC, command, telnet::ECHO]);
+        }
+    }
+
+    /// Let a player who has given a name into the world, with the
+    /// character of the player's account or else a new one.
+    fn join(&mut self, id: usize, name: &str) {
+        if let Some(e) = self.check_name(name) {
+            self.send(id, &Reply::Error { text: e.to_string() });
+            self.send(id, &Reply::Prompt);
+            return;
+        }
+        let linked = self.accounts.as_ref().and_then(|a| a.get(name))
+            .and_then(|a| a.character.map(|c| (c, a.room)));
+        let mut session = match linked.and_then(|(c, room)| self.engine.rejoin(c, room)) {
+            Some(s) => s,
+            None => {
+                let s = self.engine.join(name);
+                if let Some(ref mut a) = self.accounts {
+                    if let Err(e) = a.link(name, Some(s.player), None) {
+                        eprintln!("Cannot save the accounts: {}", e);
+                    }
+                }
+                s
+            },
+        };
+        if self.accounts.is_some() {
+            session.account = Some(name.to_lowercase());
+        }
+        let session = self.engine.attach(session);
+        self.checkpoint();
+        if let Some(c) = self.clients.get_mut(&id) {
+            c.session = Some(session);
+        }
+        let (_, txt) = output::capture(|| {
+            say!("Hello, {}!", name);
+            say!("If you don't know what to do, type \"help\" (without the quotes).");
+            say!("To leave the game, type \"quit\".");
+            say!("");
+        });
+        self.send_text(id, txt);
+        self.input(id, "look");
+    }
+
+    /// Handle a line typed by a player.
+    fn input(&mut self, id: usize, line: &str) {
+        let (session, password) = match self.clients.get(&id) {
+            Some(c) => (c.session, c.login.is_some()),
+            None => return,
+        };
+        if (line == "quit" || line == "q") && !password {
+            self.send(id, &Reply::Goodbye);
+            self.disconnect(id);
+            return;
+        }
+        let session = match session {
+            Some(s) => s,
+            None => return self.login(id, line),
+        };
+        let player = self.engine.session(session).map(|s| self.engine.player_name(s)).unwrap_or_default();
+        if is_local(line) {
+            let reason = "That command is not available on the server.".to_string();
+            self.log(session, &player, line, &Outcome::Refused { reason: reason.clone() });
+            self.send(id, &Reply::Error { text: reason });
+        } else {
+            if let Some(ref mut a) = self.audit {
+                a.begin();
+            }
+            let events = self.engine.step(session, line);
+            if let Some(outcome) = self.audit.as_mut().map(|a| a.outcome(session, &events)) {
+                self.log(session, &player, line, &outcome);
+            }
+            for event in events {
+                self.emit(event, session);
+            }
+        }
+        // Unless the client is gone.
+        if self.clients.contains_key(&id) {
+            self.send(id, &Reply::Prompt);
+        }
+    }
+
+    /// Write a command to the audit log, if one is kept.
+    fn log(&mut self, session: SessionId, player: &str, line: &str, outcome: &Outcome) {
+        if let Some(ref mut a) = self.audit {
+            if let Err(e) = a.write(session, player, line, outcome) {
+                eprintln!("Cannot write the audit log: {}", e);
+            }
+        }
+    }
+
+    /// Take the character of a player who left out of the world.  The
+    /// characters of accounts are kept for when their players return.
+    fn leave(&mut self, id: SessionId) {
+        let session = match self.engine.close(id) {
+            Some(s) => s,
+            None => return,
+        };
+        // Sessions kept before they had accounts go by the name of
+        // their characters.
+        let name = session.account.clone().unwrap_or_else(|| self.engine.player_name(&session));
+        match self.accounts {
+            Some(ref mut a) => {
+                let character = session.player;
+                let room = self.engine.park(session);
+                if let Err(e) = a.link(&name, Some(character), room) {
+                    eprintln!("Cannot save the accounts: {}", e);
+                }
+            },
+            None => self.engine.leave(session),
+        }
+        self.checkpoint();
+    }
+
+    /// Take a snapshot for the journal, and keep the world's state in
+    /// storage, after players came or went.
+    fn checkpoint(&mut self) {
+        if let Err(e) = self.engine.checkpoint() {
+            eprintln!("Cannot write the journal: {}", e);
+        }
+        if let Err(e) = self.engine.save_state() {
+            eprintln!("Cannot keep the world state: {}", e);
+        }
+    }
+
+    /// Take a player who left out of the world.
+    fn disconnect(&mut self, id: usize) {
+        let client = match self.clients.remove(&id) {
+            Some(c) => c,
+            None => return,
+        };
+        match client.writer {
+            Writer::Stream(mut stream) => {
+                if let Some(z) = client.compressor {
+                    let _ = stream.write_all(&z.finish());
+                }
+                let _ = stream.shutdown(Shutdown::Both);
+            },
+            // The thread serving the browser sends the close frame.
+            Writer::WebSocket(out) => {
+                let _ = out.send(Outgoing::Close);
+            },
+        }
+        if let Some(s) = client.session {
+            self.leave(s);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Sessions: players sharing a world, each with a character of its
+//! own.  The engine plays the characters of many sessions in turn,
+//! and other players show up in rooms like other characters do; they
+//! can be looked at, given things or attacked.
+//!
+//! A session keeps what the parser remembers between the lines its
+//! player types:
+//!
+//! * what `it` and `them` stand for: the object of the last command,
+//!   as in `take lantern`, then `light it`,
+//! * a command waiting for its object after the game asked for it, as
+//!   in `take`, then `lantern` after "Was möchtest du nehmen?", and
+//! * aliases the player named for commands, with `alias NAME COMMAND`,
+//!   listed with `alias` and removed with `unalias NAME`.
+//!
+//! Players in sessions can also talk to each other (see `chat`), and
+//! builders and wizards can change the world while it is played (see
+//! `wizard` and `roles`).
+
+use std::collections::BTreeMap;
+
+use super::types::*;
+use super::command;
+use super::engine::{self, Engine};
+use super::chat::DEFAULT_CHANNEL;
+
+/// Words standing for the object of the last command.
+const PRONOUNS: &'static [&'static str] = &["it", "them"];
+
+/// A player playing a shared world.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// The player's entity.
+    pub player: InternalName,
+    /// The object of the last command, which pronouns stand for.
+    object: Option<String>,
+    /// The command the game asked the object of.
+    pending: Option<String>,
+    /// Commands named by the player, by name.
+    pub aliases: BTreeMap<String, String>,
+    /// The lowercase name of the account the player logged in with,
+    /// if any, which roles are given to (see `roles`).
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+impl Session {
+    /// Start a session playing the given character.
+    pub fn new(player: InternalName) -> Session {
+        Session { player: player, object: None, pending: None, aliases: BTreeMap::new(), account: None }
+    }
+
+    /// Return the command a line typed stands for: aliases are
+    /// replaced, pronouns stand for the last object, and a line which
+    /// is not a command completes the command the game asked about.
+    pub fn expand(&self, line: &str) -> String {
+        let line = self.resolve_alias(line);
+        let mut res = line.split_whitespace()
+            .map(|w| match self.object {
+                Some(ref o) if PRONOUNS.contains(&w.to_lowercase().as_str()) => o.as_str(),
+                _ => w,
+            })
+            .collect::<Vec<&str>>().join(" ");
+        if let Some(ref p) = self.pending {
+            if !res.is_empty() && command::parse(&res).is_err() && !engine::is_meta(&res) {
+                res = format!("{} {}", p, res);
+            }
+        }
+        res
+    }
+
+    /// Return a line with the alias it starts with, if any, replaced
+    /// by its command.
+    pub fn resolve_alias(&self, line: &str) -> String {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let alias = words.first().and_then(|w| self.aliases.get(*w));
+        if let Some(a) = alias {
+            words[0] = a.as_str();
+        }
+        words.join(" ")
+    }
+
+    /// Remember what a command carried out refers to.
+    fn remember(&mut self, line: &str) {
+        self.pending = None;
+        if let Ok(cmd) = command::parse(line) {
+            match cmd.direct_object {
+                Some(ref o) if cmd.verb != command::Verb::Move => self.object = Some(o.join(" ")),
+                Some(_) => {},
+                None => if cmd.verb.needs_object() {
+                    self.pending = Some(line.to_string());
+                },
+            }
+        }
+    }
+
+    /// Handle the commands for aliases.  Returns false if the line is
+    /// not one of them.
+    fn alias_command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("alias") => match words.next() {
+                None if self.aliases.is_empty() => say!("No aliases."),
+                None =>
+                    for (name, cmd) in self.aliases.iter() {
+                        say!("{} = {}", name, cmd);
+                    },
+                Some(name) => {
+                    let cmd = words.collect::<Vec<&str>>().join(" ");
+                    if cmd.is_empty() {
+                        say!("Usage: alias NAME COMMAND");
+                    } else {
+                        say!("{} = {}", name, cmd);
+                        self.aliases.insert(name.to_string(), cmd);
+                    }
+                },
+            },
+            Some("unalias") => match words.next().and_then(|n| self.aliases.remove(n)) {
+                Some(_) => say!("Alias removed."),
+                None => say!("No such alias."),
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Engine {
+    /// Add a player called `name` to the world, with a new character
+    /// made from the world's player template at the start location.
+    pub fn join(&mut self, name: &str) -> Session {
+        let id = self.world.add_starting_player();
+        if let Some(e) = self.world.entity_mut(&id) {
+            e.name = name.split_whitespace().map(|w| w.to_lowercase()).collect();
+            e.short_description = name.to_string();
+        }
+        self.chat.join(id, DEFAULT_CHANNEL);
+        Session::new(id)
+    }
+
+    /// Handle a line typed in a session.  Returns false if the
+    /// session's game is over.
+    pub fn handle_session(&mut self, session: &mut Session, line: &str) -> bool {
+        if session.alias_command(line) {
+            return true;
+        }
+        let resolved = session.resolve_alias(line);
+        if self.chat_command(session, &resolved) || self.wizard_command(session, &resolved) {
+            return true;
+        }
+        let line = session.expand(line);
+        self.player = session.player;
+        let alive = self.handle(&line);
+        session.player = self.player;
+        session.remember(&line);
+        alive
+    }
+
+    /// Take the player of a session out of the world.  Everything the
+    /// character carries is left in the room.
+    pub fn leave(&mut self, session: Session) {
+        self.chat.leave_all(&session.player);
+        let world = &mut self.world;
+        let carried: Vec<InternalName> = world.entity(&session.player).and_then(|e| e.character())
+            .map(|c| c.inventory.iter().chain(c.worn.iter()).cloned().collect())
+            .unwrap_or_default();
+        if let Some(room) = world.location_of(&session.player) {
+            if let Some(r) = world.entity_mut(&room).and_then(|e| e.room_mut()) {
+                r.entities.extend(carried);
+            }
+        }
+        world.destroy(&session.player);
+    }
+}
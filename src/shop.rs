@@ -0,0 +1,141 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Shops.  Shopkeepers sell their stock to players and buy items from
+//! them, paying out of and into their purse.
+
+use super::types::*;
+use super::command::{Command, Connector};
+use super::error::Error;
+use super::print_wrap;
+
+impl<'a> PlayerState<'a> {
+    /// Find the shopkeeper for a command.  If the command names one
+    /// ("buy candle from guard", "sell club to guard"), it is looked
+    /// up among the entities in the player's location, otherwise the
+    /// first shopkeeper there is used.  Dead shopkeepers don't trade.
+    fn find_shopkeeper(&self, cmd: &Command) -> Option<InternalName> {
+        let candidates = match cmd.indirect_object {
+            Some((Connector::From, ref w)) | Some((Connector::To, ref w)) =>
+                self.world.find_entity(w, &self.room_contents()).into_iter().collect(),
+            _ =>
+                self.room_contents(),
+        };
+        candidates.into_iter().find(|n| {
+            let e = self.world.entity(n).unwrap();
+            e.shop().is_some() && e.character().map(|c| c.stats.health > 0).unwrap_or(true)
+        })
+    }
+
+    /// Print the stock of a shop with prices.
+    pub fn list(&mut self, cmd: &Command) -> Result<(), Error> {
+        let keeper = match self.find_shopkeeper(cmd) {
+            Some(k) => k,
+            None => {
+                say!("{}", msg!(self.world, "nothing_for_sale"));
+                return Ok(());
+            }
+        };
+        let stock = self.world.entity(&keeper).unwrap().shop().unwrap().stock.clone();
+        if stock.len() == 0 {
+            say!("{}", msg!(self.world, "sold_out"));
+            return Ok(());
+        }
+        say!("{}", msg!(self.world, "for_sale"));
+        for n in stock.iter() {
+            let price = self.world.entity(n).unwrap().price().unwrap_or(0);
+            say!("{}", msg!(self.world, "price", item = self.world.short_description(n), price = price));
+        }
+        Ok(())
+    }
+
+    /// Buy the direct object from a shopkeeper's stock.
+    pub fn buy(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "buy_what"));
+                return Ok(());
+            }
+        };
+        let keeper = match self.find_shopkeeper(cmd) {
+            Some(k) => k,
+            None => {
+                say!("{}", msg!(self.world, "nothing_for_sale"));
+                return Ok(());
+            }
+        };
+        let stock = self.world.entity(&keeper).unwrap().shop().unwrap().stock.clone();
+        let item = match self.world.find_entity(words, &stock) {
+            Some(i) => i,
+            None => {
+                say!("{}", msg!(self.world, "not_sold_here"));
+                return Ok(());
+            }
+        };
+        let price = self.world.entity(&item).unwrap().price().unwrap_or(0);
+        if price > self.me().purse {
+            print_wrap(&msg!(self.world, "too_expensive", price = price, coins = self.me().purse), 72);
+            return Ok(());
+        }
+        self.me_mut().purse -= price;
+        if let Some(e) = self.world.entity_mut(&keeper) {
+            if let Some(c) = e.character_mut() {
+                c.purse += price;
+            }
+            if let Some(s) = e.shop_mut() {
+                s.stock.retain(|n| *n != item);
+            }
+        }
+        self.me_mut().inventory.push(item);
+        say!("{}", msg!(self.world, "bought", price = price));
+        Ok(())
+    }
+
+    /// Sell the direct object to a shopkeeper.
+    pub fn sell(&mut self, cmd: &Command) -> Result<(), Error> {
+        let words = match cmd.direct_object {
+            Some(ref w) => w,
+            None => {
+                say!("{}", msg!(self.world, "sell_what"));
+                return Ok(());
+            }
+        };
+        let item = match self.world.find_entity(words, &self.me().inventory) {
+            Some(i) => i,
+            None => {
+                say!("{}", msg!(self.world, "not_carried"));
+                return Ok(());
+            }
+        };
+        let keeper = match self.find_shopkeeper(cmd) {
+            Some(k) => k,
+            None => {
+                say!("{}", msg!(self.world, "nobody_buys"));
+                return Ok(());
+            }
+        };
+        let rate = self.world.entity(&keeper).unwrap().shop().unwrap().buy_rate;
+        let offer = match self.world.entity(&item).unwrap().price() {
+            Some(p) => p * rate / 100,
+            None => {
+                say!("{}", msg!(self.world, "nobody_wants"));
+                return Ok(());
+            }
+        };
+        if let Some(c) = self.world.entity_mut(&keeper).and_then(|e| e.character_mut()) {
+            if c.purse < offer {
+                say!("{}", msg!(self.world, "no_money"));
+                return Ok(());
+            }
+            c.purse -= offer;
+        }
+        if let Some(s) = self.world.entity_mut(&keeper).and_then(|e| e.shop_mut()) {
+            s.stock.push(item);
+        }
+        self.me_mut().inventory.retain(|n| *n != item);
+        self.me_mut().purse += offer;
+        say!("{}", msg!(self.world, "sold", price = offer));
+        Ok(())
+    }
+}
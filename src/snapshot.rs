@@ -0,0 +1,321 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Snapshots and the journal.  A snapshot is a copy of everything
+//! that changes while a world is played: what a saved game holds
+//! (see `save`), and the engine's sessions and roles.  Rolling back
+//! to a snapshot puts the world back the way it was, which `undo` and
+//! speculating about what a command would do are built on:
+//!
+//! ```text
+//! let before = engine.snapshot();
+//! engine.step(anna, "pull lever");
+//! engine.rollback(&before);
+//! ```
+//!
+//! The journal keeps a shared world safe from crashes.  Every few
+//! seconds, a snapshot is written to the journal's file, and every
+//! line stepped in between is appended to a log next to it, called
+//! like the file with `.log` appended, before it is carried out, and
+//! so is every turn of a shared world.
+//! After a crash, `recover` reads the last snapshot and steps the
+//! logged lines again; since the world's random numbers are seeded,
+//! they do what they did before.  Sessions opened or closed are not
+//! logged, so the program hosting the engine takes a snapshot with
+//! `checkpoint` when they come and go, as the server does.
+//!
+//! Who is on which chat channel is not kept in snapshots.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ron;
+use serde_json;
+
+use super::types::*;
+use super::rng::Rng;
+use super::engine::Engine;
+use super::roles::Role;
+use super::session::Session;
+use super::step::SessionId;
+
+/// The state of an engine and its world at one moment.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    world: String,
+    entities: Vec<Entity>,
+    relations: Relations,
+    respawns: Vec<Respawn>,
+    weather: BTreeMap<String, usize>,
+    rng: Rng,
+    turn: u64,
+    schedule: Vec<Job>,
+    quests: BTreeMap<String, usize>,
+    awarded: Vec<String>,
+    player: InternalName,
+    roles: BTreeMap<String, Role>,
+    sessions: BTreeMap<SessionId, Session>,
+    next_session: SessionId,
+    /// The number of lines logged before the snapshot was taken.
+    #[serde(default)]
+    logged: u64,
+}
+
+/// A line stepped, or a turn passed, as logged in the journal.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// The number of lines logged before this one.
+    n: u64,
+    session: SessionId,
+    input: String,
+    /// Whether a turn of a shared world passed, rather than a line
+    /// being stepped (see `step`).
+    #[serde(default)]
+    tick: bool,
+}
+
+/// Where an engine keeps snapshots and the lines stepped since.
+pub struct Journal {
+    path: PathBuf,
+    /// How often snapshots are taken.
+    every: Duration,
+    /// When the last snapshot was taken.
+    taken: Instant,
+    /// The number of lines logged so far.
+    logged: u64,
+    log: File,
+}
+
+/// Return the file the lines of the journal at `path` are logged to.
+fn log_file(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".log");
+    PathBuf::from(s)
+}
+
+impl Journal {
+    /// Log a line before it is stepped, or a turn before it passes.
+    fn log(&mut self, session: SessionId, input: &str, tick: bool) -> Result<(), String> {
+        let entry = Entry { n: self.logged, session: session, input: input.to_string(), tick: tick };
+        let line = try!(serde_json::to_string(&entry).map_err(|e| e.to_string()));
+        try!(writeln!(self.log, "{}", line).and_then(|_| self.log.sync_data()).map_err(|e| e.to_string()));
+        self.logged += 1;
+        Ok(())
+    }
+
+    /// Write a snapshot, replacing the last one, and start a new log.
+    fn write(&mut self, mut snapshot: Snapshot) -> Result<(), String> {
+        snapshot.logged = self.logged;
+        let txt = try!(ron::ser::to_string(&snapshot).map_err(|e| format!("cannot write snapshot: {}", e)));
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        try!(fs::write(&tmp, txt).and_then(|_| fs::rename(&tmp, &self.path)).map_err(|e| e.to_string()));
+        // Lines logged before the snapshot are skipped by `recover`,
+        // should this fail.
+        self.log = try!(File::create(log_file(&self.path)).map_err(|e| e.to_string()));
+        self.taken = Instant::now();
+        Ok(())
+    }
+}
+
+impl Snapshot {
+    /// Return the name of the world the snapshot was taken of.
+    pub fn world(&self) -> &str {
+        &self.world
+    }
+
+    /// Return the entities of the snapshot.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Return a copy of the snapshot without its entities, for storage
+    /// keeping entities on their own.
+    pub fn without_entities(&self) -> Snapshot {
+        Snapshot {
+            world: self.world.clone(),
+            entities: vec![],
+            relations: self.relations.clone(),
+            respawns: self.respawns.clone(),
+            weather: self.weather.clone(),
+            rng: self.rng.clone(),
+            turn: self.turn,
+            schedule: self.schedule.clone(),
+            quests: self.quests.clone(),
+            awarded: self.awarded.clone(),
+            player: self.player,
+            roles: self.roles.clone(),
+            sessions: self.sessions.clone(),
+            next_session: self.next_session,
+            logged: self.logged,
+        }
+    }
+
+    /// Return the snapshot with the given entities.
+    pub fn with_entities(self, entities: Vec<Entity>) -> Snapshot {
+        Snapshot { entities: entities, ..self }
+    }
+}
+
+impl Engine {
+    /// Return the state of the engine and its world.
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot_with(self.world.entities.clone())
+    }
+
+    /// Return the state of the engine and its world, with the given
+    /// entities instead of all of them.
+    pub fn snapshot_with(&self, entities: Vec<Entity>) -> Snapshot {
+        let world = &self.world;
+        Snapshot {
+            world: world.name.clone(),
+            entities: entities,
+            relations: world.relations.clone(),
+            respawns: world.respawns.clone(),
+            weather: world.regions.iter()
+                .filter_map(|(n, r)| r.weather.as_ref().map(|w| (n.clone(), w.current)))
+                .collect(),
+            rng: world.rng.clone(),
+            turn: world.turn,
+            schedule: world.schedule.clone(),
+            quests: world.quests.iter().filter_map(|(n, q)| q.stage.map(|s| (n.clone(), s))).collect(),
+            awarded: world.scoring.awarded.clone(),
+            player: self.player,
+            roles: self.roles.clone(),
+            sessions: self.sessions.clone(),
+            next_session: self.next_session,
+            logged: 0,
+        }
+    }
+
+    /// Put the engine and its world back into the state of a
+    /// snapshot.  The snapshot is kept, and can be rolled back to
+    /// again.
+    pub fn rollback(&mut self, snapshot: &Snapshot) {
+        let world = &mut self.world;
+        world.replace_entities(snapshot.entities.clone());
+        world.relations = snapshot.relations.clone();
+        world.respawns = snapshot.respawns.clone();
+        world.rng = snapshot.rng.clone();
+        world.turn = snapshot.turn;
+        world.schedule = snapshot.schedule.clone();
+        world.scoring.awarded = snapshot.awarded.clone();
+        for (name, q) in world.quests.iter_mut() {
+            q.stage = snapshot.quests.get(name).cloned();
+        }
+        for (name, current) in snapshot.weather.iter() {
+            if let Some(w) = world.regions.get_mut(name).and_then(|r| r.weather.as_mut()) {
+                w.current = *current;
+            }
+        }
+        self.player = snapshot.player;
+        self.roles = snapshot.roles.clone();
+        self.sessions = snapshot.sessions.clone();
+        self.next_session = snapshot.next_session;
+    }
+
+    /// Call `f` with the engine, and roll back everything it changed.
+    pub fn speculate<R, F: FnOnce(&mut Engine) -> R>(&mut self, f: F) -> R {
+        let before = self.snapshot();
+        let res = f(self);
+        self.rollback(&before);
+        res
+    }
+
+    /// Undo the last game command, if it can be undone.
+    pub fn undo(&mut self) -> Result<(), String> {
+        match self.last.take() {
+            Some(s) => {
+                self.rollback(&s);
+                Ok(())
+            },
+            None => Err("there is nothing to undo".to_string()),
+        }
+    }
+
+    /// Keep a journal at `path`, with a snapshot taken every `every`,
+    /// starting with one now.
+    pub fn keep_journal<P: Into<PathBuf>>(&mut self, path: P, every: Duration) -> Result<(), String> {
+        let path = path.into();
+        let log = try!(File::create(log_file(&path)).map_err(|e| e.to_string()));
+        let mut journal = Journal { path: path, every: every, taken: Instant::now(), logged: 0, log: log };
+        try!(journal.write(self.snapshot()));
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    /// Take a snapshot for the journal now, if one is kept.
+    pub fn checkpoint(&mut self) -> Result<(), String> {
+        let snapshot = self.snapshot();
+        match self.journal {
+            Some(ref mut j) => j.write(snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// Log a line typed in a session to the journal, if one is kept,
+    /// before it is stepped.  Lines which cannot be logged are not
+    /// stepped.
+    pub fn log_step(&mut self, session: SessionId, input: &str) -> Result<(), String> {
+        match self.journal {
+            Some(ref mut j) => j.log(session, input, false),
+            None => Ok(()),
+        }
+    }
+
+    /// Log a turn of a shared world in the journal, if one is kept.
+    pub fn log_tick(&mut self) -> Result<(), String> {
+        match self.journal {
+            Some(ref mut j) => j.log(0, "", true),
+            None => Ok(()),
+        }
+    }
+
+    /// Take a snapshot for the journal if it is time to.
+    pub fn journal_tick(&mut self) {
+        if self.journal.as_ref().map_or(false, |j| j.taken.elapsed() >= j.every) {
+            if let Err(e) = self.checkpoint() {
+                eprintln!("Cannot write the journal: {}", e);
+            }
+        }
+    }
+
+    /// Restore the engine from the journal at `path` after a crash:
+    /// roll back to its snapshot, and step the lines and pass the
+    /// turns logged since again.  Returns the number of lines stepped
+    /// and turns passed.
+    pub fn recover<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, String> {
+        let path = path.as_ref();
+        let txt = try!(fs::read_to_string(path).map_err(|e| e.to_string()));
+        let snapshot: Snapshot = try!(ron::de::from_str(&txt).map_err(|e| format!("invalid snapshot: {}", e)));
+        if snapshot.world() != self.world.name {
+            return Err(format!("snapshot is of another world: {}", snapshot.world()));
+        }
+        self.rollback(&snapshot);
+        let journal = self.journal.take();
+        let mut stepped = 0;
+        if let Ok(log) = OpenOptions::new().read(true).open(log_file(path)) {
+            for line in BufReader::new(log).lines() {
+                // A line cut off by the crash ends the log.
+                let entry: Entry = match line.ok().and_then(|l| serde_json::from_str(&l).ok()) {
+                    Some(e) => e,
+                    None => break,
+                };
+                if entry.n < snapshot.logged {
+                    continue;
+                }
+                if entry.tick {
+                    self.tick();
+                } else {
+                    self.step(entry.session, &entry.input);
+                }
+                stepped += 1;
+            }
+        }
+        self.journal = journal;
+        Ok(stepped)
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Creating and destroying entities during play.  Both keep the
+//! entity and alias maps, the contents of rooms, vehicles and
+//! characters, and the relations between entities consistent.
+
+use super::types::*;
+
+impl World {
+    /// Rebuild the entity map and the alias map from the entities.
+    /// Of several entities with the same alias, the first one is
+    /// found by its alias.
+    pub fn reindex(&mut self) {
+        self.entity_map.clear();
+        self.alias_map.clear();
+        for (i, e) in self.entities.iter().enumerate() {
+            self.entity_map.insert(e.id, i);
+            if let Some(ref a) = e.alias {
+                self.alias_map.entry(a.clone()).or_insert(e.id);
+            }
+        }
+    }
+
+    /// Replace all entities of the world, as when rolling back to a
+    /// snapshot or restoring a saved game.  The entities replaced and
+    /// those replacing them count as changed.
+    pub fn replace_entities(&mut self, entities: Vec<Entity>) {
+        self.changed.extend(self.entities.iter().chain(entities.iter()).map(|e| e.id));
+        self.entities = entities;
+        self.reindex();
+    }
+
+    /// Return the entity with the given alias, if there is one.
+    pub fn by_alias(&self, alias: &str) -> Option<InternalName> {
+        self.alias_map.get(alias).cloned()
+    }
+
+    /// Add an entity to the world, optionally placing it in a room.
+    /// Returns the entity's name.
+    pub fn spawn(&mut self, entity: Entity, room: Option<InternalName>) -> InternalName {
+        let id = entity.id;
+        self.entity_map.insert(id, self.entities.len());
+        if let Some(ref a) = entity.alias {
+            self.alias_map.entry(a.clone()).or_insert(id);
+        }
+        self.entities.push(entity);
+        self.changed.insert(id);
+        if let Some(r) = room {
+            self.place(id, r);
+        }
+        id
+    }
+
+    /// Remove an entity from the world, and from every room, vehicle,
+    /// container, character, shop, hiding place and relation it is part of.
+    /// Returns the removed entity, if there was one.
+    pub fn destroy(&mut self, name: &InternalName) -> Option<Entity> {
+        let idx = match self.entity_map.get(name) {
+            Some(i) => *i,
+            None => return None,
+        };
+        let entity = self.entities.remove(idx);
+        self.reindex();
+        self.changed.insert(*name);
+        for e in self.entities.iter_mut() {
+            let mut held = false;
+            for a in e.attributes.iter_mut() {
+                let list = match *a {
+                    Attribute::Roomlike(ref mut r) => &mut r.entities,
+                    Attribute::Enterable(ref mut v) => &mut v.contents,
+                    Attribute::Container(ref mut c) => &mut c.contents,
+                    Attribute::Characterlike(ref mut c) => {
+                        if c.vehicle == Some(*name) {
+                            c.vehicle = None;
+                            held = true;
+                        }
+                        if c.worn.contains(name) {
+                            c.worn.retain(|i| i != name);
+                            held = true;
+                        }
+                        &mut c.inventory
+                    },
+                    Attribute::Shopkeeper(ref mut s) => &mut s.stock,
+                    Attribute::Concealing(ref mut c) => &mut c.hidden,
+                    _ => continue,
+                };
+                if list.contains(name) {
+                    list.retain(|i| i != name);
+                    held = true;
+                }
+            }
+            if held {
+                self.changed.insert(e.id);
+            }
+        }
+        self.relations.forget(name);
+        Some(entity)
+    }
+
+    /// Return the room, container or character holding an entity, if
+    /// any.
+    pub fn holder_of(&self, name: &InternalName) -> Option<InternalName> {
+        self.entities.iter().find(|e| e.attributes.iter().any(|a| match *a {
+            Attribute::Roomlike(ref r) => r.entities.contains(name),
+            Attribute::Container(ref c) => c.contents.contains(name),
+            Attribute::Characterlike(ref c) => c.inventory.contains(name) || c.worn.contains(name),
+            _ => false,
+        })).map(|e| e.id)
+    }
+}
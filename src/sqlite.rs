@@ -0,0 +1,227 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Storage in a SQLite database, for large worlds.  Built with the
+//! `sqlite` feature, `Sqlite` keeps everything `Files` keeps (see
+//! `storage`), but each entity of the world in a row of its own:
+//!
+//! ```text
+//! mudstuck serve big.ron --telnet 4000 --database big.db
+//! ```
+//!
+//! Each row of an entity is keyed by its name, and holds a hash of
+//! the entity next to it.  The engine tells the storage which
+//! entities changed or were destroyed since the state was last kept
+//! (see `Storage::save_changes`), so only those are written again or
+//! deleted, and only those whose hash differs are written at all.
+//! Keeping the state of a world with many entities costs little more
+//! than the changes do.  Entities are read back in the order they were
+//! first written.  Single entities can be read on their own with
+//! `Sqlite::entity`, without loading the world.
+//!
+//! Entities are not loaded on demand.  The engine plays worlds with
+//! all their entities in memory, in `World::entities`, so loading the
+//! state of a world reads all of them, and a world must fit into
+//! memory however it is kept.  Only writing entities back is done
+//! one entity at a time.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use ron;
+use rusqlite::{self, Connection, OptionalExtension};
+
+use super::types::*;
+use super::snapshot::Snapshot;
+use super::accounts::Account;
+use super::storage::Storage;
+
+/// The tables of the database.
+const SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS state (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS entities
+        (id TEXT PRIMARY KEY, hash INTEGER NOT NULL, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS accounts (name TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS games (name TEXT PRIMARY KEY, data TEXT NOT NULL);
+";
+
+/// Storage in a SQLite database.
+pub struct Sqlite {
+    db: Mutex<Db>,
+}
+
+struct Db {
+    conn: Connection,
+    /// The hashes of the entities as they were last written.
+    written: BTreeMap<InternalName, i64>,
+    /// The number of entities written since the database was opened.
+    writes: u64,
+}
+
+/// Return a hash of a text, which stays the same between builds.
+fn fnv(txt: &str) -> i64 {
+    txt.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3)) as i64
+}
+
+fn sql_error(e: rusqlite::Error) -> String {
+    format!("database error: {}", e)
+}
+
+impl Sqlite {
+    /// Open the database at `path`, making it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Sqlite, String> {
+        let conn = try!(Connection::open(path).map_err(sql_error));
+        try!(conn.execute_batch(SCHEMA).map_err(sql_error));
+        let written = {
+            let mut stmt = try!(conn.prepare("SELECT id, hash FROM entities").map_err(sql_error));
+            let rows = try!(stmt.query_map([], |r| Ok((try!(r.get::<_, String>(0)), try!(r.get::<_, i64>(1)))))
+                            .map_err(sql_error));
+            let mut written = BTreeMap::new();
+            for row in rows {
+                let (id, hash) = try!(row.map_err(sql_error));
+                let id = try!(id.parse().map_err(|_| format!("invalid entity id in database: {}", id)));
+                written.insert(id, hash);
+            }
+            written
+        };
+        Ok(Sqlite { db: Mutex::new(Db { conn: conn, written: written, writes: 0 }) })
+    }
+
+    /// Return an entity of the state kept, read on its own.
+    pub fn entity(&self, id: &InternalName) -> Result<Option<Entity>, String> {
+        let db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        let data: Option<String> = try!(db.conn.query_row("SELECT data FROM entities WHERE id = ?1",
+                                                          [id.to_string()], |r| r.get(0))
+                                        .optional().map_err(sql_error));
+        match data {
+            Some(d) => ron::de::from_str(&d).map(Some).map_err(|e| format!("invalid entity {}: {}", id, e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the number of entities written since the database was
+    /// opened.
+    pub fn entities_written(&self) -> u64 {
+        self.db.lock().map(|db| db.writes).unwrap_or(0)
+    }
+
+    /// Return the text a query for a key selects, if any.
+    fn get(&self, sql: &str, key: &str) -> Result<Option<String>, String> {
+        let db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        db.conn.query_row(sql, [key], |r| r.get(0)).optional().map_err(sql_error)
+    }
+}
+
+impl Db {
+    /// Keep the state of the world but for its entities, and the
+    /// given entities, which changed or are new, and delete those in
+    /// `gone`.  With `all`, the entities given are all of the world,
+    /// and all others are deleted.
+    fn save(&mut self, state: &Snapshot, gone: &[InternalName], all: bool) -> Result<(), String> {
+        let rest = try!(ron::ser::to_string(&state.without_entities())
+                        .map_err(|e| format!("cannot save the world state: {}", e)));
+        let Db { ref mut conn, ref mut written, ref mut writes } = *self;
+        let tx = try!(conn.transaction().map_err(sql_error));
+        try!(tx.execute("INSERT OR REPLACE INTO state (key, value) VALUES ('world', ?1)", [&rest])
+             .map_err(sql_error));
+        let mut hashes = BTreeMap::new();
+        let mut wrote = 0;
+        for e in state.entities().iter() {
+            let data = try!(ron::ser::to_string(e).map_err(|err| format!("cannot save entity {}: {}", e.id, err)));
+            let hash = fnv(&data);
+            if written.get(&e.id) != Some(&hash) {
+                // Updating rows in place keeps the order they were
+                // first written in.
+                try!(tx.execute("INSERT INTO entities (id, hash, data) VALUES (?1, ?2, ?3) \
+                                 ON CONFLICT (id) DO UPDATE SET hash = excluded.hash, data = excluded.data",
+                                rusqlite::params![e.id.to_string(), hash, data])
+                     .map_err(sql_error));
+                wrote += 1;
+            }
+            hashes.insert(e.id, hash);
+        }
+        let gone: Vec<InternalName> = if all {
+            written.keys().filter(|id| !hashes.contains_key(id)).cloned().collect()
+        } else {
+            gone.iter().filter(|id| written.contains_key(id)).cloned().collect()
+        };
+        for id in gone.iter() {
+            try!(tx.execute("DELETE FROM entities WHERE id = ?1", [id.to_string()]).map_err(sql_error));
+        }
+        try!(tx.commit().map_err(sql_error));
+        if all {
+            *written = hashes;
+        } else {
+            for id in gone.iter() {
+                written.remove(id);
+            }
+            written.extend(hashes);
+        }
+        *writes += wrote;
+        Ok(())
+    }
+}
+
+impl Storage for Sqlite {
+    fn load_state(&self) -> Result<Option<Snapshot>, String> {
+        let rest: Snapshot = match try!(self.get("SELECT value FROM state WHERE key = ?1", "world")) {
+            Some(txt) => try!(ron::de::from_str(&txt).map_err(|e| format!("invalid world state: {}", e))),
+            None => return Ok(None),
+        };
+        let db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        let mut stmt = try!(db.conn.prepare("SELECT data FROM entities ORDER BY rowid").map_err(sql_error));
+        let rows = try!(stmt.query_map([], |r| r.get::<_, String>(0)).map_err(sql_error));
+        let mut entities = vec![];
+        for row in rows {
+            let data = try!(row.map_err(sql_error));
+            entities.push(try!(ron::de::from_str(&data).map_err(|e| format!("invalid entity: {}", e))));
+        }
+        Ok(Some(rest.with_entities(entities)))
+    }
+
+    fn save_state(&self, state: &Snapshot) -> Result<(), String> {
+        let mut db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        db.save(state, &[], true)
+    }
+
+    fn save_changes(&self, state: &Snapshot, gone: &[InternalName]) -> Result<bool, String> {
+        let mut db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        db.save(state, gone, false).map(|_| true)
+    }
+
+    fn load_accounts(&self) -> Result<BTreeMap<String, Account>, String> {
+        let db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        let mut stmt = try!(db.conn.prepare("SELECT name, data FROM accounts").map_err(sql_error));
+        let rows = try!(stmt.query_map([], |r| Ok((try!(r.get::<_, String>(0)), try!(r.get::<_, String>(1)))))
+                        .map_err(sql_error));
+        let mut accounts = BTreeMap::new();
+        for row in rows {
+            let (name, data) = try!(row.map_err(sql_error));
+            let account = try!(ron::de::from_str(&data).map_err(|e| format!("invalid account {}: {}", name, e)));
+            accounts.insert(name, account);
+        }
+        Ok(accounts)
+    }
+
+    fn save_accounts(&self, accounts: &BTreeMap<String, Account>) -> Result<(), String> {
+        let mut db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        let tx = try!(db.conn.transaction().map_err(sql_error));
+        try!(tx.execute("DELETE FROM accounts", []).map_err(sql_error));
+        for (name, account) in accounts.iter() {
+            let data = try!(ron::ser::to_string(account).map_err(|e| e.to_string()));
+            try!(tx.execute("INSERT INTO accounts (name, data) VALUES (?1, ?2)", [name, &data]).map_err(sql_error));
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    fn load_game(&self, name: &str) -> Result<Option<String>, String> {
+        self.get("SELECT data FROM games WHERE name = ?1", name)
+    }
+
+    fn save_game(&self, name: &str, saved: &str) -> Result<(), String> {
+        let db = try!(self.db.lock().map_err(|_| "database is poisoned".to_string()));
+        db.conn.execute("INSERT OR REPLACE INTO games (name, data) VALUES (?1, ?2)", [name, saved])
+            .map(|_| ()).map_err(sql_error)
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Statistics about worlds.  Large worlds are hard to keep track of,
+//! so authors can ask for a summary: how many entities have which
+//! attributes, which rooms cannot be reached, which items are never
+//! placed anywhere, and how long descriptions are on average.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::types::*;
+
+/// Return the name of an attribute's kind, as used in world files.
+fn attribute_kind(a: &Attribute) -> &'static str {
+    match *a {
+        Attribute::Lockable(_) => "Lockable",
+        Attribute::Closable(_) => "Closable",
+        Attribute::Keylike(_) => "Keylike",
+        Attribute::CodeEntry(_) => "CodeEntry",
+        Attribute::Wearable(_) => "Wearable",
+        Attribute::Edible(_) => "Edible",
+        Attribute::Drinkable(_) => "Drinkable",
+        Attribute::Readable(_) => "Readable",
+        Attribute::Weapon(_) => "Weapon",
+        Attribute::Money(_) => "Money",
+        Attribute::Price(_) => "Price",
+        Attribute::Shopkeeper(_) => "Shopkeeper",
+        Attribute::Concealing(_) => "Concealing",
+        Attribute::Fixed => "Fixed",
+        Attribute::Stackable(_) => "Stackable",
+        Attribute::Enterable(_) => "Enterable",
+        Attribute::Container(_) => "Container",
+        Attribute::Transparent => "Transparent",
+        Attribute::OutOfReach => "OutOfReach",
+        Attribute::Bulky(_) => "Bulky",
+        Attribute::Attachable => "Attachable",
+        Attribute::Stateful(_) => "Stateful",
+        Attribute::Burnable(_) => "Burnable",
+        Attribute::Perishable(_) => "Perishable",
+        Attribute::Doorlike(_) => "Doorlike",
+        Attribute::Roomlike(_) => "Roomlike",
+        Attribute::Characterlike(_) => "Characterlike",
+        Attribute::Triggered(_) => "Triggered",
+        Attribute::Ruled(_) => "Ruled",
+    }
+}
+
+impl World {
+    /// Return true if the entity is somewhere in the world: in a
+    /// room, container, inventory or vehicle, hidden by something, for
+    /// sale in a shop, or in the starting inventory of players.
+    pub fn is_placed(&self, name: &InternalName) -> bool {
+        self.holder_of(name).is_some() || self.player.inventory.contains(name) ||
+            self.entities.iter().any(|e| e.attributes.iter().any(|a| match *a {
+                Attribute::Enterable(ref v) => v.contents.contains(name),
+                Attribute::Concealing(ref c) => c.hidden.contains(name),
+                Attribute::Shopkeeper(ref s) => s.stock.contains(name),
+                _ => false,
+            }))
+    }
+
+    /// Return statistics about the world.  Rooms and doors are never
+    /// counted as unplaced.
+    pub fn stats(&self) -> WorldStats {
+        let mut attributes = BTreeMap::new();
+        for e in self.entities.iter() {
+            for a in e.attributes.iter() {
+                *attributes.entry(attribute_kind(a)).or_insert(0) += 1;
+            }
+        }
+        let reached = self.reachable_rooms();
+        let unreachable = self.entities.iter()
+            .filter(|e| e.room().is_some() && !reached.contains(&e.id))
+            .map(|e| e.id).collect();
+        let unplaced = self.entities.iter()
+            .filter(|e| e.room().is_none() && e.connection().is_none() && !self.is_placed(&e.id))
+            .map(|e| e.id).collect();
+        let chars: usize = self.entities.iter().map(|e| e.long_description.chars().count()).sum();
+        WorldStats {
+            entities: self.entities.len(),
+            attributes: attributes,
+            unreachable: unreachable,
+            unplaced: unplaced,
+            average_description: if self.entities.is_empty() { 0.0 } else { chars as f64 / self.entities.len() as f64 },
+        }
+    }
+}
+
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "entities: {}", self.entities));
+        for (kind, n) in self.attributes.iter() {
+            try!(writeln!(f, "  {}: {}", kind, n));
+        }
+        try!(writeln!(f, "average description length: {:.1}", self.average_description));
+        try!(writeln!(f, "unreachable rooms: {}", self.unreachable.len()));
+        for r in self.unreachable.iter() {
+            try!(writeln!(f, "  {}", r));
+        }
+        try!(write!(f, "unplaced items: {}", self.unplaced.len()));
+        for i in self.unplaced.iter() {
+            try!(write!(f, "\n  {}", i));
+        }
+        Ok(())
+    }
+}
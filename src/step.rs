@@ -0,0 +1,242 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The engine without input or output of its own, for embedding it in
+//! GUIs, bots and tests.  The engine keeps the sessions of its
+//! players (see `session`), and carries out one line typed in a
+//! session at a time, returning everything that happened for the
+//! program hosting it to show:
+//!
+//! ```text
+//! let anna = engine.open("Anna");
+//! for e in engine.step(anna, "take lantern") {
+//!     if let Output::Text { text } = e.output {
+//!         show(e.session, &text);
+//!     }
+//! }
+//! ```
+//!
+//! Besides the text the game shows, each step reports the room the
+//! player is in, the player's vitals and inventory, and what other
+//! players said to the players of other sessions.  The game on the
+//! console and the server are built on this.
+//!
+//! In a world shared by many players (see `Engine::shared`), turns
+//! do not pass with every command, which would let the players typing
+//! fastest rush everyone's game along, but when the program hosting
+//! the engine calls `tick`, like the server does every few seconds.
+//! Each player is told what happened in the player's room meanwhile,
+//! and players see what the others in their room do to each other
+//! and what characters do to them, like fights.
+
+use std::mem;
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+use super::output;
+
+/// The number of a session kept by the engine.
+pub type SessionId = usize;
+
+/// Something the engine tells the player of a session.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Output {
+    /// Text shown by the game.
+    Text { text: String },
+    /// The room the player is in, with the doors leading out of it.
+    Room { id: String, name: String, exits: Vec<String> },
+    /// The player's health.
+    Vitals { health: i32, max_health: i32 },
+    /// What the player carries.
+    Inventory { items: Vec<String> },
+    /// The game is over for the player, and the session is closed.
+    Over,
+}
+
+/// Output for the player of a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputEvent {
+    pub session: SessionId,
+    pub output: Output,
+}
+
+impl Engine {
+    /// Let a player called `name` join the world, and keep the new
+    /// session.  Returns its number.
+    pub fn open(&mut self, name: &str) -> SessionId {
+        let session = self.join(name);
+        self.attach(session)
+    }
+
+    /// Keep a session, like one for a character which rejoined the
+    /// world.  Returns its number.
+    pub fn attach(&mut self, session: Session) -> SessionId {
+        let id = self.next_session;
+        self.next_session += 1;
+        // Commands of a world played alone cannot be taken back once
+        // it is shared.
+        self.last = None;
+        self.sessions.insert(id, session);
+        self.world.monitor.players(self.sessions.len());
+        id
+    }
+
+    /// Stop keeping a session, and return it.  Its character stays in
+    /// the world, for `leave` or `park`.
+    pub fn close(&mut self, id: SessionId) -> Option<Session> {
+        let session = self.sessions.remove(&id);
+        self.world.monitor.players(self.sessions.len());
+        session
+    }
+
+    /// Return a session kept by the engine.
+    pub fn session(&self, id: SessionId) -> Option<&Session> {
+        self.sessions.get(&id)
+    }
+
+    /// Return the session playing a character, if any.
+    pub fn session_of(&self, player: &InternalName) -> Option<SessionId> {
+        self.sessions.iter().find(|&(_, s)| s.player == *player).map(|(id, _)| *id)
+    }
+
+    /// Handle a line typed in a session, and return what happened,
+    /// without showing anything.  When the game is over for the
+    /// player, the character leaves the world and the session is
+    /// closed.  Unknown sessions are ignored.
+    pub fn step(&mut self, id: SessionId, input: &str) -> Vec<OutputEvent> {
+        let mut session = match self.sessions.remove(&id) {
+            Some(s) => s,
+            None => return vec![],
+        };
+        if let Err(e) = self.log_step(id, input) {
+            self.sessions.insert(id, session);
+            let text = format!("Cannot write the journal: {}.\n", e);
+            return vec![OutputEvent { session: id, output: Output::Text { text: text } }];
+        }
+        let started = self.world.monitor.start();
+        let (alive, text) = {
+            let _recorded = output::in_session(id);
+            output::input(input);
+            output::capture(|| self.handle_session(&mut session, input))
+        };
+        self.world.monitor.command(id, started);
+        let mut events = self.conclude(id, session, alive, text);
+        for (player, text) in self.chat.take_deliveries() {
+            if let Some(to) = self.session_of(&player) {
+                events.push(OutputEvent { session: to, output: Output::Text { text: format!("{}\n", text) } });
+            }
+        }
+        events.extend(self.witnesses());
+        self.journal_tick();
+        events
+    }
+
+    /// Let a shared world advance by one turn, and return what the
+    /// players of the sessions noticed, like characters coming and
+    /// going or fuses running out.  When the game is over for a
+    /// player, the character leaves the world and the session is
+    /// closed.  Turns do not pass while nobody plays.
+    pub fn tick(&mut self) -> Vec<OutputEvent> {
+        if self.sessions.is_empty() {
+            return vec![];
+        }
+        if let Err(e) = self.log_tick() {
+            eprintln!("Cannot write the journal: {}", e);
+            return vec![];
+        }
+        self.world.dispatch();
+        let notices = self.world.tick();
+        let expired = self.world.burn_fuses();
+        let mut events = vec![];
+        let ids: Vec<SessionId> = self.sessions.keys().cloned().collect();
+        for id in ids {
+            let session = match self.sessions.remove(&id) {
+                Some(s) => s,
+                None => continue,
+            };
+            let world = &mut self.world;
+            let (alive, text) = {
+                let _recorded = output::in_session(id);
+                output::capture(|| {
+                    let mut ps = PlayerState::new(world, session.player);
+                    ps.notice(&notices);
+                    ps.notice_fuses(&expired);
+                    if ps.is_dead() {
+                        say!("{}", msg!(ps.world, "died"));
+                        ps.show_score(true);
+                    }
+                    !ps.is_dead()
+                })
+            };
+            if alive && text.is_empty() {
+                self.sessions.insert(id, session);
+            } else {
+                events.extend(self.conclude(id, session, alive, text));
+            }
+        }
+        self.world.dispatch();
+        self.journal_tick();
+        events
+    }
+
+    /// Return the output for a session after a step or a turn: the
+    /// text shown, and what players see next to it.  The session is
+    /// kept again, unless the game is over for its player.
+    fn conclude(&mut self, id: SessionId, session: Session, alive: bool, text: String) -> Vec<OutputEvent> {
+        let mut res = vec![];
+        if !text.is_empty() {
+            res.push(Output::Text { text: text });
+        }
+        if alive {
+            res.extend(self.state_of(&session.player));
+            self.sessions.insert(id, session);
+        } else {
+            self.leave(session);
+            self.world.monitor.players(self.sessions.len());
+            res.push(Output::Over);
+        }
+        res.into_iter().map(|o| OutputEvent { session: id, output: o }).collect()
+    }
+
+    /// Return what players did in rooms, or had done to them, for the
+    /// other players there to see.
+    fn witnesses(&mut self) -> Vec<OutputEvent> {
+        let mut events = vec![];
+        for (room, player, text) in mem::replace(&mut self.world.witnessed, vec![]) {
+            for (id, s) in self.sessions.iter() {
+                if s.player != player && self.world.location_of(&s.player) == Some(room) {
+                    events.push(OutputEvent { session: *id, output: Output::Text { text: format!("{}\n", text) } });
+                }
+            }
+        }
+        events
+    }
+
+    /// Return what players see next to the text: the room a player is
+    /// in, the player's vitals and inventory.
+    fn state_of(&self, player: &InternalName) -> Vec<Output> {
+        let world = &self.world;
+        let mut res: Vec<Output> = world.location_of(player).map(|room| Output::Room {
+            id: room.to_string(),
+            name: world.short_description(&room),
+            exits: world.doors_at(&room).iter().map(|d| world.short_description(d)).collect(),
+        }).into_iter().collect();
+        if let Some(c) = world.entity(player).and_then(|e| e.character()) {
+            res.push(Output::Vitals { health: c.stats.health, max_health: c.stats.max_health });
+            res.push(Output::Inventory { items: c.inventory.iter().map(|i| world.short_description(i)).collect() });
+        }
+        res
+    }
+}
+
+impl World {
+    /// Let the other players in the room of `player` see what the
+    /// player did, or had done to them, if the world is shared.
+    pub fn witness(&mut self, player: &InternalName, text: String) {
+        if let Some(room) = self.location_of(player).filter(|_| self.shared) {
+            self.witnessed.push((room, *player, text));
+        }
+    }
+}
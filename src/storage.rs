@@ -0,0 +1,187 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Storage: where what outlasts a game is kept.  That is the state of
+//! a shared world (see `snapshot`), the accounts of its players (see
+//! `accounts`) and the games players saved by name.  The engine and
+//! the server keep these through the `Storage` trait, so they can be
+//! kept anywhere:
+//!
+//! ```text
+//! let storage = Arc::new(Files::new("/var/lib/mudstuck"));
+//! server.use_storage(storage)?;
+//! ```
+//!
+//! `Files` keeps them in files in a directory: the state of the world
+//! in `world.ron`, the accounts in `accounts.ron` and saved games in
+//! files called like them, with `.sav` appended.  Names of saved games
+//! may only contain letters, digits, `_` and `-`, like those of
+//! checkpoints.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ron;
+
+use super::types::*;
+use super::engine::Engine;
+use super::snapshot::Snapshot;
+use super::accounts::Account;
+use super::autosave::checkpoint_file;
+use super::compress::{read_file, write_file};
+
+/// Where the state of a world, accounts and saved games are kept.
+/// Storage is shared by the engine and the accounts of a server, and
+/// used from the thread running the server.
+pub trait Storage: Send + Sync {
+    /// Return the state of the world kept, if any.
+    fn load_state(&self) -> Result<Option<Snapshot>, String>;
+
+    /// Keep the state of the world, replacing the state kept before.
+    fn save_state(&self, state: &Snapshot) -> Result<(), String>;
+
+    /// Keep the changes to the state of the world kept before: `state`
+    /// holds only the entities which changed since, and `gone` names
+    /// those which were destroyed.  Returns false if the storage can
+    /// only keep the state as a whole, with `save_state`, as `Files`
+    /// does.
+    fn save_changes(&self, _: &Snapshot, _: &[InternalName]) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    /// Return the accounts kept, by the lowercase names of their
+    /// players.
+    fn load_accounts(&self) -> Result<BTreeMap<String, Account>, String>;
+
+    /// Keep the accounts, replacing those kept before.
+    fn save_accounts(&self, accounts: &BTreeMap<String, Account>) -> Result<(), String>;
+
+    /// Return the saved game with a name (see `save`), if any.
+    fn load_game(&self, name: &str) -> Result<Option<String>, String>;
+
+    /// Keep a saved game under a name, replacing the one kept under
+    /// that name before.
+    fn save_game(&self, name: &str, saved: &str) -> Result<(), String>;
+}
+
+/// Storage in files.
+#[derive(Clone, Debug)]
+pub struct Files {
+    dir: PathBuf,
+    accounts: PathBuf,
+}
+
+impl Files {
+    /// Keep everything in files in the directory `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Files {
+        let dir = dir.into();
+        Files { accounts: dir.join("accounts.ron"), dir: dir }
+    }
+
+    /// Keep the accounts in the file at `path` instead.
+    pub fn accounts_file<P: Into<PathBuf>>(self, path: P) -> Files {
+        Files { accounts: path.into(), ..self }
+    }
+
+    /// Return the contents of a file, or `None` if it does not exist.
+    fn read(&self, path: &PathBuf) -> Result<Option<String>, String> {
+        if path.exists() { read_file(path).map(Some) } else { Ok(None) }
+    }
+
+    /// Write a file, making the directory it is in if needed.
+    fn write(&self, path: &Path, txt: &str) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir).map_err(|e| format!("cannot make {}: {}", dir.display(), e)));
+        }
+        write_file(path, txt)
+    }
+}
+
+impl Storage for Files {
+    fn load_state(&self) -> Result<Option<Snapshot>, String> {
+        match try!(self.read(&self.dir.join("world.ron"))) {
+            Some(txt) => ron::de::from_str(&txt).map(Some).map_err(|e| format!("invalid world state: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_state(&self, state: &Snapshot) -> Result<(), String> {
+        let txt = try!(ron::ser::to_string(state).map_err(|e| format!("cannot save the world state: {}", e)));
+        self.write(&self.dir.join("world.ron"), &txt)
+    }
+
+    fn load_accounts(&self) -> Result<BTreeMap<String, Account>, String> {
+        match try!(self.read(&self.accounts)) {
+            Some(txt) => ron::de::from_str(&txt).map_err(|e| format!("cannot read {}: {}", self.accounts.display(), e)),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    fn save_accounts(&self, accounts: &BTreeMap<String, Account>) -> Result<(), String> {
+        let txt = try!(ron::ser::to_string_pretty(accounts, ron::ser::PrettyConfig::default())
+                       .map_err(|e| e.to_string()));
+        self.write(&self.accounts, &txt)
+    }
+
+    fn load_game(&self, name: &str) -> Result<Option<String>, String> {
+        self.read(&self.dir.join(try!(checkpoint_file(name))))
+    }
+
+    fn save_game(&self, name: &str, saved: &str) -> Result<(), String> {
+        self.write(&self.dir.join(try!(checkpoint_file(name))), saved)
+    }
+}
+
+impl Engine {
+    /// Keep the state of the world, and the games saved with `save`
+    /// and `checkpoint`, in `storage` from now on.
+    pub fn use_storage(&mut self, storage: Arc<Storage>) {
+        self.storage = Some(storage);
+        self.stored = false;
+    }
+
+    /// Keep the state of the world in storage, if the engine has it.
+    /// Once the state was kept or loaded, storage which can is given
+    /// only the entities which changed since.
+    pub fn save_state(&mut self) -> Result<(), String> {
+        let storage = match self.storage {
+            Some(ref s) => s.clone(),
+            None => return Ok(()),
+        };
+        if self.stored {
+            let (changed, gone): (Vec<InternalName>, Vec<InternalName>) =
+                self.world.changed.iter().partition(|id| self.world.entity_map.contains_key(id));
+            let entities = changed.iter().filter_map(|id| self.world.entity(id)).cloned().collect();
+            if try!(storage.save_changes(&self.snapshot_with(entities), &gone)) {
+                self.world.changed.clear();
+                return Ok(());
+            }
+        }
+        try!(storage.save_state(&self.snapshot()));
+        self.world.changed.clear();
+        self.stored = true;
+        Ok(())
+    }
+
+    /// Put the world back into the state kept in storage.  Returns
+    /// false if there is none.
+    pub fn load_state(&mut self) -> Result<bool, String> {
+        let state = match self.storage {
+            Some(ref s) => try!(s.load_state()),
+            None => None,
+        };
+        match state {
+            Some(ref s) if s.world() != self.world.name =>
+                Err(format!("world state is of another world: {}", s.world())),
+            Some(s) => {
+                self.rollback(&s);
+                self.world.changed.clear();
+                self.stored = true;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The telnet protocol, as far as the server needs it.  Clients send
+//! lines of text, mixed with commands negotiating options, which
+//! start with the byte IAC.  The commands are dropped from the input,
+//! and text sent to clients has its lines ended with CR LF and IAC
+//! bytes doubled, as the protocol requires.
+//!
+//! The server offers three options.  With GMCP, the Generic MUD
+//! Communication Protocol, MUD clients get structured data in
+//! subnegotiations, like `Room.Info {"name": ...}`, alongside the
+//! text.  With MCCP2, the MUD Client Compression Protocol, everything
+//! sent to the client after the subnegotiation starting it is
+//! compressed with zlib; this needs the `compression` feature.  With
+//! MSSP, the MUD Server Status Protocol, crawlers listing MUDs learn
+//! the server's name, how many players are on, and more.  The
+//! negotiations a client sends are kept by the decoder, so the server
+//! learns whether the client agreed.
+
+use std::mem;
+#[cfg(feature = "compression")]
+use std::io::Write;
+
+#[cfg(feature = "compression")]
+use flate2;
+
+/// Interpret as command.
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+/// Start of subnegotiation.
+pub const SB: u8 = 250;
+/// End of subnegotiation.
+pub const SE: u8 = 240;
+/// The echo option, which servers take on so that clients do not
+/// show passwords as they are typed.
+pub const ECHO: u8 = 1;
+/// The GMCP option.
+pub const GMCP: u8 = 201;
+/// The MCCP2 option.
+pub const MCCP2: u8 = 86;
+/// The MSSP option.
+pub const MSSP: u8 = 70;
+/// Starts the name of an MSSP variable.
+const MSSP_VAR: u8 = 1;
+/// Starts the value of an MSSP variable.
+const MSSP_VAL: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Data,
+    /// After IAC.
+    Command,
+    /// After WILL, WONT, DO or DONT, before the option.
+    Option(u8),
+    /// In a subnegotiation.
+    Sub,
+    /// After IAC in a subnegotiation.
+    SubCommand,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::Data
+    }
+}
+
+/// Splits what a client sends into lines, dropping telnet commands.
+#[derive(Default)]
+pub struct Decoder {
+    state: State,
+    line: Vec<u8>,
+    /// The negotiations received, as command and option.
+    negotiations: Vec<(u8, u8)>,
+    /// The length in bytes after which lines are cut off, if any.
+    max_line: Option<usize>,
+}
+
+impl Decoder {
+    /// Return a decoder cutting lines off after `max` bytes, so that
+    /// clients cannot fill the memory with a line never ended.
+    pub fn with_max_line(max: usize) -> Decoder {
+        Decoder { max_line: Some(max), ..Decoder::default() }
+    }
+
+    /// Add a byte to the line, unless it is full.
+    fn push(&mut self, b: u8) {
+        if self.max_line.map_or(true, |m| self.line.len() < m) {
+            self.line.push(b);
+        }
+    }
+
+    /// Feed bytes received from the client, returning the lines they
+    /// complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<String> {
+        let mut lines = vec![];
+        for &b in data {
+            self.state = match (self.state, b) {
+                (State::Data, IAC) => State::Command,
+                (State::Data, b'\n') => {
+                    lines.push(String::from_utf8_lossy(&self.line).into_owned());
+                    self.line.clear();
+                    State::Data
+                },
+                (State::Data, b'\r') | (State::Data, 0) => State::Data,
+                (State::Data, b) => {
+                    self.push(b);
+                    State::Data
+                },
+                // A doubled IAC is a data byte.
+                (State::Command, IAC) => {
+                    self.push(IAC);
+                    State::Data
+                },
+                (State::Command, WILL) | (State::Command, WONT) | (State::Command, DO) | (State::Command, DONT) =>
+                    State::Option(b),
+                (State::Command, SB) => State::Sub,
+                (State::Command, _) => State::Data,
+                (State::Option(command), option) => {
+                    self.negotiations.push((command, option));
+                    State::Data
+                },
+                (State::Sub, IAC) => State::SubCommand,
+                (State::Sub, _) => State::Sub,
+                (State::SubCommand, SE) => State::Data,
+                (State::SubCommand, _) => State::Sub,
+            };
+        }
+        lines
+    }
+
+    /// Return the negotiations received since the last call, as
+    /// command and option, like `(DO, GMCP)`.
+    pub fn take_negotiations(&mut self) -> Vec<(u8, u8)> {
+        mem::replace(&mut self.negotiations, vec![])
+    }
+}
+
+/// Return text as sent to a client: lines end with CR LF, and IAC
+/// bytes are doubled.
+pub fn encode(text: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(text.len());
+    for &b in text.as_bytes() {
+        match b {
+            b'\n' => res.extend_from_slice(b"\r\n"),
+            IAC => res.extend_from_slice(&[IAC, IAC]),
+            b => res.push(b),
+        }
+    }
+    res
+}
+
+/// Return a GMCP message as sent to a client: the package and its
+/// data, in a subnegotiation.
+pub fn gmcp(package: &str, data: &str) -> Vec<u8> {
+    let mut res = vec![IAC, SB, GMCP];
+    for &b in package.as_bytes().iter().chain(b" ").chain(data.as_bytes()) {
+        if b == IAC {
+            res.push(IAC);
+        }
+        res.push(b);
+    }
+    res.extend_from_slice(&[IAC, SE]);
+    res
+}
+
+/// Return the status of a server as sent to a client, as MSSP
+/// variables and their values.
+pub fn mssp(vars: &[(String, String)]) -> Vec<u8> {
+    let mut res = vec![IAC, SB, MSSP];
+    for &(ref var, ref val) in vars {
+        res.push(MSSP_VAR);
+        res.extend(var.bytes().filter(|&b| b > MSSP_VAL && b != IAC));
+        res.push(MSSP_VAL);
+        res.extend(val.bytes().filter(|&b| b > MSSP_VAL && b != IAC));
+    }
+    res.extend_from_slice(&[IAC, SE]);
+    res
+}
+
+/// Return true if output to clients can be compressed in this build.
+pub fn compression_supported() -> bool {
+    cfg!(feature = "compression")
+}
+
+/// Compresses what is sent to a client which agreed to MCCP2.
+#[cfg(feature = "compression")]
+pub struct Compressor(flate2::write::ZlibEncoder<Vec<u8>>);
+
+#[cfg(not(feature = "compression"))]
+pub struct Compressor;
+
+#[cfg(feature = "compression")]
+impl Compressor {
+    pub fn new() -> Compressor {
+        Compressor(flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default()))
+    }
+
+    /// Return data compressed, and flushed so the client can show it
+    /// right away.
+    pub fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        let _ = self.0.write_all(data).and_then(|_| self.0.flush());
+        mem::replace(self.0.get_mut(), vec![])
+    }
+
+    /// Return the end of the compressed data.
+    pub fn finish(self) -> Vec<u8> {
+        self.0.finish().unwrap_or_default()
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+impl Compressor {
+    pub fn new() -> Compressor {
+        Compressor
+    }
+
+    pub fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        vec![]
+    }
+}
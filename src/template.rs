@@ -143,3 +143,16 @@ pub fn parse(txt: &str) -> Result<Ast, String> {
     }
     return Ok(ret);
 }
+
+/// Parse a single expression, without the leading `#` used in
+/// templates.  This is used for conditions, which are not
+/// interpolated into strings but evaluated on their own.
+pub fn parse_expression(txt: &str) -> Result<Ast, String> {
+    let mut s = Scanner::new(txt);
+    let ret = try!(parse_expr(&mut s));
+    scanner::skip_ws(&mut s);
+    match s.current() {
+        None => Ok(ret),
+        Some(c) => Err(format!("unexpected character after expression: {}", c)),
+    }
+}
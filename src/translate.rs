@@ -0,0 +1,234 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Exchanging translations with translators.  All descriptions of a
+//! world and the game's messages can be exported to the PO format of
+//! gettext, which translation tools understand, and translated files
+//! can be merged back into the world:
+//!
+//! ```text
+//! mudstuck translations world.ron en > world.en.po
+//! mudstuck play world.ron --translations world.en.po
+//! ```
+//!
+//! Each entry names what it translates in its context: an entity by
+//! its UUID and field, like `936da01f-9abd-4d9d-80c7-02af85c822a8:long`,
+//! or a message by its key, like `message:taken`.  The alias of the
+//! entity is given as a reference for translators.  The locale is
+//! taken from the `Language` of the header.
+//!
+//! Entries marked as fuzzy are not merged, and neither are entries
+//! whose text has changed in the world since they were exported, so
+//! outdated translations never replace the descriptions.
+
+use std::mem;
+
+use super::types::*;
+use super::messages;
+use super::messages::DEFAULT_LOCALE;
+
+/// The fields of entities which can be translated.
+const FIELDS: &'static [&'static str] = &["short", "long", "sound", "smell"];
+
+/// An entry of a PO file.
+#[derive(Default)]
+struct Entry {
+    context: String,
+    source: String,
+    text: String,
+    fuzzy: bool,
+    /// Line the entry starts on.
+    line: usize,
+}
+
+/// Return a string quoted for PO files.
+fn quote(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Return the contents of a quoted string of a PO file.
+fn unquote(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return Err(format!("string expected: {}", s));
+    }
+    let mut res = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some(c) if c == '"' || c == '\\' => res.push(c),
+            Some(c) => return Err(format!("unknown escape: \\{}", c)),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+    Ok(res)
+}
+
+/// Parse the entries of a PO file.
+fn parse(txt: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+    let mut entry = Entry::default();
+    // The field continued by strings on the following lines.
+    let mut field = "";
+    for (n, line) in txt.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+        let (keyword, rest) = match line.find(' ') {
+            Some(i) if !line.starts_with('"') => (&line[..i], &line[i + 1..]),
+            _ => ("", line),
+        };
+        let starts_entry = keyword == "msgctxt" || (keyword == "msgid" && field != "msgctxt");
+        if (line.is_empty() || starts_entry) && field != "" {
+            entries.push(mem::replace(&mut entry, Entry::default()));
+            field = "";
+        }
+        if starts_entry {
+            entry.line = n;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("#,") {
+            entry.fuzzy = line.contains("fuzzy");
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let s = try!(unquote(rest).map_err(|e| format!("line {}: {}", n, e)));
+        match keyword {
+            "msgctxt" => entry.context = s,
+            "msgid" => entry.source = s,
+            "msgstr" => entry.text = s,
+            "" => match field {
+                "msgctxt" => entry.context.push_str(&s),
+                "msgid" => entry.source.push_str(&s),
+                "msgstr" => entry.text.push_str(&s),
+                _ => return Err(format!("line {}: string outside of an entry", n)),
+            },
+            _ => return Err(format!("line {}: unknown keyword: {}", n, keyword)),
+        }
+        if keyword != "" {
+            field = keyword;
+        }
+    }
+    if field != "" {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+impl Entity {
+    /// Return a field of this entity's descriptions as written, if it
+    /// has one.
+    fn source_text(&self, field: &str) -> Option<&str> {
+        match field {
+            "short" => Some(&self.short_description),
+            "long" => Some(&self.long_description),
+            "sound" => self.sound_description.as_ref().map(|s| s.as_str()),
+            "smell" => self.smell_description.as_ref().map(|s| s.as_str()),
+            _ => None,
+        }
+    }
+}
+
+impl Translation {
+    fn field_mut(&mut self, field: &str) -> &mut String {
+        match field {
+            "short" => &mut self.short,
+            "long" => &mut self.long,
+            "sound" => &mut self.sound,
+            _ => &mut self.smell,
+        }
+    }
+}
+
+impl World {
+    /// Return the game's message with the given key as written in the
+    /// world, or as it comes with the game.
+    fn message_text(&self, locale: &str, key: &str) -> Option<String> {
+        self.messages.get(locale).and_then(|m| m.get(key)).map(|m| m.clone())
+            .or_else(|| messages::builtin(locale, key).map(|m| m.to_string()))
+    }
+
+    /// Return all descriptions of the world and the game's messages in
+    /// PO format, with the translations for the locale there are.
+    pub fn export_translations(&self, locale: &str) -> String {
+        let mut res = format!("# Translations of {}.\nmsgid \"\"\nmsgstr \"\"\n\
+                               \"Language: {}\\n\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n",
+                              self.name, locale);
+        for e in self.entities.iter() {
+            for field in FIELDS.iter() {
+                let source = match e.source_text(field) {
+                    Some(s) if s.len() > 0 => s,
+                    _ => continue,
+                };
+                let mut t = e.translations.get(locale).cloned().unwrap_or_default();
+                res.push_str(&format!("\n#: {}\nmsgctxt {}\nmsgid {}\nmsgstr {}\n",
+                                      self.reference(&e.id), quote(&format!("{}:{}", e.id, field)),
+                                      quote(source), quote(t.field_mut(field))));
+            }
+        }
+        for key in messages::keys() {
+            let source = self.message_text(DEFAULT_LOCALE, key).unwrap_or_default();
+            let text = self.message_text(locale, key).unwrap_or_default();
+            res.push_str(&format!("\nmsgctxt {}\nmsgid {}\nmsgstr {}\n",
+                                  quote(&format!("message:{}", key)), quote(&source), quote(&text)));
+        }
+        res
+    }
+
+    /// Merge the translations of a PO file into the world.  Returns
+    /// the locale of the file and the number of translations merged.
+    pub fn import_translations(&mut self, txt: &str) -> Result<(String, usize), String> {
+        let entries = try!(parse(txt));
+        let locale = try!(entries.iter().find(|e| e.context.is_empty() && e.source.is_empty())
+                          .and_then(|e| e.text.lines().filter_map(|l| l.trim().splitn(2, "Language:").nth(1)).next())
+                          .map(|l| l.trim().to_string())
+                          .filter(|l| l.len() > 0)
+                          .ok_or("the file has no language".to_string()));
+        let mut merged = 0;
+        for entry in entries.iter().filter(|e| !e.context.is_empty() && !e.text.is_empty() && !e.fuzzy) {
+            let mut parts = entry.context.splitn(2, ':');
+            let (what, field) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+            if what == "message" {
+                if self.message_text(DEFAULT_LOCALE, field).as_ref() != Some(&entry.source) {
+                    continue;
+                }
+                if messages::builtin(&locale, field) != Some(entry.text.as_str()) {
+                    self.messages.entry(locale.clone()).or_insert(Default::default())
+                        .insert(field.to_string(), entry.text.clone());
+                }
+                merged += 1;
+                continue;
+            }
+            let id = try!(what.parse::<InternalName>().ok().filter(|id| self.entity(id).is_some())
+                          .ok_or(format!("line {}: unknown entity: {}", entry.line, what)));
+            if !FIELDS.contains(&field) {
+                return Err(format!("line {}: unknown field: {}", entry.line, field));
+            }
+            let e = self.entity_mut(&id).unwrap();
+            if e.source_text(field) != Some(entry.source.as_str()) {
+                continue;
+            }
+            *e.translations.entry(locale.clone()).or_insert(Default::default()).field_mut(field) = entry.text.clone();
+            merged += 1;
+        }
+        Ok((locale, merged))
+    }
+}
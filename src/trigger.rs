@@ -0,0 +1,168 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Triggers: small scripts of entities, run when something happens
+//! to them, so puzzles can be written without touching the engine.
+//! An entity with the `Triggered` attribute lists its triggers:
+//!
+//! ```text
+//! Triggered([
+//!     Trigger(on: "take", text: "Ein Alarm schrillt!",
+//!             effects: [Lock(@vault_door), SetState(@alarm, "ringing")]),
+//!     Trigger(on: "enter", condition: Some("(carries player lamp)"),
+//!             text: "Das Licht der Lampe vertreibt die Fledermäuse.", once: true),
+//! ])
+//! ```
+//!
+//! Triggers fire on these events (see `events`):
+//!
+//! * `enter` and `leave`, for rooms a character goes into or out of,
+//! * `take` and `drop`, for items,
+//! * `open` and `close`, for doors and containers,
+//! * `die`, for characters, and
+//! * `say`, for rooms players talk in (see `chat`).
+//!
+//! The condition and the text are templates, evaluated on behalf of
+//! the character causing the event, and the text is shown to it.  The
+//! effects are carried out after the text is shown.
+
+use super::types::*;
+use super::print_wrap;
+
+/// Return the entities affected by an event, with the triggers
+/// fired on them, and the character causing it.
+fn affected(event: &Event) -> (Vec<(InternalName, &'static str)>, Option<InternalName>) {
+    match *event {
+        Event::PlayerMoved { who, from, to } => (vec![(from, "leave"), (to, "enter")], Some(who)),
+        Event::EntityOpened { who, what } => (vec![(what, "open")], Some(who)),
+        Event::EntityClosed { who, what } => (vec![(what, "close")], Some(who)),
+        Event::ItemTaken { who, item } => (vec![(item, "take")], Some(who)),
+        Event::ItemDropped { who, item } => (vec![(item, "drop")], Some(who)),
+        Event::NpcDied { npc, killer } => (vec![(npc, "die")], killer),
+        Event::Said { who, room, .. } => (vec![(room, "say")], Some(who)),
+    }
+}
+
+impl World {
+    /// Remove an entity from the room, container or character holding
+    /// it.
+    fn take_away(&mut self, name: &InternalName) {
+        while let Some(holder) = self.holder_of(name) {
+            if let Some(e) = self.entity_mut(&holder) {
+                for a in e.attributes.iter_mut() {
+                    match *a {
+                        Attribute::Roomlike(ref mut r) => r.entities.retain(|i| i != name),
+                        Attribute::Container(ref mut c) => c.contents.retain(|i| i != name),
+                        Attribute::Characterlike(ref mut c) => {
+                            c.inventory.retain(|i| i != name);
+                            c.worn.retain(|i| i != name);
+                        },
+                        _ => {},
+                    }
+                }
+            }
+        }
+    }
+
+    /// Carry out an effect of a trigger, on behalf of the character
+    /// `who`, if any.
+    pub fn apply_effect(&mut self, effect: &Effect, who: Option<InternalName>) {
+        self.apply_effect_for(effect, who, None)
+    }
+
+    /// Carry out an effect like `apply_effect`, for the entity `this`
+    /// whose trigger or rule it is, if any.  Only scripts care.
+    pub fn apply_effect_for(&mut self, effect: &Effect, who: Option<InternalName>, this: Option<InternalName>) {
+        match *effect {
+            Effect::Unlock(ref id) | Effect::Lock(ref id) =>
+                if let Some(l) = self.entity_mut(id).and_then(|e| e.lock_mut()) {
+                    l.locked = if let Effect::Lock(_) = *effect { true } else { false };
+                },
+            Effect::Open(ref id) | Effect::Close(ref id) =>
+                if let Some(c) = self.entity_mut(id).and_then(|e| e.closed_mut()) {
+                    *c = if let Effect::Close(_) = *effect { true } else { false };
+                },
+            Effect::Move(ref id, room) => {
+                self.take_away(id);
+                self.place(*id, room);
+            },
+            Effect::Destroy(ref id) => {
+                self.destroy(id);
+            },
+            Effect::Give(ref id) =>
+                if let Some(w) = who {
+                    self.take_away(id);
+                    if let Some(c) = self.entity_mut(&w).and_then(|e| e.character_mut()) {
+                        c.inventory.push(*id);
+                    }
+                },
+            Effect::SetProperty(ref id, ref key, ref value) =>
+                if let Some(e) = self.entity_mut(id) {
+                    e.set_property(key, value.clone());
+                },
+            Effect::SetState(ref id, ref state) =>
+                if let Some(m) = self.entity_mut(id).and_then(|e| e.machine_mut()) {
+                    m.state = state.clone();
+                },
+            Effect::Affect(ref change) =>
+                if let Some(c) = who.and_then(|w| self.entity_mut(&w)).and_then(|e| e.character_mut()) {
+                    super::apply_change(&mut c.stats, &mut c.conditions, change);
+                },
+            Effect::Behave(ref id, ref behaviors) =>
+                if let Some(c) = self.entity_mut(id).and_then(|e| e.character_mut()) {
+                    c.behaviors = behaviors.clone();
+                },
+            Effect::Award(ref name) => {
+                self.award(name);
+            },
+            Effect::After(turns, ref effects) =>
+                self.after(turns, Task::Effects(effects.clone())),
+            Effect::Script(ref source) =>
+                self.run_script(source, who, this),
+        }
+    }
+
+    /// Fire the triggers of the entities an event affects.
+    pub fn run_triggers(&mut self, event: &Event) {
+        let (targets, who) = affected(event);
+        // Only characters can act as players in templates.
+        let who = who.filter(|w| self.entity(w).and_then(|e| e.character()).is_some());
+        for (target, on) in targets {
+            let triggers = match self.entity(&target).and_then(|e| e.triggers()) {
+                Some(t) => t.clone(),
+                None => continue,
+            };
+            for (i, t) in triggers.iter().enumerate().filter(|&(_, t)| t.on == on && !t.fired) {
+                let (fires, text) = match who {
+                    Some(w) => {
+                        let ps = PlayerState::new(self, w);
+                        (t.condition.as_ref().map_or(Ok(true), |c| ps.eval_condition(c)), ps.eval_str(&t.text))
+                    },
+                    None =>
+                        (t.condition.as_ref().map_or(Ok(true), |c| self.eval_condition(c, None)), self.eval_for(&t.text, None)),
+                };
+                match fires {
+                    Ok(true) => {},
+                    Ok(false) => continue,
+                    Err(e) => {
+                        say!("{}", msg!(self, "error", error = e));
+                        continue;
+                    },
+                }
+                match text {
+                    Ok(ref s) if s.is_empty() => {},
+                    Ok(s) => print_wrap(&s, 72),
+                    Err(e) => say!("{}", msg!(self, "error", error = e)),
+                }
+                if t.once {
+                    if let Some(ts) = self.entity_mut(&target).and_then(|e| e.triggers_mut()) {
+                        ts[i].fired = true;
+                    }
+                }
+                for effect in t.effects.iter() {
+                    self.apply_effect_for(effect, who, Some(target));
+                }
+            }
+        }
+    }
+}
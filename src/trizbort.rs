@@ -0,0 +1,193 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Importing maps drawn with Trizbort.  A Trizbort map becomes a
+//! skeleton world with
+//!
+//! * a room for each room of the map, with the room's name as short
+//!   description and its description as long description,
+//! * the objects listed for a room as items in it,
+//! * a passage named like its direction for each end of a line which
+//!   can be left through, so `go north` works as drawn, and
+//! * a region for each Trizbort region.
+//!
+//! Rooms are given coordinates from the directions of the lines
+//! connecting them, starting at the first room, which is also the
+//! start location.  Everything else, like doors, locks and
+//! descriptions of items, is left to be written by hand.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use roxmltree;
+
+use super::types::*;
+use super::builder::WorldBuilder;
+
+/// Trizbort ports, with the directions they stand for and the offset
+/// of the room in that direction.
+const PORTS: &'static [(&'static str, &'static str, (i32, i32, i32))] =
+    &[
+        ("n", "north", (0, 1, 0)),
+        ("ne", "northeast", (1, 1, 0)),
+        ("e", "east", (1, 0, 0)),
+        ("se", "southeast", (1, -1, 0)),
+        ("s", "south", (0, -1, 0)),
+        ("sw", "southwest", (-1, -1, 0)),
+        ("w", "west", (-1, 0, 0)),
+        ("nw", "northwest", (-1, 1, 0)),
+        ("up", "up", (0, 0, 1)),
+        ("down", "down", (0, 0, -1)),
+        ("in", "in", (0, 0, 0)),
+        ("out", "out", (0, 0, 0)),
+    ];
+
+/// One end of a line: the room, and the direction the line leaves it
+/// in.
+struct End {
+    room: String,
+    direction: String,
+}
+
+/// Turn a name into an alias, using `taken` to keep aliases unique.
+fn alias_for(name: &str, taken: &mut Vec<String>) -> String {
+    let mut base: String = name.to_lowercase().chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    base = base.split('_').filter(|w| w.len() > 0).collect::<Vec<_>>().join("_");
+    if base.len() == 0 {
+        base = "unnamed".to_string();
+    }
+    let mut alias = base.clone();
+    let mut n = 1;
+    while taken.contains(&alias) {
+        n += 1;
+        alias = format!("{}_{}", base, n);
+    }
+    taken.push(alias.clone());
+    alias
+}
+
+/// Return the text of the child element of a node with the given
+/// tag, if there is one.
+fn child_text<'a>(node: &roxmltree::Node<'a, 'a>, tag: &str) -> Option<&'a str> {
+    node.children().find(|c| c.has_tag_name(tag)).and_then(|c| c.text())
+}
+
+/// Return the direction one end of a line leaves its room in: the
+/// text written at that end, or the direction of its port.
+fn direction(line: &roxmltree::Node, dock: &roxmltree::Node, index: &str) -> String {
+    let text = line.attribute(if index == "0" { "startText" } else { "endText" }).unwrap_or("");
+    if text.len() > 0 {
+        return text.to_lowercase();
+    }
+    let port = dock.attribute("port").unwrap_or("");
+    PORTS.iter().find(|&&(p, _, _)| p == port).map_or(port.to_string(), |&(_, d, _)| d.to_string())
+}
+
+/// Return the offset of the room in the given direction.
+fn offset(direction: &str) -> Option<(i32, i32, i32)> {
+    PORTS.iter().find(|&&(_, d, o)| d == direction && o != (0, 0, 0)).map(|&(_, _, o)| o)
+}
+
+impl World {
+    /// Convert a map in the Trizbort XML format into a skeleton world.
+    pub fn import_trizbort(xml: &str) -> Result<World, String> {
+        let doc = try!(roxmltree::Document::parse(xml).map_err(|e| format!("invalid Trizbort file: {}", e)));
+        let root = doc.root_element();
+        if !root.has_tag_name("trizbort") {
+            return Err("invalid Trizbort file: trizbort element expected".to_string());
+        }
+        let title = root.children().find(|c| c.has_tag_name("info"))
+            .and_then(|i| child_text(&i, "title"))
+            .unwrap_or("Trizbort Map");
+        let map = try!(root.children().find(|c| c.has_tag_name("map")).ok_or("invalid Trizbort file: map missing".to_string()));
+
+        let mut taken = vec![];
+        let mut rooms = vec![];
+        let mut aliases = BTreeMap::new();
+        for room in map.children().filter(|c| c.has_tag_name("room")) {
+            let id = try!(room.attribute("id").ok_or("invalid Trizbort file: room without id".to_string()));
+            let alias = alias_for(room.attribute("name").unwrap_or(""), &mut taken);
+            aliases.insert(id.to_string(), alias.clone());
+            rooms.push((alias, room));
+        }
+        if rooms.len() == 0 {
+            return Err("the map has no rooms".to_string());
+        }
+
+        // Each end of a line which can be left through becomes a
+        // passage from its room to the room at the other end.
+        let mut exits: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+        for line in map.children().filter(|c| c.has_tag_name("line")) {
+            let mut ends = vec![];
+            for dock in line.children().filter(|c| c.has_tag_name("dock")) {
+                let index = dock.attribute("index").unwrap_or("");
+                if let Some(room) = dock.attribute("id").and_then(|i| aliases.get(i)) {
+                    ends.push((index.to_string(), End { room: room.clone(), direction: direction(&line, &dock, index) }));
+                }
+            }
+            ends.sort_by(|a, b| a.0.cmp(&b.0));
+            if ends.len() != 2 {
+                continue;
+            }
+            let (a, b) = (&ends[0].1, &ends[1].1);
+            exits.entry(a.room.clone()).or_insert(vec![]).push((a.direction.clone(), b.room.clone()));
+            if line.attribute("flow") != Some("oneWay") {
+                exits.entry(b.room.clone()).or_insert(vec![]).push((b.direction.clone(), a.room.clone()));
+            }
+        }
+
+        // Rooms are positioned by walking the passages from the
+        // first room.  Rooms which would end up on a position which
+        // is already taken are left without one.
+        let mut positions: BTreeMap<String, (i32, i32, i32)> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        positions.insert(rooms[0].0.clone(), (0, 0, 0));
+        queue.push_back(rooms[0].0.clone());
+        while let Some(room) = queue.pop_front() {
+            let here = positions[&room];
+            for &(ref dir, ref to) in exits.get(&room).map_or(&[][..], |v| &v[..]) {
+                if let Some(o) = offset(dir) {
+                    let p = (here.0 + o.0, here.1 + o.1, here.2 + o.2);
+                    if !positions.contains_key(to) && !positions.values().any(|q| *q == p) {
+                        positions.insert(to.clone(), p);
+                        queue.push_back(to.clone());
+                    }
+                }
+            }
+        }
+
+        let mut builder = WorldBuilder::new(title);
+        for &(ref alias, ref room) in rooms.iter() {
+            let name = room.attribute("name").unwrap_or("");
+            let description = room.attribute("description").or(child_text(room, "description")).unwrap_or("");
+            builder = builder.room(alias).named(name).desc(name).long(description.trim());
+            if let Some(&(x, y, z)) = positions.get(alias) {
+                builder = builder.position(x, y, z);
+            }
+            match room.attribute("region") {
+                Some(region) if region.len() > 0 && region != "NoRegion" => {
+                    builder = builder.in_region(region).define_region(region, Region {
+                        description: String::new(),
+                        ambient: vec![],
+                        dark: false,
+                        weather: None,
+                    });
+                },
+                _ => {},
+            }
+            let objects = child_text(room, "objects").unwrap_or("");
+            for object in objects.split(|c| c == '|' || c == '\n').map(|o| o.trim()).filter(|o| o.len() > 0) {
+                let item = alias_for(object, &mut taken);
+                builder = builder.item(&item).named(object).desc(object).in_room(alias);
+            }
+        }
+        for (from, es) in exits.iter() {
+            for &(ref dir, ref to) in es.iter() {
+                let passage = alias_for(&format!("{} {}", from, dir), &mut taken);
+                builder = builder.door(&passage).named(dir).desc(dir).leading(from, to);
+            }
+        }
+        builder.start(&rooms[0].0).build()
+    }
+}
@@ -4,50 +4,885 @@
 //! Shared data types.
 
 use uuid::Uuid;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use rng::Rng;
+use events::Events;
+use extension::Extensions;
+use metrics::Monitor;
 
 pub type InternalName = Uuid;
 pub type Name = Vec<String>;
 
+/// A handle for playing as a character.  The player is an entity of
+/// the world like any other character; its inventory, stats and
+/// location are those of that entity.  A player state is serialized
+/// as the name of that entity only, and deserialized with a
+/// `PlayerSeed` holding the world it belongs to.
 pub struct PlayerState<'a> {
-    pub world: &'a World,
-    pub location: InternalName,
+    pub world: &'a mut World,
+    /// The player's entity.
+    pub id: InternalName,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct World {
     pub name: String,
     pub entities: Vec<Entity>,
+    /// Kinds entities can inherit from, by name.
+    pub kinds: BTreeMap<String, Kind>,
+    /// Regions rooms can belong to, by name.
+    pub regions: BTreeMap<String, Region>,
+    /// Goals for the player, by name.
+    #[serde(default)]
+    pub quests: BTreeMap<String, Quest>,
+    /// Relations between entities, like attachment or ownership.
+    pub relations: Relations,
+    /// Rules for entities which come back after they are gone.
+    pub respawns: Vec<Respawn>,
     pub entity_map: BTreeMap<InternalName, usize>,
+    /// Entities with aliases, by alias.
+    #[serde(default)]
+    pub alias_map: BTreeMap<String, InternalName>,
     pub start_location: InternalName,
+    /// The character new players start as.
+    #[serde(default)]
+    pub player: PlayerTemplate,
+    pub rng: Rng,
+    /// Turns played so far.
+    #[serde(default)]
+    pub turn: u64,
+    /// Tasks to be carried out in later turns.
+    #[serde(default)]
+    pub schedule: Vec<Job>,
+    /// How game time passes.
+    #[serde(default)]
+    pub clock: Clock,
+    /// What players get points for, and the points they got.
+    #[serde(default)]
+    pub scoring: Scoring,
+    /// How the game is saved automatically, if at all.
+    #[serde(skip)]
+    pub autosave: Option<Autosave>,
+    /// Language descriptions are shown in, if entities have
+    /// translations for it.  Without a locale, or without a
+    /// translation, the descriptions as written are shown.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Messages of the game replaced by the world, by locale and
+    /// key.
+    #[serde(default)]
+    pub messages: BTreeMap<String, BTreeMap<String, String>>,
+    /// Events waiting to be delivered, and their listeners.
+    #[serde(skip)]
+    pub events: Events,
+    /// What extensions added (see `extension`).
+    #[serde(skip)]
+    pub extensions: Extensions,
+    /// Where metrics are reported (see `metrics`).
+    #[serde(skip)]
+    pub monitor: Monitor,
+    /// Entities changed or destroyed since the state of the world
+    /// was last kept in storage (see `storage`).
+    #[serde(skip)]
+    pub changed: BTreeSet<InternalName>,
+    /// Whether the world is shared by the players of sessions: turns
+    /// pass on the engine's clock rather than with every command, and
+    /// players see what others do in their room (see `step`).
+    #[serde(skip)]
+    pub shared: bool,
+    /// What players did in rooms, or had done to them, for the other
+    /// players there to see: the room, the player, who was told in
+    /// other words, and the text.
+    #[serde(skip)]
+    pub witnessed: Vec<(InternalName, InternalName, String)>,
+}
+
+/// Saving the game automatically every `every` turns, to the file at
+/// `path`.
+#[derive(Clone, Debug)]
+pub struct Autosave {
+    pub every: u64,
+    pub path: PathBuf,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Entity {
+    #[serde(default)]
     pub id: InternalName,
     pub name: Name,
+    #[serde(default)]
     pub alias: Option<String>,
+    /// Kind this entity inherits defaults from, if any.
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Free-form tags for addressing groups of entities, like
+    /// "undead" or "light_source".
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Game-specific state, like the position of a dial.
+    #[serde(default)]
+    pub properties: BTreeMap<String, Property>,
+    pub short_description: String,
+    pub long_description: String,
+    /// What can be heard of this entity, if anything.  Rooms include
+    /// the sounds of everything in them.
+    #[serde(default)]
+    pub sound_description: Option<String>,
+    /// What can be smelled of this entity, if anything.  Rooms
+    /// include the smells of everything in them.
+    #[serde(default)]
+    pub smell_description: Option<String>,
+    /// Descriptions in other languages, by locale, like "en".
+    #[serde(default)]
+    pub translations: BTreeMap<String, Translation>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// The descriptions of an entity in another language.  Descriptions
+/// which are empty have not been translated.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Translation {
+    #[serde(default)]
+    pub short: String,
+    #[serde(default)]
+    pub long: String,
+    #[serde(default)]
+    pub sound: String,
+    #[serde(default)]
+    pub smell: String,
+}
+
+/// A kind of entity, like "door" or "container".  Entities of a kind
+/// inherit its descriptions where their own are empty, and each of
+/// its attributes which they don't have themselves.  Kinds can in
+/// turn inherit from a parent kind.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Kind {
+    pub parent: Option<String>,
     pub short_description: String,
     pub long_description: String,
+    /// Translations of the descriptions, by locale.
+    #[serde(default)]
+    pub translations: BTreeMap<String, Translation>,
+    pub tags: Vec<String>,
     pub attributes: Vec<Attribute>,
 }
 
+/// How many entities an entity may be related to by a relation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Arity {
+    /// Any number of pairs.
+    ManyToMany,
+    /// Each subject is related to at most one object, like an item to
+    /// its owner.
+    ManyToOne,
+    /// Each subject and each object appear in at most one pair.
+    OneToOne,
+}
+
+/// Named binary relations between entities.  Each relation has an
+/// arity, which is kept when pairs are added by replacing pairs which
+/// would violate it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Relations {
+    pub arities: BTreeMap<String, Arity>,
+    pub pairs: BTreeMap<String, Vec<(InternalName, InternalName)>>,
+}
+
+/// Value of an entity property.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Reference(InternalName),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Attribute {
-    Lockable(bool),
+    Lockable(Lock),
     Closable(bool),
+    Keylike(Key),
+    CodeEntry(CodeInput),
+    Wearable(Wearable),
+    Edible(Consumable),
+    Drinkable(Consumable),
+    /// Text written on an entity, shown when it is read.  The text
+    /// is a template.
+    Readable(String),
+    Weapon(Weapon),
+    /// Money, worth the given number of coins.  Money is added to the
+    /// purse of whoever takes it.
+    Money(u32),
+    /// Price of an item in coins.
+    Price(u32),
+    Shopkeeper(Shop),
+    Concealing(Concealment),
+    /// Fixed in place, like scenery.  Cannot be taken.
+    Fixed,
+    /// A stack of identical items, with the number of items in it.
+    Stackable(u32),
+    Enterable(Vehicle),
+    Container(Container),
+    /// The contents of a closed container can be seen, like those of
+    /// a glass case.
+    Transparent,
+    /// Can be seen, but not touched, like a lamp high up on the
+    /// ceiling or a key across a chasm.
+    OutOfReach,
+    /// How much room an item takes up in containers.  Items without
+    /// bulk take up one unit.
+    Bulky(u32),
+    /// Can be tied or attached to other things, like a rope or a
+    /// chain.
+    Attachable,
+    Stateful(Machine),
+    Burnable(Fuse),
+    Perishable(Decay),
     Doorlike(Connection),
     Roomlike(Room),
     Characterlike(Character),
+    /// Scripts run when something happens to the entity.
+    Triggered(Vec<Trigger>),
+    /// Changes to how commands concerning the entity are carried out.
+    Ruled(Vec<Rule>),
+}
+
+/// State of a lock and the keys that fit it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lock {
+    pub locked: bool,
+    /// Entities which are accepted as keys.
+    pub keys: Vec<InternalName>,
+    /// Key class accepted by this lock.  Every key of this class fits,
+    /// which is how master keys are made.
+    pub key_class: Option<String>,
+    /// Code opening this lock, for combination locks.  Combination
+    /// locks are opened by entering the code on a code input, and can
+    /// be locked without a key.
+    pub code: Option<String>,
+}
+
+/// A device for entering the code of a combination lock, like a
+/// dial ("turn dial to 7") or a keypad ("enter 1234 on keypad").  The
+/// input so far is kept in `entered`.  Once it is as long as the code
+/// of the target's lock, the lock is opened if they match, and the
+/// input starts over.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodeInput {
+    pub target: InternalName,
+    pub entered: String,
+}
+
+/// A key, belonging to zero or more key classes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Key {
+    pub classes: Vec<String>,
+}
+
+/// Places on a body where things can be worn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Slot {
+    Head,
+    Hands,
+    Torso,
+}
+
+/// Something that can be worn in a body slot.  Warmth and armor are
+/// added up over everything a character wears.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Wearable {
+    pub slot: Slot,
+    pub warmth: i32,
+    pub armor: i32,
+}
+
+/// Something that can be eaten or drunk in a number of portions.
+/// The effect is a template which is evaluated and shown each time a
+/// portion is consumed.  When no portions are left, the item is gone.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Consumable {
+    pub portions: u32,
+    pub effect: String,
+    /// Change to the stats of whoever consumes a portion.
+    pub change: StatChange,
+}
+
+/// Something to fight with.  A hit does between one and `damage`
+/// points of damage, before armor is subtracted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Weapon {
+    pub damage: u32,
+}
+
+/// A shop.  The stock is sold at the items' prices; items are bought
+/// from players for `buy_rate` percent of their price.  Money goes
+/// to and comes from the shopkeeper's purse.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Shop {
+    pub stock: Vec<InternalName>,
+    pub buy_rate: u32,
+}
+
+/// Where something is hidden, relative to the entity hiding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Hiding {
+    Under,
+    Behind,
+    Inside,
+}
+
+/// Entities hidden by another entity.  They are not part of the room
+/// until revealed by searching the hiding entity or looking at the
+/// right place ("look under rubble").  The reveal text is a template
+/// shown when that happens.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Concealment {
+    pub hidden: Vec<InternalName>,
+    pub position: Hiding,
+    pub reveal: String,
+}
+
+/// Something players can enter or board, like a boat, a cart or a
+/// horse.  A player inside moves the vehicle along, together with
+/// its contents.  If `rooms` is not empty, the vehicle can only go to
+/// the rooms listed there.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Vehicle {
+    pub contents: Vec<InternalName>,
+    pub rooms: Vec<InternalName>,
+}
+
+/// Something other things can be put into, like a bag or a chest.
+/// If it has a capacity, the bulk of its contents may not exceed it.
+/// Closable containers must be open to put things in or take them
+/// out.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Container {
+    pub contents: Vec<InternalName>,
+    pub capacity: Option<u32>,
+}
+
+/// A state machine, for entities going through several named states,
+/// like a lever ("off", "on") or a furnace ("cold", "lit",
+/// "roaring").  Templates can ask for the state with `(state x)`.
+/// Devices which can be switched on and off use the states "on" and
+/// "off", and the triggers of the same names.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Machine {
+    pub state: String,
+    pub transitions: Vec<Transition>,
+}
+
+/// An allowed change of state.  The trigger is the verb causing it,
+/// as first listed in the verb table ("light", "open", "use"), or
+/// "on" and "off" for switching.  The effect is a template shown when
+/// the transition happens, after the state has changed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub trigger: String,
+    pub effect: Option<String>,
+}
+
+/// A script run when something happens to an entity, like being
+/// taken or entered (see `trigger`).  The condition, if any, must
+/// hold for the trigger to fire.  The text is a template shown to the
+/// character causing it, and the effects are carried out afterwards.
+/// Triggers firing only once are marked as fired.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Trigger {
+    pub on: String,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub once: bool,
+    #[serde(default)]
+    pub fired: bool,
+}
+
+/// A rule for commands with a verb, followed at a stage of carrying
+/// them out when its condition holds (see `rules`).  The condition
+/// and the text are templates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub verb: String,
+    pub stage: ActionStage,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// The stages of carrying out a command.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActionStage {
+    /// Before anything happens; rules refuse the command.
+    Check,
+    /// Rules replace the usual action.
+    CarryOut,
+    /// After the usual action; rules replace its messages.
+    Report,
+}
+
+/// Something a trigger does to the world.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Effect {
+    Unlock(InternalName),
+    Lock(InternalName),
+    Open(InternalName),
+    Close(InternalName),
+    /// Move an entity into a room.
+    Move(InternalName, InternalName),
+    /// Remove an entity from the world.
+    Destroy(InternalName),
+    /// Give an item to the character causing the trigger.
+    Give(InternalName),
+    SetProperty(InternalName, String, Property),
+    /// Put the state machine of an entity into a state.
+    SetState(InternalName, String),
+    /// Change the stats of the character causing the trigger.
+    Affect(StatChange),
+    /// Carry out effects after the given number of turns.
+    After(u64, Vec<Effect>),
+    /// Change what a character does on its own.
+    Behave(InternalName, Vec<Behavior>),
+    /// Give the player an award, unless it has been given before.
+    Award(String),
+    /// Run a script (see `script`).
+    Script(String),
+}
+
+/// A goal for the player, reached in stages (see `quest`).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Quest {
+    pub title: String,
+    /// Condition starting the quest.  Without one, the quest starts
+    /// right away.
+    #[serde(default)]
+    pub start: Option<String>,
+    pub stages: Vec<Stage>,
+    /// Effects carried out when the last stage is completed.
+    #[serde(default)]
+    pub reward: Vec<Effect>,
+    /// The stage the player is at, if the quest has been started.
+    /// All stages are completed at the number of stages.
+    #[serde(default)]
+    pub stage: Option<usize>,
+}
+
+/// A stage of a quest: a goal shown in the journal, the condition
+/// completing it, and a text shown when it is completed.  All three
+/// are templates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stage {
+    pub goal: String,
+    pub condition: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+/// What players get points for, by name, the ranks they reach with
+/// them, and the awards given so far, in order (see `score`).
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Scoring {
+    #[serde(default)]
+    pub awards: BTreeMap<String, Award>,
+    #[serde(default)]
+    pub ranks: Vec<Rank>,
+    #[serde(default)]
+    pub awarded: Vec<String>,
+}
+
+/// Points for something players do, once.  The title is the
+/// achievement shown for it, if it has one.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Award {
+    pub points: u32,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// A rank players reach with the given points.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rank {
+    pub points: u32,
+    pub title: String,
+}
+
+/// How game time passes: the minutes each turn takes, the hour the
+/// game starts at, and the phases of the day (see `clock`).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Clock {
+    pub minutes_per_turn: u32,
+    pub start: u32,
+    #[serde(default)]
+    pub phases: Vec<Phase>,
+}
+
+/// A phase of the day, lasting from the given hour to the start of
+/// the next phase.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    pub hour: u32,
+}
+
+/// A task to be carried out at the end of turn `at`, and again every
+/// `every` turns afterwards, if given (see `scheduler`).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(default)]
+    pub at: u64,
+    #[serde(default)]
+    pub every: Option<u64>,
+    pub task: Task,
+}
+
+/// Something the scheduler does.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Task {
+    /// Show a message, a template, in a room or to a character.
+    Say(InternalName, String),
+    Effects(Vec<Effect>),
+}
+
+/// A fuse, like a match or the fuse of a bomb.  Once lit, it burns
+/// down by one turn each turn.  When no turns are left, the expiry
+/// template is shown, and if `consume` is set, the entity is used up.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fuse {
+    pub lit: bool,
+    pub turns: u32,
+    pub expiry: String,
+    pub consume: bool,
+}
+
+/// Decay of an entity, like rotting food.  The entity loses one turn
+/// each turn, and when no turns are left, the message is shown and
+/// the entity disappears.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Decay {
+    pub turns: u32,
+    pub message: String,
+}
+
+/// A rule bringing an entity back after it has been destroyed or, for
+/// characters, killed.  `entity` is the entity as it reappears in
+/// `room`, `delay` turns after it went missing.  `countdown` is
+/// running while the entity is missing.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Respawn {
+    pub entity: Entity,
+    pub room: InternalName,
+    pub delay: u32,
+    pub countdown: Option<u32>,
+    pub message: String,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Connection {
     pub endpoints: (InternalName, InternalName),
+    pub barrier: Option<Barrier>,
+    /// One-way connections (trapdoors, chutes) only lead from the
+    /// first endpoint to the second and are no exit of the second.
+    pub one_way: bool,
+    /// Teleport connections (portals) link rooms which need not be
+    /// next to each other.
+    pub teleport: bool,
+    /// Text shown when passing through the connection.
+    pub transition: Option<String>,
+    /// The directions the connection leads in, like "north", each
+    /// with the room it leads out of in that direction.  It can be
+    /// passed through by its direction from that room.
+    #[serde(default)]
+    pub directions: Vec<(InternalName, String)>,
 }
 
+/// A condition guarding a connection.  As long as the condition
+/// evaluates to true, nobody can pass and the refusal message is
+/// shown instead.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Barrier {
+    pub condition: String,
+    pub refusal: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Room {
     pub entities: Vec<InternalName>,
+    /// Region this room belongs to, if any.
+    pub region: Option<String>,
+    /// Map coordinates (x, y, z) of this room, if any.  x grows to the
+    /// east, y to the north and z upwards.  Rooms connected by a door
+    /// must be next to each other.
+    pub position: Option<(i32, i32, i32)>,
+}
+
+/// A group of rooms with shared behavior, like a cave system or a
+/// forest.  The description is a template added to the description
+/// of each room in the region.  Ambient messages are templates shown
+/// from time to time to players in the region.  Dark regions can only
+/// be seen in with a light source.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Region {
+    pub description: String,
+    pub ambient: Vec<String>,
+    pub dark: bool,
+    pub weather: Option<Weather>,
 }
 
+/// The weather of a region.  Each turn, it changes to another of its
+/// states with a chance of one in `change`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Weather {
+    pub states: Vec<WeatherState>,
+    /// Index of the current state.
+    pub current: usize,
+    pub change: u32,
+}
+
+/// A state of the weather, like "rain" or "fog".  The message is a
+/// template shown in all rooms of the region when the weather turns
+/// to this state.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WeatherState {
+    pub name: String,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Character {
     pub inventory: Vec<InternalName>,
+    /// Dialogue topics.  Maps topic keywords to templated responses.
+    pub topics: BTreeMap<String, String>,
+    /// Templated response for topics the character knows nothing
+    /// about.
+    pub fallback: String,
+    /// Route the character walks along, if any.
+    pub patrol: Option<Patrol>,
+    pub stats: Stats,
+    pub conditions: Vec<Condition>,
+    /// Hostile characters attack players in the same room.
+    pub hostile: bool,
+    /// Coins the character owns.
+    pub purse: u32,
+    /// Items currently worn, at most one per body slot.
+    pub worn: Vec<InternalName>,
+    /// Vehicle the character has entered, if any.
+    pub vehicle: Option<InternalName>,
+    /// How the character reacts to being given items.  The first
+    /// rule matching the item applies.
+    pub gifts: Vec<Gift>,
+    /// Templated responses to being shown items, by item.
+    pub shown: BTreeMap<InternalName, String>,
+    /// What the character does on its own, most urgent first (see
+    /// `npc`).
+    #[serde(default)]
+    pub behaviors: Vec<Behavior>,
+}
+
+/// A rule for items given to a character.  Without an item, the rule
+/// matches every item.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Gift {
+    pub item: Option<InternalName>,
+    pub response: Response,
+}
+
+/// What a character does with an item given to it.  The strings are
+/// templates shown to the player.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Response {
+    Refuse(String),
+    Accept(String),
+    /// Accept the item and give the player another one in exchange,
+    /// from the character's inventory or stock.
+    Trade(String, InternalName),
+}
+
+/// Vital statistics of a character.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stats {
+    pub health: i32,
+    pub max_health: i32,
+    pub strength: i32,
+}
+
+/// The character new players start as: what they are called and
+/// look like, their stats and conditions, and what they carry.  The
+/// items of the inventory are taken by the first player, unless they
+/// are somewhere else in the world already.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerTemplate {
+    pub name: Name,
+    #[serde(default)]
+    pub short_description: String,
+    #[serde(default)]
+    pub long_description: String,
+    pub stats: Stats,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub inventory: Vec<InternalName>,
+    #[serde(default)]
+    pub purse: u32,
+}
+
+/// Status conditions a character can suffer from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Condition {
+    /// Costs one point of health every turn.
+    Poisoned,
+    Exhausted,
+}
+
+/// A change to the stats and conditions of a character, for example
+/// caused by eating or drinking something.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct StatChange {
+    pub health: i32,
+    pub inflict: Vec<Condition>,
+    pub cure: Vec<Condition>,
+}
+
+/// A patrol route.  The character moves to the next room of the
+/// route every `pace` turns, starting over at the end.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Patrol {
+    pub route: Vec<InternalName>,
+    pub position: usize,
+    pub pace: u32,
+    /// Turns left until the next move.
+    pub wait: u32,
+}
+
+/// Something a character does on its own between the players'
+/// commands.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Behavior {
+    /// Go through a random open door, on average every so many
+    /// turns.
+    Wander(u32),
+    /// Stay in a room, and go back to it when away.
+    Guard(InternalName),
+    /// Go after players leaving the room.
+    Follow,
+    /// Run away from players when health is down to the given
+    /// percentage.
+    Flee(u32),
+}
+
+/// Something which happened in the world, delivered to the listeners
+/// subscribed to events (see `events`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A character went from one room into another.
+    PlayerMoved { who: InternalName, from: InternalName, to: InternalName },
+    /// A character opened a door or container.
+    EntityOpened { who: InternalName, what: InternalName },
+    /// A character closed a door or container.
+    EntityClosed { who: InternalName, what: InternalName },
+    /// A character took an item.
+    ItemTaken { who: InternalName, item: InternalName },
+    /// A character dropped an item.
+    ItemDropped { who: InternalName, item: InternalName },
+    /// A character died, killed by another one if `killer` is given.
+    NpcDied { npc: InternalName, killer: Option<InternalName> },
+    /// A player said something to everyone in a room (see `chat`).
+    Said { who: InternalName, room: InternalName, text: String },
+}
+
+/// A problem found when validating a world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// An entity refers to an entity which does not exist.
+    Dangling { entity: InternalName, reference: InternalName },
+    /// The start location does not exist or is not a room.
+    BadStart(InternalName),
+    /// Several entities share an alias.
+    DuplicateAlias(String, Vec<InternalName>),
+    /// A room which cannot be reached from the start location.
+    Unreachable(InternalName),
+    /// The player's starting inventory holds an entity which does not
+    /// exist.
+    BadInventory(InternalName),
+}
+
+/// The result of validating a world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+}
+
+/// How bad a finding of the linter is.  Errors keep the world from
+/// loading or break it while it is played, warnings point out things
+/// which are probably mistakes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Something the linter found, with a readable reference to the
+/// entity it concerns, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub entity: Option<InternalName>,
+    pub reference: String,
+    pub message: String,
+}
+
+/// Statistics about a world, for authors auditing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldStats {
+    pub entities: usize,
+    /// Number of entities with each kind of attribute.
+    pub attributes: BTreeMap<&'static str, usize>,
+    /// Rooms which cannot be reached from the start location.
+    pub unreachable: Vec<InternalName>,
+    /// Items which are not in any room, container, inventory or
+    /// vehicle, so they never show up in the game.
+    pub unplaced: Vec<InternalName>,
+    /// Average length of the long descriptions, in characters.
+    pub average_description: f64,
+}
+
+/// A change to the entities of a world.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Change {
+    Added(Entity),
+    Removed(InternalName),
+    /// The entity with the same id is replaced by this one.
+    Modified(Entity),
+}
+
+/// The differences between two worlds, which turn one into the
+/// other when applied.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Changeset {
+    pub name: Option<String>,
+    pub start_location: Option<InternalName>,
+    pub entities: Vec<Change>,
+    /// Kinds which are new or changed, or `None` if they are removed.
+    pub kinds: BTreeMap<String, Option<Kind>>,
+    /// Regions which are new or changed, or `None` if they are
+    /// removed.
+    pub regions: BTreeMap<String, Option<Region>>,
+    /// All relations, if they changed.
+    pub relations: Option<Relations>,
+    /// All respawn rules, if they changed.
+    pub respawns: Option<Vec<Respawn>>,
 }
 
 /// String to be used as a verb.
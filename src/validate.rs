@@ -0,0 +1,190 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Checking worlds for consistency.  Worlds written by hand easily
+//! refer to entities which don't exist or contain rooms nobody can
+//! get to, so loaded worlds are validated before they are played.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::types::*;
+
+/// Add the entities an effect refers to.
+fn effect_references(effect: &Effect, refs: &mut Vec<InternalName>) {
+    match *effect {
+        Effect::Unlock(id) | Effect::Lock(id) | Effect::Open(id) | Effect::Close(id) |
+        Effect::Destroy(id) | Effect::Give(id) | Effect::SetProperty(id, _, _) |
+        Effect::SetState(id, _) => refs.push(id),
+        Effect::Move(id, room) => {
+            refs.push(id);
+            refs.push(room);
+        },
+        Effect::Behave(id, ref bs) => {
+            refs.push(id);
+            refs.extend(bs.iter().filter_map(|b| match *b { Behavior::Guard(r) => Some(r), _ => None }));
+        },
+        Effect::Affect(_) | Effect::After(_, _) | Effect::Award(_) | Effect::Script(_) => {},
+    }
+}
+
+/// Return all entities the given entity refers to.
+fn references(e: &Entity) -> Vec<InternalName> {
+    let mut refs = vec![];
+    for a in e.attributes.iter() {
+        match *a {
+            Attribute::Lockable(ref l) => refs.extend(l.keys.iter().cloned()),
+            Attribute::CodeEntry(ref c) => refs.push(c.target),
+            Attribute::Shopkeeper(ref s) => refs.extend(s.stock.iter().cloned()),
+            Attribute::Concealing(ref c) => refs.extend(c.hidden.iter().cloned()),
+            Attribute::Enterable(ref v) => {
+                refs.extend(v.contents.iter().cloned());
+                refs.extend(v.rooms.iter().cloned());
+            },
+            Attribute::Container(ref c) => refs.extend(c.contents.iter().cloned()),
+            Attribute::Doorlike(ref c) => {
+                refs.push(c.endpoints.0);
+                refs.push(c.endpoints.1);
+            },
+            Attribute::Roomlike(ref r) => refs.extend(r.entities.iter().cloned()),
+            Attribute::Triggered(ref ts) =>
+                for effect in ts.iter().flat_map(|t| t.effects.iter()) {
+                    effect_references(effect, &mut refs);
+                },
+            Attribute::Ruled(ref rs) =>
+                for effect in rs.iter().flat_map(|r| r.effects.iter()) {
+                    effect_references(effect, &mut refs);
+                },
+            Attribute::Characterlike(ref c) => {
+                refs.extend(c.inventory.iter().cloned());
+                refs.extend(c.worn.iter().cloned());
+                refs.extend(c.vehicle.iter().cloned());
+                refs.extend(c.shown.keys().cloned());
+                if let Some(ref p) = c.patrol {
+                    refs.extend(p.route.iter().cloned());
+                }
+                refs.extend(c.behaviors.iter().filter_map(|b| match *b { Behavior::Guard(r) => Some(r), _ => None }));
+                for g in c.gifts.iter() {
+                    refs.extend(g.item.iter().cloned());
+                    if let Response::Trade(_, i) = g.response {
+                        refs.push(i);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    refs
+}
+
+impl World {
+    /// Check the world for references to entities which don't exist,
+    /// also in the player's starting inventory, a start location which is no room, aliases used by several
+    /// entities and rooms which cannot be reached from the start
+    /// location.
+    pub fn validate(&self) -> Report {
+        let mut problems = vec![];
+        if self.entity(&self.start_location).and_then(|e| e.room()).is_none() {
+            problems.push(Problem::BadStart(self.start_location));
+        }
+        for e in self.entities.iter() {
+            for r in references(e) {
+                if self.entity(&r).is_none() {
+                    problems.push(Problem::Dangling { entity: e.id, reference: r });
+                }
+            }
+        }
+        for i in self.player.inventory.iter() {
+            if self.entity(i).is_none() {
+                problems.push(Problem::BadInventory(*i));
+            }
+        }
+        for pairs in self.relations.pairs.values() {
+            for &(a, b) in pairs.iter() {
+                for r in [a, b].iter() {
+                    if self.entity(r).is_none() {
+                        problems.push(Problem::Dangling { entity: a, reference: *r });
+                    }
+                }
+            }
+        }
+
+        let mut aliases: BTreeMap<&str, Vec<InternalName>> = BTreeMap::new();
+        for e in self.entities.iter() {
+            if let Some(ref a) = e.alias {
+                aliases.entry(a).or_insert(vec![]).push(e.id);
+            }
+        }
+        for (a, ids) in aliases {
+            if ids.len() > 1 {
+                problems.push(Problem::DuplicateAlias(a.to_string(), ids));
+            }
+        }
+
+        let reached = self.reachable_rooms();
+        for e in self.entities.iter() {
+            if e.room().is_some() && !reached.contains(&e.id) {
+                problems.push(Problem::Unreachable(e.id));
+            }
+        }
+        Report { problems: problems }
+    }
+
+    /// Return the rooms which can be reached from the start location
+    /// through doors, whether they are open or not.
+    pub fn reachable_rooms(&self) -> BTreeSet<InternalName> {
+        self.reachable_from(&self.start_location)
+    }
+
+    /// Return the rooms which can be reached from the given room
+    /// through doors, whether they are open or not.
+    pub fn reachable_from(&self, start: &InternalName) -> BTreeSet<InternalName> {
+        let mut reached = BTreeSet::new();
+        let mut todo = vec![*start];
+        while let Some(room) = todo.pop() {
+            if !reached.insert(room) {
+                continue;
+            }
+            for d in self.doors_at(&room) {
+                todo.extend(self.other_side(&d, &room));
+            }
+        }
+        reached
+    }
+}
+
+impl Report {
+    /// Return true if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Dangling { ref entity, ref reference } =>
+                write!(f, "entity {} refers to missing entity {}", entity, reference),
+            Problem::BadStart(ref s) =>
+                write!(f, "start location {} is not a room", s),
+            Problem::DuplicateAlias(ref a, ref ids) =>
+                write!(f, "alias {} is used by {} entities", a, ids.len()),
+            Problem::Unreachable(ref r) =>
+                write!(f, "room {} cannot be reached", r),
+            Problem::BadInventory(ref i) =>
+                write!(f, "starting inventory holds missing entity {}", i),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, p) in self.problems.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f, "; "));
+            }
+            try!(write!(f, "{}", p));
+        }
+        Ok(())
+    }
+}
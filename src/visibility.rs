@@ -0,0 +1,53 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Visibility and reachability.  Players can refer to everything they
+//! can see, but can only handle what they can reach: things out of
+//! reach and the contents of closed containers can at best be looked
+//! at.
+
+use super::types::*;
+
+impl World {
+    /// Return the contents of a container which can be seen: all of
+    /// them if it is open or transparent, otherwise none.
+    pub fn visible_contents(&self, name: &InternalName) -> Vec<InternalName> {
+        match self.entity(name) {
+            Some(e) if e.attributes.iter().any(|a| match a { &Attribute::Transparent => true, _ => false }) =>
+                e.container().map(|c| c.contents.clone()).unwrap_or(vec![]),
+            _ =>
+                self.open_contents(name),
+        }
+    }
+}
+
+impl<'a> PlayerState<'a> {
+    /// Return true if the player can touch an entity: it is not out
+    /// of reach, and if it is inside a container, that container is
+    /// open and can be reached itself.
+    pub fn can_reach(&self, name: &InternalName) -> bool {
+        let out_of_reach = self.world.entity(name)
+            .map(|e| e.attributes.iter().any(|a| match a { &Attribute::OutOfReach => true, _ => false }))
+            .unwrap_or(false);
+        if out_of_reach {
+            return false;
+        }
+        match self.world.holder_of(name) {
+            Some(h) if self.world.entity(&h).and_then(|e| e.container()).is_some() =>
+                self.world.is_open(&h) && self.can_reach(&h),
+            _ =>
+                true,
+        }
+    }
+
+    /// Return true if the player can touch an entity, otherwise tell
+    /// the player that it is out of reach.
+    pub fn reach(&self, name: &InternalName) -> bool {
+        if self.can_reach(name) {
+            true
+        } else {
+            say!("{}", msg!(self.world, "out_of_reach"));
+            false
+        }
+    }
+}
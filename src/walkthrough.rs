@@ -0,0 +1,153 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Walkthroughs: tests for worlds.  A walkthrough is a list of lines
+//! to type, each followed by fragments of the output it must produce,
+//! so authors can check that their puzzles can still be solved after
+//! changing a world:
+//!
+//! ```text
+//! # Walkthrough for the tiny world.
+//! world: tiny.mud
+//! seed: 42
+//!
+//! > open door
+//! Geöffnet.
+//! > go door
+//! Vorratskammer
+//! !Eichentür ist geschlossen.
+//! ```
+//!
+//! Lines starting with `>` are typed, the lines after them are
+//! fragments which must be part of the output, or must not be part of
+//! it if they start with `!`.  Line breaks and runs of spaces in the
+//! output count as single spaces.  Before the first line typed, the
+//! walkthrough may name its world file, relative to the walkthrough,
+//! and the seed of the random number generator (0 by default).  Lines
+//! starting with `#` are comments.
+//!
+//! Walkthroughs can be shipped in bundles, as files ending in
+//! `.walk`, whose world is the one of the bundle.
+
+use std::fmt;
+use std::path::Path;
+
+use super::types::*;
+use super::engine::Engine;
+use super::bundle::Bundle;
+use super::compress::read_file;
+use super::output;
+
+/// A line to type and what its output must and must not contain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub input: String,
+    pub expected: Vec<String>,
+    pub unexpected: Vec<String>,
+}
+
+/// A walkthrough of a world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Walkthrough {
+    /// The world file, relative to the walkthrough, if it is given.
+    pub world: Option<String>,
+    pub seed: u64,
+    pub steps: Vec<Step>,
+}
+
+/// A step of a walkthrough whose output is not as expected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    /// The number of the step, counting from 1.
+    pub step: usize,
+    pub input: String,
+    /// What is wrong with the output.
+    pub problem: String,
+    pub output: String,
+}
+
+/// Return the text with all runs of whitespace replaced by single
+/// spaces.
+fn normalize(txt: &str) -> String {
+    txt.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Walkthrough {
+    /// Read a walkthrough.
+    pub fn parse(txt: &str) -> Result<Walkthrough, String> {
+        let mut res = Walkthrough { world: None, seed: 0, steps: vec![] };
+        for (i, line) in txt.lines().enumerate() {
+            let line = line.trim();
+            if line.len() == 0 || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('>') {
+                res.steps.push(Step { input: line[1..].trim().to_string(), expected: vec![], unexpected: vec![] });
+                continue;
+            }
+            match res.steps.last_mut() {
+                Some(s) if line.starts_with('!') => s.unexpected.push(normalize(&line[1..])),
+                Some(s) => s.expected.push(normalize(line)),
+                None if line.starts_with("world:") => res.world = Some(line["world:".len()..].trim().to_string()),
+                None if line.starts_with("seed:") =>
+                    res.seed = try!(line["seed:".len()..].trim().parse()
+                                    .map_err(|_| format!("line {}: invalid seed", i + 1))),
+                None => return Err(format!("line {}: expected world, seed or a line to type", i + 1)),
+            }
+        }
+        Ok(res)
+    }
+
+    /// Play the walkthrough in the world.  Returns the first step
+    /// whose output is not as expected, if any.
+    pub fn run(&self, world: World) -> Result<(), Failure> {
+        let mut engine = Engine::new(world, self.seed);
+        for (i, step) in self.steps.iter().enumerate() {
+            let (alive, out) = output::capture(|| engine.handle(&step.input));
+            let fail = |problem: String| Failure { step: i + 1, input: step.input.clone(), problem: problem, output: out.clone() };
+            let text = normalize(&out);
+            if let Some(f) = step.expected.iter().find(|f| !text.contains(f.as_str())) {
+                return Err(fail(format!("missing: {}", f)));
+            }
+            if let Some(f) = step.unexpected.iter().find(|f| text.contains(f.as_str())) {
+                return Err(fail(format!("unexpected: {}", f)));
+            }
+            if !alive && i + 1 < self.steps.len() {
+                return Err(fail("the game is over".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the walkthrough at `path` and play it in its world.
+    pub fn run_file<P: AsRef<Path>>(path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let walk = try!(Walkthrough::parse(&try!(read_file(path))).map_err(|e| format!("{}: {}", path.display(), e)));
+        let file = try!(walk.world.as_ref().ok_or(format!("{}: no world given", path.display())));
+        let world = try!(World::load_from_path(path.parent().unwrap_or(Path::new("")).join(file)));
+        walk.run(world).map_err(|f| f.to_string())
+    }
+}
+
+impl Bundle {
+    /// Play all walkthroughs in the bundle, the files ending in
+    /// `.walk`, in the bundle's world.
+    pub fn run_walkthroughs(&self) -> Result<(), String> {
+        for (name, txt) in self.files.iter().filter(|&(n, _)| n.ends_with(".walk")) {
+            let walk = try!(Walkthrough::parse(txt).map_err(|e| format!("{}: {}", name, e)));
+            try!(walk.run(try!(self.world())).map_err(|f| format!("{}: {}", name, f)));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "step {} ({}): {}", self.step, self.input, self.problem));
+        try!(write!(f, "output was:"));
+        for l in self.output.lines() {
+            try!(write!(f, "\n  {}", l));
+        }
+        Ok(())
+    }
+}
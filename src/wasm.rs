@@ -0,0 +1,73 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! JavaScript bindings, for playing worlds in browsers.  Built for
+//! `wasm32-unknown-unknown` with the `wasm` feature, the engine runs
+//! in the page, and a script hands it the lines the player types:
+//!
+//! ```text
+//! const game = new Game(source, 42);
+//! for (const o of JSON.parse(game.step("look"))) {
+//!     if (o.type === "text") show(o.text);
+//! }
+//! ```
+//!
+//! Worlds are given as their source, in the authoring language (see
+//! `dsl`) or, with `Game.fromWorldFile`, in the world file format.
+//! Nothing is shown until the first step, usually `look`.  Each step
+//! returns the outputs of the headless engine (see `step`) as a JSON
+//! array.
+
+use wasm_bindgen::prelude::*;
+
+use serde_json;
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+use super::step::SessionId;
+
+/// A game played in a browser.
+#[wasm_bindgen]
+pub struct Game {
+    engine: Engine,
+    session: SessionId,
+}
+
+impl Game {
+    /// Start a game of a world.
+    pub fn start(world: World, seed: u64) -> Game {
+        let mut engine = Engine::new(world, seed);
+        let session = engine.attach(Session::new(engine.player));
+        Game { engine: engine, session: session }
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// Start a game of a world written in the authoring language.
+    /// Throws the error message if the world cannot be compiled.
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: &str, seed: u32) -> Result<Game, JsValue> {
+        World::compile(source).map(|w| Game::start(w, seed as u64)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Start a game of a world in the world file format.  Throws the
+    /// error message if the world cannot be loaded.
+    #[wasm_bindgen(js_name = fromWorldFile)]
+    pub fn from_world_file(source: &str, seed: u32) -> Result<Game, JsValue> {
+        World::load_from_str(source).map(|w| Game::start(w, seed as u64)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Handle a line typed by the player, and return what happened as
+    /// a JSON array of outputs.
+    pub fn step(&mut self, input: &str) -> String {
+        let outputs: Vec<_> = self.engine.step(self.session, input).into_iter().map(|e| e.output).collect();
+        serde_json::to_string(&outputs).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Return true once the game is over.
+    pub fn over(&self) -> bool {
+        self.engine.session(self.session).is_none()
+    }
+}
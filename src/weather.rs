@@ -0,0 +1,48 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Weather.  Regions can have weather, which changes from time to
+//! time and can be asked for in templates with `(weather here)` or
+//! `(weather here "rain")`.
+
+use super::types::*;
+
+impl World {
+    /// Return the name of the current weather in the region of the
+    /// given room, if the region has weather.
+    pub fn weather_at(&self, room: &InternalName) -> Option<&str> {
+        self.region_of(room)
+            .and_then(|r| r.weather.as_ref())
+            .and_then(|w| w.states.get(w.current))
+            .map(|s| s.name.as_str())
+    }
+
+    /// Give the weather of every region the chance to change.  Returns
+    /// the messages of new weather states for all rooms of the
+    /// regions concerned.
+    pub fn advance_weather(&mut self) -> Vec<(InternalName, String)> {
+        let mut changed = Vec::new();
+        for (name, r) in self.regions.iter_mut() {
+            if let Some(w) = r.weather.as_mut() {
+                if w.states.len() < 2 || self.rng.roll(w.change) != 1 {
+                    continue;
+                }
+                // Pick one of the other states.
+                let next = (w.current + self.rng.roll(w.states.len() as u32 - 1) as usize) % w.states.len();
+                w.current = next;
+                changed.push((name.clone(), w.states[next].message.clone()));
+            }
+        }
+
+        let mut notices = Vec::new();
+        for (region, message) in changed {
+            let text = self.eval_str(&message).unwrap_or(message.clone());
+            for e in self.entities.iter() {
+                if e.room().and_then(|r| r.region.as_ref()) == Some(&region) {
+                    notices.push((e.id, text.clone()));
+                }
+            }
+        }
+        notices
+    }
+}
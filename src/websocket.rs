@@ -0,0 +1,191 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! The WebSocket protocol spoken with browsers.  Browsers connect to
+//! the server (see `server`) like telnet clients do, but exchange
+//! JSON messages, one per WebSocket text frame.  Players send what
+//! they type:
+//!
+//! ```text
+//! {"type": "input", "text": "take lantern"}
+//! ```
+//!
+//! and the server answers with what the game shows, the room the
+//! player is in, the player's vitals and inventory after each
+//! command, and a prompt when it waits for the next one:
+//!
+//! ```text
+//! {"type": "text", "text": "Genommen.\n"}
+//! {"type": "room", "id": "936da01f-...", "name": "Ein feuchter Keller", "exits": ["eine Tür"]}
+//! {"type": "vitals", "health": 10, "max_health": 10}
+//! {"type": "inventory", "items": ["Eine Laterne"]}
+//! {"type": "prompt"}
+//! ```
+//!
+//! Messages which cannot be understood are answered with an `error`
+//! message, and the server says `goodbye` before it closes the
+//! connection.
+//!
+//! Each browser's connection is owned by the thread serving it,
+//! which alone reads and writes its frames: the server thread passes
+//! what it sends on to that thread (see `Outgoing`).  Control frames
+//! count against the limits like messages do, and the server sends a
+//! close frame when it closes the connection.
+//!
+//! Connections need the `websocket` feature.
+
+use std::net::TcpStream;
+#[cfg(feature = "websocket")]
+use std::io;
+#[cfg(feature = "websocket")]
+use std::time::Duration;
+
+use serde_json;
+#[cfg(feature = "websocket")]
+use tungstenite;
+
+use super::server::Reply;
+
+/// How long reading waits for a frame before the thread serving the
+/// connection looks for messages to send.
+#[cfg(feature = "websocket")]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many times the thread serving a connection waits for the
+/// browser to answer its close frame.
+#[cfg(feature = "websocket")]
+const CLOSE_POLLS: u32 = 20;
+
+/// A message sent by a browser.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Request {
+    Input { text: String },
+}
+
+/// What the server thread sends a browser, by way of the thread
+/// serving its connection.
+pub enum Outgoing {
+    /// A message, sent in a text frame.
+    Text(String),
+    /// The server closes the connection.
+    Close,
+}
+
+/// A frame read from a browser.
+pub enum Frame {
+    /// A message, in a text frame.
+    Text(String),
+    /// A binary or control frame.
+    Other,
+    /// Nothing arrived in time.
+    Idle,
+    /// The connection is closed.
+    Closed,
+}
+
+/// Return a reply as the message sent to browsers.
+pub fn encode(reply: &Reply) -> String {
+    serde_json::to_string(reply).unwrap_or_default()
+}
+
+/// Return the line typed by a player, from a message sent by a
+/// browser.
+pub fn decode(msg: &str) -> Result<String, String> {
+    match serde_json::from_str(msg) {
+        Ok(Request::Input { text }) => Ok(text),
+        Err(e) => Err(format!("invalid message: {}", e)),
+    }
+}
+
+/// A connection with a browser, after the handshake.
+#[cfg(feature = "websocket")]
+pub struct Connection(tungstenite::WebSocket<TcpStream>);
+
+#[cfg(not(feature = "websocket"))]
+pub struct Connection;
+
+/// Return true if reading failed only because nothing arrived in
+/// time.
+#[cfg(feature = "websocket")]
+fn timed_out(e: &tungstenite::Error) -> bool {
+    match *e {
+        tungstenite::Error::Io(ref e) => e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Connection {
+    /// Answer the handshake of a browser connecting, which may send
+    /// messages of up to `max` bytes.
+    pub fn accept(stream: TcpStream, max: usize) -> Result<Connection, String> {
+        let config = tungstenite::protocol::WebSocketConfig {
+            max_message_size: Some(max),
+            max_frame_size: Some(max),
+            ..Default::default()
+        };
+        let ws = try!(tungstenite::accept_with_config(stream, Some(config)).map_err(|e| e.to_string()));
+        try!(ws.get_ref().set_read_timeout(Some(POLL_INTERVAL)).map_err(|e| e.to_string()));
+        Ok(Connection(ws))
+    }
+
+    /// Read the next frame of the browser, waiting for it a little
+    /// while at most.
+    pub fn read(&mut self) -> Frame {
+        match self.0.read() {
+            Ok(tungstenite::Message::Text(t)) => Frame::Text(t),
+            Ok(tungstenite::Message::Close(_)) => {
+                // Send the answer to the close frame.
+                let _ = self.0.flush();
+                Frame::Closed
+            },
+            Ok(_) => Frame::Other,
+            Err(ref e) if timed_out(e) => Frame::Idle,
+            Err(_) => Frame::Closed,
+        }
+    }
+
+    /// Send a message to the browser.  Returns false if it cannot be
+    /// sent.
+    pub fn send(&mut self, msg: String) -> bool {
+        self.0.send(tungstenite::Message::Text(msg)).is_ok()
+    }
+
+    /// Send a close frame, and wait a little for the browser to
+    /// answer it.
+    pub fn close(&mut self) {
+        if self.0.close(None).is_err() {
+            return;
+        }
+        for _ in 0..CLOSE_POLLS {
+            match self.0.read() {
+                Err(ref e) if timed_out(e) => {},
+                Err(_) => return,
+                Ok(_) => {},
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "websocket"))]
+impl Connection {
+    pub fn accept(_stream: TcpStream, _max: usize) -> Result<Connection, String> {
+        Err("WebSocket is not supported in this build".to_string())
+    }
+
+    pub fn read(&mut self) -> Frame {
+        Frame::Closed
+    }
+
+    pub fn send(&mut self, _msg: String) -> bool {
+        false
+    }
+
+    pub fn close(&mut self) {}
+}
+
+/// Return true if the crate is built with WebSocket support.
+pub fn supported() -> bool {
+    cfg!(feature = "websocket")
+}
@@ -0,0 +1,225 @@
+// Copyright 2016 Martin Grabmueller. See the LICENSE file at the
+// top-level directory of this distribution for license information.
+
+//! Wizard commands, for building and debugging worlds while they are
+//! played.  Builders can use most of them, wizards all of them (see
+//! `roles`):
+//!
+//! ```text
+//! @teleport NAME          go to a room, or to the room something is in
+//! @clone NAME             make a copy of something, here
+//! @set NAME KEY VALUE     set a property of something
+//! @dig EXIT ROOM          make a new room, with an exit leading there
+//! @destroy NAME           take something out of the world (wizards only)
+//! @grant PLAYER ROLE      give a player a role (wizards only)
+//! @revoke PLAYER          make a player a player again (wizards only)
+//! ```
+//!
+//! Names are aliases, like `oak_door`, or the words entities are
+//! called by, and are looked for everywhere in the world.  Values of
+//! properties are numbers, `true` or `false`, or else text.
+//!
+//! Rooms made with `@dig` are called like their description, and have
+//! a way back: the opposite direction if the exit is one, and `back`
+//! otherwise.
+
+use std::collections::BTreeMap;
+
+
+use super::types::*;
+use super::engine::Engine;
+use super::session::Session;
+use super::roles::Role;
+
+/// Directions with their opposites, for the ways back from rooms dug.
+const OPPOSITES: &'static [(&'static str, &'static str)] =
+    &[("north", "south"), ("south", "north"), ("east", "west"), ("west", "east"), ("up", "down"), ("down", "up")];
+
+/// Return a new entity, called by the words of its description.
+fn new_entity(id: InternalName, short: &str, attributes: Vec<Attribute>) -> Entity {
+    Entity {
+        id: id,
+        name: short.split_whitespace().map(|w| w.to_lowercase()).collect(),
+        alias: None,
+        kind: None,
+        tags: vec![],
+        properties: BTreeMap::new(),
+        short_description: short.to_string(),
+        long_description: String::new(),
+        sound_description: None,
+        smell_description: None,
+        translations: BTreeMap::new(),
+        attributes: attributes,
+    }
+}
+
+/// Return a one-way exit from one room to another.
+fn exit(id: InternalName, name: &str, from: InternalName, to: InternalName) -> Entity {
+    new_entity(id, name, vec![Attribute::Doorlike(Connection {
+        endpoints: (from, to),
+        barrier: None,
+        one_way: true,
+        teleport: false,
+        transition: None,
+        directions: vec![],
+    })])
+}
+
+/// Return the value of a property as typed.
+fn property_value(s: &str) -> Property {
+    match s {
+        "true" => Property::Bool(true),
+        "false" => Property::Bool(false),
+        _ => s.parse().map(Property::Int).unwrap_or(Property::Str(s.to_string())),
+    }
+}
+
+impl World {
+    /// Return the entity with the given alias, or else the first one
+    /// called by the given words, anywhere in the world.
+    fn find_anywhere(&self, name: &str) -> Option<InternalName> {
+        let words: Name = name.split_whitespace().map(|w| w.to_lowercase()).collect();
+        let all: Vec<InternalName> = self.entities.iter().map(|e| e.id).collect();
+        self.by_alias(name).or_else(|| self.find_entity(&words, &all))
+    }
+
+    /// Make a copy of an entity, in a room.  What the entity holds is
+    /// not copied.
+    fn clone_entity(&mut self, id: &InternalName, room: InternalName) -> Option<InternalName> {
+        let mut e = match self.entity(id) {
+            Some(e) if e.room().is_none() && e.connection().is_none() => e.clone(),
+            _ => return None,
+        };
+        e.id = self.next_id();
+        e.alias = None;
+        e.tags.retain(|t| t != "player");
+        for a in e.attributes.iter_mut() {
+            match *a {
+                Attribute::Container(ref mut c) => c.contents.clear(),
+                Attribute::Enterable(ref mut v) => v.contents.clear(),
+                Attribute::Characterlike(ref mut c) => {
+                    c.inventory.clear();
+                    c.worn.clear();
+                    c.vehicle = None;
+                },
+                Attribute::Shopkeeper(ref mut s) => s.stock.clear(),
+                Attribute::Concealing(ref mut c) => c.hidden.clear(),
+                _ => {},
+            }
+        }
+        Some(self.spawn(e, Some(room)))
+    }
+}
+
+impl Engine {
+    /// Handle the wizard commands.  Returns false if the line is not
+    /// one of them.
+    pub fn wizard_command(&mut self, session: &Session, line: &str) -> bool {
+        if !line.starts_with('@') {
+            return false;
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let needed = match command {
+            "@destroy" | "@grant" | "@revoke" => Role::Wizard,
+            _ => Role::Builder,
+        };
+        if let Err(e) = self.check_role(session, needed) {
+            say!("{}", e);
+            return true;
+        }
+        let me = session.player;
+        let here = match self.world.location_of(&me) {
+            Some(r) => r,
+            None => self.world.start_location,
+        };
+        // The entity named by the arguments.
+        let target = |w: &World| -> Result<InternalName, String> {
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(format!("Usage: {} NAME", command));
+            }
+            w.find_anywhere(&name).ok_or(format!("There is nothing called {}.", name))
+        };
+        let res = match command {
+            "@teleport" => target(&self.world).and_then(|id| {
+                let room = if self.world.entity(&id).and_then(|e| e.room()).is_some() {
+                    Some(id)
+                } else {
+                    self.world.location_of(&id)
+                };
+                match room {
+                    Some(r) => {
+                        self.world.place(me, r);
+                        self.player = me;
+                        self.with_player(|ps| ps.look());
+                        Ok(())
+                    },
+                    None => Err("That is not in any room.".to_string()),
+                }
+            }),
+            "@clone" => target(&self.world).and_then(|id| match self.world.clone_entity(&id, here) {
+                Some(_) => {
+                    say!("Cloned {}.", self.world.short_description(&id));
+                    Ok(())
+                },
+                None => Err("Rooms and exits cannot be cloned.".to_string()),
+            }),
+            "@destroy" => target(&self.world).and_then(|id| {
+                if self.world.entity(&id).map_or(false, |e| e.room().is_some() || e.tags.iter().any(|t| t == "player")) {
+                    return Err("Rooms and players cannot be destroyed.".to_string());
+                }
+                let name = self.world.short_description(&id);
+                self.world.destroy(&id);
+                say!("Destroyed {}.", name);
+                Ok(())
+            }),
+            "@set" if args.len() < 3 => Err("Usage: @set NAME KEY VALUE".to_string()),
+            "@set" => {
+                let name = args[..args.len() - 2].join(" ");
+                match self.world.find_anywhere(&name) {
+                    Some(id) => {
+                        let (key, value) = (args[args.len() - 2], args[args.len() - 1]);
+                        self.world.set_property(&id, key, property_value(value));
+                        say!("Set {} of {} to {}.", key, self.world.short_description(&id), value);
+                        Ok(())
+                    },
+                    None => Err(format!("There is nothing called {}.", name)),
+                }
+            },
+            "@dig" if args.len() < 2 => Err("Usage: @dig EXIT ROOM".to_string()),
+            "@dig" => {
+                let name = args[1..].join(" ");
+                let id = self.world.next_id();
+                let room = self.world.spawn(new_entity(id, &name, vec![Attribute::Roomlike(Room {
+                    entities: vec![],
+                    region: None,
+                    position: None,
+                })]), None);
+                let back = OPPOSITES.iter().find(|&&(d, _)| d == args[0]).map_or("back", |&(_, o)| o);
+                let (there, back_id) = (self.world.next_id(), self.world.next_id());
+                self.world.spawn(exit(there, args[0], here, room), None);
+                self.world.spawn(exit(back_id, back, room, here), None);
+                say!("Dug {}, {} of here.", name, args[0]);
+                Ok(())
+            },
+            "@grant" if args.len() != 2 => Err("Usage: @grant PLAYER ROLE".to_string()),
+            "@grant" => args[1].parse().map(|role| {
+                self.grant(args[0], role);
+                say!("{} is a {} now.", args[0], role);
+            }),
+            "@revoke" if args.len() != 1 => Err("Usage: @revoke PLAYER".to_string()),
+            "@revoke" => {
+                self.grant(args[0], Role::Player);
+                say!("{} is a player now.", args[0]);
+                Ok(())
+            },
+            _ => Err(format!("Unknown wizard command: {}", command)),
+        };
+        if let Err(e) = res {
+            say!("{}", e);
+        }
+        true
+    }
+}